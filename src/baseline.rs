@@ -0,0 +1,321 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 基线快照
+//!
+//! 运维人员在链路状态良好时用 `baseline save` 从结果日志生成各接口的典型表现并保存，
+//! 监控运行时将实时评分与基线比较，发现相对于链路自身常态的逐渐劣化
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::network::InterfaceScore;
+use crate::results_log::LogRecord;
+
+/// `baseline save` 命令选项
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BaselineOptions {
+    /// 只使用最近一段时间的记录（为空表示全部记录）
+    pub since: Option<Duration>,
+    /// 结果日志路径（为空时使用配置文件中的 results_log）
+    pub log_path: Option<PathBuf>,
+    /// 基线文件路径（为空时使用配置文件中的 baseline_file）
+    pub output: Option<PathBuf>,
+}
+
+/// 单个接口的基线
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct InterfaceBaseline {
+    /// 可达时的平均延迟（毫秒）
+    pub latency_ms: Option<f64>,
+    /// 平均综合评分
+    pub score: f64,
+    /// 统计的检查次数
+    pub samples: usize,
+}
+
+/// 基线快照
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Baseline {
+    /// 生成时间
+    pub captured_at: DateTime<Local>,
+    /// 各接口基线
+    pub interfaces: BTreeMap<String, InterfaceBaseline>,
+}
+
+/// 偏离基线的指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// 延迟升高
+    Latency,
+    /// 评分下降
+    Score,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Metric::Latency => "延迟",
+            Metric::Score => "评分",
+        })
+    }
+}
+
+/// 一次偏离基线
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deviation {
+    pub interface: String,
+    pub metric: Metric,
+    /// 基线值
+    pub baseline: f64,
+    /// 当前值
+    pub current: f64,
+}
+
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.metric {
+            Metric::Latency => write!(
+                f,
+                "{} 延迟为基线的 {:.1} 倍（{:.2}ms，基线 {:.2}ms）",
+                self.interface,
+                self.current / self.baseline,
+                self.current,
+                self.baseline
+            ),
+            Metric::Score => write!(
+                f,
+                "{} 评分降至基线的 {:.0}%（{:.2}，基线 {:.2}）",
+                self.interface,
+                self.current / self.baseline * 100.0,
+                self.current,
+                self.baseline
+            ),
+        }
+    }
+}
+
+impl Baseline {
+    /// 从结果日志中统计各接口的典型表现
+    pub fn from_records(records: &[LogRecord], since: Option<DateTime<Local>>) -> Self {
+        let mut stats: BTreeMap<String, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+
+        for record in records {
+            let LogRecord::Check {
+                ts,
+                interface,
+                avg_latency_ms,
+                score,
+                ..
+            } = record
+            else {
+                continue;
+            };
+            if since.is_some_and(|s| *ts < s) {
+                continue;
+            }
+
+            let entry = stats.entry(interface.clone()).or_default();
+            entry.1.push(*score);
+            if let Some(latency) = avg_latency_ms {
+                entry.0.push(*latency);
+            }
+        }
+
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let interfaces = stats
+            .into_iter()
+            .map(|(name, (latencies, scores))| {
+                let baseline = InterfaceBaseline {
+                    latency_ms: (!latencies.is_empty()).then(|| mean(&latencies)),
+                    score: mean(&scores),
+                    samples: scores.len(),
+                };
+                (name, baseline)
+            })
+            .collect();
+
+        Self {
+            captured_at: Local::now(),
+            interfaces,
+        }
+    }
+
+    /// 读取基线文件
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("无法读取基线文件: {:?}", path.as_ref()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("解析基线文件失败: {:?}", path.as_ref()))
+    }
+
+    /// 保存基线文件
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("写入基线文件失败: {:?}", path.as_ref()))
+    }
+
+    /// 找出偏离基线达到 `factor` 倍的接口：延迟升高到基线的 `factor` 倍以上，
+    /// 或评分降到基线的 1/`factor` 以下；完全不可达的接口不在此列
+    pub fn deviations(&self, scores: &[InterfaceScore], factor: f64) -> Vec<Deviation> {
+        let mut deviations = Vec::new();
+
+        for score in scores.iter().filter(|s| s.reachable_count > 0) {
+            let Some(baseline) = self.interfaces.get(&score.interface) else {
+                continue;
+            };
+
+            if let Some(latency) = baseline.latency_ms.filter(|l| *l > 0.0) {
+                if score.avg_latency_ms >= latency * factor {
+                    deviations.push(Deviation {
+                        interface: score.interface.clone(),
+                        metric: Metric::Latency,
+                        baseline: latency,
+                        current: score.avg_latency_ms,
+                    });
+                }
+            }
+
+            if baseline.score > 0.0 && score.score <= baseline.score / factor {
+                deviations.push(Deviation {
+                    interface: score.interface.clone(),
+                    metric: Metric::Score,
+                    baseline: baseline.score,
+                    current: score.score,
+                });
+            }
+        }
+
+        deviations
+    }
+}
+
+/// 跟踪正在偏离基线的指标，只在开始偏离和恢复时提示
+#[derive(Debug, Default)]
+pub struct DeviationTracker {
+    active: HashSet<(String, Metric)>,
+}
+
+impl DeviationTracker {
+    /// 更新本轮偏离情况
+    /// 返回: (新出现的偏离, 已恢复的接口与指标)
+    pub fn update(
+        &mut self,
+        deviations: Vec<Deviation>,
+    ) -> (Vec<Deviation>, Vec<(String, Metric)>) {
+        let current: HashSet<(String, Metric)> = deviations
+            .iter()
+            .map(|d| (d.interface.clone(), d.metric))
+            .collect();
+
+        let started = deviations
+            .into_iter()
+            .filter(|d| !self.active.contains(&(d.interface.clone(), d.metric)))
+            .collect();
+        let mut recovered: Vec<_> = self.active.difference(&current).cloned().collect();
+        recovered.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.active = current;
+        (started, recovered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Eligibility;
+    use crate::results_log;
+
+    const LOG: &str = r#"
+{"type":"check","ts":"2026-01-01T00:00:00+00:00","interface":"wan_ct","reachable_count":2,"target_count":2,"avg_latency_ms":10.0,"avg_packet_loss":0.0,"score":80.0,"active":"wan_ct"}
+{"type":"check","ts":"2026-01-01T00:01:00+00:00","interface":"wan_ct","reachable_count":2,"target_count":2,"avg_latency_ms":14.0,"avg_packet_loss":0.0,"score":76.0,"active":"wan_ct"}
+{"type":"check","ts":"2026-01-01T00:01:00+00:00","interface":"wan_cm","reachable_count":0,"target_count":2,"avg_latency_ms":null,"avg_packet_loss":1.0,"score":0.0,"active":"wan_ct"}
+{"type":"switch","ts":"2026-01-01T00:01:00+00:00","from":"wan_cm","to":"wan_ct"}
+"#;
+
+    fn score(interface: &str, latency: f64, value: f64) -> InterfaceScore {
+        InterfaceScore {
+            interface: interface.to_string(),
+            reachable_count: 2,
+            reachable_v4: 2,
+            reachable_v6: 0,
+            avg_latency_ms: latency,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: 0.0,
+            score: value,
+            eligibility: Eligibility::Eligible,
+        }
+    }
+
+    #[test]
+    fn test_baseline_save_and_load() {
+        let baseline = Baseline::from_records(&results_log::parse(LOG), None);
+        assert_eq!(
+            baseline.interfaces["wan_ct"],
+            InterfaceBaseline {
+                latency_ms: Some(12.0),
+                score: 78.0,
+                samples: 2,
+            }
+        );
+        assert_eq!(baseline.interfaces["wan_cm"].latency_ms, None);
+
+        let path = std::env::temp_dir().join(format!(
+            "routes-monitor-baseline-{}.json",
+            std::process::id()
+        ));
+        baseline.save(&path).unwrap();
+        let loaded = Baseline::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded, baseline);
+
+        // 时间窗口之前的记录不计入
+        let since = DateTime::parse_from_rfc3339("2026-01-01T00:01:00+00:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let baseline = Baseline::from_records(&results_log::parse(LOG), Some(since));
+        assert_eq!(baseline.interfaces["wan_ct"].latency_ms, Some(14.0));
+    }
+
+    #[test]
+    fn test_deviation_detection() {
+        let baseline = Baseline::from_records(&results_log::parse(LOG), None);
+
+        // 延迟在基线 3 倍以内、评分正常
+        assert!(baseline
+            .deviations(&[score("wan_ct", 30.0, 70.0)], 3.0)
+            .is_empty());
+
+        let deviations = baseline.deviations(&[score("wan_ct", 36.0, 20.0)], 3.0);
+        assert_eq!(
+            deviations.iter().map(|d| d.metric).collect::<Vec<_>>(),
+            vec![Metric::Latency, Metric::Score]
+        );
+        assert_eq!(
+            deviations[0].to_string(),
+            "wan_ct 延迟为基线的 3.0 倍（36.00ms，基线 12.00ms）"
+        );
+
+        // 只在开始偏离和恢复时提示
+        let mut tracker = DeviationTracker::default();
+        let (started, recovered) = tracker.update(deviations.clone());
+        assert_eq!(started.len(), 2);
+        assert!(recovered.is_empty());
+        let (started, _) = tracker.update(deviations);
+        assert!(started.is_empty());
+        let (_, recovered) =
+            tracker.update(baseline.deviations(&[score("wan_ct", 36.0, 70.0)], 3.0));
+        assert_eq!(recovered, vec![("wan_ct".to_string(), Metric::Score)]);
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::baseline::BaselineOptions;
+use crate::config::ConfigFormat;
+use crate::report::{self, ReportOptions};
+
+/// 使用说明
+pub const USAGE: &str = "\
+用法:
+  routes-monitor [监控选项]              运行监控循环
+  routes-monitor report [选项]           汇总结果日志
+  routes-monitor dump-config [选项]      输出合并默认值并校验后的实际配置
+  routes-monitor baseline save [选项]    从结果日志生成各接口的基线快照
+  routes-monitor --init [路径] [--force] 生成带注释的示例配置文件（未指定路径时输出到标准输出），
+                                         已存在的文件需要 --force 才会覆盖
+
+监控选项:
+  --config <路径>        配置文件路径，多次指定时每个配置独立运行
+  --test-server <地址>   同时启动内置 TCP 回显服务作为本地探测目标（仅用于测试），如 127.0.0.1:7
+  --once                 只执行一次检查（必要时切换）后退出，没有可达接口时以非零状态退出；
+                         也可设置环境变量 ROUTES_MONITOR_ONCE=1
+  --check                只读检查：探测并评分一次，把评分表输出到标准输出后退出，不修改路由；
+                         没有可达接口时以非零状态退出
+
+report 选项:
+  --since <时长>       只统计最近一段时间，例如 30m、24h、7d
+  --interface <名称>   只显示指定接口
+  --log <路径>         结果日志路径（默认读取配置中的 results_log）
+  --json               以 JSON 输出
+
+dump-config 选项:
+  --format <格式>      toml（默认）或 json
+
+baseline save 选项:
+  --since <时长>       只使用最近一段时间的记录，例如 24h、7d
+  --log <路径>         结果日志路径（默认读取配置中的 results_log）
+  --output <路径>      基线文件路径（默认读取配置中的 baseline_file）";
+
+/// 监控循环选项
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MonitorOptions {
+    /// 配置文件路径（为空时按默认规则查找）
+    pub configs: Vec<PathBuf>,
+    /// 内置 TCP 回显测试服务的监听地址
+    pub test_server: Option<String>,
+    /// 只执行一次检查后退出（供 cron 等外部调度使用）
+    pub once: bool,
+    /// 只探测评分并输出评分表，不切换
+    pub check: bool,
+}
+
+/// 生成示例配置的选项
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InitOptions {
+    /// 输出路径（为空时输出到标准输出）
+    pub path: Option<PathBuf>,
+    /// 覆盖已存在的文件
+    pub force: bool,
+}
+
+/// 命令行子命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// 运行监控循环（默认）
+    Monitor(MonitorOptions),
+    /// 汇总结果日志
+    Report(ReportOptions),
+    /// 输出实际生效的配置
+    DumpConfig(ConfigFormat),
+    /// 保存基线快照
+    BaselineSave(BaselineOptions),
+    /// 生成示例配置
+    Init(InitOptions),
+}
+
+/// 解析命令行参数（不含程序名）
+pub fn parse_args<I>(args: I) -> Result<Command>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+
+    let command = match args.next().as_deref() {
+        None => return Ok(Command::Monitor(MonitorOptions::default())),
+        Some(first @ ("--config" | "--test-server" | "--once" | "--check")) => {
+            let mut options = MonitorOptions::default();
+            let mut next = Some(first.to_string());
+            while let Some(arg) = next {
+                match arg.as_str() {
+                    "--config" => options.configs.push(PathBuf::from(value(&mut args, &arg)?)),
+                    "--test-server" => options.test_server = Some(value(&mut args, &arg)?),
+                    "--once" => options.once = true,
+                    "--check" => options.check = true,
+                    _ => anyhow::bail!("未知参数: {}\n\n{}", arg, USAGE),
+                }
+                next = args.next();
+            }
+            Command::Monitor(options)
+        }
+        Some("report") => {
+            let mut options = ReportOptions::default();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--since" => {
+                        options.since = Some(report::parse_duration(&value(&mut args, &arg)?)?)
+                    }
+                    "--interface" => options.interface = Some(value(&mut args, &arg)?),
+                    "--log" => options.log_path = Some(PathBuf::from(value(&mut args, &arg)?)),
+                    "--json" => options.json = true,
+                    _ => anyhow::bail!("未知参数: {}\n\n{}", arg, USAGE),
+                }
+            }
+            Command::Report(options)
+        }
+        Some("dump-config") => {
+            let mut format = ConfigFormat::Toml;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--format" => format = value(&mut args, &arg)?.parse()?,
+                    _ => anyhow::bail!("未知参数: {}\n\n{}", arg, USAGE),
+                }
+            }
+            Command::DumpConfig(format)
+        }
+        Some("--init") => {
+            let mut options = InitOptions::default();
+            for arg in args.by_ref() {
+                match arg.as_str() {
+                    "--force" => options.force = true,
+                    path if !path.starts_with("--") && options.path.is_none() => {
+                        options.path = Some(PathBuf::from(path))
+                    }
+                    _ => anyhow::bail!("未知参数: {}\n\n{}", arg, USAGE),
+                }
+            }
+            Command::Init(options)
+        }
+        Some("baseline") => {
+            match args.next().as_deref() {
+                Some("save") => {}
+                _ => anyhow::bail!("用法: routes-monitor baseline save [选项]\n\n{}", USAGE),
+            }
+            let mut options = BaselineOptions::default();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--since" => {
+                        options.since = Some(report::parse_duration(&value(&mut args, &arg)?)?)
+                    }
+                    "--log" => options.log_path = Some(PathBuf::from(value(&mut args, &arg)?)),
+                    "--output" => options.output = Some(PathBuf::from(value(&mut args, &arg)?)),
+                    _ => anyhow::bail!("未知参数: {}\n\n{}", arg, USAGE),
+                }
+            }
+            Command::BaselineSave(options)
+        }
+        Some(other) => anyhow::bail!("未知命令: {}\n\n{}", other, USAGE),
+    };
+
+    Ok(command)
+}
+
+/// 解析布尔型环境变量
+pub fn parse_flag(name: &str, value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "" | "0" | "false" | "no" | "off" => Ok(false),
+        _ => anyhow::bail!("{} 无效: {}", name, value),
+    }
+}
+
+/// 读取选项的值
+fn value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String> {
+    args.next()
+        .ok_or_else(|| anyhow::anyhow!("参数 {} 缺少值", option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn parse(args: &[&str]) -> Result<Command> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_report_args() {
+        assert_eq!(
+            parse(&[]).unwrap(),
+            Command::Monitor(MonitorOptions::default())
+        );
+        assert_eq!(
+            parse(&["--config", "a.toml", "--config", "b.toml"]).unwrap(),
+            Command::Monitor(MonitorOptions {
+                configs: vec![PathBuf::from("a.toml"), PathBuf::from("b.toml")],
+                test_server: None,
+                once: false,
+                check: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--test-server", "127.0.0.1:7", "--config", "a.toml"]).unwrap(),
+            Command::Monitor(MonitorOptions {
+                configs: vec![PathBuf::from("a.toml")],
+                test_server: Some("127.0.0.1:7".to_string()),
+                once: false,
+                check: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--once", "--config", "a.toml"]).unwrap(),
+            Command::Monitor(MonitorOptions {
+                configs: vec![PathBuf::from("a.toml")],
+                test_server: None,
+                once: true,
+                check: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--check"]).unwrap(),
+            Command::Monitor(MonitorOptions {
+                check: true,
+                ..MonitorOptions::default()
+            })
+        );
+        assert!(parse_flag("ROUTES_MONITOR_ONCE", "1").unwrap());
+        assert!(!parse_flag("ROUTES_MONITOR_ONCE", "").unwrap());
+        assert!(parse_flag("ROUTES_MONITOR_ONCE", "maybe").is_err());
+        assert!(parse(&["--config"]).is_err());
+
+        let command =
+            parse(&["report", "--since", "24h", "--interface", "wan_a", "--json"]).unwrap();
+        assert_eq!(
+            command,
+            Command::Report(ReportOptions {
+                since: Some(Duration::from_secs(86400)),
+                interface: Some("wan_a".to_string()),
+                json: true,
+                log_path: None,
+            })
+        );
+
+        assert!(parse(&["report", "--since"]).is_err());
+        assert_eq!(
+            parse(&["dump-config"]).unwrap(),
+            Command::DumpConfig(ConfigFormat::Toml)
+        );
+        assert_eq!(
+            parse(&["dump-config", "--format", "json"]).unwrap(),
+            Command::DumpConfig(ConfigFormat::Json)
+        );
+        assert!(parse(&["dump-config", "--format", "yaml"]).is_err());
+        assert_eq!(
+            parse(&["baseline", "save", "--since", "7d", "--output", "b.json"]).unwrap(),
+            Command::BaselineSave(BaselineOptions {
+                since: Some(Duration::from_secs(7 * 86400)),
+                log_path: None,
+                output: Some(PathBuf::from("b.json")),
+            })
+        );
+        assert!(parse(&["baseline"]).is_err());
+        assert_eq!(
+            parse(&["--init"]).unwrap(),
+            Command::Init(InitOptions::default())
+        );
+        assert_eq!(
+            parse(&["--init", "/etc/routes-monitor/config.toml", "--force"]).unwrap(),
+            Command::Init(InitOptions {
+                path: Some(PathBuf::from("/etc/routes-monitor/config.toml")),
+                force: true,
+            })
+        );
+        assert!(parse(&["--init", "a.toml", "b.toml"]).is_err());
+        assert!(parse(&["bogus"]).is_err());
+    }
+}
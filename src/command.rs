@@ -0,0 +1,339 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::io;
+use std::process::{ExitStatus, Output, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// 限时执行命令的输出
+#[derive(Debug)]
+pub struct TimedOutput {
+    /// 标准输出（超时时为已读取的部分）
+    pub stdout: Vec<u8>,
+    /// 标准错误（超时时为空）
+    pub stderr: Vec<u8>,
+    /// 退出状态，超时被终止时为 `None`
+    pub status: Option<ExitStatus>,
+}
+
+/// 外部命令执行器
+///
+/// 所有对 uci/ip 等系统命令的调用都经由此接口，便于在测试中替换为模拟实现
+pub trait CommandRunner: Send + Sync {
+    /// 执行命令并等待其输出
+    fn run<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [&'a str],
+    ) -> BoxFuture<'a, io::Result<Output>>;
+
+    /// 在 `limit` 内执行命令，超时时终止进程并保留已输出的内容
+    fn run_with_timeout<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [&'a str],
+        limit: Duration,
+    ) -> BoxFuture<'a, io::Result<TimedOutput>>;
+
+    /// 将 `input` 写入标准输入并在 `limit` 内执行命令，超时时终止进程并返回错误
+    fn run_with_input<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [&'a str],
+        input: &'a [u8],
+        limit: Duration,
+    ) -> BoxFuture<'a, io::Result<Output>>;
+}
+
+/// 调用系统命令的执行器
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [&'a str],
+    ) -> BoxFuture<'a, io::Result<Output>> {
+        Command::new(program).args(args).output().boxed()
+    }
+
+    fn run_with_timeout<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [&'a str],
+        limit: Duration,
+    ) -> BoxFuture<'a, io::Result<TimedOutput>> {
+        async move {
+            let mut child = Command::new(program)
+                .args(args)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+
+            let Some(stdout) = child.stdout.take() else {
+                return Err(io::Error::other("无法读取命令输出"));
+            };
+
+            let (stdout, completed) = read_with_timeout(stdout, limit).await;
+            let mut stderr = Vec::new();
+            let status = if completed {
+                // 标准输出已结束，进程即将退出，错误输出很短
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_end(&mut stderr).await;
+                }
+                Some(child.wait().await?)
+            } else {
+                let _ = child.kill().await;
+                None
+            };
+
+            Ok(TimedOutput {
+                stdout,
+                stderr,
+                status,
+            })
+        }
+        .boxed()
+    }
+
+    fn run_with_input<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [&'a str],
+        input: &'a [u8],
+        limit: Duration,
+    ) -> BoxFuture<'a, io::Result<Output>> {
+        async move {
+            let mut child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+
+            let run = async {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // 程序可能不读取输入就退出，写入失败不影响结果
+                    let _ = stdin.write_all(input).await;
+                }
+                child.wait_with_output().await
+            };
+
+            match timeout(limit, run).await {
+                Ok(output) => output,
+                Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "命令执行超时")),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// 在超时时间内读取全部输出
+/// 返回: (已读取的内容, 是否在超时前读到结尾)，超时时保留已读取的部分
+async fn read_with_timeout<R: AsyncRead + Unpin>(
+    mut reader: R,
+    limit: Duration,
+) -> (Vec<u8>, bool) {
+    let mut output = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    let completed = timeout(limit, async {
+        loop {
+            match reader.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => output.extend_from_slice(&chunk[..n]),
+            }
+        }
+    })
+    .await
+    .is_ok();
+
+    (output, completed)
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::sync::Mutex;
+
+    type Handler = Box<dyn Fn(&str) -> Output + Send + Sync>;
+
+    /// 记录调用并按命令行返回预设输出的模拟执行器
+    pub struct MockRunner {
+        calls: Mutex<Vec<String>>,
+        /// 经标准输入传给命令的内容
+        inputs: Mutex<Vec<String>>,
+        handler: Handler,
+        /// 以指定前缀开头的命令在返回前等待的时间
+        delays: Vec<(String, Duration)>,
+        /// 以指定前缀开头的命令无法执行
+        failures: Vec<String>,
+    }
+
+    impl MockRunner {
+        /// 使用处理函数创建，参数为以空格拼接的完整命令行
+        pub fn new<F>(handler: F) -> Self
+        where
+            F: Fn(&str) -> Output + Send + Sync + 'static,
+        {
+            Self {
+                calls: Mutex::new(Vec::new()),
+                inputs: Mutex::new(Vec::new()),
+                handler: Box::new(handler),
+                delays: Vec::new(),
+                failures: Vec::new(),
+            }
+        }
+
+        /// 以指定前缀开头的命令执行出错（如程序不存在）
+        pub fn with_failure(mut self, prefix: &str) -> Self {
+            self.failures.push(prefix.to_string());
+            self
+        }
+
+        /// 以指定前缀开头的命令等待 `delay` 后才返回
+        pub fn with_delay(mut self, prefix: &str, delay: Duration) -> Self {
+            self.delays.push((prefix.to_string(), delay));
+            self
+        }
+
+        /// 已执行的命令行
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// 经标准输入传给命令的内容
+        pub fn inputs(&self) -> Vec<String> {
+            self.inputs.lock().unwrap().clone()
+        }
+
+        /// 以指定前缀开头的命令执行次数
+        pub fn count(&self, prefix: &str) -> usize {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|c| c.starts_with(prefix))
+                .count()
+        }
+    }
+
+    impl CommandRunner for MockRunner {
+        fn run<'a>(
+            &'a self,
+            program: &'a str,
+            args: &'a [&'a str],
+        ) -> BoxFuture<'a, io::Result<Output>> {
+            let line = std::iter::once(program)
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let output = (self.handler)(&line);
+            let delay = self
+                .delays
+                .iter()
+                .find(|(prefix, _)| line.starts_with(prefix.as_str()))
+                .map(|(_, delay)| *delay);
+            let failed = self.failures.iter().any(|prefix| line.starts_with(prefix));
+            self.calls.lock().unwrap().push(line);
+            async move {
+                if let Some(delay) = delay {
+                    tokio::time::sleep(delay).await;
+                }
+                if failed {
+                    return Err(io::Error::new(io::ErrorKind::NotFound, "命令不存在"));
+                }
+                Ok(output)
+            }
+            .boxed()
+        }
+
+        fn run_with_timeout<'a>(
+            &'a self,
+            program: &'a str,
+            args: &'a [&'a str],
+            _limit: Duration,
+        ) -> BoxFuture<'a, io::Result<TimedOutput>> {
+            self.run(program, args)
+                .map(|output| {
+                    output.map(|output| TimedOutput {
+                        stdout: output.stdout,
+                        stderr: output.stderr,
+                        status: Some(output.status),
+                    })
+                })
+                .boxed()
+        }
+
+        fn run_with_input<'a>(
+            &'a self,
+            program: &'a str,
+            args: &'a [&'a str],
+            input: &'a [u8],
+            _limit: Duration,
+        ) -> BoxFuture<'a, io::Result<Output>> {
+            self.inputs
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(input).into_owned());
+            self.run(program, args)
+        }
+    }
+
+    /// 构造命令输出
+    pub fn output(code: i32, stdout: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// 构造只有错误输出的失败命令
+    pub fn error_output(code: i32, stderr: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: Vec::new(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_with_timeout_keeps_partial_output() {
+        // 命令输出两行后卡住
+        let (mut writer, reader) = tokio::io::duplex(1024);
+        writer
+            .write_all(b"64 bytes from 8.8.8.8: icmp_seq=1 ttl=117 time=10.0 ms\n")
+            .await
+            .unwrap();
+
+        let (output, completed) = read_with_timeout(reader, Duration::from_secs(20)).await;
+        assert!(!completed);
+        assert_eq!(
+            output,
+            b"64 bytes from 8.8.8.8: icmp_seq=1 ttl=117 time=10.0 ms\n"
+        );
+
+        // 输出结束时正常返回
+        drop(writer);
+        let (_, reader) = tokio::io::duplex(16);
+        let (output, completed) = read_with_timeout(reader, Duration::from_secs(20)).await;
+        assert!(completed && output.is_empty());
+    }
+}
@@ -4,23 +4,81 @@ use anyhow::{Context, Result};
 // This software is licensed under CC BY-NC 4.0
 // Attribution required, Commercial use prohibited
 
+use ipnetwork::IpNetwork;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// 主配置结构体
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     /// 全局设置
+    #[serde(default)]
     pub global: GlobalConfig,
     /// 网络接口列表
     pub interfaces: Vec<NetworkInterface>,
-    /// 要监控的目标 IP 列表
+    /// 要监控的目标 IP 列表（可由 RM_TARGETS 环境变量提供）
+    #[serde(default)]
     pub targets: Vec<TargetIP>,
+    /// 由本程序管理 UCI 静态路由的地址（未配置时使用 targets）
+    #[serde(default)]
+    pub managed_routes: Option<Vec<ManagedRoute>>,
+    /// 主目标全部失败时用于确认接口是否真正断开的目标
+    #[serde(default)]
+    pub confirm_targets: Vec<ConfirmTarget>,
+    /// HTTP 接口（未配置时不启动）
+    #[serde(default)]
+    pub http: Option<HttpConfig>,
+    /// 本地控制套接字（未配置时不启动）
+    #[serde(default)]
+    pub control: Option<ControlConfig>,
+    /// 由多个物理接口组成的虚拟接口
+    #[serde(default)]
+    pub virtual_interfaces: Vec<VirtualInterface>,
+    /// 切换通知（未配置时不发送）
+    #[serde(default)]
+    pub notify: Option<NotifyConfig>,
+}
+
+/// 切换通知配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotifyConfig {
+    /// Webhook 地址（Slack、Discord 等）
+    pub webhook_url: String,
+    /// 请求体模板，支持 `{from}`、`{to}`、`{score}`、`{previous_score}`、`{reason}`、`{timestamp}`
+    /// 占位符；未设置时发送 JSON 格式的切换详情
+    #[serde(default)]
+    pub template: Option<String>,
+    /// 请求超时（秒）
+    #[serde(default = "default_notify_timeout")]
+    pub timeout: u64,
+}
+
+fn default_notify_timeout() -> u64 {
+    5
+}
+
+/// HTTP 接口配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HttpConfig {
+    /// 监听地址，例如 "127.0.0.1:9420"
+    pub listen: String,
+}
+
+/// 本地控制套接字配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ControlConfig {
+    /// Unix 套接字路径，例如 "/var/run/routes-monitor.sock"
+    pub socket: PathBuf,
 }
 
 /// 全局配置
+///
+/// 未配置的字段使用 [`GlobalConfig::default`] 中的默认值
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct GlobalConfig {
     /// 检查间隔（秒）
     pub check_interval: u64,
@@ -28,15 +86,596 @@ pub struct GlobalConfig {
     pub timeout: u64,
     /// 并发测试数量
     pub concurrent_tests: usize,
+    /// 同时进行的速度测试数量（所有接口共享）
+    #[serde(default = "default_concurrent_speed_tests")]
+    pub concurrent_speed_tests: usize,
     /// 连续失败多少次才切换接口
     pub failure_threshold: u32,
     /// 日志级别 (trace, debug, info, warn, error)
     pub log_level: String,
+    /// 日志格式
+    #[serde(default)]
+    pub log_format: LogFormat,
     /// 是否启用自动切换
     pub auto_switch: bool,
+    /// 路由模式：故障转移或多接口负载均衡
+    #[serde(default)]
+    pub mode: RoutingMode,
+    /// 负载均衡模式下，评分与最佳接口相差不超过该值的接口共同分担流量
+    #[serde(default = "default_balance_threshold")]
+    pub balance_threshold: f64,
     /// 是否管理UCI静态路由（修改/etc/config/network）
     #[serde(default)]
     pub manage_uci_routes: bool,
+    /// 演练模式：照常评分和决策，只记录将要执行的切换，不修改路由和 UCI 配置
+    #[serde(default)]
+    pub dry_run: bool,
+    /// 严格校验：将配置警告视为错误
+    #[serde(default)]
+    pub strict_validation: bool,
+    /// 结果日志路径（JSONL，供 report 命令汇总）
+    #[serde(default)]
+    pub results_log: Option<String>,
+    /// SQLite 数据库路径，记录每轮检查的接口评分和切换事件
+    #[serde(default)]
+    pub sqlite_path: Option<String>,
+    /// SQLite 数据保留天数（0 表示不清理）
+    #[serde(default = "default_sqlite_retention_days")]
+    pub sqlite_retention_days: u64,
+    /// 连通性探测方式（也可写作 `probe_backend`）
+    #[serde(default, alias = "probe_backend")]
+    pub prober: Prober,
+    /// `prober = "ping"` 时使用的 ping 程序
+    #[serde(default)]
+    pub ping_tool: PingTool,
+    /// 接口网关解析结果的缓存时间（秒）
+    #[serde(default = "default_gateway_cache_ttl")]
+    pub gateway_cache_ttl: u64,
+    /// 域名目标经系统解析器解析结果的缓存时间（秒）
+    #[serde(default = "default_dns_cache_ttl")]
+    pub dns_cache_ttl: u64,
+    /// 被切走的接口在回切惩罚期开始时扣除的评分
+    #[serde(default = "default_failback_penalty")]
+    pub failback_penalty: f64,
+    /// 回切惩罚线性衰减到 0 所需的时间（秒，0 表示禁用）
+    #[serde(default)]
+    pub failback_penalty_window: u64,
+    /// 最佳接口须领先当前接口的分数，不足时视为当前接口仍为最佳（0 表示禁用）
+    #[serde(default)]
+    pub switch_margin: f64,
+    /// 回切到被切走的接口时要求的初始领先分数（0 表示禁用）
+    #[serde(default)]
+    pub failback_margin: f64,
+    /// 回切领先分数线性衰减到 0 所需的时间（秒）
+    #[serde(default = "default_failback_margin_window")]
+    pub failback_margin_window: u64,
+    /// 禁止自动切换的时间窗口（cron 表达式，按本地时间匹配）
+    #[serde(default)]
+    pub freeze_windows: Vec<String>,
+    /// 速度评分方式
+    #[serde(default)]
+    pub speed_scoring: SpeedScoring,
+    /// 延迟评分使用的统计量
+    #[serde(default)]
+    pub latency_metric: LatencyMetric,
+    /// 捕获单次检查中的 panic 并继续下一轮，而不是终止进程
+    #[serde(default)]
+    pub recover_from_panics: bool,
+    /// 每轮检查时读取 UCI 中接口的 disabled 状态，跳过已在 OpenWrt 中禁用的接口
+    #[serde(default)]
+    pub respect_uci_disabled: bool,
+    /// ping 命令整体超时时的丢包计算方式
+    #[serde(default)]
+    pub timeout_loss: TimeoutLoss,
+    /// 本轮在所有接口上都不可达的目标不参与评分
+    #[serde(default)]
+    pub ignore_universally_down_targets: bool,
+    /// TCP/UDP 探测时每次使用随机源端口
+    #[serde(default)]
+    pub randomize_source_port: bool,
+    /// 继续探测已禁用的接口（作为备用展示评分，不参与选择）
+    #[serde(default)]
+    pub probe_disabled_interfaces: bool,
+    /// 切换方式
+    #[serde(default)]
+    pub switch_style: SwitchStyle,
+    /// 读写 UCI 路由和重载网络的方式
+    #[serde(default)]
+    pub backend: Backend,
+    /// 本程序使用的策略路由规则起始优先级
+    #[serde(default = "default_rule_priority_base")]
+    pub rule_priority_base: u32,
+    /// 本程序使用的策略路由规则优先级数量（从起始优先级开始）
+    #[serde(default = "default_rule_priority_range")]
+    pub rule_priority_range: u32,
+    /// 排空切换时等待旧接口上现有连接结束的时间（秒）
+    #[serde(default = "default_drain_time")]
+    pub drain_time: u64,
+    /// 正式测量前先向目标发送一次预热 ping（结果丢弃）
+    #[serde(default)]
+    pub warmup_ping: bool,
+    /// 每次探测发送的 ping 报文数
+    #[serde(default = "default_ping_count")]
+    pub ping_count: u32,
+    /// ping 负载大小（字节），调大可发现 MTU 问题
+    #[serde(default = "default_ping_size")]
+    pub ping_size: u32,
+    /// 在 /metrics 中输出逐目标的指标（序列数较多）
+    #[serde(default)]
+    pub metrics_per_target: bool,
+    /// 各检查类型的权重，配置后每个目标的健康度按加权成功比例计算（0.0-1.0）
+    #[serde(default)]
+    pub check_weights: Option<CheckWeights>,
+    /// 新出现的接口需连续观察多少轮检查后才可参与选择（0 表示不观察）
+    #[serde(default)]
+    pub new_interface_probation: u32,
+    /// 向 keepalive 接口发送保活 ping 的间隔（秒，0 表示禁用）
+    #[serde(default)]
+    pub keepalive_interval: u64,
+    /// 目标连续多少轮在所有接口上不可达后降低探测频率（0 表示禁用）
+    #[serde(default)]
+    pub dead_target_threshold: u32,
+    /// 降低频率后每隔多少轮重新探测一次
+    #[serde(default = "default_dead_target_reprobe_every")]
+    pub dead_target_reprobe_every: u32,
+    /// 将 warn/error 日志与切换事件写入系统日志（logread 可见）
+    #[serde(default)]
+    pub syslog: bool,
+    /// 切换后按路由表探测关键目标，确认流量确实经由新接口，失败时回滚
+    #[serde(default)]
+    pub functional_verify: bool,
+    /// 评分波动惩罚系数：按最近 stability_window 轮评分的标准差扣分（0 表示禁用）
+    #[serde(default)]
+    pub stability_weight: f64,
+    /// 计算评分波动的滚动窗口（检查轮数）
+    #[serde(default = "default_stability_window")]
+    pub stability_window: usize,
+    /// 评分指数移动平均的平滑系数 alpha（0-1，本轮评分的占比，1 表示不平滑）
+    #[serde(default = "default_score_smoothing")]
+    pub score_smoothing: f64,
+    /// 网络重载后静态路由仍未生效时改用 restart 重启网络服务（会中断连接）
+    #[serde(default)]
+    pub allow_network_restart: bool,
+    /// 每轮最多探测次数（目标 × 接口，0 表示不限制），关键目标总会探测
+    #[serde(default)]
+    pub max_probes_per_cycle: usize,
+    /// 最近一小时内最多切换次数（0 表示不限制），达到后暂停切换
+    #[serde(default)]
+    pub max_switches_per_hour: u32,
+    /// 切换后的冷却时间（秒，0 表示不限制），期间不按评分再次切换
+    #[serde(default)]
+    pub switch_cooldown: u64,
+    /// 保留最近多少轮检查记录供 `GET /history` 查询（0 表示不记录）
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+    /// 固定使用的接口：该接口仍有可达目标时不切换到其他接口
+    #[serde(default)]
+    pub pinned_interface: Option<String>,
+    /// 覆盖配置文件路径（相对路径基于主配置文件所在目录），合并到本配置之上
+    #[serde(default)]
+    pub override_file: Option<String>,
+    /// 丢包率低于该值才视为可达（0-1，默认 1.0 即有任一应答就算可达）
+    #[serde(default = "default_reachability_loss_threshold")]
+    pub reachability_loss_threshold: f64,
+    /// 可达目标比例低于该值（0-1）的接口不参与选择
+    #[serde(default = "default_min_reachable_ratio")]
+    pub min_reachable_ratio: f64,
+    /// ping 出现部分丢包时立即补测的最多次数，结果取平均后再记录（0 表示不补测）
+    #[serde(default)]
+    pub retry_lossy_probes: u32,
+    /// 外部评分命令：每个接口的测试结果以 JSON 写入标准输入，标准输出为评分
+    #[serde(default)]
+    pub scorer_command: Option<String>,
+    /// 系统负载（1 分钟平均）超过该值时推迟速度测试，连通性探测照常进行
+    #[serde(default)]
+    pub max_load: Option<f64>,
+    /// 基线快照文件（`baseline save` 生成），配置后将实时评分与基线比较
+    #[serde(default)]
+    pub baseline_file: Option<String>,
+    /// 偏离基线的倍数：延迟达到基线的该倍数或评分降到基线的该分之一时告警
+    #[serde(default = "default_baseline_deviation")]
+    pub baseline_deviation: f64,
+    /// 切换前执行的检查命令，退出状态非 0 时取消切换
+    #[serde(default)]
+    pub pre_switch_command: Option<String>,
+    /// 切换后执行的检查命令，退出状态非 0 时回滚到原接口
+    #[serde(default)]
+    pub post_switch_command: Option<String>,
+    /// 切换前后检查命令的超时时间（秒）
+    #[serde(default = "default_switch_command_timeout")]
+    pub switch_command_timeout: u64,
+    /// 探测前读取接口收发计数估算带宽利用率
+    #[serde(default)]
+    pub measure_utilization: bool,
+    /// 利用率达到该值（0-1）的接口视为饱和，推迟其速度测试
+    #[serde(default = "default_saturation_threshold")]
+    pub saturation_threshold: f64,
+    /// 选择接口的优化目标
+    #[serde(default)]
+    pub optimize_for: OptimizeFor,
+    /// 按性价比选择时成本的权重，评分除以 `1 + cost_weight * cost`
+    #[serde(default = "default_cost_weight")]
+    pub cost_weight: f64,
+    /// 评分权重与归一化基准
+    #[serde(default)]
+    pub scoring: ScoringWeights,
+}
+
+/// 综合评分中各指标的权重与归一化基准
+///
+/// 各项指标先归一化到 0-100 分，再按权重加权求和；权重按其总和折算为比例，评分范围为 0-100
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScoringWeights {
+    /// 可达目标比例的权重
+    pub weight_reachable: f64,
+    /// 延迟的权重
+    pub weight_latency: f64,
+    /// 丢包率的权重
+    pub weight_packet_loss: f64,
+    /// 下载速度的权重
+    pub weight_speed: f64,
+    /// 抖动的权重（默认 0，不计入评分）
+    pub weight_jitter: f64,
+    /// 延迟基准（毫秒）：延迟不高于该值得满分，更高时按反比递减
+    pub latency_reference_ms: f64,
+    /// 抖动基准（毫秒）：抖动不高于该值得满分，更高时按反比递减
+    pub jitter_reference_ms: f64,
+    /// 速度基准（KB/s）：绝对速度评分下达到该速度得满分
+    pub speed_reference_kb: f64,
+}
+
+impl ScoringWeights {
+    /// 按总和折算为比例的权重（总和为 0 时保持不变，配置校验会拒绝这种情况）
+    pub fn normalized(self) -> Self {
+        let total = self.weight_reachable
+            + self.weight_latency
+            + self.weight_packet_loss
+            + self.weight_speed
+            + self.weight_jitter;
+        if total <= 0.0 {
+            return self;
+        }
+        Self {
+            weight_reachable: self.weight_reachable / total,
+            weight_latency: self.weight_latency / total,
+            weight_packet_loss: self.weight_packet_loss / total,
+            weight_speed: self.weight_speed / total,
+            weight_jitter: self.weight_jitter / total,
+            ..self
+        }
+    }
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        Self {
+            weight_reachable: 0.30,
+            weight_latency: 0.10,
+            weight_packet_loss: 0.20,
+            weight_speed: 0.40,
+            weight_jitter: 0.0,
+            latency_reference_ms: 10.0,
+            jitter_reference_ms: 2.0,
+            speed_reference_kb: 1024.0,
+        }
+    }
+}
+
+/// 各检查类型在目标健康度中的权重
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CheckWeights {
+    /// ICMP（ping）
+    pub icmp: f64,
+    /// HTTP（test_url 下载）
+    pub http: f64,
+}
+
+impl Default for CheckWeights {
+    fn default() -> Self {
+        Self {
+            icmp: 1.0,
+            http: 1.0,
+        }
+    }
+}
+
+/// 评分偏移允许的最大绝对值（评分范围为 0-100）
+const MAX_SCORE_OFFSET: f64 = 50.0;
+
+fn default_gateway_cache_ttl() -> u64 {
+    30
+}
+
+fn default_dns_cache_ttl() -> u64 {
+    300
+}
+
+fn default_failback_penalty() -> f64 {
+    20.0
+}
+
+fn default_failback_margin_window() -> u64 {
+    3600
+}
+
+fn default_concurrent_speed_tests() -> usize {
+    1
+}
+
+fn default_balance_threshold() -> f64 {
+    10.0
+}
+
+fn default_drain_time() -> u64 {
+    30
+}
+
+fn default_dead_target_reprobe_every() -> u32 {
+    10
+}
+
+fn default_rule_priority_base() -> u32 {
+    100
+}
+
+fn default_rule_priority_range() -> u32 {
+    900
+}
+
+fn default_score_smoothing() -> f64 {
+    1.0
+}
+
+fn default_stability_window() -> usize {
+    10
+}
+
+fn default_history_size() -> usize {
+    60
+}
+
+fn default_saturation_threshold() -> f64 {
+    0.9
+}
+
+fn default_sqlite_retention_days() -> u64 {
+    30
+}
+
+fn default_cost_weight() -> f64 {
+    1.0
+}
+
+fn default_switch_command_timeout() -> u64 {
+    30
+}
+
+fn default_baseline_deviation() -> f64 {
+    3.0
+}
+
+fn default_reachability_loss_threshold() -> f64 {
+    1.0
+}
+
+fn default_min_reachable_ratio() -> f64 {
+    0.5
+}
+
+/// 示例配置的文件头
+const EXAMPLE_HEADER: &str = "\
+# RoutesMonitor 配置文件（由 `routes-monitor --init` 生成）
+#
+# 全局设置均为默认值，各参数的完整说明见 README 的「配置说明」一节。
+# 请按实际环境修改接口名称（OpenWrt 逻辑接口名，如 wan、wwan）和探测目标。
+
+";
+
+/// 示例配置的种子：只包含接口和目标，其余使用默认值
+const EXAMPLE_SEED: &str = r#"
+[[interfaces]]
+name = "wan"
+display_name = "主线路"
+priority = 1
+enabled = true
+
+[[interfaces]]
+name = "wwan"
+display_name = "备用 4G"
+priority = 2
+enabled = true
+
+[[targets]]
+address = "8.8.8.8"
+description = "Google DNS"
+weight = 1.0
+
+[[targets]]
+address = "1.1.1.1"
+description = "Cloudflare DNS"
+weight = 1.0
+"#;
+
+/// 示例配置中各配置段和常用参数的注释
+const EXAMPLE_COMMENTS: &[(&str, &str)] = &[
+    (
+        "confirm_targets",
+        "主目标全部失败时用于确认接口是否真正断开的目标",
+    ),
+    ("virtual_interfaces", "由多个物理接口组成的虚拟接口"),
+    ("global", "全局设置"),
+    ("global.check_interval", "检查间隔（秒）"),
+    ("global.timeout", "单次探测超时时间（秒）"),
+    ("global.concurrent_tests", "每个接口同时探测的目标数"),
+    (
+        "global.failure_threshold",
+        "当前接口连续多少轮不是最佳接口才切换",
+    ),
+    (
+        "global.log_level",
+        "日志级别: trace、debug、info、warn、error",
+    ),
+    ("global.auto_switch", "是否自动切换接口"),
+    (
+        "global.mode",
+        "路由模式: failover（故障转移）或 balance（多接口按评分分流）",
+    ),
+    (
+        "global.manage_uci_routes",
+        "是否管理 /etc/config/network 中的 UCI 静态路由",
+    ),
+    (
+        "global.dry_run",
+        "演练模式：只记录将要执行的切换，不修改路由",
+    ),
+    ("global.prober", "连通性探测方式: ping、native 或 raw"),
+    (
+        "global.switch_style",
+        "切换方式: immediate 或 drain（排空现有连接后再切换）",
+    ),
+    ("global.backend", "读写 UCI 的方式: shell 或 ubus"),
+    ("global.ping_count", "每次探测发送的 ping 报文数"),
+    ("global.ping_size", "ping 负载大小（字节）"),
+    ("global.scoring", "评分权重与归一化基准"),
+    (
+        "interfaces",
+        "网络接口：priority 越小越优先，gateway 留空时自动获取",
+    ),
+    ("targets", "探测目标：可设置 test_url 进行速度测试"),
+];
+
+/// 配置输出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => anyhow::bail!("未知的配置格式: {}（可选 toml、json）", s),
+        }
+    }
+}
+
+/// 连通性探测方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prober {
+    /// 调用 ping 命令（也可写作 `command`）
+    #[default]
+    #[serde(alias = "command")]
+    Ping,
+    /// 使用无特权 ICMP 套接字，失败时回退到 ping 命令
+    Native,
+    /// 使用 ICMP 原始套接字（需 root 或 CAP_NET_RAW），失败时回退到 ping 命令
+    Raw,
+}
+
+/// ping 程序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PingTool {
+    /// 每个目标调用一次 ping
+    #[default]
+    Ping,
+    /// 每个接口调用一次 fping 批量探测所有 IP 目标
+    Fping,
+}
+
+/// 选择接口的优化目标
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizeFor {
+    /// 只看链路表现
+    #[default]
+    Performance,
+    /// 按接口成本折算评分（性价比），成本低的链路在表现相近时优先
+    Value,
+}
+
+/// 速度评分方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedScoring {
+    /// 以速度基准（默认 1MB/s）为满分
+    #[default]
+    Absolute,
+    /// 相对本轮最快接口按比例计分
+    Relative,
+}
+
+/// 路由模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingMode {
+    /// 所有流量走评分最高的接口，失败后切换
+    #[default]
+    Failover,
+    /// 评分接近的接口按评分比例设置多路径默认路由
+    Balance,
+}
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 纯文本
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象，结构化字段作为同级字段
+    Json,
+}
+
+/// 延迟评分使用的统计量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LatencyMetric {
+    /// 各目标平均延迟的平均值
+    #[default]
+    Mean,
+    /// 逐包往返时间的 95 百分位，偶发尖峰的链路得分更低
+    P95,
+}
+
+/// ping 命令整体超时时的丢包计算方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutLoss {
+    /// 根据超时前已输出的应答计算丢包率和延迟
+    #[default]
+    Partial,
+    /// 视为全部丢包
+    Total,
+}
+
+/// 切换方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchStyle {
+    /// 直接切换
+    #[default]
+    Immediate,
+    /// 先以较高 metric 添加新默认路由，等待现有连接排空后再删除旧路由
+    Drain,
+}
+
+/// 读写 UCI 路由和重载网络的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// 调用 uci 和 /etc/init.d/network 命令
+    #[default]
+    Shell,
+    /// 通过 ubus 调用 uci 和 network 对象
+    Ubus,
 }
 
 /// 网络接口配置
@@ -54,6 +693,107 @@ pub struct NetworkInterface {
     pub table_id: Option<u32>,
     /// 网关地址
     pub gateway: Option<String>,
+    /// 评分偏移（加到计算出的评分上，用于人工微调）
+    #[serde(default)]
+    pub score_offset: f64,
+    /// 探测域名目标时使用的 DNS 服务器（未设置时使用系统解析）
+    #[serde(default)]
+    pub resolver: Option<String>,
+    /// 额外探测的 VLAN 子接口
+    #[serde(default)]
+    pub vlans: Vec<VlanProbe>,
+    /// 接口与各 VLAN 探测结果的合并方式
+    #[serde(default)]
+    pub vlan_aggregation: VlanAggregation,
+    /// 未被选中时定期发送保活 ping，防止链路因空闲断开
+    #[serde(default)]
+    pub keepalive: bool,
+    /// 该接口专用的探测目标（替换全局 targets）
+    #[serde(default)]
+    pub targets: Option<Vec<TargetIP>>,
+    /// 追加在全局（或 targets 覆盖后）目标之后的额外目标
+    #[serde(default)]
+    pub extra_targets: Vec<TargetIP>,
+    /// 同一链路上的备用网关：每轮临时添加经该网关的 /32 路由单独探测，结果只用于报告
+    #[serde(default)]
+    pub alternate_gateways: Vec<String>,
+    /// 接口支持的地址族，单栈接口只探测和路由对应地址族的目标
+    #[serde(default)]
+    pub family: AddressFamily,
+    /// 链路带宽（Mbps），用于计算带宽利用率
+    #[serde(default)]
+    pub bandwidth_mbps: Option<f64>,
+    /// 相对成本（任意单位，0 表示免费），`optimize_for = "value"` 时使用
+    #[serde(default)]
+    pub cost: f64,
+    /// 该接口作为当前接口时覆盖全局的失败阈值
+    #[serde(default)]
+    pub failure_threshold: Option<u32>,
+}
+
+impl NetworkInterface {
+    /// 该接口实际使用的探测目标：`targets` 覆盖全局列表，`extra_targets` 追加在其后，
+    /// 并排除接口地址族不支持的目标
+    pub fn effective_targets(&self, global: &[TargetIP]) -> Vec<TargetIP> {
+        let base = self.targets.as_deref().unwrap_or(global);
+        base.iter()
+            .chain(&self.extra_targets)
+            .filter(|t| self.family.supports(&t.address))
+            .cloned()
+            .collect()
+    }
+}
+
+/// 接口支持的地址族
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    /// 仅 IPv4
+    V4,
+    /// 仅 IPv6
+    V6,
+    /// 双栈
+    #[default]
+    Dual,
+}
+
+impl AddressFamily {
+    /// 是否支持该地址（可带 CIDR 后缀）；域名的地址族由解析结果决定，总是支持
+    pub fn supports(self, address: &str) -> bool {
+        let ip = address.split('/').next().unwrap_or(address);
+        match (self, ip.parse::<std::net::IpAddr>()) {
+            (AddressFamily::V4, Ok(ip)) => ip.is_ipv4(),
+            (AddressFamily::V6, Ok(ip)) => ip.is_ipv6(),
+            _ => true,
+        }
+    }
+}
+
+/// VLAN 子接口探测配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VlanProbe {
+    /// VLAN ID
+    pub id: u16,
+    /// 承载 VLAN 的物理设备（例如 eth0）
+    pub device: String,
+}
+
+impl VlanProbe {
+    /// VLAN 子接口的设备名，例如 eth0.100
+    pub fn device_name(&self) -> String {
+        format!("{}.{}", self.device, self.id)
+    }
+}
+
+/// 接口与各 VLAN 探测结果的合并方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VlanAggregation {
+    /// 取最差的结果（任一 VLAN 不可达即视为目标不可达）
+    #[default]
+    Worst,
+    /// 取平均值，可达比例按 VLAN 数计算
+    Average,
 }
 
 /// 目标 IP 配置
@@ -63,48 +803,684 @@ pub struct TargetIP {
     pub address: String,
     /// 描述
     pub description: String,
-    /// 测试 URL（用于速度测试，可选）
+    /// 测试 URL（用于速度测试，可选；上传测速时为接收上传的地址）
     pub test_url: Option<String>,
+    /// 速度测试方向
+    #[serde(default)]
+    pub speed_direction: SpeedDirection,
     /// 权重（影响速度评分）
     pub weight: f64,
+    /// 关键目标：若只有一个接口可达，则将其路由固定到该接口
+    #[serde(default)]
+    pub critical: bool,
+    /// 每隔多少轮检查探测一次（其余轮次沿用上次结果）
+    #[serde(default = "default_check_every")]
+    pub check_every: u32,
+    /// 探测报文使用的 DSCP 值（0-63），用于验证带 QoS 标记的流量所走的路径
+    #[serde(default)]
+    pub dscp: Option<u8>,
+    /// 连通性探测方式
+    #[serde(default)]
+    pub probe: ProbeType,
+    /// TCP 探测的目标端口
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// 覆盖全局的每次探测 ping 报文数
+    #[serde(default)]
+    pub ping_count: Option<u32>,
+    /// 覆盖全局的 ping 负载大小（字节）
+    #[serde(default)]
+    pub ping_size: Option<u32>,
+}
+
+/// 速度测试方向
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedDirection {
+    /// 从 `test_url` 下载
+    #[default]
+    Download,
+    /// 向 `test_url` 上传（`curl -T`），适用于关心上行带宽的非对称链路
+    Upload,
+}
+
+/// 目标的连通性探测方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeType {
+    /// ICMP echo（ping）
+    #[default]
+    Icmp,
+    /// TCP 连接（适用于屏蔽 ICMP 的目标）
+    Tcp,
+}
+
+/// 本程序可使用的最大策略路由优先级（32766 起为 main/default 表的系统规则）
+const MAX_RULE_PRIORITY: u32 = 32765;
+
+/// DSCP 的最大值（6 位）
+const MAX_DSCP: u8 = 63;
+
+/// ping 默认的负载大小（字节）
+pub const DEFAULT_PING_SIZE: u32 = 56;
+
+/// ping 负载的最大值（IPv4 报文 65535 字节减去 IP 与 ICMP 首部）
+const MAX_PING_SIZE: u32 = 65507;
+
+impl TargetIP {
+    /// 探测报文的 ToS 字节（DSCP 左移 2 位，ECN 位为 0）
+    pub fn tos(&self) -> Option<u8> {
+        self.dscp.map(|dscp| dscp << 2)
+    }
+}
+
+fn default_check_every() -> u32 {
+    1
+}
+
+fn default_ping_count() -> u32 {
+    4
 }
 
-impl Config {
-    /// 从文件加载配置
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("无法读取配置文件: {:?}", path.as_ref()))?;
+fn default_ping_size() -> u32 {
+    DEFAULT_PING_SIZE
+}
+
+/// 托管路由配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ManagedRoute {
+    /// IP 地址或网段
+    pub address: String,
+    /// 描述
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// 虚拟接口：将多条物理链路作为一个整体参与比较和选择
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VirtualInterface {
+    /// 虚拟接口名称（不能与物理接口重名）
+    pub name: String,
+    /// 成员接口名称
+    pub members: Vec<String>,
+    /// 成员评分的合并方式
+    #[serde(default)]
+    pub aggregation: VirtualAggregation,
+}
+
+/// 虚拟接口成员评分的合并方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VirtualAggregation {
+    /// 取评分最高的成员
+    #[default]
+    Best,
+    /// 取成员评分的平均值，速度按成员之和计算
+    Average,
+}
+
+/// 确认目标配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConfirmTarget {
+    /// IP 地址或域名
+    pub address: String,
+    /// 描述
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// 将覆盖配置合并到主配置上
+///
+/// 表按键递归合并，覆盖值优先；元素都是带 `name`（接口）或 `address`（目标）的表的数组
+/// 按该键逐项合并，键相同的元素合并、新元素追加在末尾；其余数组和标量直接替换
+pub fn merge_config(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_config(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (toml::Value::Array(base), toml::Value::Array(overlay))
+            if base.iter().chain(&overlay).all(|v| merge_key(v).is_some()) =>
+        {
+            for item in overlay {
+                let key = merge_key(&item).cloned();
+                match base
+                    .iter_mut()
+                    .find(|existing| merge_key(existing) == key.as_ref())
+                {
+                    Some(existing) => merge_config(existing, item),
+                    None => base.push(item),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// 数组元素合并时使用的键
+fn merge_key(value: &toml::Value) -> Option<&toml::Value> {
+    let table = value.as_table()?;
+    table.get("name").or_else(|| table.get("address"))
+}
+
+/// 两个目标地址之间的关系
+#[derive(Debug, PartialEq)]
+enum TargetOverlap {
+    /// 相同地址（包括 `/32` 与不带前缀的同一 IP）
+    Duplicate,
+    /// 网段相互包含
+    Overlap,
+}
+
+/// 比较两个目标地址，域名只按字符串比较
+fn target_overlap(a: &str, b: &str) -> Option<TargetOverlap> {
+    match (a.parse::<IpNetwork>(), b.parse::<IpNetwork>()) {
+        (Ok(a), Ok(b)) => {
+            if a.network() == b.network() && a.prefix() == b.prefix() {
+                Some(TargetOverlap::Duplicate)
+            } else if a.contains(b.network()) || b.contains(a.network()) {
+                Some(TargetOverlap::Overlap)
+            } else {
+                None
+            }
+        }
+        _ => (a == b).then_some(TargetOverlap::Duplicate),
+    }
+}
+
+/// 目标地址是否合法：IP 地址（可带 CIDR 后缀，如 `/32`、`/128`）或域名
+fn valid_target_address(address: &str) -> bool {
+    if address.parse::<IpNetwork>().is_ok() {
+        return true;
+    }
+    if address.contains(['/', ':']) || address.len() > 253 {
+        return false;
+    }
+
+    // 最后一段全为数字的是写错的 IPv4 地址（如 8.8.8.256），不当作域名
+    let labels: Vec<&str> = address.trim_end_matches('.').split('.').collect();
+    labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    }) && !labels
+        .last()
+        .is_some_and(|label| label.chars().all(|c| c.is_ascii_digit()))
+}
+
+impl Config {
+    /// 从文件加载配置
+    ///
+    /// 若设置了 `ROUTES_MONITOR_CONFIG_LOCAL` 环境变量或 `global.override_file`，
+    /// 先将覆盖文件合并到主配置上（见 [`merge_config`]），再对合并结果做校验
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content =
+            fs::read_to_string(path).with_context(|| format!("无法读取配置文件: {:?}", path))?;
+
+        let mut config: Config = match Self::override_path(path, &content) {
+            Some(local) if local.exists() => {
+                info!("加载覆盖配置文件: {:?}", local);
+                let mut base: toml::Value =
+                    toml::from_str(&content).with_context(|| "配置文件解析失败")?;
+                let overlay = fs::read_to_string(&local)
+                    .with_context(|| format!("无法读取覆盖配置文件: {:?}", local))?;
+                let overlay: toml::Value =
+                    toml::from_str(&overlay).with_context(|| "覆盖配置文件解析失败")?;
+                merge_config(&mut base, overlay);
+                base.try_into().with_context(|| "合并后的配置解析失败")?
+            }
+            local => {
+                if let Some(local) = local {
+                    warn!("覆盖配置文件不存在，已忽略: {:?}", local);
+                }
+                toml::from_str(&content).with_context(|| "配置文件解析失败")?
+            }
+        };
+
+        // 优先级: 环境变量 > 覆盖配置文件 > 配置文件 > 默认值
+        config.apply_env_overrides(|key| std::env::var(key).ok())?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// 覆盖配置文件路径：环境变量优先，其次为主配置中的 `global.override_file`
+    /// （相对路径基于主配置文件所在目录）
+    fn override_path(path: &Path, content: &str) -> Option<PathBuf> {
+        if let Ok(local) = std::env::var("ROUTES_MONITOR_CONFIG_LOCAL") {
+            return Some(PathBuf::from(local));
+        }
+
+        let value: toml::Value = toml::from_str(content).ok()?;
+        let file = value.get("global")?.get("override_file")?.as_str()?;
+        Some(path.parent().unwrap_or(Path::new(".")).join(file))
+    }
+
+    /// 用环境变量覆盖配置项（在校验之前调用）
+    ///
+    /// 支持 `RM_CHECK_INTERVAL`、`RM_AUTO_SWITCH`、`RM_FAILURE_THRESHOLD`
+    /// 和 `RM_TARGETS`（逗号分隔的地址，替换配置文件中的目标）
+    pub fn apply_env_overrides<F>(&mut self, lookup: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(value) = lookup("RM_CHECK_INTERVAL") {
+            self.global.check_interval = value
+                .trim()
+                .parse()
+                .with_context(|| format!("RM_CHECK_INTERVAL 无效: {}", value))?;
+        }
+
+        if let Some(value) = lookup("RM_AUTO_SWITCH") {
+            self.global.auto_switch = match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => anyhow::bail!("RM_AUTO_SWITCH 无效: {}", value),
+            };
+        }
+
+        if let Some(value) = lookup("RM_FAILURE_THRESHOLD") {
+            self.global.failure_threshold = value
+                .trim()
+                .parse()
+                .with_context(|| format!("RM_FAILURE_THRESHOLD 无效: {}", value))?;
+        }
+
+        if let Some(value) = lookup("RM_TARGETS") {
+            self.targets = value
+                .split(',')
+                .map(str::trim)
+                .filter(|address| !address.is_empty())
+                .map(|address| TargetIP {
+                    address: address.to_string(),
+                    description: address.to_string(),
+                    test_url: None,
+                    weight: 1.0,
+                    critical: false,
+                    check_every: 1,
+                    dscp: None,
+                    probe: ProbeType::Icmp,
+                    port: None,
+                    ping_count: None,
+                    ping_size: None,
+                    speed_direction: SpeedDirection::default(),
+                })
+                .collect();
+        }
+
+        Ok(())
+    }
+
+    /// 将配置（含默认值）序列化为指定格式
+    pub fn dump(&self, format: ConfigFormat) -> Result<String> {
+        match format {
+            ConfigFormat::Toml => toml::to_string_pretty(self).context("配置序列化失败"),
+            ConfigFormat::Json => serde_json::to_string_pretty(self).context("配置序列化失败"),
+        }
+    }
+
+    /// 带注释的示例配置（TOML）：全局设置为默认值，附带示例接口和目标
+    pub fn example_toml() -> Result<String> {
+        let config: Config = toml::from_str(EXAMPLE_SEED).context("示例配置解析失败")?;
+        config.validate()?;
+        let body = config.dump(ConfigFormat::Toml)?;
+
+        let mut output = String::from(EXAMPLE_HEADER);
+        let mut section = String::new();
+        let mut seen = Vec::new();
+        for line in body.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                section = trimmed.trim_matches(|c| c == '[' || c == ']').to_string();
+                if !seen.contains(&section) {
+                    if let Some((_, comment)) = EXAMPLE_COMMENTS.iter().find(|(k, _)| *k == section)
+                    {
+                        if !output.ends_with("\n\n") {
+                            output.push('\n');
+                        }
+                        output.push_str(&format!("# {}\n", comment));
+                    }
+                    seen.push(section.clone());
+                }
+            } else if let Some((key, _)) = trimmed.split_once(" = ") {
+                let path = match section.as_str() {
+                    "" => key.to_string(),
+                    section => format!("{}.{}", section, key),
+                };
+                if let Some((_, comment)) = EXAMPLE_COMMENTS.iter().find(|(k, _)| *k == path) {
+                    output.push_str(&format!("# {}\n", comment));
+                }
+            }
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    /// 接口作为当前接口时使用的失败阈值：接口配置优先，未配置时使用全局阈值
+    pub fn failure_threshold_for(&self, interface: &str) -> u32 {
+        self.interfaces
+            .iter()
+            .find(|i| i.name == interface)
+            .and_then(|i| i.failure_threshold)
+            .unwrap_or(self.global.failure_threshold)
+    }
+
+    /// 验证配置有效性
+    pub fn validate(&self) -> Result<()> {
+        // 验证至少有一个启用的接口
+        if !self.interfaces.iter().any(|i| i.enabled) {
+            anyhow::bail!("至少需要启用一个网络接口");
+        }
+
+        // 验证至少有一个目标 IP
+        if self.targets.is_empty() {
+            anyhow::bail!("至少需要配置一个目标 IP");
+        }
+
+        // 验证全局配置
+        if self.global.check_interval == 0 {
+            anyhow::bail!("检查间隔不能为 0");
+        }
+
+        if self.global.timeout == 0 {
+            anyhow::bail!("超时时间不能为 0");
+        }
+
+        if self.global.concurrent_tests == 0 {
+            anyhow::bail!("并发测试数量不能为 0");
+        }
+
+        if self.global.concurrent_speed_tests == 0 {
+            anyhow::bail!("并发速度测试数量不能为 0");
+        }
+
+        if !self.global.failback_penalty.is_finite() || self.global.failback_penalty < 0.0 {
+            anyhow::bail!("回切惩罚必须为非负数");
+        }
+
+        if !self.global.switch_margin.is_finite() || self.global.switch_margin < 0.0 {
+            anyhow::bail!("切换领先分数必须为非负数");
+        }
+
+        if !self.global.balance_threshold.is_finite() || self.global.balance_threshold < 0.0 {
+            anyhow::bail!("负载均衡评分阈值必须为非负数");
+        }
+
+        if !self.global.failback_margin.is_finite() || self.global.failback_margin < 0.0 {
+            anyhow::bail!("回切领先分数必须为非负数");
+        }
+
+        if !self.global.stability_weight.is_finite() || self.global.stability_weight < 0.0 {
+            anyhow::bail!("稳定性权重必须为非负数");
+        }
+
+        if self.global.stability_weight > 0.0 && self.global.stability_window < 2 {
+            anyhow::bail!("稳定性窗口至少需要 2 轮");
+        }
+
+        // 0 为 local 表规则，32766/32767 为 main/default 表规则
+        let base = self.global.rule_priority_base;
+        let range = self.global.rule_priority_range;
+        if base == 0 || range == 0 || base.saturating_add(range) > MAX_RULE_PRIORITY + 1 {
+            anyhow::bail!(
+                "策略路由优先级范围无效: {}-{}（必须在 1-{} 之间）",
+                base,
+                base.saturating_add(range).saturating_sub(1),
+                MAX_RULE_PRIORITY
+            );
+        }
+
+        let alpha = self.global.score_smoothing;
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            anyhow::bail!("评分平滑系数必须在 (0, 1] 范围内");
+        }
+
+        let saturation = self.global.saturation_threshold;
+        if !(saturation > 0.0 && saturation <= 1.0) {
+            anyhow::bail!("饱和利用率阈值必须在 (0, 1] 范围内");
+        }
+
+        let threshold = self.global.reachability_loss_threshold;
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            anyhow::bail!("可达丢包率阈值必须在 (0, 1] 范围内");
+        }
+
+        if !(0.0..=1.0).contains(&self.global.min_reachable_ratio) {
+            anyhow::bail!("最低可达比例必须在 [0, 1] 范围内");
+        }
+
+        let scoring = &self.global.scoring;
+        let weights = [
+            scoring.weight_reachable,
+            scoring.weight_latency,
+            scoring.weight_packet_loss,
+            scoring.weight_speed,
+            scoring.weight_jitter,
+        ];
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            anyhow::bail!("评分权重必须为非负数");
+        }
+        if weights.iter().sum::<f64>() <= 0.0 {
+            anyhow::bail!("评分权重不能全部为 0");
+        }
+        if [
+            scoring.latency_reference_ms,
+            scoring.speed_reference_kb,
+            scoring.jitter_reference_ms,
+        ]
+        .iter()
+        .any(|r| !r.is_finite() || *r <= 0.0)
+        {
+            anyhow::bail!("评分的延迟、速度和抖动基准必须为正数");
+        }
+
+        if let Some(weights) = &self.global.check_weights {
+            let all = [weights.icmp, weights.http];
+            if all.iter().any(|w| !w.is_finite() || *w < 0.0) {
+                anyhow::bail!("检查权重必须为非负数");
+            }
+            if all.iter().sum::<f64>() <= 0.0 {
+                anyhow::bail!("检查权重不能全部为 0");
+            }
+        }
+
+        if self
+            .global
+            .max_load
+            .is_some_and(|l| !l.is_finite() || l <= 0.0)
+        {
+            anyhow::bail!("负载上限必须为正数");
+        }
+
+        if !self.global.cost_weight.is_finite() || self.global.cost_weight < 0.0 {
+            anyhow::bail!("成本权重必须为非负数");
+        }
+
+        if !self.global.baseline_deviation.is_finite() || self.global.baseline_deviation <= 1.0 {
+            anyhow::bail!("基线偏离倍数必须大于 1");
+        }
+
+        if self
+            .global
+            .scorer_command
+            .as_deref()
+            .is_some_and(|c| c.trim().is_empty())
+        {
+            anyhow::bail!("外部评分命令不能为空");
+        }
+
+        let gates = [
+            &self.global.pre_switch_command,
+            &self.global.post_switch_command,
+        ];
+        if gates
+            .iter()
+            .any(|c| c.as_deref().is_some_and(|c| c.trim().is_empty()))
+        {
+            anyhow::bail!("切换检查命令不能为空");
+        }
+
+        if self.targets.iter().any(|t| t.check_every == 0) {
+            anyhow::bail!("目标的 check_every 不能为 0");
+        }
 
-        let config: Config = toml::from_str(&content).with_context(|| "配置文件解析失败")?;
+        let all_targets = self.targets.iter().chain(
+            self.interfaces
+                .iter()
+                .flat_map(|i| i.targets.iter().flatten().chain(&i.extra_targets)),
+        );
+        for target in all_targets {
+            if !valid_target_address(&target.address) {
+                anyhow::bail!("目标地址无效: {:?}", target.address);
+            }
 
-        config.validate()?;
+            let ping_count = target.ping_count.unwrap_or(self.global.ping_count);
+            if ping_count == 0 {
+                anyhow::bail!("目标 {} 的 ping_count 不能为 0", target.address);
+            }
+            if target.ping_size.unwrap_or(self.global.ping_size) > MAX_PING_SIZE {
+                anyhow::bail!(
+                    "目标 {} 的 ping_size 超出范围 (0..={})",
+                    target.address,
+                    MAX_PING_SIZE
+                );
+            }
 
-        Ok(config)
-    }
+            if target.dscp.is_some_and(|dscp| dscp > MAX_DSCP) {
+                anyhow::bail!(
+                    "目标 {} 的 DSCP 值超出范围 (0..={})",
+                    target.address,
+                    MAX_DSCP
+                );
+            }
 
-    /// 验证配置有效性
-    pub fn validate(&self) -> Result<()> {
-        // 验证至少有一个启用的接口
-        if !self.interfaces.iter().any(|i| i.enabled) {
-            anyhow::bail!("至少需要启用一个网络接口");
+            match (target.probe, target.port) {
+                (ProbeType::Tcp, None | Some(0)) => {
+                    anyhow::bail!("目标 {} 使用 TCP 探测，需要设置有效的 port", target.address)
+                }
+                (ProbeType::Icmp, Some(_)) => {
+                    anyhow::bail!(
+                        "目标 {} 设置了 port，但只有 TCP 探测使用端口",
+                        target.address
+                    )
+                }
+                _ => {}
+            }
         }
 
-        // 验证至少有一个目标 IP
-        if self.targets.is_empty() {
-            anyhow::bail!("至少需要配置一个目标 IP");
+        // 路由表 ID：0 为未指定表，不能用于策略路由；各接口需使用不同的表
+        let mut table_ids = HashMap::new();
+        for interface in &self.interfaces {
+            let Some(table_id) = interface.table_id else {
+                continue;
+            };
+            if table_id == 0 {
+                anyhow::bail!("接口 {} 的路由表 ID 不能为 0（未指定表）", interface.name);
+            }
+            if let Some(other) = table_ids.insert(table_id, &interface.name) {
+                anyhow::bail!(
+                    "接口 {} 与 {} 使用了相同的路由表 ID {}",
+                    other,
+                    interface.name,
+                    table_id
+                );
+            }
         }
 
-        // 验证全局配置
-        if self.global.check_interval == 0 {
-            anyhow::bail!("检查间隔不能为 0");
-        }
+        for interface in &self.interfaces {
+            if let Some(resolver) = &interface.resolver {
+                if resolver.parse::<std::net::IpAddr>().is_err() {
+                    anyhow::bail!(
+                        "接口 {} 的 DNS 服务器地址无效: {}",
+                        interface.name,
+                        resolver
+                    );
+                }
+            }
 
-        if self.global.timeout == 0 {
-            anyhow::bail!("超时时间不能为 0");
+            if let Some(gateway) = &interface.gateway {
+                if gateway.parse::<std::net::IpAddr>().is_err() {
+                    anyhow::bail!("接口 {} 的网关地址无效: {}", interface.name, gateway);
+                }
+            }
+
+            for gateway in &interface.alternate_gateways {
+                if gateway.parse::<std::net::IpAddr>().is_err() {
+                    anyhow::bail!("接口 {} 的备用网关地址无效: {}", interface.name, gateway);
+                }
+            }
+
+            if interface
+                .bandwidth_mbps
+                .is_some_and(|b| !b.is_finite() || b <= 0.0)
+            {
+                anyhow::bail!("接口 {} 的带宽必须为正数", interface.name);
+            }
+
+            if !interface.cost.is_finite() || interface.cost < 0.0 {
+                anyhow::bail!("接口 {} 的成本必须为非负数", interface.name);
+            }
+
+            if interface.targets.as_ref().is_some_and(|t| t.is_empty()) {
+                anyhow::bail!("接口 {} 的 targets 覆盖不能为空", interface.name);
+            }
+
+            // 接口的失败阈值优先于 global.failure_threshold，见 failure_threshold_for
+            if interface.failure_threshold == Some(0) {
+                anyhow::bail!("接口 {} 的 failure_threshold 不能为 0", interface.name);
+            }
+
+            let base = interface.targets.as_deref().unwrap_or(&self.targets);
+            for (i, extra) in interface.extra_targets.iter().enumerate() {
+                let duplicated = base
+                    .iter()
+                    .chain(&interface.extra_targets[..i])
+                    .any(|t| t.address == extra.address);
+                if duplicated {
+                    anyhow::bail!(
+                        "接口 {} 的额外目标 {} 与已有目标重复",
+                        interface.name,
+                        extra.address
+                    );
+                }
+            }
+
+            for vlan in &interface.vlans {
+                if vlan.id == 0 || vlan.id > 4094 || vlan.device.trim().is_empty() {
+                    anyhow::bail!(
+                        "接口 {} 的 VLAN 配置无效: {}",
+                        interface.name,
+                        vlan.device_name()
+                    );
+                }
+            }
+
+            if !interface.score_offset.is_finite()
+                || interface.score_offset.abs() > MAX_SCORE_OFFSET
+            {
+                anyhow::bail!(
+                    "接口 {} 的评分偏移 {} 超出范围 (-{}..={})",
+                    interface.name,
+                    interface.score_offset,
+                    MAX_SCORE_OFFSET,
+                    MAX_SCORE_OFFSET
+                );
+            }
         }
 
-        if self.global.concurrent_tests == 0 {
-            anyhow::bail!("并发测试数量不能为 0");
+        for window in &self.global.freeze_windows {
+            crate::decision::parse_freeze_window(window)?;
         }
 
         // 验证接口名称唯一性
@@ -115,14 +1491,174 @@ impl Config {
             }
         }
 
+        // 验证虚拟接口
+        let mut grouped = std::collections::HashSet::new();
+        for virtual_interface in &self.virtual_interfaces {
+            if !names.insert(virtual_interface.name.clone()) {
+                anyhow::bail!("虚拟接口名称重复: {}", virtual_interface.name);
+            }
+            if virtual_interface.members.is_empty() {
+                anyhow::bail!("虚拟接口 {} 没有成员", virtual_interface.name);
+            }
+            for member in &virtual_interface.members {
+                if !self.interfaces.iter().any(|i| &i.name == member) {
+                    anyhow::bail!(
+                        "虚拟接口 {} 的成员 {} 不是已配置的接口",
+                        virtual_interface.name,
+                        member
+                    );
+                }
+                if !grouped.insert(member.as_str()) {
+                    anyhow::bail!("接口 {} 属于多个虚拟接口", member);
+                }
+            }
+        }
+
+        if let Some(pinned) = &self.global.pinned_interface {
+            self.check_pinnable(pinned)?;
+        }
+
+        if let Some(notify) = &self.notify {
+            let url = &notify.webhook_url;
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                anyhow::bail!("通知 webhook_url 必须是 http(s) 地址: {}", url);
+            }
+            if notify.timeout == 0 {
+                anyhow::bail!("通知超时时间必须大于 0");
+            }
+        }
+
+        if let Some(control) = &self.control {
+            if control.socket.as_os_str().is_empty() {
+                anyhow::bail!("控制套接字路径不能为空");
+            }
+        }
+
+        // 汇总所有警告，一次性报告
+        let warnings = self.validation_warnings();
+        if !warnings.is_empty() {
+            if self.global.strict_validation {
+                anyhow::bail!(
+                    "配置存在 {} 个警告（严格模式下视为错误）:\n  - {}",
+                    warnings.len(),
+                    warnings.join("\n  - ")
+                );
+            }
+
+            for warning in &warnings {
+                warn!("配置警告: {}", warning);
+            }
+        }
+
         Ok(())
     }
 
+    /// 收集配置中可能存在问题但不影响运行的项
+    pub fn validation_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        // 权重只对速度评分生效，没有 test_url 时高权重没有意义
+        for target in &self.targets {
+            if target.weight > 1.0 && target.test_url.is_none() {
+                warnings.push(format!(
+                    "目标 {} 的权重为 {}，但未配置 test_url，权重不会影响速度评分",
+                    target.address, target.weight
+                ));
+            }
+        }
+
+        // 路由表 ID：253-255 为系统保留表（default/main/local）
+        for interface in &self.interfaces {
+            if let Some(table_id) = interface.table_id {
+                if (253..=255).contains(&table_id) {
+                    warnings.push(format!(
+                        "接口 {} 的路由表 ID {} 为系统保留值",
+                        interface.name, table_id
+                    ));
+                }
+            }
+        }
+
+        // 重复或重叠的目标地址会被重复测试并重复计入评分
+        for (i, target) in self.targets.iter().enumerate() {
+            for earlier in &self.targets[..i] {
+                match target_overlap(&earlier.address, &target.address) {
+                    Some(TargetOverlap::Duplicate) => warnings.push(format!(
+                        "目标地址重复: {} 与 {}",
+                        earlier.address, target.address
+                    )),
+                    Some(TargetOverlap::Overlap) => warnings.push(format!(
+                        "目标网段重叠: {} 与 {}",
+                        earlier.address, target.address
+                    )),
+                    None => {}
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// 需要管理静态路由的地址列表
+    /// 未配置 managed_routes 时与探测目标保持一致（向后兼容）
+    pub fn route_addresses(&self) -> Vec<&str> {
+        match &self.managed_routes {
+            Some(routes) => routes.iter().map(|r| r.address.as_str()).collect(),
+            None => self.targets.iter().map(|t| t.address.as_str()).collect(),
+        }
+    }
+
+    /// 确认目标（转换为探测目标）
+    pub fn confirm_target_ips(&self) -> Vec<TargetIP> {
+        self.confirm_targets
+            .iter()
+            .map(|t| TargetIP {
+                address: t.address.clone(),
+                description: t
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| "确认目标".to_string()),
+                test_url: None,
+                weight: 1.0,
+                critical: false,
+                check_every: 1,
+                dscp: None,
+                probe: ProbeType::Icmp,
+                port: None,
+                ping_count: None,
+                ping_size: None,
+                speed_direction: SpeedDirection::default(),
+            })
+            .collect()
+    }
+
+    /// 地址是否为关键探测目标
+    pub fn is_critical_target(&self, address: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|t| t.critical && t.address == address)
+    }
+
     /// 获取启用的接口列表
     pub fn enabled_interfaces(&self) -> Vec<&NetworkInterface> {
         self.interfaces.iter().filter(|i| i.enabled).collect()
     }
 
+    /// 已禁用的接口（按优先级排序）
+    pub fn disabled_interfaces(&self) -> Vec<&NetworkInterface> {
+        let mut interfaces: Vec<_> = self.interfaces.iter().filter(|i| !i.enabled).collect();
+        interfaces.sort_by_key(|i| i.priority);
+        interfaces
+    }
+
+    /// 检查接口能否被固定（必须是已配置且启用的接口）
+    pub fn check_pinnable(&self, name: &str) -> Result<()> {
+        if !self.interfaces.iter().any(|i| i.name == name && i.enabled) {
+            anyhow::bail!("固定接口 {} 不是已配置且启用的接口", name);
+        }
+        Ok(())
+    }
+
     /// 根据优先级排序的接口列表
     pub fn sorted_interfaces(&self) -> Vec<&NetworkInterface> {
         let mut interfaces = self.enabled_interfaces();
@@ -131,16 +1667,126 @@ impl Config {
     }
 }
 
+impl GlobalConfig {
+    /// 重新加载配置时保留启动时建立的设置：路由管理器、数据导出、日志和跨轮次的状态
+    /// （切换频率窗口、观察期、评分历史、探测预算、目标熔断）都按启动时的值创建，需重启才能修改
+    /// 返回: 新配置中被改动、已恢复为当前值的设置名
+    pub fn retain_startup_settings(&mut self, current: &GlobalConfig) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        macro_rules! retain {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if self.$field != current.$field {
+                        changed.push(stringify!($field));
+                        self.$field = current.$field.clone();
+                    }
+                )*
+            };
+        }
+        retain!(
+            dry_run,
+            backend,
+            switch_style,
+            drain_time,
+            rule_priority_base,
+            rule_priority_range,
+            allow_network_restart,
+            gateway_cache_ttl,
+            sqlite_path,
+            sqlite_retention_days,
+            syslog,
+            log_format,
+            keepalive_interval,
+            baseline_file,
+            max_switches_per_hour,
+            new_interface_probation,
+            stability_window,
+            max_probes_per_cycle,
+            dead_target_threshold,
+            dead_target_reprobe_every,
+        );
+        changed
+    }
+}
+
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
             check_interval: 60,
             timeout: 5,
             concurrent_tests: 4,
+            concurrent_speed_tests: default_concurrent_speed_tests(),
             failure_threshold: 3,
             log_level: "info".to_string(),
+            log_format: LogFormat::default(),
             auto_switch: true,
+            mode: RoutingMode::default(),
+            balance_threshold: default_balance_threshold(),
             manage_uci_routes: false,
+            dry_run: false,
+            strict_validation: false,
+            results_log: None,
+            sqlite_path: None,
+            sqlite_retention_days: default_sqlite_retention_days(),
+            prober: Prober::default(),
+            ping_tool: PingTool::default(),
+            gateway_cache_ttl: default_gateway_cache_ttl(),
+            dns_cache_ttl: default_dns_cache_ttl(),
+            failback_penalty: default_failback_penalty(),
+            failback_penalty_window: 0,
+            switch_margin: 0.0,
+            failback_margin: 0.0,
+            failback_margin_window: default_failback_margin_window(),
+            freeze_windows: Vec::new(),
+            speed_scoring: SpeedScoring::default(),
+            latency_metric: LatencyMetric::default(),
+            recover_from_panics: false,
+            respect_uci_disabled: false,
+            timeout_loss: TimeoutLoss::default(),
+            ignore_universally_down_targets: false,
+            randomize_source_port: false,
+            probe_disabled_interfaces: false,
+            switch_style: SwitchStyle::default(),
+            backend: Backend::default(),
+            rule_priority_base: default_rule_priority_base(),
+            rule_priority_range: default_rule_priority_range(),
+            drain_time: default_drain_time(),
+            warmup_ping: false,
+            ping_count: default_ping_count(),
+            ping_size: default_ping_size(),
+            metrics_per_target: false,
+            check_weights: None,
+            new_interface_probation: 0,
+            keepalive_interval: 0,
+            dead_target_threshold: 0,
+            dead_target_reprobe_every: default_dead_target_reprobe_every(),
+            syslog: false,
+            functional_verify: false,
+            stability_weight: 0.0,
+            stability_window: default_stability_window(),
+            score_smoothing: default_score_smoothing(),
+            allow_network_restart: false,
+            max_probes_per_cycle: 0,
+            max_switches_per_hour: 0,
+            switch_cooldown: 0,
+            history_size: default_history_size(),
+            pinned_interface: None,
+            override_file: None,
+            reachability_loss_threshold: default_reachability_loss_threshold(),
+            min_reachable_ratio: default_min_reachable_ratio(),
+            retry_lossy_probes: 0,
+            scorer_command: None,
+            max_load: None,
+            baseline_file: None,
+            baseline_deviation: default_baseline_deviation(),
+            pre_switch_command: None,
+            post_switch_command: None,
+            switch_command_timeout: default_switch_command_timeout(),
+            measure_utilization: false,
+            saturation_threshold: default_saturation_threshold(),
+            optimize_for: OptimizeFor::default(),
+            cost_weight: default_cost_weight(),
+            scoring: ScoringWeights::default(),
         }
     }
 }
@@ -149,9 +1795,8 @@ impl Default for GlobalConfig {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_config_validation() {
-        let config = Config {
+    fn sample_config() -> Config {
+        Config {
             global: GlobalConfig::default(),
             interfaces: vec![NetworkInterface {
                 name: "eth0".to_string(),
@@ -160,15 +1805,621 @@ mod tests {
                 enabled: true,
                 table_id: Some(100),
                 gateway: Some("192.168.1.1".to_string()),
+                score_offset: 0.0,
+                resolver: None,
+                vlans: Vec::new(),
+                vlan_aggregation: VlanAggregation::default(),
+                keepalive: false,
+                targets: None,
+                extra_targets: Vec::new(),
+                alternate_gateways: Vec::new(),
+                family: AddressFamily::default(),
+                bandwidth_mbps: None,
+                cost: 0.0,
+                failure_threshold: None,
             }],
             targets: vec![TargetIP {
                 address: "8.8.8.8".to_string(),
                 description: "Google DNS".to_string(),
                 test_url: None,
                 weight: 1.0,
+                critical: false,
+                check_every: 1,
+                dscp: None,
+                probe: ProbeType::Icmp,
+                port: None,
+                ping_count: None,
+                ping_size: None,
+                speed_direction: SpeedDirection::default(),
             }],
+            managed_routes: None,
+            confirm_targets: Vec::new(),
+            virtual_interfaces: Vec::new(),
+            http: None,
+            control: None,
+            notify: None,
+        }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = sample_config();
+        assert!(config.validate().is_ok());
+        assert!(config.validation_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_managed_routes_separate_from_targets() {
+        let mut config = sample_config();
+        // 未配置时回退到探测目标
+        assert_eq!(config.route_addresses(), vec!["8.8.8.8"]);
+
+        let config_toml = r#"
+            [global]
+            check_interval = 60
+            timeout = 5
+            concurrent_tests = 4
+            failure_threshold = 3
+            log_level = "info"
+            auto_switch = true
+
+            [[interfaces]]
+            name = "eth0"
+            display_name = "以太网"
+            priority = 1
+            enabled = true
+
+            [[targets]]
+            address = "8.8.8.8"
+            description = "Google DNS"
+            weight = 1.0
+
+            [[managed_routes]]
+            address = "1.0.1.0/24"
+            description = "CN 网段"
+
+            [[managed_routes]]
+            address = "1.0.2.0/23"
+        "#;
+        config = toml::from_str(config_toml).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.targets.len(), 1);
+        assert_eq!(config.route_addresses(), vec!["1.0.1.0/24", "1.0.2.0/23"]);
+
+        // 显式配置空列表表示不管理任何路由
+        config.managed_routes = Some(Vec::new());
+        assert!(config.route_addresses().is_empty());
+    }
+
+    #[test]
+    fn test_validation_warnings_lenient() {
+        let mut config = sample_config();
+        config.interfaces[0].table_id = Some(254);
+        config.targets[0].weight = 2.0;
+        config.targets.push(config.targets[0].clone());
+
+        let warnings = config.validation_warnings();
+        // 两个高权重无 test_url 的目标 + 保留路由表 + 重复地址
+        assert_eq!(warnings.len(), 4);
+        assert!(warnings.iter().any(|w| w.contains("路由表 ID 254")));
+        assert!(warnings.iter().any(|w| w.contains("目标地址重复")));
+
+        // 宽松模式下警告不会导致校验失败
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validation_warnings_strict() {
+        let mut config = sample_config();
+        config.global.strict_validation = true;
+        assert!(config.validate().is_ok());
+
+        config.interfaces[0].table_id = Some(253);
+        config.targets.push(config.targets[0].clone());
+
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("2 个警告"));
+        assert!(err.contains("路由表 ID 253"));
+        assert!(err.contains("目标地址重复"));
+    }
+
+    #[test]
+    fn test_dump_config_roundtrip() {
+        let mut config = sample_config();
+        config.global.check_weights = Some(CheckWeights::default());
+        config.global.freeze_windows = vec!["* 2-3 * * *".to_string()];
+        config.http = Some(HttpConfig {
+            listen: "127.0.0.1:9420".to_string(),
+        });
+
+        // 输出的配置重新解析后应与原配置等价
+        let dumped = config.dump(ConfigFormat::Toml).unwrap();
+        let parsed: Config = toml::from_str(&dumped).unwrap();
+        parsed.validate().unwrap();
+        assert_eq!(parsed.dump(ConfigFormat::Toml).unwrap(), dumped);
+
+        let json = config.dump(ConfigFormat::Json).unwrap();
+        let parsed: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.dump(ConfigFormat::Toml).unwrap(), dumped);
+    }
+
+    #[test]
+    fn test_interface_target_overrides() {
+        let target = |address: &str| TargetIP {
+            address: address.to_string(),
+            description: String::new(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+        let addresses = |targets: Vec<TargetIP>| -> Vec<String> {
+            targets.into_iter().map(|t| t.address).collect()
+        };
+
+        let mut config = sample_config();
+        config.targets.push(target("1.1.1.1"));
+
+        // 追加：全局目标 + 额外目标
+        config.interfaces[0].extra_targets = vec![target("10.0.0.1")];
+        config.validate().unwrap();
+        let interface = &config.interfaces[0];
+        assert_eq!(
+            addresses(interface.effective_targets(&config.targets)),
+            vec!["8.8.8.8", "1.1.1.1", "10.0.0.1"]
+        );
+
+        // 替换：只用接口自己的目标，额外目标仍追加在后
+        config.interfaces[0].targets = Some(vec![target("223.5.5.5")]);
+        config.validate().unwrap();
+        let interface = &config.interfaces[0];
+        assert_eq!(
+            addresses(interface.effective_targets(&config.targets)),
+            vec!["223.5.5.5", "10.0.0.1"]
+        );
+
+        // 额外目标不能与已有目标重复
+        config.interfaces[0].extra_targets = vec![target("223.5.5.5")];
+        assert!(config.validate().is_err());
+        config.interfaces[0].targets = None;
+        config.interfaces[0].extra_targets = vec![target("8.8.8.8")];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_merge_config_layers() {
+        let mut base: toml::Value = toml::from_str(
+            r#"
+            [global]
+            check_interval = 300
+            auto_switch = true
+
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+
+            [[interfaces]]
+            name = "wan_b"
+            display_name = "B"
+            priority = 2
+            enabled = true
+
+            [[targets]]
+            address = "8.8.8.8"
+            description = "Google"
+            weight = 1.0
+            "#,
+        )
+        .unwrap();
+        let overlay: toml::Value = toml::from_str(
+            r#"
+            [global]
+            check_interval = 60
+
+            [[interfaces]]
+            name = "wan_b"
+            enabled = false
+            gateway = "10.0.0.1"
+
+            [[interfaces]]
+            name = "lte"
+            display_name = "LTE"
+            priority = 3
+            enabled = true
+
+            [http]
+            listen = "127.0.0.1:9420"
+            "#,
+        )
+        .unwrap();
+        merge_config(&mut base, overlay);
+        let config: Config = base.try_into().unwrap();
+
+        // 标量覆盖，未覆盖的保持原值
+        assert_eq!(config.global.check_interval, 60);
+        assert!(config.global.auto_switch);
+
+        // 接口按名称合并，新接口追加
+        let names: Vec<&str> = config.interfaces.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["wan_a", "wan_b", "lte"]);
+        let wan_b = &config.interfaces[1];
+        assert!(!wan_b.enabled);
+        assert_eq!(wan_b.gateway.as_deref(), Some("10.0.0.1"));
+        assert_eq!(wan_b.display_name, "B");
+        assert_eq!(config.targets.len(), 1);
+
+        // 只在覆盖文件中出现的配置段
+        assert_eq!(config.http.unwrap().listen, "127.0.0.1:9420");
+
+        // 普通数组直接替换
+        let mut base: toml::Value = toml::from_str("a = [1, 2]").unwrap();
+        merge_config(&mut base, toml::from_str("a = [3]").unwrap());
+        assert_eq!(base["a"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_override_file_loaded_from_global() {
+        let dir = std::env::temp_dir().join(format!("routes-monitor-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("config.toml"),
+            r#"
+            [global]
+            override_file = "local.toml"
+
+            [[interfaces]]
+            name = "wan"
+            display_name = "WAN"
+            priority = 1
+            enabled = false
+
+            [[targets]]
+            address = "8.8.8.8"
+            description = "Google"
+            weight = 1.0
+            "#,
+        )
+        .unwrap();
+
+        // 覆盖文件不存在时忽略（主配置中没有启用的接口，校验失败）
+        assert!(Config::from_file(dir.join("config.toml")).is_err());
+
+        // 校验针对合并后的结果
+        fs::write(
+            dir.join("local.toml"),
+            "[[interfaces]]\nname = \"wan\"\nenabled = true\n",
+        )
+        .unwrap();
+        let config = Config::from_file(dir.join("config.toml")).unwrap();
+        assert!(config.interfaces[0].enabled);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                pairs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+
+        let mut config = sample_config();
+        config
+            .apply_env_overrides(env(&[("RM_CHECK_INTERVAL", "30")]))
+            .unwrap();
+        assert_eq!(config.global.check_interval, 30);
+
+        config
+            .apply_env_overrides(env(&[("RM_AUTO_SWITCH", "false")]))
+            .unwrap();
+        assert!(!config.global.auto_switch);
+
+        config
+            .apply_env_overrides(env(&[("RM_FAILURE_THRESHOLD", "5")]))
+            .unwrap();
+        assert_eq!(config.global.failure_threshold, 5);
+
+        config
+            .apply_env_overrides(env(&[("RM_TARGETS", "1.1.1.1, 223.5.5.5,")]))
+            .unwrap();
+        let targets: Vec<_> = config.targets.iter().map(|t| t.address.as_str()).collect();
+        assert_eq!(targets, vec!["1.1.1.1", "223.5.5.5"]);
+
+        // 未设置的变量不影响配置，无效值报错
+        config.apply_env_overrides(env(&[])).unwrap();
+        assert_eq!(config.global.check_interval, 30);
+        assert!(config
+            .apply_env_overrides(env(&[("RM_CHECK_INTERVAL", "abc")]))
+            .is_err());
+        assert!(config
+            .apply_env_overrides(env(&[("RM_AUTO_SWITCH", "maybe")]))
+            .is_err());
+
+        // 最小配置：只写接口，其余来自默认值与环境变量
+        let mut config: Config = toml::from_str(
+            r#"
+            [[interfaces]]
+            name = "wan"
+            display_name = "WAN"
+            priority = 1
+            enabled = true
+            table_id = 100
+            "#,
+        )
+        .unwrap();
+        config
+            .apply_env_overrides(env(&[("RM_TARGETS", "8.8.8.8")]))
+            .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.global.timeout, GlobalConfig::default().timeout);
+    }
+
+    #[test]
+    fn test_example_config_loads() {
+        let example = Config::example_toml().unwrap();
+        assert!(example.contains("# 检查间隔（秒）\ncheck_interval = 60\n"));
+
+        let config: Config = toml::from_str(&example).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.interfaces.len(), 2);
+        assert_eq!(config.targets.len(), 2);
+        assert_eq!(
+            config.global.check_interval,
+            GlobalConfig::default().check_interval
+        );
+    }
+
+    #[test]
+    fn test_pinned_interface_validation() {
+        let mut config = sample_config();
+        config.global.pinned_interface = Some(config.interfaces[0].name.clone());
+        assert!(config.validate().is_ok());
+
+        config.global.pinned_interface = Some("missing".to_string());
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("固定接口"));
+
+        config.interfaces[0].enabled = false;
+        config.interfaces.push(NetworkInterface {
+            enabled: true,
+            name: "wan_c".to_string(),
+            ..config.interfaces[0].clone()
+        });
+        config.global.pinned_interface = Some(config.interfaces[0].name.clone());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_gateway_and_table_id_validation() {
+        let mut config = sample_config();
+        config.interfaces[0].gateway = Some("192.168.1".to_string());
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("网关地址无效: 192.168.1"));
+
+        config.interfaces[0].gateway = Some("fe80::1".to_string());
+        assert!(config.validate().is_ok());
+
+        let mut other = config.interfaces[0].clone();
+        other.name = "eth1".to_string();
+        config.interfaces.push(other);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("相同的路由表 ID 100"));
+
+        config.interfaces[1].table_id = Some(0);
+        assert!(config.validate().is_err());
+
+        config.interfaces[1].table_id = None;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_failure_threshold_override() {
+        let mut config = sample_config();
+        assert_eq!(config.failure_threshold_for("eth0"), 3);
+        assert_eq!(config.failure_threshold_for("unknown"), 3);
+
+        config.interfaces[0].failure_threshold = Some(1);
+        assert_eq!(config.failure_threshold_for("eth0"), 1);
+        assert!(config.validate().is_ok());
+
+        config.interfaces[0].failure_threshold = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_score_offset_range() {
+        let mut config = sample_config();
+        config.interfaces[0].score_offset = -50.0;
+        assert!(config.validate().is_ok());
+
+        config.interfaces[0].score_offset = 50.5;
+        assert!(config.validate().is_err());
+
+        config.interfaces[0].score_offset = f64::NAN;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_scoring_weights_validation() {
+        let mut config = sample_config();
+        config.global.scoring = toml::from_str("weight_speed = 0.0").unwrap();
+        assert_eq!(config.global.scoring.weight_reachable, 0.3);
+        assert!(config.validate().is_ok());
+
+        config.global.scoring = ScoringWeights {
+            weight_reachable: 0.0,
+            weight_latency: 0.0,
+            weight_packet_loss: 0.0,
+            weight_speed: 0.0,
+            ..ScoringWeights::default()
+        };
+        assert!(config.validate().is_err());
+
+        config.global.scoring = ScoringWeights {
+            latency_reference_ms: 0.0,
+            ..ScoringWeights::default()
         };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rule_priority_range_validation() {
+        let mut config = sample_config();
+        config.global.rule_priority_base = 3000;
+        config.global.rule_priority_range = 100;
+        assert!(config.validate().is_ok());
+
+        // 不能占用 local 表规则或 main/default 表规则的优先级
+        config.global.rule_priority_base = 0;
+        assert!(config.validate().is_err());
+        config.global.rule_priority_base = 32700;
+        assert!(config.validate().is_err());
+        config.global.rule_priority_base = 100;
+        config.global.rule_priority_range = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_ping_count_and_size_validation() {
+        let mut config = sample_config();
+        config.global.ping_count = 0;
+        assert!(config.validate().is_err());
+
+        // 目标覆盖优先于全局值
+        config.targets[0].ping_count = Some(1);
+        assert!(config.validate().is_ok());
+
+        config.targets[0].ping_size = Some(65508);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tcp_probe_requires_port() {
+        let mut config = sample_config();
+        config.targets[0].probe = ProbeType::Tcp;
+        assert!(config.validate().is_err());
 
+        config.targets[0].port = Some(443);
         assert!(config.validate().is_ok());
+
+        // ICMP 探测不使用端口
+        config.targets[0].probe = ProbeType::Icmp;
+        assert!(config.validate().is_err());
+
+        let target: TargetIP = toml::from_str(
+            r#"
+            address = "1.1.1.1"
+            description = "Cloudflare"
+            weight = 1.0
+            probe = "tcp"
+            port = 443
+        "#,
+        )
+        .unwrap();
+        assert_eq!(target.probe, ProbeType::Tcp);
+    }
+
+    #[test]
+    fn test_target_address_validation() {
+        for address in [
+            "8.8.8.8",
+            "10.0.0.1/32",
+            "2001:4860:4860::8888",
+            "2001:4860:4860::8888/128",
+            "dns.google",
+            "example.com.",
+        ] {
+            assert!(valid_target_address(address), "{}", address);
+        }
+        for address in [
+            "",
+            "8.8.8.256",
+            "10.0.0.1/33",
+            "2001:db8::g",
+            "2001:db8::1/129",
+            "exa mple.com",
+            "-bad.example",
+        ] {
+            assert!(!valid_target_address(address), "{}", address);
+        }
+
+        let mut config = sample_config();
+        config.targets[0].address = "2001:db8:::1".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_duplicate_and_overlapping_targets() {
+        let warnings_for = |addresses: &[&str]| {
+            let mut config = sample_config();
+            config.targets = addresses
+                .iter()
+                .map(|address| TargetIP {
+                    address: address.to_string(),
+                    ..config.targets[0].clone()
+                })
+                .collect();
+            config.validation_warnings()
+        };
+
+        // 完全相同的地址
+        let warnings = warnings_for(&["8.8.8.8", "1.1.1.1", "8.8.8.8"]);
+        assert_eq!(warnings, vec!["目标地址重复: 8.8.8.8 与 8.8.8.8"]);
+
+        // /32 与不带前缀的同一 IP
+        let warnings = warnings_for(&["45.128.210.197/32", "45.128.210.197"]);
+        assert_eq!(
+            warnings,
+            vec!["目标地址重复: 45.128.210.197/32 与 45.128.210.197"]
+        );
+
+        // 相互包含的网段
+        let warnings = warnings_for(&["10.0.0.0/8", "10.1.2.0/24", "192.168.0.0/24"]);
+        assert_eq!(warnings, vec!["目标网段重叠: 10.0.0.0/8 与 10.1.2.0/24"]);
+
+        // 域名只按字符串比较，不同网段不告警
+        assert!(warnings_for(&["example.com", "10.0.0.0/24", "10.0.1.0/24"]).is_empty());
+        assert_eq!(warnings_for(&["example.com", "example.com"]).len(), 1);
+    }
+
+    #[test]
+    fn test_retain_startup_settings() {
+        let current = GlobalConfig::default();
+        let mut reloaded = GlobalConfig {
+            dry_run: true,
+            backend: Backend::Ubus,
+            max_switches_per_hour: 3,
+            failure_threshold: 7,
+            ..GlobalConfig::default()
+        };
+
+        let retained = reloaded.retain_startup_settings(&current);
+        assert_eq!(retained, ["dry_run", "backend", "max_switches_per_hour"]);
+        assert!(!reloaded.dry_run);
+        assert_eq!(reloaded.backend, current.backend);
+        assert_eq!(reloaded.max_switches_per_hour, 0);
+        // 其余设置照常更新
+        assert_eq!(reloaded.failure_threshold, 7);
+    }
+
+    #[test]
+    fn test_probe_backend_alias() {
+        let global: GlobalConfig = toml::from_str(r#"probe_backend = "raw""#).unwrap();
+        assert_eq!(global.prober, Prober::Raw);
+
+        let global: GlobalConfig = toml::from_str(r#"probe_backend = "command""#).unwrap();
+        assert_eq!(global.prober, Prober::Ping);
     }
 }
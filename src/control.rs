@@ -0,0 +1,305 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 本地控制套接字
+//!
+//! 在 Unix 套接字上逐行接收 JSON 命令并逐行返回 JSON 结果，供 LuCI 等本机前端使用，无需开放 TCP 端口：
+//!
+//! - `{"command": "status"}`：当前活动接口和最近一次检查的评分（与 `GET /status` 相同）
+//! - `{"command": "switch", "interface": "wan_b"}`：跳过评分手动切换，切换后照常验证
+//! - `{"command": "recheck"}`：立即开始下一轮检查
+//! - `{"command": "pin", "interface": "wan_a"}`：固定到指定接口并立即切换过去
+//! - `{"command": "unpin"}`：取消固定
+//! - `{"command": "result", "interface": "wan_a", "target": "8.8.8.8"}`：该接口到该目标最近一次的探测结果
+//!
+//! 同时运行多个配置时用 `config` 字段指定配置；`status`、`recheck` 和 `unpin` 未指定时作用于所有配置，
+//! 其余命令必须指定
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::AppState;
+
+/// 一行控制命令
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// 目标配置名称（同时运行多个配置时）
+    #[serde(default)]
+    config: Option<String>,
+    #[serde(flatten)]
+    command: ControlCommand,
+}
+
+/// 控制命令
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "command", rename_all = "lowercase")]
+enum ControlCommand {
+    /// 查询当前状态
+    Status,
+    /// 手动切换到指定接口
+    Switch { interface: String },
+    /// 立即开始下一轮检查
+    Recheck,
+    /// 固定到指定接口
+    Pin { interface: String },
+    /// 取消固定
+    Unpin,
+    /// 查询指定接口到指定目标最近一次的探测结果
+    Result { interface: String, target: String },
+}
+
+/// 在指定路径上监听控制套接字，上次运行遗留的套接字文件会先被删除
+pub async fn serve(path: &Path, states: Vec<Arc<AppState>>) -> Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("无法删除遗留的控制套接字: {:?}", path))?,
+        Ok(_) => anyhow::bail!("控制套接字路径已存在且不是套接字: {:?}", path),
+        Err(_) => {}
+    }
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("无法监听控制套接字: {:?}", path))?;
+    info!("控制套接字已启动: {}", path.display());
+
+    let states = Arc::new(states);
+    loop {
+        let (stream, _) = listener.accept().await.context("接受控制连接失败")?;
+        let states = Arc::clone(&states);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &states).await {
+                debug!("控制连接异常断开: {}", e);
+            }
+        });
+    }
+}
+
+/// 逐行处理一个连接上的命令，每条命令返回一行 JSON
+async fn handle_connection(stream: UnixStream, states: &[Arc<AppState>]) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => execute(request, states).await,
+            Err(e) => Err(anyhow::anyhow!("无效的控制命令: {}", e)),
+        };
+        let response = match result {
+            Ok(serde_json::Value::Object(mut fields)) => {
+                fields.insert("ok".to_string(), json!(true));
+                serde_json::Value::Object(fields)
+            }
+            Ok(_) => json!({ "ok": true }),
+            Err(e) => json!({ "ok": false, "error": format!("{:#}", e) }),
+        };
+
+        let mut text = response.to_string();
+        text.push('\n');
+        writer.write_all(text.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// 执行一条命令，返回要合并到响应中的字段
+async fn execute(request: Request, states: &[Arc<AppState>]) -> Result<serde_json::Value> {
+    let selected = select_states(states, request.config.as_deref())?;
+
+    match request.command {
+        ControlCommand::Status => Ok(json!({
+            "status": crate::http::status_value(&selected).await
+        })),
+        ControlCommand::Switch { interface } => {
+            let state = single_state(&selected)?;
+            crate::in_log_scope(state, crate::manual_switch(state, &interface)).await?;
+            Ok(json!({ "active_interface": active_interface(state).await }))
+        }
+        ControlCommand::Pin { interface } => {
+            let state = single_state(&selected)?;
+            crate::in_log_scope(state, crate::pin_interface(state, Some(&interface))).await?;
+            Ok(json!({ "active_interface": active_interface(state).await }))
+        }
+        ControlCommand::Unpin => {
+            for state in &selected {
+                crate::in_log_scope(state, crate::pin_interface(state, None)).await?;
+            }
+            Ok(serde_json::Value::Null)
+        }
+        ControlCommand::Result { interface, target } => {
+            let state = single_state(&selected)?;
+            Ok(json!({
+                "result": state.last_results.last_result(&interface, &target)
+            }))
+        }
+        ControlCommand::Recheck => {
+            for state in &selected {
+                state.recheck.notify_one();
+            }
+            Ok(serde_json::Value::Null)
+        }
+    }
+}
+
+/// 只作用于单个配置的命令要求明确的配置
+fn single_state(selected: &[Arc<AppState>]) -> Result<&Arc<AppState>> {
+    match selected {
+        [state] => Ok(state),
+        _ => anyhow::bail!("同时运行多个配置时需要用 config 指定配置"),
+    }
+}
+
+/// 当前活动接口
+async fn active_interface(state: &AppState) -> Option<String> {
+    state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string)
+}
+
+/// 按名称选择配置，未指定时选择全部
+fn select_states(states: &[Arc<AppState>], config: Option<&str>) -> Result<Vec<Arc<AppState>>> {
+    let Some(name) = config else {
+        return Ok(states.to_vec());
+    };
+    let state = states
+        .iter()
+        .find(|s| s.name.as_deref() == Some(name))
+        .with_context(|| format!("未知的配置: {}", name))?;
+    Ok(vec![Arc::clone(state)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::time::Duration;
+
+    /// 发送一行命令并读取一行响应
+    async fn send(stream: &mut BufReader<UnixStream>, command: &str) -> serde_json::Value {
+        stream
+            .write_all(format!("{}\n", command).as_bytes())
+            .await
+            .unwrap();
+        let mut reply = String::new();
+        stream.read_line(&mut reply).await.unwrap();
+        serde_json::from_str(&reply).unwrap()
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let request: Request =
+            serde_json::from_str(r#"{"command": "switch", "interface": "wan_b"}"#).unwrap();
+        assert_eq!(
+            request.command,
+            ControlCommand::Switch {
+                interface: "wan_b".to_string()
+            }
+        );
+        assert_eq!(request.config, None);
+
+        let request: Request =
+            serde_json::from_str(r#"{"command": "recheck", "config": "home"}"#).unwrap();
+        assert_eq!(request.command, ControlCommand::Recheck);
+        assert_eq!(request.config.as_deref(), Some("home"));
+
+        assert!(serde_json::from_str::<Request>(r#"{"command": "reboot"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_control_socket_commands() {
+        let config: Config = toml::from_str(
+            r#"
+            [global]
+            dry_run = true
+            manage_uci_routes = false
+
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        let state = Arc::new(AppState::new(config));
+        let path = std::env::temp_dir().join(format!(
+            "routes-monitor-control-{}.sock",
+            std::process::id()
+        ));
+        let server_path = path.clone();
+        let server_states = vec![Arc::clone(&state)];
+        tokio::spawn(async move { serve(&server_path, server_states).await });
+
+        let stream = loop {
+            match UnixStream::connect(&path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let mut stream = BufReader::new(stream);
+
+        let reply = send(&mut stream, r#"{"command": "status"}"#).await;
+        assert_eq!(reply["ok"], true);
+        assert_eq!(reply["status"]["active_interface"], serde_json::Value::Null);
+
+        // 演练模式下手动切换只记录，不修改路由
+        let reply = send(
+            &mut stream,
+            r#"{"command": "switch", "interface": "wan_a"}"#,
+        )
+        .await;
+        assert_eq!(reply["ok"], true);
+
+        let reply = send(
+            &mut stream,
+            r#"{"command": "switch", "interface": "wan_x"}"#,
+        )
+        .await;
+        assert_eq!(reply["ok"], false);
+        assert!(reply["error"].as_str().unwrap().contains("wan_x"));
+
+        let reply = send(&mut stream, "not json").await;
+        assert_eq!(reply["ok"], false);
+
+        let reply = send(&mut stream, r#"{"command": "pin", "interface": "wan_x"}"#).await;
+        assert_eq!(reply["ok"], false);
+        assert!(state.pinned_interface.lock().unwrap().is_none());
+
+        let reply = send(&mut stream, r#"{"command": "pin", "interface": "wan_a"}"#).await;
+        assert_eq!(reply["ok"], true);
+        assert_eq!(
+            state.pinned_interface.lock().unwrap().as_deref(),
+            Some("wan_a")
+        );
+        let reply = send(&mut stream, r#"{"command": "unpin"}"#).await;
+        assert_eq!(reply["ok"], true);
+        assert!(state.pinned_interface.lock().unwrap().is_none());
+
+        let reply = send(
+            &mut stream,
+            r#"{"command": "result", "interface": "wan_a", "target": "8.8.8.8"}"#,
+        )
+        .await;
+        assert_eq!(reply["ok"], true);
+        assert_eq!(reply["result"], serde_json::Value::Null);
+
+        let reply = send(&mut stream, r#"{"command": "recheck"}"#).await;
+        assert_eq!(reply["ok"], true);
+        tokio::time::timeout(Duration::from_secs(1), state.recheck.notified())
+            .await
+            .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! SQLite 数据导出
+//!
+//! 将每轮检查的接口评分和切换事件写入 SQLite 文件，便于用 SQL 分析切换历史和各接口趋势。
+//! 表结构在启动时自动创建和升级；写入在独立线程中批量进行，不阻塞监控循环。
+//! 时间统一以 UTC 的 `YYYY-MM-DD HH:MM:SS` 格式保存，可直接使用 SQLite 的日期函数
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use log::{debug, warn};
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::results_log::LogRecord;
+
+/// 各版本的表结构升级语句，`PRAGMA user_version` 记录已应用的版本
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE checks (
+        id INTEGER PRIMARY KEY,
+        ts TEXT NOT NULL,
+        active TEXT
+    );
+    CREATE INDEX checks_ts ON checks (ts);
+    CREATE TABLE interface_scores (
+        check_id INTEGER NOT NULL REFERENCES checks (id) ON DELETE CASCADE,
+        interface TEXT NOT NULL,
+        reachable_count INTEGER NOT NULL,
+        target_count INTEGER NOT NULL,
+        avg_latency_ms REAL,
+        avg_packet_loss REAL NOT NULL,
+        score REAL NOT NULL
+    );
+    CREATE INDEX interface_scores_check ON interface_scores (check_id);
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY,
+        ts TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        from_interface TEXT,
+        to_interface TEXT,
+        duration_secs REAL
+    );
+    CREATE INDEX events_ts ON events (ts);
+"];
+
+/// 过期数据的清理间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// SQLite 数据库
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// 打开数据库，必要时创建或升级表结构
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(&path)
+            .with_context(|| format!("无法打开 SQLite 数据库: {:?}", path.as_ref()))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            conn.execute_batch(migration)
+                .with_context(|| format!("升级数据库表结构到版本 {} 失败", i + 1))?;
+            conn.pragma_update(None, "user_version", i + 1)?;
+        }
+
+        Ok(Self { conn })
+    }
+
+    /// 写入一批记录：同一时间的检查记录归为一次检查，切换记录写入事件表
+    pub fn insert(&mut self, records: &[LogRecord]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        let mut checks: BTreeMap<DateTime<Local>, Vec<&LogRecord>> = BTreeMap::new();
+        for record in records {
+            match record {
+                LogRecord::Check { ts, .. } => checks.entry(*ts).or_default().push(record),
+                LogRecord::Switch {
+                    ts,
+                    from,
+                    to,
+                    duration_secs,
+                } => {
+                    tx.execute(
+                        "INSERT INTO events (ts, kind, from_interface, to_interface, duration_secs)
+                         VALUES (?1, 'switch', ?2, ?3, ?4)",
+                        params![sql_time(*ts), from, to, duration_secs],
+                    )?;
+                }
+            }
+        }
+
+        for (ts, scores) in checks {
+            let active = scores.iter().find_map(|record| match record {
+                LogRecord::Check { active, .. } => active.clone(),
+                _ => None,
+            });
+            tx.execute(
+                "INSERT INTO checks (ts, active) VALUES (?1, ?2)",
+                params![sql_time(ts), active],
+            )?;
+            let check_id = tx.last_insert_rowid();
+
+            for record in scores {
+                if let LogRecord::Check {
+                    interface,
+                    reachable_count,
+                    target_count,
+                    avg_latency_ms,
+                    avg_packet_loss,
+                    score,
+                    ..
+                } = record
+                {
+                    tx.execute(
+                        "INSERT INTO interface_scores (check_id, interface, reachable_count,
+                         target_count, avg_latency_ms, avg_packet_loss, score)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                        params![
+                            check_id,
+                            interface,
+                            *reachable_count as i64,
+                            *target_count as i64,
+                            avg_latency_ms,
+                            avg_packet_loss,
+                            score
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 删除早于 `before` 的检查和事件
+    /// 返回: 删除的检查与事件条数
+    pub fn prune(&self, before: DateTime<Utc>) -> Result<usize> {
+        let cutoff = sql_time(before);
+        let checks = self
+            .conn
+            .execute("DELETE FROM checks WHERE ts < ?1", [&cutoff])?;
+        let events = self
+            .conn
+            .execute("DELETE FROM events WHERE ts < ?1", [&cutoff])?;
+        Ok(checks + events)
+    }
+}
+
+/// 转换为 SQLite 日期函数使用的 UTC 时间格式
+fn sql_time<Tz: chrono::TimeZone>(ts: DateTime<Tz>) -> String {
+    ts.with_timezone(&Utc)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// 后台写入线程的句柄，发送记录不会阻塞调用方
+pub struct Writer {
+    sender: mpsc::Sender<Vec<LogRecord>>,
+}
+
+impl Writer {
+    /// 打开数据库并启动写入线程；`retention_days` 为 0 时不清理旧数据
+    pub fn spawn<P: AsRef<Path>>(path: P, retention_days: u64) -> Result<Self> {
+        let mut database = Database::open(path)?;
+        let (sender, receiver) = mpsc::channel::<Vec<LogRecord>>();
+
+        thread::Builder::new()
+            .name("sqlite-writer".to_string())
+            .spawn(move || {
+                let mut last_prune: Option<Instant> = None;
+                while let Ok(mut batch) = receiver.recv() {
+                    // 合并积压的记录，在同一事务中写入
+                    batch.extend(receiver.try_iter().flatten());
+                    if let Err(e) = database.insert(&batch) {
+                        warn!("写入 SQLite 数据库失败: {:#}", e);
+                    }
+
+                    if retention_days > 0
+                        && last_prune.is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL)
+                    {
+                        last_prune = Some(Instant::now());
+                        let before = Utc::now() - ChronoDuration::days(retention_days as i64);
+                        match database.prune(before) {
+                            Ok(removed) => debug!("已清理 {} 条过期的 SQLite 记录", removed),
+                            Err(e) => warn!("清理 SQLite 旧数据失败: {:#}", e),
+                        }
+                    }
+                }
+            })
+            .context("无法启动 SQLite 写入线程")?;
+
+        Ok(Self { sender })
+    }
+
+    /// 提交一批记录
+    pub fn send(&self, records: Vec<LogRecord>) {
+        if self.sender.send(records).is_err() {
+            warn!("SQLite 写入线程已停止，记录被丢弃");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn check(ts: DateTime<Local>, interface: &str, score: f64) -> LogRecord {
+        LogRecord::Check {
+            ts,
+            interface: interface.to_string(),
+            reachable_count: 2,
+            target_count: 2,
+            avg_latency_ms: Some(10.0),
+            avg_packet_loss: 0.0,
+            score,
+            active: Some("wan_a".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_insert_and_query_checks() {
+        let mut database = Database::init(Connection::open_in_memory().unwrap()).unwrap();
+        let first = Local.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let second = Local.with_ymd_and_hms(2026, 1, 2, 0, 0, 0).unwrap();
+
+        database
+            .insert(&[
+                check(first, "wan_a", 80.0),
+                check(first, "wan_b", 60.0),
+                LogRecord::Switch {
+                    ts: first,
+                    from: None,
+                    to: "wan_a".to_string(),
+                    duration_secs: Some(1.5),
+                },
+            ])
+            .unwrap();
+        database
+            .insert(&[check(second, "wan_a", 70.0), check(second, "wan_b", 50.0)])
+            .unwrap();
+
+        let conn = &database.conn;
+        let checks: i64 = conn
+            .query_row("SELECT COUNT(*) FROM checks", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(checks, 2);
+
+        let average: f64 = conn
+            .query_row(
+                "SELECT AVG(score) FROM interface_scores WHERE interface = 'wan_a'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(average, 75.0);
+
+        let (to, duration): (String, f64) = conn
+            .query_row(
+                "SELECT to_interface, duration_secs FROM events WHERE kind = 'switch'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((to.as_str(), duration), ("wan_a", 1.5));
+
+        // 清理第一天的数据，检查关联的评分一并删除
+        let removed = database.prune(second.with_timezone(&Utc)).unwrap();
+        assert_eq!(removed, 2);
+        let scores: i64 = conn
+            .query_row("SELECT COUNT(*) FROM interface_scores", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(scores, 2);
+    }
+
+    #[test]
+    fn test_schema_created_once() {
+        let path =
+            std::env::temp_dir().join(format!("routes-monitor-db-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut database = Database::open(&path).unwrap();
+        database
+            .insert(&[check(Local::now(), "wan_a", 80.0)])
+            .unwrap();
+        drop(database);
+
+        // 再次打开时不重复建表，已有数据保留
+        let database = Database::open(&path).unwrap();
+        let version: i64 = database
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        let checks: i64 = database
+            .conn
+            .query_row("SELECT COUNT(*) FROM checks", [], |row| row.get(0))
+            .unwrap();
+        drop(database);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        assert_eq!(checks, 1);
+    }
+}
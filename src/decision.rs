@@ -0,0 +1,463 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use cron::Schedule;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::network::{Eligibility, IneligibleReason, InterfaceScore};
+
+/// 解析冻结窗口的 cron 表达式
+///
+/// 使用 `cron` crate 的格式（秒 分 时 日 月 周 [年]），
+/// 也接受省略秒字段的 5 段标准写法
+pub fn parse_freeze_window(expr: &str) -> Result<Schedule> {
+    let expr = expr.trim();
+    let normalized = if expr.split_whitespace().count() == 5 {
+        format!("* {}", expr)
+    } else {
+        expr.to_string()
+    };
+
+    Schedule::from_str(&normalized).with_context(|| format!("无效的冻结窗口表达式: {}", expr))
+}
+
+/// 返回当前时间命中的冻结窗口
+pub fn active_freeze_window(windows: &[(String, Schedule)], now: DateTime<Local>) -> Option<&str> {
+    windows
+        .iter()
+        .find(|(_, schedule)| schedule.includes(now))
+        .map(|(expr, _)| expr.as_str())
+}
+
+/// 新出现接口的观察期
+///
+/// 首次检查时已存在的接口直接信任；之后新出现（或消失后重新出现）的接口
+/// 需连续出现 `required` 轮检查后才可参与选择
+#[derive(Debug, Default)]
+pub struct ProbationTracker {
+    required: u32,
+    initialized: bool,
+    /// 各接口连续出现的检查轮数
+    seen: HashMap<String, u32>,
+}
+
+impl ProbationTracker {
+    pub fn new(required: u32) -> Self {
+        Self {
+            required,
+            ..Default::default()
+        }
+    }
+
+    /// 记录本轮存在的接口，返回仍处于观察期的接口及还需观察的轮数
+    pub fn observe<'a>(
+        &mut self,
+        present: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<(String, u32)> {
+        let mut seen = HashMap::new();
+        for name in present {
+            let count = if self.initialized {
+                self.seen.get(name).copied().unwrap_or(0).saturating_add(1)
+            } else {
+                self.required.saturating_add(1)
+            };
+            seen.insert(name.to_string(), count);
+        }
+        self.seen = seen;
+        self.initialized = true;
+
+        let mut pending: Vec<(String, u32)> = self
+            .seen
+            .iter()
+            .filter(|(_, count)| **count <= self.required)
+            .map(|(name, count)| (name.clone(), self.required - count + 1))
+            .collect();
+        pending.sort();
+        pending
+    }
+}
+
+/// 各接口最近若干轮的评分，用于衡量链路稳定性
+#[derive(Debug, Default)]
+pub struct ScoreHistory {
+    window: usize,
+    samples: HashMap<String, VecDeque<f64>>,
+}
+
+impl ScoreHistory {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            ..Default::default()
+        }
+    }
+
+    /// 记录本轮评分，超出窗口的旧评分被丢弃
+    pub fn record(&mut self, scores: &[InterfaceScore]) {
+        for score in scores {
+            let samples = self.samples.entry(score.interface.clone()).or_default();
+            samples.push_back(score.score);
+            while samples.len() > self.window {
+                samples.pop_front();
+            }
+        }
+    }
+
+    /// 窗口内评分的方差（样本不足 2 个时为 `None`）
+    pub fn variance(&self, interface: &str) -> Option<f64> {
+        let samples = self.samples.get(interface)?;
+        if samples.len() < 2 {
+            return None;
+        }
+
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        Some(samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n)
+    }
+}
+
+/// 每小时切换次数上限
+///
+/// 记录滚动一小时窗口内的切换时间，达到上限后阻止切换直到最早的记录移出窗口
+#[derive(Debug)]
+pub struct SwitchRateLimiter {
+    /// 每小时最多切换次数（0 表示不限制）
+    limit: u32,
+    switches: VecDeque<Instant>,
+}
+
+impl SwitchRateLimiter {
+    /// 滚动窗口长度
+    const WINDOW: Duration = Duration::from_secs(3600);
+
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            switches: VecDeque::new(),
+        }
+    }
+
+    /// 记录一次切换
+    pub fn record(&mut self, at: Instant) {
+        if self.limit > 0 {
+            self.switches.push_back(at);
+        }
+    }
+
+    /// 达到上限时返回窗口内的切换次数与解除限制前的剩余时间
+    pub fn exceeded(&mut self, now: Instant) -> Option<(u32, Duration)> {
+        while self
+            .switches
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= Self::WINDOW)
+        {
+            self.switches.pop_front();
+        }
+
+        let count = self.switches.len() as u32;
+        if self.limit == 0 || count < self.limit {
+            return None;
+        }
+
+        let oldest = self.switches.front()?;
+        Some((count, Self::WINDOW - now.duration_since(*oldest)))
+    }
+}
+
+/// 阻止切换的条件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "gate", rename_all = "snake_case")]
+pub enum SwitchGate {
+    /// 连续非最佳次数未达到阈值
+    ThresholdNotMet { failures: u32, threshold: u32 },
+    /// 自动切换已禁用
+    AutoSwitchDisabled,
+    /// 处于冻结窗口内
+    FreezeWindow { window: String },
+    /// 回切到最近被切走的接口，但领先分数不足
+    FailbackMargin { margin: f64, required: f64 },
+    /// 距上次切换不足冷却时间
+    Cooldown { remaining_secs: u64 },
+    /// 接口已固定，且固定接口仍有可达目标
+    Pinned { interface: String },
+    /// 最近一小时的切换次数已达上限
+    RateLimited {
+        switches: u32,
+        limit: u32,
+        retry_after_secs: u64,
+    },
+}
+
+impl SwitchGate {
+    /// 阻止切换到最佳接口时，在该接口评分上显示的不可选原因；
+    /// 阈值、回切余量和自动切换开关不针对接口本身，不映射
+    pub fn ineligibility(&self) -> Option<IneligibleReason> {
+        match self {
+            SwitchGate::Cooldown { remaining_secs } => Some(IneligibleReason::Cooldown {
+                remaining_secs: *remaining_secs,
+            }),
+            SwitchGate::Pinned { interface } => Some(IneligibleReason::Pinned {
+                interface: interface.clone(),
+            }),
+            SwitchGate::FreezeWindow { window } => Some(IneligibleReason::FreezeWindow {
+                window: window.clone(),
+            }),
+            SwitchGate::RateLimited {
+                retry_after_secs, ..
+            } => Some(IneligibleReason::RateLimited {
+                retry_after_secs: *retry_after_secs,
+            }),
+            SwitchGate::ThresholdNotMet { .. }
+            | SwitchGate::AutoSwitchDisabled
+            | SwitchGate::FailbackMargin { .. } => None,
+        }
+    }
+}
+
+/// 决策结果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionOutcome {
+    /// 尚未设置活动接口，直接切换
+    NoActiveInterface,
+    /// 当前接口已是最佳接口
+    AlreadyBest,
+    /// 达到切换阈值
+    ThresholdReached,
+    /// 当前接口完全不可达，跳过防抖条件立即切换
+    Emergency,
+    /// 被切换条件阻止
+    Blocked,
+}
+
+/// 切换决策依据
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwitchDecision {
+    /// 当前活动接口
+    pub current: Option<String>,
+    /// 当前接口评分
+    pub current_score: Option<f64>,
+    /// 最佳接口
+    pub best: String,
+    /// 最佳接口评分
+    pub best_score: f64,
+    /// 最佳接口领先当前接口的分数
+    pub margin: Option<f64>,
+    /// 当前接口连续非最佳次数
+    pub failures: u32,
+    /// 切换阈值
+    pub failure_threshold: u32,
+    /// 触发的阻止条件
+    pub gates: Vec<SwitchGate>,
+    /// 决策结果
+    pub outcome: DecisionOutcome,
+}
+
+impl SwitchDecision {
+    /// 是否执行切换
+    pub fn should_switch(&self) -> bool {
+        self.gates.is_empty()
+            && matches!(
+                self.outcome,
+                DecisionOutcome::NoActiveInterface
+                    | DecisionOutcome::ThresholdReached
+                    | DecisionOutcome::Emergency
+            )
+    }
+
+    /// 最佳接口被冷却、固定、冻结或限频阻止时，把原因记到其评分上
+    pub fn mark_blocked_best(&self, scores: &mut [InterfaceScore]) {
+        if self.should_switch() || self.current.as_deref() == Some(self.best.as_str()) {
+            return;
+        }
+        let Some(reason) = self.gates.iter().find_map(SwitchGate::ineligibility) else {
+            return;
+        };
+        if let Some(score) = scores
+            .iter_mut()
+            .find(|s| s.interface == self.best && s.is_eligible())
+        {
+            score.eligibility = Eligibility::Ineligible(reason);
+        }
+    }
+}
+
+/// 单次检查汇总
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckSummary {
+    /// 检查时间
+    pub checked_at: chrono::DateTime<chrono::Local>,
+    /// 各接口评分（已排序）
+    pub scores: Vec<InterfaceScore>,
+    /// 切换决策（没有可用接口时为空）
+    pub decision: Option<SwitchDecision>,
+    /// 是否成功切换
+    pub switched: bool,
+    /// 检查耗时（秒）
+    pub elapsed_secs: f64,
+}
+
+/// 单轮检查记录
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleRecord {
+    /// 检查时间
+    pub checked_at: DateTime<Local>,
+    /// 各接口评分
+    pub scores: Vec<InterfaceScore>,
+    /// 本轮选出的最佳接口（没有可用接口时为空）
+    pub chosen: Option<String>,
+    /// 是否发生切换
+    pub switched: bool,
+}
+
+/// 最近若干轮的检查记录，超出容量时丢弃最旧的记录
+#[derive(Debug, Default)]
+pub struct CycleHistory {
+    capacity: usize,
+    records: VecDeque<CycleRecord>,
+}
+
+impl CycleHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// 修改容量，超出部分从最旧的记录开始丢弃
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// 记录一轮检查
+    pub fn push(&mut self, record: CycleRecord) {
+        self.records.push_back(record);
+        self.evict();
+    }
+
+    /// 按时间顺序（从旧到新）返回所有记录
+    pub fn records(&self) -> Vec<CycleRecord> {
+        self.records.iter().cloned().collect()
+    }
+
+    fn evict(&mut self) {
+        while self.records.len() > self.capacity {
+            self.records.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Eligibility;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_freeze_window_matching() {
+        // 每天 02:00-03:59 冻结（5 段写法）
+        let windows = vec![(
+            "* 2-3 * * *".to_string(),
+            parse_freeze_window("* 2-3 * * *").unwrap(),
+        )];
+
+        let inside = Local.with_ymd_and_hms(2026, 3, 1, 2, 30, 15).unwrap();
+        let outside = Local.with_ymd_and_hms(2026, 3, 1, 4, 0, 0).unwrap();
+
+        assert_eq!(active_freeze_window(&windows, inside), Some("* 2-3 * * *"));
+        assert_eq!(active_freeze_window(&windows, outside), None);
+
+        assert!(parse_freeze_window("0 30 1 * * Sun").is_ok());
+        assert!(parse_freeze_window("not a cron").is_err());
+    }
+
+    #[test]
+    fn test_score_history_rolling_variance() {
+        let score = |interface: &str, value: f64| InterfaceScore {
+            interface: interface.to_string(),
+            reachable_count: 1,
+            reachable_v4: 1,
+            reachable_v6: 0,
+            avg_latency_ms: 10.0,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: 0.0,
+            score: value,
+            eligibility: Eligibility::Eligible,
+        };
+        let mut history = ScoreHistory::new(3);
+
+        history.record(&[score("wan_a", 50.0)]);
+        assert_eq!(history.variance("wan_a"), None);
+        assert_eq!(history.variance("wan_b"), None);
+
+        history.record(&[score("wan_a", 70.0)]);
+        assert_eq!(history.variance("wan_a"), Some(100.0));
+
+        // 超出窗口后只保留最近 3 轮
+        history.record(&[score("wan_a", 60.0)]);
+        history.record(&[score("wan_a", 60.0)]);
+        history.record(&[score("wan_a", 60.0)]);
+        assert_eq!(history.variance("wan_a"), Some(0.0));
+    }
+
+    #[test]
+    fn test_cycle_history_evicts_oldest() {
+        let record = |switched: bool| CycleRecord {
+            checked_at: Local::now(),
+            scores: Vec::new(),
+            chosen: Some("wan_a".to_string()),
+            switched,
+        };
+        let mut history = CycleHistory::new(2);
+
+        history.push(record(true));
+        history.push(record(false));
+        history.push(record(false));
+        let switched: Vec<bool> = history.records().iter().map(|r| r.switched).collect();
+        assert_eq!(switched, vec![false, false]);
+
+        // 缩小容量时丢弃最旧的记录，容量为 0 时不记录
+        history.push(record(true));
+        history.set_capacity(1);
+        assert!(history.records()[0].switched);
+        history.set_capacity(0);
+        history.push(record(true));
+        assert!(history.records().is_empty());
+    }
+
+    #[test]
+    fn test_new_interface_probation() {
+        let mut tracker = ProbationTracker::new(2);
+
+        // 启动时已有的接口不需要观察
+        assert!(tracker.observe(["wan_a"]).is_empty());
+
+        // 新接口需观察 2 轮，第 3 轮起可参与选择
+        let pending = |name: &str, remaining| vec![(name.to_string(), remaining)];
+        assert_eq!(tracker.observe(["wan_a", "wan_b"]), pending("wan_b", 2));
+        assert_eq!(tracker.observe(["wan_a", "wan_b"]), pending("wan_b", 1));
+        assert!(tracker.observe(["wan_a", "wan_b"]).is_empty());
+
+        // 消失后重新出现视为新接口
+        assert!(tracker.observe(["wan_a"]).is_empty());
+        assert_eq!(tracker.observe(["wan_a", "wan_b"]), pending("wan_b", 2));
+
+        // 未配置观察期时不限制
+        let mut tracker = ProbationTracker::new(0);
+        tracker.observe(["wan_a"]);
+        assert!(tracker.observe(["wan_a", "wan_b"]).is_empty());
+    }
+}
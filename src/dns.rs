@@ -0,0 +1,220 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 经指定接口查询 DNS
+//!
+//! 查询报文从绑定到接口（SO_BINDTODEVICE）的 UDP 套接字发出，保证经被探测的链路到达
+//! 该链路运营商的 DNS 服务器，而不是跟随默认路由从当前活动接口出去
+
+use crate::probe::{self, Transport};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+/// A 记录类型
+pub const TYPE_A: u16 = 1;
+/// AAAA 记录类型
+pub const TYPE_AAAA: u16 = 28;
+/// IN 类
+const CLASS_IN: u16 = 1;
+/// DNS 报文首部长度
+const HEADER_LEN: usize = 12;
+
+/// 创建绑定到指定接口、连接到 DNS 服务器的 UDP 套接字
+pub fn open_socket(interface: &str, server: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = probe::probe_socket(Some(interface), server, Transport::Udp, false)?;
+    socket.set_nonblocking(true)?;
+    socket.connect(&server.into())?;
+
+    UdpSocket::from_std(std::net::UdpSocket::from(socket))
+}
+
+/// 构造查询报文（请求递归）
+fn query_packet(id: u16, host: &str, record_type: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + host.len() + 6);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // RD = 1，问题数 = 1
+    packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&record_type.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    packet
+}
+
+/// 跳过报文中的一个域名（支持压缩指针），返回域名之后的偏移
+fn skip_name(packet: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(offset)?;
+        match len {
+            0 => return Some(offset + 1),
+            // 压缩指针占两个字节，指向的内容不影响后续偏移
+            _ if len & 0xc0 == 0xc0 => return Some(offset + 2),
+            _ => offset += 1 + len as usize,
+        }
+    }
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    let bytes = packet.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// 从应答报文中取出第一个指定类型的地址；ID 不匹配、出错或没有该类型记录时返回 `None`
+fn parse_response(packet: &[u8], id: u16, record_type: u16) -> Option<IpAddr> {
+    if read_u16(packet, 0)? != id {
+        return None;
+    }
+    let flags = read_u16(packet, 2)?;
+    // 必须是应答（QR = 1）且 RCODE = 0
+    if flags & 0x8000 == 0 || flags & 0x000f != 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let answers = read_u16(packet, 6)?;
+
+    let mut offset = HEADER_LEN;
+    for _ in 0..questions {
+        offset = skip_name(packet, offset)? + 4;
+    }
+
+    for _ in 0..answers {
+        offset = skip_name(packet, offset)?;
+        let kind = read_u16(packet, offset)?;
+        let len = read_u16(packet, offset + 8)? as usize;
+        let data = packet.get(offset + 10..offset + 10 + len)?;
+        offset += 10 + len;
+
+        match (kind, data.len()) {
+            (TYPE_A, 4) if kind == record_type => {
+                return Some(IpAddr::V4(Ipv4Addr::new(
+                    data[0], data[1], data[2], data[3],
+                )));
+            }
+            (TYPE_AAAA, 16) if kind == record_type => {
+                let octets: [u8; 16] = data.try_into().ok()?;
+                return Some(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            // CNAME 等其他记录跳过
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// 发送一次查询并等待应答
+/// 返回: 解析到的地址；服务器没有该类型的记录时为 `None`，超时视为错误
+pub async fn query(
+    socket: &UdpSocket,
+    host: &str,
+    record_type: u16,
+    wait: Duration,
+) -> io::Result<Option<IpAddr>> {
+    let id = fastrand::u16(..);
+    socket.send(&query_packet(id, host, record_type)).await?;
+
+    let mut buffer = [0u8; 1500];
+    // 忽略 ID 不匹配的报文（如上一次超时查询迟到的应答）
+    timeout(wait, async {
+        loop {
+            let len = socket.recv(&mut buffer).await?;
+            if read_u16(&buffer[..len], 0) == Some(id) {
+                return Ok(parse_response(&buffer[..len], id, record_type));
+            }
+        }
+    })
+    .await
+    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+/// 构造只含一条答案记录的应答（测试用的 DNS 服务器）
+#[cfg(test)]
+pub fn response(query: &[u8], record_type: u16, data: &[u8]) -> Vec<u8> {
+    let mut packet = query.to_vec();
+    packet[2] = 0x81;
+    packet[3] = 0x80;
+    packet[7] = 1;
+    // 名称使用指向问题部分的压缩指针
+    packet.extend_from_slice(&[0xc0, 0x0c]);
+    packet.extend_from_slice(&record_type.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    packet.extend_from_slice(&300u32.to_be_bytes());
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket2::SockRef;
+
+    #[test]
+    fn test_query_packet() {
+        let packet = query_packet(0x1234, "www.example.com", TYPE_A);
+        assert_eq!(&packet[..4], &[0x12, 0x34, 0x01, 0x00]);
+        assert_eq!(&packet[HEADER_LEN..HEADER_LEN + 4], b"\x03www");
+        assert_eq!(&packet[packet.len() - 4..], &[0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let query = query_packet(7, "www.example.com", TYPE_A);
+        let packet = response(&query, TYPE_A, &[93, 184, 216, 34]);
+        assert_eq!(
+            parse_response(&packet, 7, TYPE_A),
+            Some("93.184.216.34".parse().unwrap())
+        );
+        // ID 不匹配或请求的是其他类型
+        assert_eq!(parse_response(&packet, 8, TYPE_A), None);
+        assert_eq!(parse_response(&packet, 7, TYPE_AAAA), None);
+
+        let query = query_packet(7, "www.example.com", TYPE_AAAA);
+        let packet = response(&query, TYPE_AAAA, &Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(
+            parse_response(&packet, 7, TYPE_AAAA),
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+
+        // NXDOMAIN 与截断的报文
+        let mut nxdomain = response(&query, TYPE_AAAA, &[]);
+        nxdomain[3] = 0x83;
+        assert_eq!(parse_response(&nxdomain, 7, TYPE_AAAA), None);
+        assert_eq!(
+            parse_response(&packet[..packet.len() - 1], 7, TYPE_AAAA),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_bound_to_interface() {
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let socket = open_socket("lo", address).unwrap();
+        // 查询套接字绑定到被探测的接口
+        assert_eq!(
+            SockRef::from(&socket).device().unwrap().as_deref(),
+            Some(&b"lo"[..])
+        );
+
+        let responder = std::thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (len, peer) = server.recv_from(&mut buffer).unwrap();
+            let reply = response(&buffer[..len], TYPE_A, &[93, 184, 216, 34]);
+            server.send_to(&reply, peer).unwrap();
+        });
+        let address = query(&socket, "www.example.com", TYPE_A, Duration::from_secs(5))
+            .await
+            .unwrap();
+        responder.join().unwrap();
+        assert_eq!(address, Some("93.184.216.34".parse().unwrap()));
+    }
+}
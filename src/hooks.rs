@@ -0,0 +1,118 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 切换前后的检查命令
+//!
+//! 命令经 `sh -c` 执行，通过环境变量获得切换信息：
+//! `ROUTES_MONITOR_EVENT`（pre_switch / post_switch）、`ROUTES_MONITOR_FROM`（原接口，可能为空）
+//! 和 `ROUTES_MONITOR_TO`（新接口）
+
+use anyhow::{Context, Result};
+use std::time::Duration;
+
+use crate::command::CommandRunner;
+
+/// 检查命令对应的切换阶段
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwitchEvent {
+    /// 切换前，命令失败则取消切换
+    PreSwitch,
+    /// 切换后，命令失败则回滚
+    PostSwitch,
+}
+
+impl SwitchEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            SwitchEvent::PreSwitch => "pre_switch",
+            SwitchEvent::PostSwitch => "post_switch",
+        }
+    }
+}
+
+/// 执行检查命令，命令以退出状态 0 结束时通过
+pub async fn run_gate(
+    runner: &dyn CommandRunner,
+    command: &str,
+    event: SwitchEvent,
+    from: Option<&str>,
+    to: &str,
+    limit: Duration,
+) -> Result<()> {
+    let event_var = format!("ROUTES_MONITOR_EVENT={}", event.as_str());
+    let from_var = format!("ROUTES_MONITOR_FROM={}", from.unwrap_or_default());
+    let to_var = format!("ROUTES_MONITOR_TO={}", to);
+    let args = [
+        event_var.as_str(),
+        from_var.as_str(),
+        to_var.as_str(),
+        "sh",
+        "-c",
+        command,
+    ];
+
+    let output = runner
+        .run_with_timeout("env", &args, limit)
+        .await
+        .context("执行检查命令失败")?;
+
+    match output.status {
+        None => anyhow::bail!("检查命令超时（{} 秒）", limit.as_secs()),
+        Some(status) if !status.success() => anyhow::bail!(
+            "检查命令退出状态 {}: {}",
+            status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Some(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::mock::{error_output, output, MockRunner};
+
+    #[tokio::test]
+    async fn test_gate_command_env_and_status() {
+        let runner = MockRunner::new(|cmd| {
+            if cmd.contains("backup-running") {
+                error_output(1, "备份进行中")
+            } else {
+                output(0, "")
+            }
+        });
+        let limit = Duration::from_secs(30);
+
+        run_gate(
+            &runner,
+            "/usr/bin/check",
+            SwitchEvent::PreSwitch,
+            None,
+            "wan_b",
+            limit,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "env ROUTES_MONITOR_EVENT=pre_switch ROUTES_MONITOR_FROM= \
+                 ROUTES_MONITOR_TO=wan_b sh -c /usr/bin/check"
+            ]
+        );
+
+        let err = run_gate(
+            &runner,
+            "backup-running",
+            SwitchEvent::PostSwitch,
+            Some("wan_a"),
+            "wan_b",
+            limit,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("备份进行中"));
+    }
+}
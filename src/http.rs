@@ -0,0 +1,399 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! HTTP 指标与状态接口
+//!
+//! 同时运行多个配置时共用一个 HTTP 接口，各配置的指标带 `config` 标签
+
+use anyhow::{Context, Result};
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Local};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, PoisonError};
+
+use crate::decision::CycleRecord;
+use crate::metrics;
+use crate::network::InterfaceScore;
+use crate::AppState;
+
+/// 各配置的应用状态
+type Monitors = Arc<Vec<Arc<AppState>>>;
+
+/// 构建路由
+pub fn router(states: Vec<Arc<AppState>>) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route("/status", get(status_handler))
+        .route("/history", get(history_handler))
+        .route("/result", get(result_handler))
+        .with_state(Arc::new(states))
+}
+
+/// 在指定地址上提供 HTTP 服务
+pub async fn serve(listen: &str, states: Vec<Arc<AppState>>) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("无法监听 HTTP 地址: {}", listen))?;
+    info!("HTTP 接口已启动: http://{}", listen);
+
+    axum::serve(listener, router(states))
+        .await
+        .context("HTTP 服务异常退出")
+}
+
+/// GET /metrics
+async fn metrics_handler(State(states): State<Monitors>) -> impl IntoResponse {
+    let mut texts = Vec::new();
+    for state in states.iter() {
+        let text = render_metrics(state).await;
+        texts.push(match &state.name {
+            Some(name) => metrics::with_config_label(&text, name),
+            None => text,
+        });
+    }
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        metrics::merge(&texts),
+    )
+}
+
+/// 单个配置的当前状态
+#[derive(Debug, Serialize)]
+struct Status {
+    /// 配置名称（同时运行多个配置时）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config: Option<String>,
+    /// 当前活动接口
+    active_interface: Option<String>,
+    /// 当前固定的接口
+    pinned_interface: Option<String>,
+    /// 最近一次检查完成的时间
+    last_check: Option<DateTime<Local>>,
+    /// 各接口的连续失败计数
+    failure_counts: BTreeMap<String, u32>,
+    /// 最近一次检查的接口评分
+    scores: Vec<InterfaceScore>,
+}
+
+/// GET /status
+async fn status_handler(State(states): State<Monitors>) -> Json<serde_json::Value> {
+    Json(status_value(&states).await)
+}
+
+/// 当前状态：单个配置时为该配置的状态对象，多个配置时为各配置状态组成的数组
+pub(crate) async fn status_value(states: &[Arc<AppState>]) -> serde_json::Value {
+    let mut statuses = Vec::new();
+    for state in states {
+        statuses.push(collect_status(state).await);
+    }
+
+    let value = match statuses.len() {
+        1 => serde_json::to_value(&statuses[0]),
+        _ => serde_json::to_value(&statuses),
+    };
+    value.unwrap_or_default()
+}
+
+/// 单个配置的检查记录
+#[derive(Debug, Serialize)]
+struct History {
+    /// 配置名称
+    config: Option<String>,
+    /// 最近若干轮的检查记录（从旧到新）
+    records: Vec<CycleRecord>,
+}
+
+/// GET /history
+/// 单个配置时返回检查记录数组，多个配置时返回各配置记录组成的数组
+async fn history_handler(State(states): State<Monitors>) -> Json<serde_json::Value> {
+    let value = match states.as_slice() {
+        [state] => serde_json::to_value(
+            state
+                .history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .records(),
+        ),
+        _ => serde_json::to_value(
+            states
+                .iter()
+                .map(|state| History {
+                    config: state.name.clone(),
+                    records: state
+                        .history
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .records(),
+                })
+                .collect::<Vec<_>>(),
+        ),
+    };
+    Json(value.unwrap_or_default())
+}
+
+/// GET /result 的查询参数
+#[derive(Debug, Deserialize)]
+struct ResultQuery {
+    /// 配置名称（同时运行多个配置时必需）
+    config: Option<String>,
+    /// 接口名称
+    interface: String,
+    /// 目标地址（与配置中的写法相同）
+    target: String,
+}
+
+/// GET /result?interface=<接口>&target=<目标>
+/// 返回该接口到该目标最近一次的探测结果，尚未探测过时返回 404
+async fn result_handler(
+    State(states): State<Monitors>,
+    Query(query): Query<ResultQuery>,
+) -> Response {
+    let state = match (&query.config, states.as_slice()) {
+        (Some(name), _) => states.iter().find(|s| s.name.as_deref() == Some(name)),
+        (None, [state]) => Some(state),
+        (None, _) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "同时运行多个配置时需要用 config 指定配置",
+            )
+                .into_response()
+        }
+    };
+    let Some(state) = state else {
+        return (StatusCode::NOT_FOUND, "未知的配置").into_response();
+    };
+
+    match state
+        .last_results
+        .last_result(&query.interface, &query.target)
+    {
+        Some(result) => Json(result).into_response(),
+        None => (StatusCode::NOT_FOUND, "没有该接口到该目标的探测结果").into_response(),
+    }
+}
+
+/// 读取单个配置的状态，各把锁只短暂持有，不阻塞监控循环
+async fn collect_status(state: &AppState) -> Status {
+    let active_interface = state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string);
+    let pinned_interface = state
+        .pinned_interface
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let failure_counts = state
+        .failure_count
+        .read()
+        .await
+        .iter()
+        .map(|(interface, count)| (interface.clone(), *count))
+        .collect();
+
+    Status {
+        config: state.name.clone(),
+        active_interface,
+        pinned_interface,
+        last_check: *state.last_check.read().await,
+        failure_counts,
+        scores: state.latest_scores.read().await.clone(),
+    }
+}
+
+/// 渲染单个配置的指标
+async fn render_metrics(state: &AppState) -> String {
+    let scores = state.latest_scores.read().await.clone();
+    let active = state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string);
+    let targets = state
+        .config()
+        .global
+        .metrics_per_target
+        .then(|| state.last_results.snapshot());
+
+    let switch_duration = *state.last_switch_duration.read().await;
+
+    metrics::render(
+        &scores,
+        active.as_deref(),
+        targets.as_deref(),
+        switch_duration,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::network::{Eligibility, TestResult};
+
+    #[tokio::test]
+    async fn test_status_endpoint() {
+        let config: Config = toml::from_str(
+            r#"
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        let state = Arc::new(AppState::new(config));
+        state
+            .failure_count
+            .write()
+            .await
+            .insert("wan_a".to_string(), 2);
+        *state.latest_scores.write().await = vec![InterfaceScore {
+            interface: "wan_a".to_string(),
+            reachable_count: 1,
+            reachable_v4: 1,
+            reachable_v6: 0,
+            avg_latency_ms: 10.0,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: 0.0,
+            score: 80.0,
+            eligibility: Eligibility::Eligible,
+        }];
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(vec![state])).await });
+
+        let status: serde_json::Value = reqwest::get(format!("http://{}/status", addr))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        // 单个配置时直接返回状态对象，尚未检查过时时间为空
+        assert_eq!(status["active_interface"], serde_json::Value::Null);
+        assert_eq!(status["pinned_interface"], serde_json::Value::Null);
+        assert_eq!(status["last_check"], serde_json::Value::Null);
+        assert_eq!(status["failure_counts"]["wan_a"], 2);
+        assert_eq!(status["scores"][0]["interface"], "wan_a");
+        assert_eq!(status["scores"][0]["score"], 80.0);
+        assert!(status.get("config").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_history_endpoint() {
+        let config: Config = toml::from_str(
+            r#"
+            [global]
+            history_size = 2
+
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        let state = Arc::new(AppState::new(config));
+        for (chosen, switched) in [("wan_a", true), ("wan_b", true), ("wan_b", false)] {
+            state.history.lock().unwrap().push(CycleRecord {
+                checked_at: Local::now(),
+                scores: Vec::new(),
+                chosen: Some(chosen.to_string()),
+                switched,
+            });
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(vec![state])).await });
+
+        let history: serde_json::Value = reqwest::get(format!("http://{}/history", addr))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        // 只保留最近 2 轮，从旧到新
+        let records = history.as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["chosen"], "wan_b");
+        assert_eq!(records[0]["switched"], true);
+        assert_eq!(records[1]["switched"], false);
+    }
+
+    #[tokio::test]
+    async fn test_result_endpoint() {
+        let config: Config = toml::from_str(
+            r#"
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        let state = Arc::new(AppState::new(config));
+        state.last_results.update(&[TestResult {
+            interface: "wan_a".to_string(),
+            target: "10.0.0.0/24".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(12.5),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: Local::now(),
+        }]);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(vec![state])).await });
+
+        let client = reqwest::Client::new();
+        let query = |interface: &'static str, target: &'static str| {
+            client
+                .get(format!("http://{}/result", addr))
+                .query(&[("interface", interface), ("target", target)])
+                .send()
+        };
+
+        let response = query("wan_a", "10.0.0.0/24").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let result: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(result["reachable"], true);
+        assert_eq!(result["latency_ms"], 12.5);
+        assert!(result["tested_at"].is_string());
+
+        let response = query("wan_b", "10.0.0.0/24").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 原生 ICMP 探测
+//!
+//! 使用无特权 ICMP 数据报套接字（需内核 `net.ipv4.ping_group_range` 允许）
+//! 或原始套接字（需 root / `CAP_NET_RAW`）直接发送 echo 请求，避免为每个目标启动 ping 子进程
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+/// ICMPv4 echo 请求/应答类型
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_ECHO_REPLY: u8 = 0;
+/// ICMPv6 echo 请求/应答类型
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// 创建套接字时表示缺少权限或内核不支持的 errno
+const EPERM: i32 = 1;
+const EACCES: i32 = 13;
+const EPROTONOSUPPORT: i32 = 93;
+
+/// 创建套接字的错误是否说明当前环境无法使用原生探测（缺少权限或协议不受支持）
+pub fn is_unsupported(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(EPERM | EACCES | EPROTONOSUPPORT))
+}
+
+/// 设置报文的 ToS（IPv4）或流量类别（IPv6）字节
+fn set_tos(socket: &Socket, target: IpAddr, tos: u8) -> io::Result<()> {
+    match target {
+        IpAddr::V4(_) => socket.set_tos(u32::from(tos)),
+        IpAddr::V6(_) => socket.set_tclass_v6(u32::from(tos)),
+    }
+}
+
+/// 创建绑定到指定接口的 ICMP 套接字（`raw` 为真时使用原始套接字）
+pub fn open_socket(
+    interface: &str,
+    target: IpAddr,
+    tos: Option<u8>,
+    raw: bool,
+) -> io::Result<UdpSocket> {
+    let (domain, protocol) = match target {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    let kind = if raw { Type::RAW } else { Type::DGRAM };
+
+    let socket = Socket::new(domain, kind, Some(protocol))?;
+    socket.bind_device(Some(interface.as_bytes()))?;
+    if let Some(tos) = tos {
+        set_tos(&socket, target, tos)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.connect(&SocketAddr::new(target, 0).into())?;
+
+    UdpSocket::from_std(std::net::UdpSocket::from(socket))
+}
+
+/// 计算 ICMP 校验和
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|chunk| {
+            let high = chunk[0] as u32;
+            let low = chunk.get(1).copied().unwrap_or(0) as u32;
+            (high << 8) | low
+        })
+        .sum();
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// 构造负载为 `size` 字节的 echo 请求报文
+///
+/// 数据报套接字的标识符由内核改写，原始套接字按 `identifier` 原样发送
+fn echo_request(target: IpAddr, identifier: u16, sequence: u16, size: usize) -> Vec<u8> {
+    let kind = match target {
+        IpAddr::V4(_) => ICMPV4_ECHO_REQUEST,
+        IpAddr::V6(_) => ICMPV6_ECHO_REQUEST,
+    };
+
+    let mut packet = vec![0u8; 8 + size];
+    packet[0] = kind;
+    packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    for (i, byte) in packet[8..].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    // ICMPv6 校验和依赖伪首部，由内核计算
+    if target.is_ipv4() {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    packet
+}
+
+/// 去掉 IPv4 原始套接字收到的 IP 首部，返回 ICMP 报文
+fn strip_ip_header(packet: &[u8]) -> &[u8] {
+    let header_len = packet.first().map_or(0, |b| usize::from(b & 0x0f) * 4);
+    packet.get(header_len..).unwrap_or_default()
+}
+
+/// 判断收到的报文是否为指定序号的 echo 应答
+///
+/// 原始套接字会收到本机所有 ICMP 报文，需额外匹配 `identifier`
+fn is_echo_reply(packet: &[u8], target: IpAddr, identifier: Option<u16>, sequence: u16) -> bool {
+    let expected = match target {
+        IpAddr::V4(_) => ICMPV4_ECHO_REPLY,
+        IpAddr::V6(_) => ICMPV6_ECHO_REPLY,
+    };
+
+    packet.len() >= 8
+        && packet[0] == expected
+        && identifier.is_none_or(|id| packet[4..6] == id.to_be_bytes())
+        && packet[6..8] == sequence.to_be_bytes()
+}
+
+/// 通过 [`open_socket`] 创建的套接字发送 `count` 个负载为 `size` 字节的 echo 请求
+/// （`raw` 为真时按原始套接字解析应答）
+///
+/// 返回每个报文的往返时间（毫秒），丢失的报文为 `None`；收发出错时返回错误
+pub async fn ping(
+    socket: &UdpSocket,
+    target: IpAddr,
+    count: u32,
+    size: u32,
+    per_packet_timeout: Duration,
+    raw: bool,
+) -> io::Result<Vec<Option<f64>>> {
+    let mut samples = Vec::with_capacity(count as usize);
+    let size = size as usize;
+    // IPv4 原始套接字收到的报文带有最长 60 字节的 IP 首部
+    let mut buffer = vec![0u8; (68 + size).max(1024)];
+    let identifier = raw.then(|| fastrand::u16(..));
+
+    for sequence in 0..count {
+        let sequence = sequence as u16;
+        let sent_at = Instant::now();
+        let request = echo_request(target, identifier.unwrap_or(0), sequence, size);
+        socket.send(&request).await?;
+
+        // 在超时时间内等待匹配序号的应答，忽略其他报文
+        let reply = timeout(per_packet_timeout, async {
+            loop {
+                let len = socket.recv(&mut buffer).await?;
+                let packet = if raw && target.is_ipv4() {
+                    strip_ip_header(&buffer[..len])
+                } else {
+                    &buffer[..len]
+                };
+                if is_echo_reply(packet, target, identifier, sequence) {
+                    return Ok::<_, io::Error>(sent_at.elapsed());
+                }
+            }
+        })
+        .await;
+
+        match reply {
+            Ok(Ok(rtt)) => samples.push(Some(rtt.as_secs_f64() * 1000.0)),
+            Ok(Err(e)) => return Err(e),
+            Err(_) => samples.push(None),
+        }
+    }
+
+    Ok(samples)
+}
+
+/// 根据每个报文的往返时间计算统计结果
+/// 返回: (是否可达, 平均延迟ms, 丢包率0.0-1.0)，与 ping 命令的解析结果一致
+pub fn stats_from_samples(samples: &[Option<f64>]) -> (bool, Option<f64>, Option<f64>) {
+    if samples.is_empty() {
+        return (false, None, Some(1.0));
+    }
+
+    let received: Vec<f64> = samples.iter().flatten().copied().collect();
+    let packet_loss = 1.0 - received.len() as f64 / samples.len() as f64;
+
+    let avg_latency = if received.is_empty() {
+        None
+    } else {
+        Some(received.iter().sum::<f64>() / received.len() as f64)
+    };
+
+    (!received.is_empty(), avg_latency, Some(packet_loss))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_stats_from_samples() {
+        let (reachable, latency, loss) =
+            stats_from_samples(&[Some(10.0), None, Some(20.0), Some(30.0)]);
+        assert!(reachable);
+        assert_eq!(latency, Some(20.0));
+        assert_eq!(loss, Some(0.25));
+
+        let (reachable, latency, loss) = stats_from_samples(&[None, None]);
+        assert!(!reachable);
+        assert_eq!(latency, None);
+        assert_eq!(loss, Some(1.0));
+
+        assert_eq!(stats_from_samples(&[]), (false, None, Some(1.0)));
+    }
+
+    #[test]
+    fn test_echo_request_roundtrip() {
+        let target = IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8));
+        let packet = echo_request(target, 0x1234, 3, 56);
+        assert_eq!(packet[0], ICMPV4_ECHO_REQUEST);
+        // 带校验和的报文再次求和应为 0
+        assert_eq!(checksum(&packet), 0);
+
+        let mut reply = packet.clone();
+        reply[0] = ICMPV4_ECHO_REPLY;
+        assert!(is_echo_reply(&reply, target, None, 3));
+        assert!(!is_echo_reply(&reply, target, None, 4));
+        assert!(!is_echo_reply(&packet, target, None, 3));
+
+        // 原始套接字还需匹配标识符
+        assert!(is_echo_reply(&reply, target, Some(0x1234), 3));
+        assert!(!is_echo_reply(&reply, target, Some(0x4321), 3));
+    }
+
+    #[test]
+    fn test_strip_ip_header() {
+        // IHL = 5（20 字节首部）
+        let mut packet = vec![0x45; 20];
+        packet.extend_from_slice(&[ICMPV4_ECHO_REPLY, 0, 0, 0, 0x12, 0x34, 0, 3]);
+        let icmp = strip_ip_header(&packet);
+        assert_eq!(icmp.len(), 8);
+        assert!(is_echo_reply(
+            icmp,
+            IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+            Some(0x1234),
+            3
+        ));
+
+        assert!(strip_ip_header(&[]).is_empty());
+        assert!(strip_ip_header(&[0x4f]).is_empty());
+    }
+
+    #[test]
+    fn test_tos_applied_to_socket() {
+        // 普通 UDP 套接字即可验证选项，无需 ICMP 权限
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        set_tos(&socket, IpAddr::V4(Ipv4Addr::LOCALHOST), 184).unwrap();
+        assert_eq!(socket.tos().unwrap(), 184);
+    }
+
+    #[test]
+    fn test_unsupported_errors() {
+        for errno in [EPERM, EACCES, EPROTONOSUPPORT] {
+            assert!(is_unsupported(&io::Error::from_raw_os_error(errno)));
+        }
+        // 网络不可达、报文过大等是单次探测的失败，不代表无法使用原生探测
+        for errno in [90, 101, 113] {
+            assert!(!is_unsupported(&io::Error::from_raw_os_error(errno)));
+        }
+        assert!(!is_unsupported(&io::Error::other("timeout")));
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! JSON 日志格式
+//!
+//! 启用后每条日志输出为一行 JSON 对象（`ts`、`level`、`target`、`msg`），
+//! 日志宏中的结构化字段（如 `info!(interface = name; "...")`）作为同级字段输出，
+//! 便于 Loki 等日志系统直接解析
+
+use log::kv::{self, Key, Value, VisitSource, VisitValue};
+use log::Record;
+use serde_json::{Map, Value as Json};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 切换为 JSON 日志格式
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// 是否使用 JSON 日志格式
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// env_logger 的格式化函数：每条日志输出一行 JSON
+pub fn format(buf: &mut env_logger::fmt::Formatter, record: &Record) -> io::Result<()> {
+    let ts = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+    writeln!(buf, "{}", to_json(record, &ts))
+}
+
+/// 把日志记录转换为 JSON 对象，结构化字段不覆盖固定字段
+fn to_json(record: &Record, ts: &str) -> Json {
+    let mut object = Map::new();
+    object.insert("ts".to_string(), Json::from(ts));
+    object.insert(
+        "level".to_string(),
+        Json::from(record.level().as_str().to_lowercase()),
+    );
+    object.insert("target".to_string(), Json::from(record.target()));
+    object.insert("msg".to_string(), Json::from(record.args().to_string()));
+
+    let mut fields = Fields(Map::new());
+    let _ = record.key_values().visit(&mut fields);
+    for (key, value) in fields.0 {
+        object.entry(key).or_insert(value);
+    }
+
+    Json::Object(object)
+}
+
+/// 收集结构化字段
+struct Fields(Map<String, Json>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let mut field = Field(Json::Null);
+        value.visit(&mut field)?;
+        self.0.insert(key.to_string(), field.0);
+        Ok(())
+    }
+}
+
+/// 把单个字段值转换为对应的 JSON 类型
+struct Field(Json);
+
+impl<'v> VisitValue<'v> for &mut Field {
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        self.0 = Json::from(value.to_string());
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        self.0 = Json::Null;
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        self.0 = Json::from(value);
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        self.0 = Json::from(value);
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        // NaN/无穷大无法表示为 JSON 数字，输出为 null
+        self.0 = Json::from(value);
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        self.0 = Json::from(value);
+        Ok(())
+    }
+
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        self.0 = Json::from(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::kv::ToValue;
+    use log::Level;
+
+    #[test]
+    fn test_record_to_json() {
+        let fields: [(&str, Value); 4] = [
+            ("interface", Value::from("wan_a")),
+            ("score", Value::from(87.5)),
+            ("jitter_ms", Option::<f64>::None.to_value()),
+            ("msg", Value::from("不应覆盖消息")),
+        ];
+        let json = to_json(
+            &Record::builder()
+                .args(format_args!("接口 {} 评分 {:.2}", "wan_a", 87.5))
+                .level(Level::Info)
+                .target("routes_monitor")
+                .key_values(&fields)
+                .build(),
+            "2026-01-01T00:00:00.000+08:00",
+        );
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "ts": "2026-01-01T00:00:00.000+08:00",
+                "level": "info",
+                "target": "routes_monitor",
+                "msg": "接口 wan_a 评分 87.50",
+                "interface": "wan_a",
+                "score": 87.5,
+                "jitter_ms": null,
+            })
+        );
+    }
+}
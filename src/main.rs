@@ -3,52 +3,338 @@
 // This software is licensed under CC BY-NC 4.0
 // Attribution required, Commercial use prohibited
 
+mod baseline;
+mod cli;
+mod command;
 mod config;
+mod control;
+mod database;
+mod decision;
+mod dns;
+mod hooks;
+mod http;
+mod icmp;
+mod json_log;
+mod metrics;
 mod network;
+mod notify;
 mod openwrt;
+mod probe;
+mod report;
+mod results_log;
+mod syslog;
+mod systemd;
+mod test_server;
+mod utilization;
 
 use anyhow::{Context, Result};
-use log::{error, info, warn};
-use std::path::PathBuf;
-use std::sync::Arc;
+use log::{debug, error, info, warn};
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError};
 use tokio::sync::RwLock;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, Duration, Instant};
 
-use config::Config;
-use network::{InterfaceScore, NetworkTester};
-use openwrt::OpenWrtManager;
+use baseline::BaselineOptions;
+use cli::{Command, InitOptions};
+use config::{Config, LogFormat, NetworkInterface, RoutingMode, TargetIP};
+use decision::{CheckSummary, DecisionOutcome, SwitchDecision, SwitchGate};
+use network::{
+    sort_scores, Eligibility, IneligibleReason, InterfaceScore, NetworkTester, ResultCache,
+    TestResult,
+};
+use openwrt::{OpenWrtManager, RouteAssignment};
+use report::ReportOptions;
+use results_log::LogRecord;
 
 /// 应用程序状态
-struct AppState {
+pub(crate) struct AppState {
+    /// 配置名称（同时运行多个配置时用于区分日志和指标）
+    name: Option<String>,
+    /// 配置文件路径（收到 SIGHUP 时重新读取）
+    config_path: Option<PathBuf>,
     /// 配置
-    config: Config,
+    config: std::sync::RwLock<Arc<Config>>,
+    /// 已重新加载、等待下一轮检查开始时生效的配置
+    pending_config: std::sync::Mutex<Option<Config>>,
     /// 网络测试器
-    tester: NetworkTester,
+    tester: std::sync::RwLock<Arc<NetworkTester>>,
     /// OpenWrt 管理器
     manager: Arc<RwLock<OpenWrtManager>>,
     /// 连续失败计数
     failure_count: Arc<RwLock<std::collections::HashMap<String, u32>>>,
+    /// 各接口最近一次被切走的时间（用于回切惩罚）
+    failed_over_at: Arc<RwLock<std::collections::HashMap<String, Instant>>>,
+    /// 已解析的冻结窗口
+    freeze_windows: std::sync::RwLock<Vec<(String, cron::Schedule)>>,
+    /// 各 (接口, 目标) 最近一次的探测结果
+    last_results: ResultCache,
+    /// 最近一次检查的接口评分
+    latest_scores: RwLock<Vec<InterfaceScore>>,
+    /// 最近一次检查完成的时间
+    last_check: RwLock<Option<chrono::DateTime<chrono::Local>>>,
+    /// 最近若干轮的检查记录
+    history: std::sync::Mutex<decision::CycleHistory>,
+    /// 当前在系统中不存在的接口
+    absent_interfaces: std::sync::Mutex<std::collections::HashSet<String>>,
+    /// 新出现接口的观察期
+    probation: std::sync::Mutex<decision::ProbationTracker>,
+    /// 持续失效目标的熔断器
+    dead_targets: std::sync::Mutex<network::DeadTargetBreaker>,
+    /// 已执行的检查轮数
+    check_count: AtomicU64,
+    /// 当前固定的接口（启动时取自配置，可由控制套接字修改）
+    pinned_interface: std::sync::Mutex<Option<String>>,
+    /// 控制套接字请求立即检查时通知监控循环
+    recheck: tokio::sync::Notify,
+    /// 各接口的评分历史（用于稳定性加权）
+    score_history: std::sync::Mutex<decision::ScoreHistory>,
+    /// 各接口评分的指数移动平均
+    smoothed_scores: std::sync::Mutex<std::collections::HashMap<String, f64>>,
+    /// 最近一次切换的耗时
+    last_switch_duration: RwLock<Option<Duration>>,
+    /// 每轮探测次数预算
+    probe_budget: std::sync::Mutex<network::ProbeBudget>,
+    /// 每小时切换次数上限
+    switch_rate: std::sync::Mutex<decision::SwitchRateLimiter>,
+    /// 最近一次成功切换的时间（用于切换冷却）
+    last_switch_at: std::sync::Mutex<Option<Instant>>,
+    /// 运维人员保存的基线快照
+    baseline: Option<baseline::Baseline>,
+    /// 正在偏离基线的接口指标
+    baseline_deviations: std::sync::Mutex<baseline::DeviationTracker>,
+    /// 执行切换检查命令
+    runner: Arc<dyn command::CommandRunner>,
+    /// 切换（提交、重载和等待生效）进行中时持有写锁，探测前获取读锁，避免测量变化中的网络
+    switching: RwLock<()>,
+    /// 本程序为各托管路由目标设置的出口（用于检测外部修改）
+    intended_routes: std::sync::Mutex<std::collections::HashMap<String, String>>,
+    /// SQLite 数据导出
+    database: Option<database::Writer>,
+    /// 启动时的路由状态，退出时恢复
+    original_routes: Option<openwrt::RouteSnapshot>,
 }
 
 impl AppState {
     fn new(config: Config) -> Self {
-        let tester = NetworkTester::new(config.global.timeout, config.global.concurrent_tests);
+        let tester = build_tester(&config);
+        let freeze_windows = parse_freeze_windows(&config);
+        let manager = OpenWrtManager::new()
+            .with_gateway_cache_ttl(Duration::from_secs(config.global.gateway_cache_ttl))
+            .with_switch_style(
+                config.global.switch_style,
+                Duration::from_secs(config.global.drain_time),
+            )
+            .with_network_restart(config.global.allow_network_restart)
+            .with_dry_run(config.global.dry_run)
+            .with_backend(config.global.backend)
+            .with_rule_priorities(
+                config.global.rule_priority_base,
+                config.global.rule_priority_range,
+            );
+
+        let probation = decision::ProbationTracker::new(config.global.new_interface_probation);
+        let score_history = decision::ScoreHistory::new(config.global.stability_window);
+        let history = decision::CycleHistory::new(config.global.history_size);
+        let probe_budget = network::ProbeBudget::new(config.global.max_probes_per_cycle);
+        let switch_rate = decision::SwitchRateLimiter::new(config.global.max_switches_per_hour);
+        let baseline = config.global.baseline_file.as_ref().and_then(|path| {
+            baseline::Baseline::load(path)
+                .inspect_err(|e| warn!("未加载基线快照: {:#}", e))
+                .ok()
+        });
+        let database = config.global.sqlite_path.as_ref().and_then(|path| {
+            database::Writer::spawn(path, config.global.sqlite_retention_days)
+                .inspect_err(|e| warn!("未启用 SQLite 数据导出: {:#}", e))
+                .ok()
+        });
+        let pinned_interface = config.global.pinned_interface.clone();
+        let dead_targets = network::DeadTargetBreaker::new(
+            config.global.dead_target_threshold,
+            config.global.dead_target_reprobe_every,
+        );
 
         Self {
-            config,
-            tester,
-            manager: Arc::new(RwLock::new(OpenWrtManager::new())),
+            name: None,
+            config_path: None,
+            config: std::sync::RwLock::new(Arc::new(config)),
+            pending_config: std::sync::Mutex::new(None),
+            tester: std::sync::RwLock::new(Arc::new(tester)),
+            manager: Arc::new(RwLock::new(manager)),
             failure_count: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            failed_over_at: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            freeze_windows: std::sync::RwLock::new(freeze_windows),
+            last_results: ResultCache::default(),
+            latest_scores: RwLock::new(Vec::new()),
+            last_check: RwLock::new(None),
+            history: std::sync::Mutex::new(history),
+            absent_interfaces: std::sync::Mutex::new(std::collections::HashSet::new()),
+            probation: std::sync::Mutex::new(probation),
+            dead_targets: std::sync::Mutex::new(dead_targets),
+            check_count: AtomicU64::new(0),
+            pinned_interface: std::sync::Mutex::new(pinned_interface),
+            recheck: tokio::sync::Notify::new(),
+            score_history: std::sync::Mutex::new(score_history),
+            smoothed_scores: std::sync::Mutex::new(std::collections::HashMap::new()),
+            last_switch_duration: RwLock::new(None),
+            probe_budget: std::sync::Mutex::new(probe_budget),
+            switch_rate: std::sync::Mutex::new(switch_rate),
+            last_switch_at: std::sync::Mutex::new(None),
+            baseline,
+            baseline_deviations: std::sync::Mutex::new(baseline::DeviationTracker::default()),
+            runner: Arc::new(command::SystemRunner),
+            switching: RwLock::new(()),
+            intended_routes: std::sync::Mutex::new(std::collections::HashMap::new()),
+            database,
+            original_routes: None,
+        }
+    }
+
+    /// 当前配置
+    fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// 当前网络测试器（随配置重新加载重建）
+    fn tester(&self) -> Arc<NetworkTester> {
+        Arc::clone(&self.tester.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// 重新读取并校验配置文件，新配置在下一轮检查开始时生效
+    fn reload_config(&self) -> Result<()> {
+        let Some(path) = &self.config_path else {
+            anyhow::bail!("未记录配置文件路径");
+        };
+        let config =
+            Config::from_file(path).with_context(|| format!("加载配置文件失败: {:?}", path))?;
+        *self
+            .pending_config
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(config);
+        Ok(())
+    }
+
+    /// 替换为已重新加载的配置，并重建依赖配置的测试器和冻结窗口
+    ///
+    /// 只在两轮检查之间调用，保证一轮检查内使用同一份配置
+    fn apply_pending_config(&self) {
+        let Some(mut config) = self
+            .pending_config
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+        let retained = config.global.retain_startup_settings(&self.config().global);
+        if !retained.is_empty() {
+            warn!(
+                "以下设置需重启后生效，继续使用启动时的值: {}",
+                retained.join(", ")
+            );
+        }
+        *self.tester.write().unwrap_or_else(PoisonError::into_inner) =
+            Arc::new(build_tester(&config));
+        *self
+            .freeze_windows
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = parse_freeze_windows(&config);
+        self.history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_capacity(config.global.history_size);
+        // 配置中的固定接口有变化时以新配置为准，否则保留运行时的固定状态
+        if config.global.pinned_interface != self.config().global.pinned_interface {
+            *self
+                .pinned_interface
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = config.global.pinned_interface.clone();
         }
+        info!(
+            "已应用重新加载的配置: {} 个接口, {} 个目标",
+            config.interfaces.len(),
+            config.targets.len()
+        );
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
     }
 }
 
+/// 按配置构建网络测试器
+fn build_tester(config: &Config) -> NetworkTester {
+    NetworkTester::new(config.global.timeout, config.global.concurrent_tests)
+        .with_prober(config.global.prober)
+        .with_ping_tool(config.global.ping_tool)
+        .with_speed_scoring(config.global.speed_scoring)
+        .with_latency_metric(config.global.latency_metric)
+        .with_dns_cache_ttl(Duration::from_secs(config.global.dns_cache_ttl))
+        .with_scoring(config.global.scoring)
+        .with_timeout_loss(config.global.timeout_loss)
+        .with_warmup_ping(config.global.warmup_ping)
+        .with_ping(config.global.ping_count, config.global.ping_size)
+        .with_randomize_source_port(config.global.randomize_source_port)
+        .with_score_offsets(&config.interfaces)
+        .with_costs(
+            config.global.optimize_for,
+            config.global.cost_weight,
+            &config.interfaces,
+        )
+        .with_check_weights(config.global.check_weights)
+        .with_reachability_loss_threshold(config.global.reachability_loss_threshold)
+        .with_min_reachable_ratio(config.global.min_reachable_ratio)
+        .with_lossy_retries(config.global.retry_lossy_probes)
+        .with_scorer_command(config.global.scorer_command.clone())
+        .with_max_load(config.global.max_load)
+        .with_concurrent_speed_tests(config.global.concurrent_speed_tests)
+}
+
+/// 解析配置中的冻结窗口
+fn parse_freeze_windows(config: &Config) -> Vec<(String, cron::Schedule)> {
+    // 配置校验时已确认表达式有效
+    config
+        .global
+        .freeze_windows
+        .iter()
+        .filter_map(|expr| {
+            decision::parse_freeze_window(expr)
+                .ok()
+                .map(|schedule| (expr.clone(), schedule))
+        })
+        .collect()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 初始化日志
-    env_logger::Builder::from_default_env()
+    // 初始化日志（启用 syslog 后 warn/error 同时写入系统日志）
+    // 配置 log_format = "json" 后改用 JSON 格式输出，文本格式不显示结构化字段
+    let logger = env_logger::Builder::from_default_env()
         .filter_level(log::LevelFilter::Info)
-        .init();
+        .format_key_values(env_logger::fmt::hidden_kv_format)
+        .build();
+    let json_logger = env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Info)
+        .format(json_log::format)
+        .build();
+    syslog::MirrorLogger::new(logger)
+        .with_json(json_logger)
+        .install()?;
+
+    let mut options = match cli::parse_args(std::env::args().skip(1))? {
+        Command::Monitor(options) => options,
+        Command::Report(options) => return run_report(&options),
+        Command::BaselineSave(options) => return run_baseline_save(&options),
+        Command::Init(options) => return run_init(&options),
+        Command::DumpConfig(format) => {
+            let config = Config::from_file(get_config_path()?).context("加载配置文件失败")?;
+            println!("{}", config.dump(format)?);
+            return Ok(());
+        }
+    };
+
+    if let Ok(value) = std::env::var("ROUTES_MONITOR_ONCE") {
+        options.once |= cli::parse_flag("ROUTES_MONITOR_ONCE", &value)?;
+    }
 
     info!("========================================");
     info!("  OpenWrt 路由监控工具");
@@ -56,12 +342,350 @@ async fn main() -> Result<()> {
     info!("  All rights reserved.");
     info!("========================================");
 
-    // 加载配置文件
-    let config_path = get_config_path()?;
-    info!("加载配置文件: {:?}", config_path);
+    // 测试服务随进程退出，监听失败时直接报错
+    if let Some(listen) = &options.test_server {
+        let listener = test_server::bind(listen).await?;
+        tokio::spawn(async move {
+            if let Err(e) = test_server::serve(listener).await {
+                error!("TCP 回显测试服务停止: {:#}", e);
+            }
+        });
+    }
+
+    let config_paths = if options.configs.is_empty() {
+        vec![get_config_path()?]
+    } else {
+        options.configs
+    };
+
+    // 加载配置文件；同时运行多个配置时以文件名区分
+    let named = config_paths.len() > 1;
+    let mut states: Vec<Arc<AppState>> = Vec::new();
+    for config_path in &config_paths {
+        info!("加载配置文件: {:?}", config_path);
+        let config = Config::from_file(config_path)
+            .with_context(|| format!("加载配置文件失败: {:?}", config_path))?;
+        if config.global.log_format == LogFormat::Json {
+            json_log::enable();
+        }
+
+        let mut state = AppState::new(config);
+        state.config_path = Some(config_path.clone());
+        if named {
+            let name = config_name(config_path);
+            if states
+                .iter()
+                .any(|s| s.name.as_deref() == Some(name.as_str()))
+            {
+                anyhow::bail!("配置名称重复: {}（取自文件名，请使用不同的文件名）", name);
+            }
+            state.name = Some(name);
+        }
+
+        // 保存托管路由的原始出口，收到停止信号时恢复（演练模式不修改路由，无需恢复）
+        if state.config().global.manage_uci_routes
+            && !state.config().global.dry_run
+            && !options.once
+            && !options.check
+        {
+            let snapshot = in_log_scope(&state, async {
+                state.manager.read().await.snapshot_routes().await
+            })
+            .await;
+            state.original_routes = snapshot
+                .inspect_err(|e| warn!("保存原始路由失败，退出时不会恢复: {:#}", e))
+                .ok();
+        }
+
+        // 按接口的 table_id 建立策略路由，退出时清除
+        if !options.once && !options.check {
+            let routed = in_log_scope(&state, async {
+                let interfaces = state.config().interfaces.clone();
+                state
+                    .manager
+                    .read()
+                    .await
+                    .setup_policy_routes(&interfaces)
+                    .await
+            })
+            .await;
+            match routed {
+                Ok(0) => {}
+                Ok(count) => info!("已为 {} 个接口建立策略路由", count),
+                Err(e) => warn!("建立策略路由失败: {:#}", e),
+            }
+        }
+        states.push(Arc::new(state));
+    }
+
+    if states.iter().any(|s| s.config().global.recover_from_panics) {
+        install_panic_hook();
+    }
+
+    if states.iter().any(|s| s.config().global.syslog) {
+        syslog::enable(Arc::new(command::SystemRunner));
+    }
+
+    for state in &states {
+        in_log_scope(state, async { print_config(&state.config()) }).await;
+    }
+
+    if options.check {
+        return run_check(&states).await;
+    }
+
+    if options.once {
+        return run_once(&states).await;
+    }
+
+    // HTTP 接口在独立任务中运行，不阻塞监控循环；多个配置共用一个监听地址
+    let configs: Vec<Arc<Config>> = states.iter().map(|s| s.config()).collect();
+    let mut http_configs = configs.iter().filter_map(|c| c.http.as_ref());
+    if let Some(http) = http_configs.next() {
+        if http_configs.next().is_some() {
+            warn!(
+                "多个配置设置了 [http]，只使用第一个的监听地址: {}",
+                http.listen
+            );
+        }
+        let listen = http.listen.clone();
+        let http_states = states.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(&listen, http_states).await {
+                error!("HTTP 接口停止: {:#}", e);
+            }
+        });
+    }
+
+    // 控制套接字同样只启动一个
+    let mut control_configs = configs.iter().filter_map(|c| c.control.as_ref());
+    if let Some(control) = control_configs.next() {
+        if control_configs.next().is_some() {
+            warn!(
+                "多个配置设置了 [control]，只使用第一个的套接字: {}",
+                control.socket.display()
+            );
+        }
+        let socket = control.socket.clone();
+        let control_states = states.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(&socket, control_states).await {
+                error!("控制套接字停止: {:#}", e);
+            }
+        });
+    }
+
+    info!("========================================");
+    info!("开始监控循环...");
+    info!("========================================");
+
+    // 作为 systemd 服务运行时报告就绪、状态并喂看门狗
+    let notifier = Arc::new(systemd::Notifier::from_env());
+
+    // 各配置的监控循环在独立任务中运行，互不影响；一个循环异常退出时记录所属配置，其余循环继续运行
+    let mut loops = tokio::task::JoinSet::new();
+    let mut loop_states = std::collections::HashMap::new();
+    for state in &states {
+        let state = Arc::clone(state);
+        // 保活在独立任务中按自己的间隔运行，与评分探测无关
+        if state.config().global.keepalive_interval > 0
+            && state.config().interfaces.iter().any(|i| i.keepalive)
+        {
+            let keepalive_state = Arc::clone(&state);
+            tokio::spawn(async move {
+                in_log_scope(
+                    &keepalive_state,
+                    run_keepalive_loop(Arc::clone(&keepalive_state)),
+                )
+                .await
+            });
+        }
+
+        let notifier = Arc::clone(&notifier);
+        let loop_state = Arc::clone(&state);
+        let handle = loops.spawn(async move {
+            in_log_scope(&state, run_monitor_loop(Arc::clone(&state), notifier)).await
+        });
+        loop_states.insert(handle.id(), loop_state);
+    }
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .context("无法注册 SIGHUP 处理")?;
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {
+                info!("收到 SIGHUP，重新加载配置");
+                for state in &states {
+                    in_log_scope(state, async {
+                        match state.reload_config() {
+                            Ok(()) => info!("配置校验通过，将在下一轮检查开始时生效"),
+                            Err(e) => error!("重新加载配置失败，继续使用当前配置: {:#}", e),
+                        }
+                    })
+                    .await;
+                }
+            }
+            result = loops.join_next_with_id() => {
+                let Some(result) = result else {
+                    for state in &states {
+                        in_log_scope(state, restore_original_routes(state)).await;
+                    }
+                    anyhow::bail!("所有监控任务均已退出");
+                };
+                let (id, error) = match result {
+                    Ok((id, Ok(()))) => (id, "监控循环已结束".to_string()),
+                    Ok((id, Err(e))) => (id, format!("{:#}", e)),
+                    Err(e) => (e.id(), e.to_string()),
+                };
+                if let Some(state) = loop_states.remove(&id) {
+                    error!(
+                        "配置 {} 的监控任务异常退出，其余配置继续运行: {}",
+                        describe_config(&state),
+                        error
+                    );
+                }
+            }
+            signal = &mut shutdown => {
+                info!("收到 {}，停止监控", signal?);
+                notifier.stopping();
+                loops.shutdown().await;
+                for state in &states {
+                    in_log_scope(state, restore_original_routes(state)).await;
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 日志中标识配置：配置文件路径，没有时使用配置名称
+fn describe_config(state: &AppState) -> String {
+    match (&state.config_path, &state.name) {
+        (Some(path), _) => path.display().to_string(),
+        (None, Some(name)) => name.clone(),
+        (None, None) => "(默认)".to_string(),
+    }
+}
+
+/// 单次运行：每个配置执行一次检查（必要时切换）后退出，不进入监控循环
+/// 任一配置检查出错或没有可达接口时返回错误（非零退出状态）
+async fn run_once(states: &[Arc<AppState>]) -> Result<()> {
+    let mut unreachable = Vec::new();
+    for state in states {
+        let usable = in_log_scope(state, async {
+            let summary = run_check_cycle(state).await?;
+            let (status, usable) = service_status(state, &summary).await;
+            info!("{}", status);
+            Ok::<_, anyhow::Error>(usable)
+        })
+        .await?;
+        if !usable {
+            unreachable.push(state.name.clone().unwrap_or_default());
+        }
+    }
+
+    match unreachable.as_slice() {
+        [] => Ok(()),
+        [name] if name.is_empty() => anyhow::bail!("没有可达的接口"),
+        names => anyhow::bail!("以下配置没有可达的接口: {}", names.join(", ")),
+    }
+}
+
+/// 只读检查：每个配置探测并评分一次，把评分表输出到标准输出后退出，不修改任何路由
+/// 任一配置没有可达接口时返回错误（非零退出状态）
+async fn run_check(states: &[Arc<AppState>]) -> Result<()> {
+    let mut unreachable = Vec::new();
+    for state in states {
+        let config = state.config();
+        let tester = state.tester();
+        let scores = in_log_scope(state, async {
+            let interfaces = config.sorted_interfaces();
+            let results = tester
+                .test_all_interfaces(&interfaces, &config.targets, &HashSet::new())
+                .await;
+            tester.calculate_scores(&results)
+        })
+        .await;
+
+        if let Some(name) = &state.name {
+            println!("[{}]", name);
+        }
+        print!("{}", render_score_table(&scores));
+        if !scores.iter().any(|s| s.reachable_count > 0) {
+            unreachable.push(state.name.clone().unwrap_or_default());
+        }
+    }
+
+    match unreachable.as_slice() {
+        [] => Ok(()),
+        [name] if name.is_empty() => anyhow::bail!("没有可达的接口"),
+        names => anyhow::bail!("以下配置没有可达的接口: {}", names.join(", ")),
+    }
+}
+
+/// 等待 SIGTERM 或 SIGINT
+/// 返回: 收到的信号名称
+async fn shutdown_signal() -> Result<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).context("无法注册 SIGTERM 处理")?;
+    let mut interrupt = signal(SignalKind::interrupt()).context("无法注册 SIGINT 处理")?;
+    tokio::select! {
+        _ = terminate.recv() => Ok("SIGTERM"),
+        _ = interrupt.recv() => Ok("SIGINT"),
+    }
+}
+
+/// 清除本程序建立的策略路由规则，并把托管路由恢复为启动时的出口；
+/// 失败时只记录错误，不阻止退出
+async fn restore_original_routes(state: &AppState) {
+    let _switching = state.switching.write().await;
+    let manager = state.manager.read().await;
+    if state
+        .config()
+        .interfaces
+        .iter()
+        .any(|i| i.table_id.is_some())
+    {
+        if let Err(e) = manager.clear_policy_rules().await {
+            error!("清除策略路由规则失败: {:#}", e);
+        }
+    }
+
+    let Some(snapshot) = &state.original_routes else {
+        return;
+    };
+    match manager.restore_routes(snapshot).await {
+        Ok(0) => info!("托管路由与启动时一致，无需恢复"),
+        Ok(restored) => info!("已恢复 {} 条托管路由的原始出口", restored),
+        Err(e) => {
+            error!("恢复原始路由失败: {:#}", e);
+            error!("启动时的路由表:\n{}", snapshot.kernel_routes.trim_end());
+        }
+    }
+}
+
+/// 多配置运行时的配置名称（配置文件名去掉扩展名）
+fn config_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
 
-    let config = Config::from_file(&config_path).context("加载配置文件失败")?;
+/// 在配置名称对应的日志作用域中运行，日志消息带上 `[名称]` 前缀
+async fn in_log_scope<F: Future>(state: &AppState, future: F) -> F::Output {
+    match &state.name {
+        Some(name) => syslog::LOG_SCOPE.scope(name.clone(), future).await,
+        None => future.await,
+    }
+}
 
+/// 显示配置摘要、接口和目标信息
+fn print_config(config: &Config) {
     info!("配置加载成功:");
     info!("  - 监控间隔: {} 秒", config.global.check_interval);
     info!("  - 超时时间: {} 秒", config.global.timeout);
@@ -77,23 +701,116 @@ async fn main() -> Result<()> {
     );
     info!("  - 接口数量: {}", config.interfaces.len());
     info!("  - 目标数量: {}", config.targets.len());
-
-    // 创建应用状态
-    let state = Arc::new(AppState::new(config));
+    info!("  - 托管路由: {} 条", config.route_addresses().len());
+    if config.global.dry_run {
+        warn!("  - 演练模式: 只记录切换决策，不修改路由和 UCI 配置");
+    }
 
     // 显示接口信息
-    print_interfaces(&state.config);
+    print_interfaces(config);
 
     // 显示目标信息
-    print_targets(&state.config);
+    print_targets(config);
+}
 
-    info!("========================================");
-    info!("开始监控循环...");
-    info!("========================================");
+/// 汇总结果日志并输出报告
+fn run_report(options: &ReportOptions) -> Result<()> {
+    let log_path = match &options.log_path {
+        Some(path) => path.clone(),
+        None => {
+            let config = Config::from_file(get_config_path()?).context("加载配置文件失败")?;
+            match config.global.results_log {
+                Some(path) => PathBuf::from(path),
+                None => anyhow::bail!("未配置 global.results_log，请使用 --log 指定结果日志"),
+            }
+        }
+    };
+
+    let records = results_log::read(&log_path)?;
+    let since = match options.since {
+        Some(window) => Some(chrono::Local::now() - chrono::Duration::from_std(window)?),
+        None => None,
+    };
+
+    let summary = report::summarize(&records, since, options.interface.as_deref());
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        print!("{}", report::render_table(&summary));
+    }
+
+    Ok(())
+}
+
+/// 生成示例配置：写入指定路径（已存在时需要 --force）或输出到标准输出
+fn run_init(options: &InitOptions) -> Result<()> {
+    let example = Config::example_toml()?;
+    let Some(path) = &options.path else {
+        print!("{}", example);
+        return Ok(());
+    };
+
+    if path.exists() && !options.force {
+        anyhow::bail!("配置文件已存在: {:?}，使用 --force 覆盖", path);
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+    }
+    std::fs::write(path, example).with_context(|| format!("无法写入配置文件: {:?}", path))?;
+    info!("已生成示例配置: {:?}", path);
+    Ok(())
+}
+
+/// 从结果日志生成基线快照并保存
+fn run_baseline_save(options: &BaselineOptions) -> Result<()> {
+    // 命令行已指定全部路径时不需要配置文件
+    let config = if options.log_path.is_none() || options.output.is_none() {
+        Some(Config::from_file(get_config_path()?).context("加载配置文件失败")?)
+    } else {
+        None
+    };
+    let global = config.as_ref().map(|c| &c.global);
+
+    let Some(log_path) = options.log_path.clone().or_else(|| {
+        global
+            .and_then(|g| g.results_log.as_ref())
+            .map(PathBuf::from)
+    }) else {
+        anyhow::bail!("未配置 global.results_log，请使用 --log 指定结果日志");
+    };
+    let Some(output) = options.output.clone().or_else(|| {
+        global
+            .and_then(|g| g.baseline_file.as_ref())
+            .map(PathBuf::from)
+    }) else {
+        anyhow::bail!("未配置 global.baseline_file，请使用 --output 指定基线文件");
+    };
 
-    // 主监控循环
-    run_monitor_loop(state).await?;
+    let records = results_log::read(&log_path)?;
+    let since = match options.since {
+        Some(window) => Some(chrono::Local::now() - chrono::Duration::from_std(window)?),
+        None => None,
+    };
+
+    let baseline = baseline::Baseline::from_records(&records, since);
+    if baseline.interfaces.is_empty() {
+        anyhow::bail!("结果日志中没有可用的检查记录");
+    }
+    baseline.save(&output)?;
 
+    println!("基线已保存到 {:?}:", output);
+    for (name, interface) in &baseline.interfaces {
+        println!(
+            "  {:<15} 延迟: {:<10} 评分: {:.2} ({} 次检查)",
+            name,
+            interface
+                .latency_ms
+                .map_or("-".to_string(), |v| format!("{:.2}ms", v)),
+            interface.score,
+            interface.samples
+        );
+    }
     Ok(())
 }
 
@@ -150,8 +867,41 @@ fn print_targets(config: &Config) {
     }
 }
 
+/// 安装记录 panic 信息和调用栈的 panic hook
+fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        error!("发生 panic: {}", info);
+        error!("调用栈:\n{}", backtrace);
+    }));
+}
+
+/// 在独立任务中运行，将任务 panic 转换为错误
+async fn catch_panic<T, F>(future: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: Future<Output = Result<T>> + Send + 'static,
+{
+    match tokio::spawn(future).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => anyhow::bail!("检查任务发生 panic，已跳过本轮"),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 执行一轮检查（先应用 SIGHUP 重新加载的配置），启用 recover_from_panics 时捕获 panic
+async fn run_check_cycle(state: &Arc<AppState>) -> Result<CheckSummary> {
+    state.apply_pending_config();
+    if !state.config().global.recover_from_panics {
+        return run_single_check(state).await;
+    }
+
+    let state = Arc::clone(state);
+    catch_panic(async move { run_single_check(&state).await }).await
+}
+
 /// 运行监控循环
-async fn run_monitor_loop(state: Arc<AppState>) -> Result<()> {
+async fn run_monitor_loop(state: Arc<AppState>, notifier: Arc<systemd::Notifier>) -> Result<()> {
     let mut iteration = 0u64;
 
     loop {
@@ -163,194 +913,3707 @@ async fn run_monitor_loop(state: Arc<AppState>) -> Result<()> {
         );
 
         // 执行一次检查
-        if let Err(e) = run_single_check(&state).await {
-            error!("检查过程出错: {}", e);
-            error!("将在 {} 秒后重试...", state.config.global.check_interval);
+        match run_check_cycle(&state).await {
+            Ok(summary) => {
+                let (status, _) = service_status(&state, &summary).await;
+                notifier.check_completed(&status);
+            }
+            Err(e) => {
+                error!("检查过程出错: {}", e);
+                error!("将在 {} 秒后重试...", state.config().global.check_interval);
+                notifier.check_completed(&format!("检查出错: {}", e));
+            }
         }
 
         // 等待下一次检查
         info!(
             "等待 {} 秒后进行下一次检查...",
-            state.config.global.check_interval
+            state.config().global.check_interval
         );
-        sleep(Duration::from_secs(state.config.global.check_interval)).await;
+        tokio::select! {
+            _ = notifier.sleep(Duration::from_secs(state.config().global.check_interval)) => {}
+            _ = state.recheck.notified() => info!("收到立即检查请求，提前开始下一轮检查"),
+        }
     }
 }
 
-/// 执行单次检查
-async fn run_single_check(state: &AppState) -> Result<()> {
-    let start_time = std::time::Instant::now();
-
-    // 获取启用的接口
-    let interfaces = state.config.sorted_interfaces();
+/// 服务状态摘要（活动接口及其评分、可用接口数）
+/// 返回: (状态文本, 是否有可用接口)
+async fn service_status(state: &AppState, summary: &CheckSummary) -> (String, bool) {
+    let usable = summary
+        .scores
+        .iter()
+        .filter(|s| s.reachable_count > 0 && s.eligibility == Eligibility::Eligible)
+        .count();
+    let active = state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string);
 
-    if interfaces.is_empty() {
-        warn!("没有启用的接口，跳过检查");
-        return Ok(());
+    let mut status = match active {
+        Some(active) => {
+            let score = summary.scores.iter().find(|s| s.interface == active);
+            match score {
+                Some(score) => format!("活动接口 {}（评分 {:.2}）", active, score.score),
+                None => format!("活动接口 {}", active),
+            }
+        }
+        None => "尚未选择接口".to_string(),
+    };
+    status.push_str(&format!("，可用接口 {}/{}", usable, summary.scores.len()));
+    if let Some(name) = &state.name {
+        status = format!("[{}] {}", name, status);
     }
 
-    info!("开始测试 {} 个接口...", interfaces.len());
+    (status, usable > 0)
+}
 
-    // 测试所有接口
-    let results = state
-        .tester
-        .test_all_interfaces(&interfaces, &state.config.targets)
-        .await;
+/// 按 keepalive_interval 定期向未被选中的保活接口发送 ping
+async fn run_keepalive_loop(state: Arc<AppState>) {
+    let period = Duration::from_secs(state.config().global.keepalive_interval);
+    let mut ticker = tokio::time::interval(period);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
-    // 计算评分
-    let scores = state.tester.calculate_scores(&results);
+    loop {
+        ticker.tick().await;
+        send_keepalives(&state).await;
+    }
+}
 
-    // 显示结果
-    print_test_results(&scores);
+/// 向所有未被选中的保活接口各发送一次 ping（目标为该接口的第一个探测目标）
+async fn send_keepalives(state: &AppState) {
+    let config = state.config();
+    let tester = state.tester();
+    let current = state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string);
 
-    // 获取最佳接口
-    if let Some(best) = state.tester.get_best_interface(&scores) {
-        info!("最佳接口: {} (评分: {:.2})", best.interface, best.score);
+    let pending: Vec<(&str, String)> = config
+        .interfaces
+        .iter()
+        .filter(|i| i.keepalive && current.as_deref() != Some(i.name.as_str()))
+        .filter_map(|i| {
+            let target = i.effective_targets(&config.targets).into_iter().next()?;
+            let address = target.address.split('/').next().unwrap_or(&target.address);
+            Some((i.name.as_str(), address.to_string()))
+        })
+        .collect();
 
-        // 检查是否需要切换
-        let should_switch = should_switch_interface(state, best).await?;
+    let tasks = pending
+        .iter()
+        .map(|(interface, target)| tester.keepalive(interface, target));
+    let _settled = wait_for_switch(state).await;
+    futures::future::join_all(tasks).await;
+}
 
-        if should_switch && state.config.global.auto_switch {
-            // 查找接口配置
-            if let Some(interface_config) = state
-                .config
-                .interfaces
-                .iter()
-                .find(|i| i.name == best.interface)
-            {
-                // 执行切换
-                info!("准备切换到接口: {}", best.interface);
-
-                // 收集所有监控目标的IP地址
-                let static_targets: Vec<String> = state
-                    .config
-                    .targets
-                    .iter()
-                    .map(|t| t.address.clone())
-                    .collect();
-
-                let static_targets_opt = if !static_targets.is_empty() {
-                    Some(static_targets.as_slice())
-                } else {
-                    None
-                };
+/// 等待进行中的切换完成，返回的锁在探测结束前阻止新的切换开始
+async fn wait_for_switch(state: &AppState) -> tokio::sync::RwLockReadGuard<'_, ()> {
+    if let Ok(guard) = state.switching.try_read() {
+        return guard;
+    }
+    info!("接口切换进行中，等待网络稳定后再探测");
+    state.switching.read().await
+}
 
-                let mut manager = state.manager.write().await;
+/// 排除在 UCI 中被禁用的接口
+async fn exclude_uci_disabled<'a>(
+    state: &AppState,
+    interfaces: Vec<&'a NetworkInterface>,
+) -> Vec<&'a NetworkInterface> {
+    let manager = state.manager.read().await;
+    let mut enabled = Vec::with_capacity(interfaces.len());
 
-                match manager
-                    .switch_to_interface(
-                        interface_config,
-                        state.config.global.manage_uci_routes,
-                        static_targets_opt,
-                    )
-                    .await
-                {
-                    Ok(_) => {
-                        info!("接口切换成功!");
-
-                        // 验证切换
-                        if let Ok(verified) = manager.verify_switch(interface_config).await {
-                            if verified {
-                                info!("接口切换验证通过");
-
-                                // 重置失败计数
-                                let mut failures = state.failure_count.write().await;
-                                failures.clear();
-                            } else {
-                                warn!("接口切换验证失败，可能需要手动检查");
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("接口切换失败: {}", e);
-                    }
-                }
-            }
-        } else if !state.config.global.auto_switch {
-            info!("自动切换已禁用，跳过接口切换");
+    for interface in interfaces {
+        if manager.is_uci_disabled(&interface.name).await {
+            info!("接口 {} 已在 UCI 中禁用，跳过", interface.name);
         } else {
-            info!("当前接口表现良好，无需切换");
+            enabled.push(interface);
         }
-    } else {
-        warn!("没有可用的接口!");
     }
 
-    let elapsed = start_time.elapsed();
-    info!("本次检查耗时: {:.2} 秒", elapsed.as_secs_f64());
-
-    Ok(())
+    enabled
 }
 
-/// 判断是否应该切换接口
-async fn should_switch_interface(state: &AppState, best: &InterfaceScore) -> Result<bool> {
-    let manager = state.manager.read().await;
-
-    // 如果没有当前接口，应该切换
-    let current = match manager.current_interface() {
-        Some(iface) => iface,
-        None => {
-            info!("尚未设置活动接口，需要切换");
-            return Ok(true);
-        }
-    };
+/// 排除本轮在所有接口上都不可达的目标，它们无法区分接口优劣
+fn exclude_universally_down_targets(state: &AppState, results: Vec<TestResult>) -> Vec<TestResult> {
+    let down = network::universally_unreachable_targets(&results);
 
-    // 如果最佳接口就是当前接口，不需要切换
-    if current == best.interface {
-        info!("当前接口 {} 已是最佳接口", current);
+    // 所有目标都不可达时更可能是链路问题，保留原结果
+    if down.is_empty() || results.iter().all(|r| down.contains(&r.target)) {
+        return results;
+    }
 
-        // 重置失败计数
-        let mut failures = state.failure_count.write().await;
-        failures.insert(current.to_string(), 0);
+    // 连续不可达的轮数由失效目标熔断器统计，持续失效的提示也由其给出
+    let breaker = state
+        .dead_targets
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for target in &down {
+        info!(
+            "目标 {} 已连续 {} 轮在所有接口上均不可达，本轮不参与评分",
+            target,
+            breaker.streak(target)
+        );
+    }
+    drop(breaker);
+    results
+        .into_iter()
+        .filter(|r| !down.contains(&r.target))
+        .collect()
+}
 
-        return Ok(false);
+/// 对主目标全部失败的接口探测确认目标，返回用于评分的结果
+async fn confirm_unreachable(
+    state: &AppState,
+    interfaces: &[&NetworkInterface],
+    results: &[TestResult],
+) -> Vec<TestResult> {
+    if state.config().confirm_targets.is_empty() {
+        return results.to_vec();
     }
 
-    // 检查当前接口的失败次数
-    let mut failures = state.failure_count.write().await;
-    let current_failures = failures.entry(current.to_string()).or_insert(0);
-    *current_failures += 1;
+    let down = network::unreachable_interfaces(results);
+    let down_interfaces: Vec<&NetworkInterface> = interfaces
+        .iter()
+        .copied()
+        .filter(|i| down.contains(&i.name))
+        .collect();
+    if down_interfaces.is_empty() {
+        return results.to_vec();
+    }
 
     info!(
-        "当前接口 {} 已连续 {} 次非最佳 (阈值: {})",
-        current, current_failures, state.config.global.failure_threshold
+        "接口 {:?} 的主目标全部不可达，探测确认目标...",
+        down_interfaces.iter().map(|i| &i.name).collect::<Vec<_>>()
     );
+    let confirmations = state
+        .tester()
+        .test_exact_targets(&down_interfaces, &state.config().confirm_target_ips())
+        .await;
 
-    // 如果失败次数超过阈值，应该切换
-    if *current_failures >= state.config.global.failure_threshold {
-        info!("达到切换阈值，准备切换接口");
-        return Ok(true);
+    for iface in network::unreachable_interfaces(&confirmations) {
+        warn!("接口 {} 的确认目标也不可达，判定为断开", iface);
+    }
+
+    let merged = network::merge_confirmations(results, &confirmations);
+    for iface in &down {
+        if !network::unreachable_interfaces(&merged).contains(iface) {
+            info!("接口 {} 的确认目标可达，主目标失败视为目标侧问题", iface);
+        }
     }
 
-    Ok(false)
+    merged
 }
 
-/// 打印测试结果
-fn print_test_results(scores: &[InterfaceScore]) {
-    info!("");
-    info!("测试结果汇总:");
-    info!(
-        "{:<15} {:<8} {:<12} {:<12} {:<12} {:<8}",
-        "接口", "可达", "延迟(ms)", "丢包率(%)", "速度(KB/s)", "评分"
-    );
-    info!("{}", "-".repeat(75));
+/// 启用 probe_disabled_interfaces 时探测已禁用的接口，返回标记为备用的评分
+async fn score_standby_interfaces(state: &AppState) -> Vec<InterfaceScore> {
+    let config = state.config();
+    let standby = standby_interfaces(&config);
+    if standby.is_empty() {
+        return Vec::new();
+    }
 
-    for score in scores {
+    let results = state
+        .tester()
+        .test_all_interfaces(&standby, &config.targets, &HashSet::new())
+        .await;
+    state.last_results.update(&results);
+
+    mark_standby(state.tester().score_interfaces(&results).await)
+}
+
+/// 需要作为备用探测的接口
+fn standby_interfaces(config: &Config) -> Vec<&NetworkInterface> {
+    if config.global.probe_disabled_interfaces {
+        config.disabled_interfaces()
+    } else {
+        Vec::new()
+    }
+}
+
+/// 将评分标记为备用
+fn mark_standby(mut scores: Vec<InterfaceScore>) -> Vec<InterfaceScore> {
+    for score in &mut scores {
+        score.eligibility = Eligibility::Ineligible(IneligibleReason::Standby);
+    }
+    scores
+}
+
+/// 记录接口消失与恢复，只在状态变化时输出日志
+fn report_absent_interfaces(state: &AppState, scores: &[InterfaceScore]) {
+    let mut absent = state
+        .absent_interfaces
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    for score in scores {
+        let is_absent = score.eligibility == Eligibility::Ineligible(IneligibleReason::Absent);
+        if is_absent && absent.insert(score.interface.clone()) {
+            warn!(
+                "接口 {} 在系统中不存在（可能已被移除），暂不参与选择，将继续探测",
+                score.interface
+            );
+        } else if !is_absent && absent.remove(&score.interface) {
+            info!("接口 {} 已重新出现，恢复参与选择", score.interface);
+        }
+    }
+}
+
+/// 标记处于观察期的新接口，它们照常评分但暂不参与选择
+fn apply_probation(state: &AppState, scores: &mut [InterfaceScore]) {
+    let present = scores
+        .iter()
+        .filter(|s| s.eligibility != Eligibility::Ineligible(IneligibleReason::Absent))
+        .map(|s| s.interface.as_str());
+    let pending = state
+        .probation
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .observe(present);
+
+    for score in scores.iter_mut() {
+        if let Some((_, remaining)) = pending.iter().find(|(name, _)| *name == score.interface) {
+            info!(
+                "接口 {} 新出现，处于观察期（还需 {} 轮），暂不参与选择",
+                score.interface, remaining
+            );
+            score.eligibility = Eligibility::Ineligible(IneligibleReason::Probation {
+                remaining: *remaining,
+            });
+        }
+    }
+}
+
+/// 探测给定接口本轮应探测的目标
+///
+/// 持续失效的目标按熔断器降低频率；设置了 check_every 的目标未到探测轮次时
+/// 沿用上一次的结果参与评分
+async fn probe_interfaces(
+    state: &AppState,
+    interfaces: &[&NetworkInterface],
+    iteration: u64,
+) -> Vec<TestResult> {
+    let targets: Vec<TargetIP> = interfaces
+        .iter()
+        .flat_map(|i| i.effective_targets(&state.config().targets))
+        .collect();
+
+    let mut skip = state
+        .dead_targets
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .skipped_targets(targets.iter().map(|t| t.address.as_str()));
+    if !skip.is_empty() {
+        debug!("本轮跳过持续失效的目标: {:?}", skip);
+    }
+
+    let mut not_due: HashSet<String> = targets
+        .iter()
+        .filter(|t| !network::due_this_cycle(t, iteration))
+        .map(|t| t.address.clone())
+        .collect();
+    skip.extend(not_due.iter().cloned());
+
+    // 超出每轮探测预算的低优先级目标本轮沿用上次结果
+    let mut candidates: Vec<(&TargetIP, usize)> = Vec::new();
+    for target in targets.iter().filter(|t| !skip.contains(&t.address)) {
+        match candidates
+            .iter_mut()
+            .find(|(t, _)| t.address == target.address)
+        {
+            Some((_, cost)) => *cost += 1,
+            None => candidates.push((target, 1)),
+        }
+    }
+    let over_budget = state
+        .probe_budget
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .over_budget(&candidates);
+    if !over_budget.is_empty() {
+        debug!("超出探测预算，本轮跳过: {:?}", over_budget);
+        skip.extend(over_budget.iter().cloned());
+        not_due.extend(over_budget);
+    }
+
+    let settled = wait_for_switch(state).await;
+    let mut results = state
+        .tester()
+        .test_all_interfaces(interfaces, &state.config().targets, &skip)
+        .await;
+    drop(settled);
+    state
+        .dead_targets
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .record(&results);
+
+    state.last_results.update(&results);
+    for result in results.iter().filter(|r| !r.reachable) {
+        if let Some(reason) = result.failure_reason {
+            let dscp = result
+                .dscp
+                .map(|dscp| format!(" (DSCP {})", dscp))
+                .unwrap_or_default();
+            info!(
+                "接口 {} 到目标 {}{} 不可达: {}",
+                result.interface, result.target, dscp, reason
+            );
+        }
+    }
+
+    // 未到探测轮次或超出预算的目标沿用上次结果（首次检查总会探测，通常都有缓存）
+    for interface in interfaces {
+        for target in interface.effective_targets(&state.config().targets) {
+            if not_due.contains(&target.address) {
+                results.extend(
+                    state
+                        .last_results
+                        .last_result(&interface.name, &target.address),
+                );
+            }
+        }
+    }
+
+    results
+}
+
+/// 经各接口配置的备用网关探测第一个 IP 目标，结果按网关记录，不参与评分
+async fn probe_alternate_gateways(state: &AppState, interfaces: &[&NetworkInterface]) {
+    let wait = Duration::from_secs(state.config().global.timeout);
+
+    for interface in interfaces {
+        if interface.alternate_gateways.is_empty() {
+            continue;
+        }
+        let Some(target) = interface
+            .effective_targets(&state.config().targets)
+            .iter()
+            .find_map(|t| {
+                t.address
+                    .split('/')
+                    .next()
+                    .and_then(|a| a.parse::<std::net::IpAddr>().ok())
+            })
+        else {
+            warn!("接口 {} 没有 IP 目标，无法探测备用网关", interface.name);
+            continue;
+        };
+
+        let manager = state.manager.read().await;
+        for gateway in &interface.alternate_gateways {
+            match manager
+                .probe_via_next_hop(&interface.name, gateway, target, wait)
+                .await
+            {
+                Ok(None) => {}
+                Ok(Some(true)) => info!(
+                    "[{} via {}] 备用网关可用，可到达 {}",
+                    interface.name, gateway, target
+                ),
+                Ok(Some(false)) => warn!(
+                    "[{} via {}] 备用网关无法到达 {}",
+                    interface.name, gateway, target
+                ),
+                Err(e) => warn!(
+                    "[{} via {}] 备用网关探测失败: {}",
+                    interface.name, gateway, e
+                ),
+            }
+        }
+    }
+}
+
+/// 执行单次检查
+async fn run_single_check(state: &AppState) -> Result<CheckSummary> {
+    let config = state.config();
+    let start_time = std::time::Instant::now();
+    let mut summary = CheckSummary {
+        checked_at: chrono::Local::now(),
+        scores: Vec::new(),
+        decision: None,
+        switched: false,
+        elapsed_secs: 0.0,
+    };
+
+    // 获取启用的接口
+    let mut interfaces = config.sorted_interfaces();
+    if config.global.respect_uci_disabled {
+        interfaces = exclude_uci_disabled(state, interfaces).await;
+    }
+
+    if interfaces.is_empty() {
+        warn!("没有启用的接口，跳过检查");
+        return Ok(summary);
+    }
+
+    info!(
+        event = "check_start",
+        iteration = state.check_count.load(Ordering::Relaxed) + 1,
+        interfaces = interfaces.len();
+        "开始测试 {} 个接口...",
+        interfaces.len()
+    );
+
+    // 接口地址变化时清除网关缓存
+    state.manager.read().await.refresh_gateway_cache().await;
+
+    // 托管路由被外部修改时恢复
+    correct_route_drift(state).await;
+
+    if config.global.measure_utilization {
+        let saturated = measure_utilization(&config, &interfaces).await;
+        state.tester().set_saturated(saturated);
+    }
+
+    // 测试所有接口
+    let iteration = state.check_count.fetch_add(1, Ordering::Relaxed) + 1;
+    let results = probe_interfaces(state, &interfaces, iteration).await;
+    probe_alternate_gateways(state, &interfaces).await;
+
+    // 备用接口只探测和展示，不参与后续的选择与路由
+    let standby_scores = score_standby_interfaces(state).await;
+
+    let mut scoring_results = results.clone();
+    if config.global.ignore_universally_down_targets {
+        scoring_results = exclude_universally_down_targets(state, scoring_results);
+    }
+
+    // 主目标全部失败的接口再探测确认目标
+    let scoring_results = confirm_unreachable(state, &interfaces, &scoring_results).await;
+
+    // 计算评分
+    let mut scores = state.tester().score_interfaces(&scoring_results).await;
+    compare_with_baseline(state, &scores);
+    report_absent_interfaces(state, &scores);
+    apply_probation(state, &mut scores);
+    apply_stability_weighting(state, &mut scores);
+    apply_score_smoothing(state, &mut scores);
+
+    // 当前接口完全断开时不施加回切惩罚，尽快恢复连通
+    let current = state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string);
+    if current_hard_down(current.as_deref(), &scores) {
+        error!(
+            "当前接口 {} 所有目标均不可达，进入紧急切换",
+            current.as_deref().unwrap_or_default()
+        );
+    } else {
+        apply_failback_penalties(state, &mut scores).await;
+    }
+
+    // 显示结果
+    print_test_results(&scores);
+    if !standby_scores.is_empty() {
+        info!("备用接口（已禁用，不参与选择）:");
+        print_test_results(&standby_scores);
+    }
+
+    let mut switch_record = None;
+    let mut chosen = None;
+
+    // 获取最佳接口
+    if let Some(best) = select_best_interface(state, &scores) {
+        chosen = Some(best.interface.clone());
         info!(
-            "{:<15} {:<8} {:<12.2} {:<12.1} {:<12.2} {:<8.2}",
-            score.interface,
-            score.reachable_count,
-            score.avg_latency_ms,
-            score.avg_packet_loss * 100.0,
-            score.avg_speed,
-            score.score
+            event = "best_interface",
+            interface = best.interface.as_str(),
+            score = best.score;
+            "最佳接口: {} (评分: {:.2})",
+            best.interface,
+            best.score
         );
+
+        // 负载均衡模式下多个接口共同承载流量时，不再做单接口切换
+        let balanced =
+            config.global.mode == RoutingMode::Balance && apply_balance(state, best, &scores).await;
+        if !balanced {
+            // 检查是否需要切换
+            let decision = should_switch_interface(state, best, &scores).await?;
+            match serde_json::to_string(&decision) {
+                Ok(json) => info!(
+                    event = "switch_decision",
+                    current = decision.current.as_deref(),
+                    best = decision.best.as_str(),
+                    margin = decision.margin,
+                    failures = decision.failures,
+                    outcome:? = decision.outcome,
+                    should_switch = decision.should_switch();
+                    "切换决策: {}",
+                    json
+                ),
+                Err(e) => warn!("序列化切换决策失败: {}", e),
+            }
+            let should_switch = decision.should_switch();
+            summary.decision = Some(decision);
+
+            if should_switch {
+                // 查找接口配置
+                if let Some(interface_config) =
+                    config.interfaces.iter().find(|i| i.name == best.interface)
+                {
+                    if config.global.dry_run {
+                        log_dry_run_switch(state, interface_config, &results).await;
+                    } else if let Some(outcome) =
+                        execute_switch(state, interface_config, &results).await
+                    {
+                        summary.switched = true;
+                        if let (Some(config), Some(decision)) = (&config.notify, &summary.decision)
+                        {
+                            let notification = notify::SwitchNotification::from_decision(
+                                decision,
+                                outcome.previous.clone(),
+                            );
+                            notify::spawn(config.clone(), notification);
+                        }
+                        switch_record = Some(LogRecord::Switch {
+                            ts: chrono::Local::now(),
+                            from: outcome.previous,
+                            to: best.interface.clone(),
+                            duration_secs: Some(outcome.duration.as_secs_f64()),
+                        });
+                    }
+                }
+            } else if !config.global.auto_switch {
+                info!("自动切换已禁用，跳过接口切换");
+            } else {
+                info!("当前接口表现良好，无需切换");
+                steer_critical_routes(state, &results).await;
+            }
+        }
+    } else {
+        warn!("没有可用的接口!");
     }
-    info!("");
+
+    if config.global.results_log.is_some() || state.database.is_some() {
+        let active = state
+            .manager
+            .read()
+            .await
+            .current_interface()
+            .map(str::to_string);
+        let ts = chrono::Local::now();
+        // 接口可能覆盖或追加了目标，目标数按接口分别计算
+        let target_count = |name: &str| {
+            config
+                .interfaces
+                .iter()
+                .find(|i| i.name == name)
+                .map_or(config.targets.len(), |i| {
+                    i.effective_targets(&config.targets).len()
+                })
+        };
+
+        let mut records: Vec<LogRecord> = scores
+            .iter()
+            .chain(&standby_scores)
+            .map(|score| LogRecord::Check {
+                ts,
+                interface: score.interface.clone(),
+                reachable_count: score.reachable_count,
+                target_count: target_count(&score.interface),
+                avg_latency_ms: score
+                    .avg_latency_ms
+                    .is_finite()
+                    .then_some(score.avg_latency_ms),
+                avg_packet_loss: score.avg_packet_loss,
+                score: score.score,
+                active: active.clone(),
+            })
+            .collect();
+        records.extend(switch_record);
+
+        if let Some(path) = &config.global.results_log {
+            if let Err(e) = results_log::append(path, &records) {
+                warn!("写入结果日志失败: {}", e);
+            }
+        }
+        if let Some(database) = &state.database {
+            database.send(records);
+        }
+    }
+
+    let elapsed = start_time.elapsed();
+    info!("本次检查耗时: {:.2} 秒", elapsed.as_secs_f64());
+
+    summary.elapsed_secs = elapsed.as_secs_f64();
+    if let Some(decision) = &summary.decision {
+        decision.mark_blocked_best(&mut scores);
+    }
+    summary.scores = scores;
+    summary.scores.extend(standby_scores);
+    *state.latest_scores.write().await = summary.scores.clone();
+    *state.last_check.write().await = Some(summary.checked_at);
+    state
+        .history
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(decision::CycleRecord {
+            checked_at: summary.checked_at,
+            scores: summary.scores.clone(),
+            chosen,
+            switched: summary.switched,
+        });
+    Ok(summary)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// 间隔采样各接口的收发计数并报告带宽利用率
+/// 返回: 利用率达到饱和阈值的接口
+async fn measure_utilization(config: &Config, interfaces: &[&NetworkInterface]) -> HashSet<String> {
+    let read = |interface: &NetworkInterface| match utilization::read_counters(&interface.name) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            debug!("无法读取接口 {} 的流量计数: {}", interface.name, e);
+            None
+        }
+    };
+
+    let before: Vec<_> = interfaces.iter().map(|i| read(i)).collect();
+    sleep(utilization::SAMPLE_INTERVAL).await;
+
+    let mut saturated = HashSet::new();
+    for (interface, before) in interfaces.iter().zip(before) {
+        let Some(usage) = before.zip(read(interface)).and_then(|(before, after)| {
+            utilization::Utilization::between(
+                before,
+                after,
+                utilization::SAMPLE_INTERVAL,
+                interface.bandwidth_mbps,
+            )
+        }) else {
+            continue;
+        };
+
+        match usage.ratio {
+            Some(ratio) if ratio >= config.global.saturation_threshold => {
+                warn!(
+                    "接口 {} 带宽接近饱和（下行 {:.1} Mbps，上行 {:.1} Mbps，利用率 {:.0}%），本轮推迟速度测试",
+                    interface.name,
+                    usage.rx_mbps,
+                    usage.tx_mbps,
+                    ratio * 100.0
+                );
+                saturated.insert(interface.name.clone());
+            }
+            Some(ratio) => info!(
+                "接口 {} 当前流量: 下行 {:.1} Mbps，上行 {:.1} Mbps，利用率 {:.0}%",
+                interface.name,
+                usage.rx_mbps,
+                usage.tx_mbps,
+                ratio * 100.0
+            ),
+            None => info!(
+                "接口 {} 当前流量: 下行 {:.1} Mbps，上行 {:.1} Mbps",
+                interface.name, usage.rx_mbps, usage.tx_mbps
+            ),
+        }
+    }
+    saturated
+}
+
+/// 计算回切惩罚或回切领先分数：被切走时为 `max_penalty`，在 `window` 内线性衰减到 0
+fn failback_penalty(max_penalty: f64, window: Duration, elapsed: Duration) -> f64 {
+    if window.is_zero() || elapsed >= window {
+        return 0.0;
+    }
+    max_penalty * (1.0 - elapsed.as_secs_f64() / window.as_secs_f64())
+}
+
+/// 成功完成的切换
+struct SwitchOutcome {
+    /// 原接口
+    previous: Option<String>,
+    /// 从决定切换到验证完成的耗时
+    duration: Duration,
+}
+
+/// 执行切换并验证
+/// 返回: 切换并验证通过时的结果，切换失败、验证未通过或已回滚时为 `None`
+async fn execute_switch(
+    state: &AppState,
+    interface_config: &NetworkInterface,
+    results: &[TestResult],
+) -> Option<SwitchOutcome> {
+    let started = Instant::now();
+    info!("准备切换到接口: {}", interface_config.name);
+
+    // 为所有监控目标分配路由出口
+    let static_targets = build_route_plan(state, &interface_config.name, results);
+
+    let static_targets_opt = if !static_targets.is_empty() {
+        Some(static_targets.as_slice())
+    } else {
+        None
+    };
+
+    let mut _switching = state.switching.write().await;
+    let mut manager = state.manager.write().await;
+    let previous = manager.current_interface().map(str::to_string);
+
+    if let Some(command) = &state.config().global.pre_switch_command {
+        if let Err(e) = run_switch_gate(
+            state,
+            command,
+            hooks::SwitchEvent::PreSwitch,
+            previous.as_deref(),
+            &interface_config.name,
+        )
+        .await
+        {
+            warn!(
+                "切换前检查未通过，取消切换到 {}: {:#}",
+                interface_config.name, e
+            );
+            return None;
+        }
+    }
+
+    if let Err(e) = manager
+        .switch_to_interface(
+            interface_config,
+            state.config().global.manage_uci_routes,
+            static_targets_opt,
+        )
+        .await
+    {
+        error!("接口切换失败: {}", e);
+        return None;
+    }
+    if let Some(drain_time) = manager.pending_drain_time() {
+        // 排空等待期间释放锁，不阻塞 /status、控制套接字和保活
+        drop(manager);
+        drop(_switching);
+        sleep(drain_time).await;
+        _switching = state.switching.write().await;
+        manager = state.manager.write().await;
+        if let Err(e) = manager.complete_drain().await {
+            error!("完成排空切换失败: {}", e);
+            return None;
+        }
+    }
+    if state.config().global.manage_uci_routes {
+        remember_routes(state, &static_targets);
+    }
+
+    let applied: &[RouteAssignment] = if state.config().global.manage_uci_routes {
+        &static_targets
+    } else {
+        &[]
+    };
+    let verification = verify_after_switch(
+        state,
+        &mut manager,
+        interface_config,
+        previous.as_deref(),
+        applied,
+        results,
+    )
+    .await;
+
+    match verification {
+        Verification::Passed => {}
+        Verification::RolledBack => {
+            // 新接口无法承载流量，按刚被切走处理，避免下一轮立即再次切换
+            state
+                .failed_over_at
+                .write()
+                .await
+                .insert(interface_config.name.clone(), Instant::now());
+            return None;
+        }
+        // 验证未通过且未能回滚：不通知、不记录为一次切换，失败计数保留，下一轮重新评估
+        Verification::Failed => return None,
+    }
+
+    let duration = started.elapsed();
+    info!("接口切换成功! 耗时 {:.2} 秒", duration.as_secs_f64());
+    syslog::event(&format!(
+        "已从 {} 切换到接口 {}（耗时 {:.2} 秒）",
+        previous.as_deref().unwrap_or("(无)"),
+        interface_config.name,
+        duration.as_secs_f64()
+    ));
+    *state.last_switch_duration.write().await = Some(duration);
+    *state
+        .last_switch_at
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+
+    if let Some(previous) = &previous {
+        state
+            .switch_rate
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(Instant::now());
+        state
+            .failed_over_at
+            .write()
+            .await
+            .insert(previous.clone(), Instant::now());
+    }
+
+    // 重置失败计数
+    state.failure_count.write().await.clear();
+
+    Some(SwitchOutcome { previous, duration })
+}
+
+/// 手动切换到指定接口：跳过评分和切换决策，切换后照常验证
+async fn manual_switch(state: &AppState, interface: &str) -> Result<()> {
+    let config = state.config();
+    let Some(interface_config) = config
+        .interfaces
+        .iter()
+        .find(|i| i.name == interface && i.enabled)
+    else {
+        anyhow::bail!("未知或未启用的接口: {}", interface);
+    };
+    info!("收到手动切换请求: {}", interface);
+
+    // 使用最近一次的探测结果规划托管路由
+    let results = state.last_results.snapshot();
+    if config.global.dry_run {
+        log_dry_run_switch(state, interface_config, &results).await;
+        return Ok(());
+    }
+    match execute_switch(state, interface_config, &results).await {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("切换到接口 {} 失败或未通过验证", interface),
+    }
+}
+
+/// 固定到指定接口并立即切换过去；`None` 取消固定
+async fn pin_interface(state: &AppState, interface: Option<&str>) -> Result<()> {
+    let Some(interface) = interface else {
+        let previous = state
+            .pinned_interface
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(previous) = previous {
+            info!("已取消固定接口 {}", previous);
+        }
+        return Ok(());
+    };
+
+    state.config().check_pinnable(interface)?;
+    *state
+        .pinned_interface
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(interface.to_string());
+    info!("已固定接口 {}", interface);
+
+    let current = state
+        .manager
+        .read()
+        .await
+        .current_interface()
+        .map(str::to_string);
+    if current.as_deref() == Some(interface) {
+        return Ok(());
+    }
+    manual_switch(state, interface).await
+}
+
+/// 演练模式：记录将要执行的切换和需要改写的 UCI 路由，不修改任何配置
+/// 失败计数不清零，后续轮次照常累计，便于观察真实的切换时机
+async fn log_dry_run_switch(
+    state: &AppState,
+    interface_config: &NetworkInterface,
+    results: &[TestResult],
+) {
+    let manager = state.manager.read().await;
+    info!(
+        "[演练] 将从 {} 切换到接口 {}",
+        manager.current_interface().unwrap_or("(无)"),
+        interface_config.name
+    );
+
+    if state.config().global.manage_uci_routes {
+        let plan = build_route_plan(state, &interface_config.name, results);
+        if let Err(e) = manager.manage_static_routes(&plan).await {
+            warn!("[演练] 读取 UCI 路由失败: {}", e);
+        }
+    }
+}
+
+/// 切换后的验证结果
+#[derive(Debug, PartialEq)]
+enum Verification {
+    /// 验证通过
+    Passed,
+    /// 验证未通过，但无法或无需回滚
+    Failed,
+    /// 路由验证、功能验证或切换后检查失败，已回滚到原接口
+    RolledBack,
+}
+
+/// 验证切换结果：先按本次应用的路由分配检查路由配置，启用 functional_verify 时再按路由表
+/// 探测关键目标，配置了 post_switch_command 时最后执行该命令；路由与分配不一致时撤销本次
+/// 路由改写，功能验证或检查命令失败则回滚到原接口
+async fn verify_after_switch(
+    state: &AppState,
+    manager: &mut OpenWrtManager,
+    interface: &NetworkInterface,
+    previous: Option<&str>,
+    plan: &[RouteAssignment],
+    results: &[TestResult],
+) -> Verification {
+    match manager.verify_switch(interface, plan).await {
+        Ok(true) => info!("接口切换验证通过"),
+        Ok(false) => {
+            warn!("接口切换验证失败，回滚到切换前的路由");
+            return match manager.rollback_switch().await {
+                Ok(restored) => {
+                    error!(
+                        "接口 {} 切换验证失败，已回滚 {} 条路由，活动接口恢复为 {}",
+                        interface.name,
+                        restored,
+                        manager.current_interface().unwrap_or("(无)")
+                    );
+                    Verification::RolledBack
+                }
+                Err(e) => {
+                    error!(
+                        "接口 {} 切换验证失败，回滚失败，需要手动检查: {:#}",
+                        interface.name, e
+                    );
+                    Verification::Failed
+                }
+            };
+        }
+        Err(e) => {
+            warn!("接口切换验证出错: {}", e);
+            return Verification::Failed;
+        }
+    }
+
+    if state.config().global.functional_verify && !verify_traffic(state, manager, interface).await {
+        return rollback(state, manager, interface, previous, results, "功能验证失败").await;
+    }
+
+    if let Some(command) = &state.config().global.post_switch_command {
+        if let Err(e) = run_switch_gate(
+            state,
+            command,
+            hooks::SwitchEvent::PostSwitch,
+            previous,
+            &interface.name,
+        )
+        .await
+        {
+            warn!("切换后检查未通过: {:#}", e);
+            return rollback(
+                state,
+                manager,
+                interface,
+                previous,
+                results,
+                "切换后检查失败",
+            )
+            .await;
+        }
+    }
+
+    Verification::Passed
+}
+
+/// 按路由表探测关键目标（无关键目标时用第一个目标），确认流量确实经由新接口
+async fn verify_traffic(
+    state: &AppState,
+    manager: &OpenWrtManager,
+    interface: &NetworkInterface,
+) -> bool {
+    let config = state.config();
+    // 优先使用关键目标
+    let Some(target) = config
+        .targets
+        .iter()
+        .find(|t| t.critical)
+        .or_else(|| config.targets.first())
+    else {
+        return true;
+    };
+    let address = target.address.split('/').next().unwrap_or(&target.address);
+    let wait = Duration::from_secs(config.global.timeout);
+
+    manager
+        .verify_traffic(interface, address, wait)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("功能验证出错: {}", e);
+            false
+        })
+}
+
+/// 切换后验证失败时回滚到原接口
+async fn rollback(
+    state: &AppState,
+    manager: &mut OpenWrtManager,
+    interface: &NetworkInterface,
+    previous: Option<&str>,
+    results: &[TestResult],
+    reason: &str,
+) -> Verification {
+    let config = state.config();
+    let Some(previous) =
+        previous.and_then(|name| config.interfaces.iter().find(|i| i.name == name))
+    else {
+        error!("接口 {} {}，且没有可回滚的接口", interface.name, reason);
+        return Verification::Failed;
+    };
+
+    error!(
+        "接口 {} {}，回滚到接口 {}",
+        interface.name, reason, previous.name
+    );
+    let plan = build_route_plan(state, &previous.name, results);
+    let plan = (!plan.is_empty()).then_some(plan.as_slice());
+    let switched = match manager
+        .switch_to_interface(previous, config.global.manage_uci_routes, plan)
+        .await
+    {
+        // 回滚时新接口已无法承载流量，不等待排空
+        Ok(()) => manager.complete_drain().await,
+        Err(e) => Err(e),
+    };
+    match switched {
+        Ok(()) => {
+            if let (true, Some(plan)) = (config.global.manage_uci_routes, plan) {
+                remember_routes(state, plan);
+            }
+            Verification::RolledBack
+        }
+        Err(e) => {
+            error!("回滚到接口 {} 失败: {}", previous.name, e);
+            Verification::Failed
+        }
+    }
+}
+
+/// 执行切换前后的检查命令
+async fn run_switch_gate(
+    state: &AppState,
+    command: &str,
+    event: hooks::SwitchEvent,
+    from: Option<&str>,
+    to: &str,
+) -> Result<()> {
+    let limit = Duration::from_secs(state.config().global.switch_command_timeout);
+    hooks::run_gate(state.runner.as_ref(), command, event, from, to, limit).await
+}
+
+/// 当前接口是否完全不可达（需要紧急切换）
+fn current_hard_down(current: Option<&str>, scores: &[InterfaceScore]) -> bool {
+    current.is_some_and(|current| {
+        scores
+            .iter()
+            .any(|s| s.interface == current && s.reachable_count == 0)
+    })
+}
+
+/// 记录本轮评分，并按各接口近期评分的标准差扣分，偏好长期稳定的链路
+fn apply_stability_weighting(state: &AppState, scores: &mut [InterfaceScore]) {
+    let weight = state.config().global.stability_weight;
+    if weight <= 0.0 {
+        return;
+    }
+
+    let mut history = state
+        .score_history
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    history.record(scores);
+
+    for score in scores.iter_mut() {
+        let Some(variance) = history.variance(&score.interface) else {
+            continue;
+        };
+
+        let penalty = weight * variance.sqrt();
+        if penalty > 0.0 {
+            debug!(
+                "接口 {} 评分波动（标准差 {:.2}），评分 {:.2} 扣除 {:.2}",
+                score.interface,
+                variance.sqrt(),
+                score.score,
+                penalty
+            );
+            score.score = (score.score - penalty).max(0.0);
+        }
+    }
+
+    sort_scores(scores);
+}
+
+/// 用指数移动平均平滑评分（`score_smoothing` 为本轮评分的占比），
+/// 单轮的偶发波动不会立即改变排名；平滑后的评分保存供下一轮使用
+fn apply_score_smoothing(state: &AppState, scores: &mut [InterfaceScore]) {
+    let alpha = state.config().global.score_smoothing;
+    if alpha >= 1.0 {
+        return;
+    }
+
+    let mut smoothed = state
+        .smoothed_scores
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for score in scores.iter_mut() {
+        let raw = score.score;
+        let ema = match smoothed.get(&score.interface) {
+            Some(previous) => alpha * raw + (1.0 - alpha) * previous,
+            None => raw,
+        };
+        smoothed.insert(score.interface.clone(), ema);
+        if (ema - raw).abs() > f64::EPSILON {
+            debug!("接口 {} 评分 {:.2} 平滑为 {:.2}", score.interface, raw, ema);
+        }
+        score.score = ema;
+    }
+
+    sort_scores(scores);
+}
+
+/// 负载均衡模式：评分与最佳接口相差不超过 `balance_threshold` 的接口按评分比例分担默认路由
+///
+/// 返回是否已在多个接口间分流；不足两个接口时收起多路径路由，交由故障转移处理。
+/// 冻结窗口内保持当前路由不变；固定接口可达时只经固定接口出口
+async fn apply_balance(state: &AppState, best: &InterfaceScore, scores: &[InterfaceScore]) -> bool {
+    let config = state.config();
+    if !config.global.auto_switch {
+        return false;
+    }
+
+    let frozen = decision::active_freeze_window(
+        &state
+            .freeze_windows
+            .read()
+            .unwrap_or_else(PoisonError::into_inner),
+        chrono::Local::now(),
+    )
+    .map(str::to_string);
+    if let Some(window) = frozen {
+        info!("处于冻结窗口 [{}] 内，保持当前的负载均衡路由", window);
+        return state.manager.read().await.ecmp_active();
+    }
+
+    let pinned = state
+        .pinned_interface
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .filter(|pinned| {
+            scores
+                .iter()
+                .any(|s| s.interface == *pinned && s.reachable_count > 0)
+        });
+    if let Some(pinned) = &pinned {
+        info!("接口已固定为 {}，暂停负载均衡", pinned);
+    }
+
+    let threshold = config.global.balance_threshold;
+    let members: Vec<(&NetworkInterface, f64)> = scores
+        .iter()
+        .filter(|s| match &pinned {
+            Some(pinned) => s.interface == *pinned,
+            None => s.is_eligible() && best.score - s.score <= threshold,
+        })
+        .filter_map(|s| {
+            config
+                .interfaces
+                .iter()
+                .find(|i| i.name == s.interface)
+                .map(|i| (i, s.score))
+        })
+        .collect();
+
+    let mut manager = state.manager.write().await;
+    if members.len() < 2 {
+        if manager.ecmp_active() {
+            info!("可分担流量的接口不足两个，恢复单出口默认路由");
+            if let Err(e) = manager.setup_ecmp_routes(&members).await {
+                error!("恢复单出口默认路由失败: {:#}", e);
+            }
+        }
+        return false;
+    }
+
+    match manager.setup_ecmp_routes(&members).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!("设置多路径默认路由失败，按故障转移处理: {:#}", e);
+            false
+        }
+    }
+}
+
+/// 选择最佳接口
+///
+/// 配置了虚拟接口时先在虚拟接口与其余物理接口之间比较，
+/// 选中虚拟接口则返回其中评分最高的可用成员
+fn select_best_interface<'a>(
+    state: &AppState,
+    scores: &'a [InterfaceScore],
+) -> Option<&'a InterfaceScore> {
+    let virtual_interfaces = &state.config().virtual_interfaces;
+    if virtual_interfaces.is_empty() {
+        return state.tester().get_best_interface(scores);
+    }
+
+    let units = network::virtual_interface_scores(virtual_interfaces, scores);
+    info!("按虚拟接口汇总:");
+    print_test_results(&units);
+
+    let best = state.tester().get_best_interface(&units)?;
+    match virtual_interfaces.iter().find(|v| v.name == best.interface) {
+        Some(virtual_interface) => {
+            let members: Vec<InterfaceScore> = scores
+                .iter()
+                .filter(|s| virtual_interface.members.contains(&s.interface))
+                .cloned()
+                .collect();
+            let member = state.tester().get_best_interface(&members)?;
+            info!(
+                "最佳虚拟接口: {}，使用成员 {}",
+                virtual_interface.name, member.interface
+            );
+            scores.iter().find(|s| s.interface == member.interface)
+        }
+        None => scores.iter().find(|s| s.interface == best.interface),
+    }
+}
+
+/// 将本轮评分与基线快照比较，在接口开始偏离和恢复时提示
+fn compare_with_baseline(state: &AppState, scores: &[InterfaceScore]) {
+    let Some(baseline) = &state.baseline else {
+        return;
+    };
+
+    let deviations = baseline.deviations(scores, state.config().global.baseline_deviation);
+    let (started, recovered) = state
+        .baseline_deviations
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .update(deviations);
+    for deviation in started {
+        warn!("偏离基线: {}", deviation);
+    }
+    for (interface, metric) in recovered {
+        info!("接口 {} 的{}已恢复到基线附近", interface, metric);
+    }
+}
+
+/// 对最近被切走的接口扣除回切惩罚，并重新排序
+async fn apply_failback_penalties(state: &AppState, scores: &mut [InterfaceScore]) {
+    let window = Duration::from_secs(state.config().global.failback_penalty_window);
+    if window.is_zero() {
+        return;
+    }
+
+    let failed_over_at = state.failed_over_at.read().await;
+    for score in scores.iter_mut() {
+        let Some(at) = failed_over_at.get(&score.interface) else {
+            continue;
+        };
+
+        let penalty =
+            failback_penalty(state.config().global.failback_penalty, window, at.elapsed());
+        if penalty > 0.0 {
+            info!(
+                "接口 {} 处于回切惩罚期，评分 {:.2} 扣除 {:.2}",
+                score.interface, score.score, penalty
+            );
+            score.score = (score.score - penalty).max(0.0);
+        }
+    }
+
+    sort_scores(scores);
+}
+
+/// 为托管路由生成路由分配
+/// 默认全部指向最佳接口；关键目标若只有一个接口可达，则固定到该接口
+fn build_route_plan(
+    state: &AppState,
+    best_interface: &str,
+    results: &[TestResult],
+) -> Vec<RouteAssignment> {
+    state
+        .config()
+        .route_addresses()
+        .into_iter()
+        .map(|address| {
+            let mut interface = best_interface.to_string();
+
+            if state.config().is_critical_target(address) {
+                let reachable = state.tester().reachable_interfaces(results, address);
+                if let [only] = reachable.as_slice() {
+                    if *only != best_interface {
+                        info!(
+                            "关键目标 {} 仅可经 {} 到达，路由固定到该接口",
+                            address, only
+                        );
+                    }
+                    interface = only.to_string();
+                }
+            }
+
+            if !supports_address(state, &interface, address) {
+                match family_capable_interface(state, address, results) {
+                    Some(capable) => {
+                        info!(
+                            "接口 {} 不支持目标 {} 的地址族，路由改经 {}",
+                            interface, address, capable
+                        );
+                        interface = capable;
+                    }
+                    None => warn!("没有支持目标 {} 地址族的接口", address),
+                }
+            }
+
+            RouteAssignment {
+                target: address.to_string(),
+                interface,
+            }
+        })
+        .collect()
+}
+
+/// 接口的地址族是否支持该目标（未知接口视为支持）
+fn supports_address(state: &AppState, interface: &str, address: &str) -> bool {
+    state
+        .config()
+        .interfaces
+        .iter()
+        .find(|i| i.name == interface)
+        .is_none_or(|i| i.family.supports(address))
+}
+
+/// 支持目标地址族的启用接口：优先本轮可达该目标的接口，其次按优先级
+fn family_capable_interface(
+    state: &AppState,
+    address: &str,
+    results: &[TestResult],
+) -> Option<String> {
+    let reachable = state.tester().reachable_interfaces(results, address);
+    state
+        .config()
+        .enabled_interfaces()
+        .into_iter()
+        .filter(|i| i.family.supports(address))
+        .min_by_key(|i| (!reachable.contains(&i.name.as_str()), i.priority))
+        .map(|i| i.name.clone())
+}
+
+/// 未切换接口时，按可达性调整关键目标的路由
+async fn steer_critical_routes(state: &AppState, results: &[TestResult]) {
+    if !state.config().global.manage_uci_routes {
+        return;
+    }
+
+    let manager = state.manager.read().await;
+    let Some(current) = manager.current_interface() else {
+        return;
+    };
+
+    let critical_plan: Vec<RouteAssignment> = build_route_plan(state, current, results)
+        .into_iter()
+        .filter(|assignment| state.config().is_critical_target(&assignment.target))
+        .collect();
+
+    if critical_plan.is_empty() {
+        return;
+    }
+
+    match manager.manage_static_routes(&critical_plan).await {
+        Ok(()) => remember_routes(state, &critical_plan),
+        Err(e) => warn!("调整关键目标路由失败: {}", e),
+    }
+}
+
+/// 记录本程序设置的路由出口，作为检测外部修改的依据
+fn remember_routes(state: &AppState, plan: &[RouteAssignment]) {
+    // 演练模式没有实际设置路由，不作为外部修改的检测依据
+    if state.config().global.dry_run {
+        return;
+    }
+    let mut intended = state
+        .intended_routes
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for assignment in plan {
+        intended.insert(assignment.target.clone(), assignment.interface.clone());
+    }
+}
+
+/// 检测托管路由是否被外部修改（其他管理员、脚本或重启恢复默认配置），
+/// 发现后告警并恢复为本程序设置的出口
+async fn correct_route_drift(state: &AppState) -> Vec<openwrt::RouteDrift> {
+    if !state.config().global.manage_uci_routes {
+        return Vec::new();
+    }
+
+    let mut intended: Vec<RouteAssignment> = state
+        .intended_routes
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|(target, interface)| RouteAssignment {
+            target: target.clone(),
+            interface: interface.clone(),
+        })
+        .collect();
+    if intended.is_empty() {
+        return Vec::new();
+    }
+    intended.sort_by(|a, b| a.target.cmp(&b.target));
+
+    let drift = match state.manager.read().await.route_drift(&intended).await {
+        Ok(drift) => drift,
+        Err(e) => {
+            warn!("检查托管路由失败: {}", e);
+            return Vec::new();
+        }
+    };
+    if drift.is_empty() {
+        return drift;
+    }
+
+    for entry in &drift {
+        let message = format!(
+            "托管路由 {} 被外部修改: 应指向 {}，实际为 {}",
+            entry.target,
+            entry.expected,
+            entry.actual.as_deref().unwrap_or("(已删除)")
+        );
+        warn!("{}", message);
+        syslog::event(&message);
+    }
+
+    // 恢复路由同样需要提交并重载网络，期间暂停探测
+    let corrections: Vec<RouteAssignment> = intended
+        .into_iter()
+        .filter(|a| drift.iter().any(|d| d.target == a.target))
+        .collect();
+    let _switching = state.switching.write().await;
+    match state
+        .manager
+        .read()
+        .await
+        .manage_static_routes(&corrections)
+        .await
+    {
+        Ok(()) => info!("已恢复 {} 条托管路由", corrections.len()),
+        Err(e) => error!("恢复托管路由失败: {}", e),
+    }
+
+    drift
+}
+
+/// 判断是否应该切换接口，返回结构化的决策依据
+async fn should_switch_interface(
+    state: &AppState,
+    best: &InterfaceScore,
+    scores: &[InterfaceScore],
+) -> Result<SwitchDecision> {
+    let manager = state.manager.read().await;
+    // 当前接口配置了失败阈值时优先使用
+    let threshold = manager
+        .current_interface()
+        .map_or(state.config().global.failure_threshold, |current| {
+            state.config().failure_threshold_for(current)
+        });
+
+    let mut decision = SwitchDecision {
+        current: manager.current_interface().map(str::to_string),
+        current_score: None,
+        best: best.interface.clone(),
+        best_score: best.score,
+        margin: None,
+        failures: 0,
+        failure_threshold: threshold,
+        gates: Vec::new(),
+        outcome: DecisionOutcome::Blocked,
+    };
+
+    if !state.config().global.auto_switch {
+        decision.gates.push(SwitchGate::AutoSwitchDisabled);
+    }
+
+    if let Some(window) = decision::active_freeze_window(
+        &state
+            .freeze_windows
+            .read()
+            .unwrap_or_else(PoisonError::into_inner),
+        chrono::Local::now(),
+    ) {
+        info!("处于冻结窗口 [{}] 内，暂停自动切换", window);
+        decision.gates.push(SwitchGate::FreezeWindow {
+            window: window.to_string(),
+        });
+    }
+
+    let pinned = state
+        .pinned_interface
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    if let Some(pinned) = pinned {
+        let pinned_up = scores
+            .iter()
+            .any(|s| s.interface == pinned && s.reachable_count > 0);
+        if !pinned_up {
+            warn!("固定接口 {} 完全不可达，忽略固定，按评分正常选择", pinned);
+        } else if best.interface != pinned {
+            info!("接口已固定为 {}，不切换到 {}", pinned, best.interface);
+            decision
+                .gates
+                .push(SwitchGate::Pinned { interface: pinned });
+        }
+    }
+
+    // 如果没有当前接口，应该切换
+    let current = match manager.current_interface() {
+        Some(iface) => iface,
+        None => {
+            info!("尚未设置活动接口，需要切换");
+            decision.outcome = DecisionOutcome::NoActiveInterface;
+            return Ok(decision);
+        }
+    };
+
+    decision.current_score = scores
+        .iter()
+        .find(|s| s.interface == current)
+        .map(|s| s.score);
+    decision.margin = decision.current_score.map(|c| best.score - c);
+
+    // 如果最佳接口就是当前接口，不需要切换
+    if current == best.interface {
+        info!("当前接口 {} 已是最佳接口", current);
+
+        // 重置失败计数
+        let mut failures = state.failure_count.write().await;
+        failures.insert(current.to_string(), 0);
+
+        decision.outcome = DecisionOutcome::AlreadyBest;
+        return Ok(decision);
+    }
+
+    // 当前接口完全断开且有可用接口时立即切换，不等待失败阈值
+    if current_hard_down(Some(current), scores) && best.reachable_count > 0 {
+        error!(
+            "紧急切换: 当前接口 {} 完全不可达，立即切换到 {}",
+            current, best.interface
+        );
+        decision.outcome = DecisionOutcome::Emergency;
+        // 冻结窗口、固定接口和切换限频都不阻止紧急切换，只有关闭自动切换仍然生效
+        decision.gates.retain(|gate| {
+            let keep = *gate == SwitchGate::AutoSwitchDisabled;
+            if !keep {
+                warn!("紧急切换忽略切换条件: {:?}", gate);
+            }
+            keep
+        });
+        return Ok(decision);
+    }
+
+    // 领先不足切换门槛时视为当前接口仍为最佳，避免分数相近的接口来回切换；
+    // 当前接口不能参与选择（备用、观察期、可达比例不足）时分数再高也不能留住它
+    let switch_margin = state.config().global.switch_margin;
+    let current_eligible = scores
+        .iter()
+        .any(|s| s.interface == current && s.is_eligible());
+    if let Some(margin) = decision
+        .margin
+        .filter(|margin| switch_margin > 0.0 && current_eligible && *margin < switch_margin)
+    {
+        info!(
+            "接口 {} 仅领先当前接口 {} {:.2} 分，未达到切换门槛 {:.2}，保持当前接口",
+            best.interface, current, margin, switch_margin
+        );
+
+        let mut failures = state.failure_count.write().await;
+        failures.insert(current.to_string(), 0);
+
+        decision.outcome = DecisionOutcome::AlreadyBest;
+        return Ok(decision);
+    }
+
+    // 检查当前接口的失败次数
+    let mut failures = state.failure_count.write().await;
+    let current_failures = failures.entry(current.to_string()).or_insert(0);
+    *current_failures += 1;
+    decision.failures = *current_failures;
+
+    info!(
+        "当前接口 {} 已连续 {} 次非最佳 (阈值: {})",
+        current, current_failures, threshold
+    );
+
+    // 如果失败次数超过阈值，应该切换
+    if *current_failures >= threshold {
+        info!("达到切换阈值，准备切换接口");
+        decision.outcome = DecisionOutcome::ThresholdReached;
+        apply_switch_cooldown(state, &mut decision);
+        apply_switch_rate_limit(state, &mut decision);
+    } else {
+        decision.gates.push(SwitchGate::ThresholdNotMet {
+            failures: *current_failures,
+            threshold,
+        });
+    }
+
+    if let Some(margin) = decision.margin {
+        apply_failback_margin(state, &best.interface, margin, &mut decision).await;
+    }
+
+    Ok(decision)
+}
+
+/// 最佳接口是最近被切走的接口时，要求其领先当前接口的分数达到随时间衰减的回切门槛
+async fn apply_failback_margin(
+    state: &AppState,
+    best: &str,
+    margin: f64,
+    decision: &mut SwitchDecision,
+) {
+    let window = Duration::from_secs(state.config().global.failback_margin_window);
+    let Some(at) = state.failed_over_at.read().await.get(best).copied() else {
+        return;
+    };
+
+    let required = failback_penalty(state.config().global.failback_margin, window, at.elapsed());
+    if required > 0.0 && margin < required {
+        info!(
+            "接口 {} 刚被切走，回切需领先 {:.2} 分（当前领先 {:.2}）",
+            best, required, margin
+        );
+        decision
+            .gates
+            .push(SwitchGate::FailbackMargin { margin, required });
+    }
+}
+
+/// 最近一小时切换次数达到上限时阻止按评分切换（紧急切换不受限制）
+fn apply_switch_rate_limit(state: &AppState, decision: &mut SwitchDecision) {
+    let limit = state.config().global.max_switches_per_hour;
+    let Some((switches, retry_after)) = state
+        .switch_rate
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .exceeded(Instant::now())
+    else {
+        return;
+    };
+
+    error!(
+        "最近一小时已切换 {} 次，达到上限 {}，暂停切换并保持当前接口，{} 秒后解除",
+        switches,
+        limit,
+        retry_after.as_secs()
+    );
+    decision.gates.push(SwitchGate::RateLimited {
+        switches,
+        limit,
+        retry_after_secs: retry_after.as_secs(),
+    });
+}
+
+/// 距上次切换不足 switch_cooldown 时阻止按评分切换（紧急切换不受限制）
+fn apply_switch_cooldown(state: &AppState, decision: &mut SwitchDecision) {
+    let cooldown = Duration::from_secs(state.config().global.switch_cooldown);
+    let Some(last_switch) = *state
+        .last_switch_at
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+    else {
+        return;
+    };
+    let Some(remaining) = cooldown.checked_sub(last_switch.elapsed()) else {
+        return;
+    };
+    if remaining.is_zero() {
+        return;
+    }
+
+    info!(
+        "距上次切换不足冷却时间 {} 秒，暂不切换到 {}，{} 秒后解除",
+        cooldown.as_secs(),
+        decision.best,
+        remaining.as_secs()
+    );
+    decision.gates.push(SwitchGate::Cooldown {
+        remaining_secs: remaining.as_secs(),
+    });
+}
+
+/// 打印测试结果
+fn print_test_results(scores: &[InterfaceScore]) {
+    info!("");
+    info!("测试结果汇总:");
+    info!("{}", score_table_header());
+    info!("{}", "-".repeat(92));
+
+    for score in scores {
+        info!(
+            event = "interface_result",
+            interface = score.interface.as_str(),
+            eligible = score.is_eligible(),
+            reachable = score.reachable_count,
+            reachable_v4 = score.reachable_v4,
+            reachable_v6 = score.reachable_v6,
+            latency_ms = score.avg_latency_ms,
+            p95_latency_ms = score.p95_latency_ms,
+            jitter_ms = score.avg_jitter_ms,
+            packet_loss = score.avg_packet_loss,
+            speed_kbps = score.avg_speed,
+            score = score.score;
+            "{}",
+            score_table_row(score)
+        );
+    }
+
+    for score in scores {
+        if let Eligibility::Ineligible(reason) = &score.eligibility {
+            info!("{}: 不参与选择（{}）", score.interface, reason);
+        }
+    }
+    info!("");
+}
+
+/// 评分表的表头
+fn score_table_header() -> String {
+    format!(
+        "{:<15} {:<12} {:<12} {:<12} {:<12} {:<12} {:<8}",
+        "接口", "可达(v4/v6)", "延迟(ms)", "抖动(ms)", "丢包率(%)", "速度(KB/s)", "评分"
+    )
+}
+
+/// 评分表中一个接口的行
+fn score_table_row(score: &InterfaceScore) -> String {
+    let name = match &score.eligibility {
+        Eligibility::Eligible => score.interface.clone(),
+        Eligibility::Ineligible(reason) => format!("{}({})", score.interface, reason.label()),
+    };
+    let reachable = format!(
+        "{}({}/{})",
+        score.reachable_count, score.reachable_v4, score.reachable_v6
+    );
+    let jitter = score
+        .avg_jitter_ms
+        .map_or_else(|| "-".to_string(), |j| format!("{:.2}", j));
+    format!(
+        "{:<15} {:<12} {:<12.2} {:<12} {:<12.1} {:<12.2} {:<8.2}",
+        name,
+        reachable,
+        score.avg_latency_ms,
+        jitter,
+        score.avg_packet_loss * 100.0,
+        score.avg_speed,
+        score.score
+    )
+}
+
+/// 完整的评分表文本（`--check` 输出到标准输出）
+fn render_score_table(scores: &[InterfaceScore]) -> String {
+    let mut table = format!("{}\n{}\n", score_table_header(), "-".repeat(92));
+    for score in scores {
+        table.push_str(&score_table_row(score));
+        table.push('\n');
+    }
+    for score in scores {
+        if let Eligibility::Ineligible(reason) = &score.eligibility {
+            table.push_str(&format!("{}: 不参与选择（{}）\n", score.interface, reason));
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AddressFamily;
+
+    fn test_config() -> Config {
+        toml::from_str(
+            r#"
+            [global]
+            check_interval = 60
+            timeout = 5
+            concurrent_tests = 4
+            failure_threshold = 3
+            log_level = "info"
+            auto_switch = true
+
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+
+            [[interfaces]]
+            name = "wan_b"
+            display_name = "B"
+            priority = 2
+            enabled = true
+
+            [[targets]]
+            address = "8.8.8.8"
+            description = "公共目标"
+            weight = 1.0
+
+            [[targets]]
+            address = "10.0.0.1/32"
+            description = "仅 B 可达的关键目标"
+            weight = 1.0
+            critical = true
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn result(interface: &str, target: &str, reachable: bool) -> TestResult {
+        TestResult {
+            interface: interface.to_string(),
+            target: target.to_string(),
+            resolved_address: None,
+            reachable,
+            latency_ms: reachable.then_some(10.0),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(if reachable { 0.0 } else { 1.0 }),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: (!reachable).then_some(network::FailureReason::Timeout),
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_critical_target_pinned_to_unique_interface() {
+        let state = AppState::new(test_config());
+        let results = vec![
+            result("wan_a", "8.8.8.8", true),
+            result("wan_b", "8.8.8.8", true),
+            result("wan_a", "10.0.0.1/32", false),
+            result("wan_b", "10.0.0.1/32", true),
+        ];
+
+        // wan_a 总体评分更高，但关键目标只有 wan_b 可达
+        let plan = build_route_plan(&state, "wan_a", &results);
+        assert_eq!(
+            plan,
+            vec![
+                RouteAssignment {
+                    target: "8.8.8.8".to_string(),
+                    interface: "wan_a".to_string(),
+                },
+                RouteAssignment {
+                    target: "10.0.0.1/32".to_string(),
+                    interface: "wan_b".to_string(),
+                },
+            ]
+        );
+
+        // 两个接口都可达时，关键目标跟随最佳接口
+        let results = vec![
+            result("wan_a", "10.0.0.1/32", true),
+            result("wan_b", "10.0.0.1/32", true),
+        ];
+        let plan = build_route_plan(&state, "wan_a", &results);
+        assert!(plan.iter().all(|a| a.interface == "wan_a"));
+    }
+
+    #[test]
+    fn test_route_plan_uses_managed_routes() {
+        let mut config = test_config();
+        config.managed_routes = Some(vec![
+            config::ManagedRoute {
+                address: "1.0.1.0/24".to_string(),
+                description: None,
+            },
+            config::ManagedRoute {
+                address: "10.0.0.1/32".to_string(),
+                description: None,
+            },
+        ]);
+        let state = AppState::new(config);
+        let results = vec![
+            result("wan_a", "8.8.8.8", true),
+            result("wan_a", "10.0.0.1/32", false),
+            result("wan_b", "10.0.0.1/32", true),
+        ];
+
+        // 只管理 managed_routes 中的地址，探测目标 8.8.8.8 不生成路由
+        let plan = build_route_plan(&state, "wan_a", &results);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].target, "1.0.1.0/24");
+        assert_eq!(plan[0].interface, "wan_a");
+        // 与关键探测目标地址相同的托管路由仍按唯一可达接口固定
+        assert_eq!(plan[1].target, "10.0.0.1/32");
+        assert_eq!(plan[1].interface, "wan_b");
+    }
+
+    #[test]
+    fn test_route_plan_respects_interface_family() {
+        let mut config = test_config();
+        config.interfaces[0].family = AddressFamily::V4;
+        config.managed_routes = Some(vec![
+            config::ManagedRoute {
+                address: "1.0.1.0/24".to_string(),
+                description: None,
+            },
+            config::ManagedRoute {
+                address: "2001:db8::/32".to_string(),
+                description: None,
+            },
+        ]);
+        let state = AppState::new(config);
+
+        // wan_a 只支持 IPv4，IPv6 路由改经双栈的 wan_b
+        let plan = build_route_plan(&state, "wan_a", &[]);
+        assert_eq!(plan[0].interface, "wan_a");
+        assert_eq!(plan[1].target, "2001:db8::/32");
+        assert_eq!(plan[1].interface, "wan_b");
+    }
+
+    fn score(interface: &str, value: f64) -> InterfaceScore {
+        InterfaceScore {
+            interface: interface.to_string(),
+            reachable_count: 1,
+            reachable_v4: 1,
+            reachable_v6: 0,
+            avg_latency_ms: 10.0,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: 0.0,
+            score: value,
+            eligibility: Eligibility::Eligible,
+        }
+    }
+
+    #[test]
+    fn test_score_smoothing_reduces_flapping() {
+        // wan_a 通常领先，第 3 轮出现一次偶发的评分下跌
+        let rounds = [80.0, 80.0, 40.0, 80.0, 80.0];
+        let smoothing = |alpha: f64| {
+            let mut config = test_config();
+            config.global.score_smoothing = alpha;
+            AppState::new(config)
+        };
+        let best_per_round = |alpha: f64| {
+            let state = smoothing(alpha);
+            rounds
+                .iter()
+                .map(|&a| {
+                    let mut scores = vec![score("wan_a", a), score("wan_b", 70.0)];
+                    sort_scores(&mut scores);
+                    apply_score_smoothing(&state, &mut scores);
+                    state
+                        .tester()
+                        .get_best_interface(&scores)
+                        .unwrap()
+                        .interface
+                        .clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        let flips = |best: &[String]| best.windows(2).filter(|w| w[0] != w[1]).count();
+
+        // 不平滑时跟随瞬时评分来回切换
+        assert_eq!(flips(&best_per_round(1.0)), 2);
+
+        // 平滑后单轮下跌只拉低移动平均（0.2 × 40 + 0.8 × 80 = 72），仍领先 wan_b
+        let state = smoothing(0.2);
+        let mut scores = vec![score("wan_a", 80.0)];
+        apply_score_smoothing(&state, &mut scores);
+        let mut scores = vec![score("wan_a", 40.0)];
+        apply_score_smoothing(&state, &mut scores);
+        assert!((scores[0].score - 72.0).abs() < 1e-9);
+        assert_eq!(flips(&best_per_round(0.2)), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failback_penalty_decays_over_window() {
+        let mut config = test_config();
+        config.global.failback_penalty = 20.0;
+        config.global.failback_penalty_window = 600;
+        let state = AppState::new(config);
+
+        // wan_a 刚被切走
+        state
+            .failed_over_at
+            .write()
+            .await
+            .insert("wan_a".to_string(), Instant::now());
+
+        let mut scores = vec![score("wan_a", 80.0), score("wan_b", 70.0)];
+        apply_failback_penalties(&state, &mut scores).await;
+        assert_eq!(scores[0].interface, "wan_b");
+        assert_eq!(scores[1].score, 60.0);
+
+        // 衰减到一半
+        tokio::time::advance(Duration::from_secs(300)).await;
+        let mut scores = vec![score("wan_a", 80.0), score("wan_b", 70.0)];
+        apply_failback_penalties(&state, &mut scores).await;
+        assert_eq!(scores[0].interface, "wan_a");
+        assert!((scores[0].score - 70.0).abs() < 1e-9);
+
+        // 窗口结束后不再扣分
+        tokio::time::advance(Duration::from_secs(300)).await;
+        let mut scores = vec![score("wan_a", 80.0), score("wan_b", 70.0)];
+        apply_failback_penalties(&state, &mut scores).await;
+        assert_eq!(scores[0].score, 80.0);
+
+        assert_eq!(failback_penalty(20.0, Duration::ZERO, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_virtual_interface_selects_best_member() {
+        let mut config = test_config();
+        config.interfaces.push(NetworkInterface {
+            name: "lte".to_string(),
+            ..config.interfaces[0].clone()
+        });
+        config.virtual_interfaces = vec![config::VirtualInterface {
+            name: "fiber".to_string(),
+            members: vec!["wan_a".to_string(), "wan_b".to_string()],
+            aggregation: config::VirtualAggregation::Average,
+        }];
+        let state = AppState::new(config);
+
+        // 单看物理接口 wan_b 最好，但光纤组平均分 (84 + 80) / 2 = 82 低于 LTE
+        let scores = vec![
+            score("wan_b", 84.0),
+            score("lte", 83.0),
+            score("wan_a", 80.0),
+        ];
+        assert_eq!(
+            state
+                .tester()
+                .get_best_interface(&scores)
+                .unwrap()
+                .interface,
+            "wan_b"
+        );
+        assert_eq!(
+            select_best_interface(&state, &scores).unwrap().interface,
+            "lte"
+        );
+
+        // 光纤组平均分 84 高于 LTE，选用组内评分最高的成员
+        let scores = vec![
+            score("wan_a", 85.0),
+            score("lte", 83.0),
+            score("wan_b", 83.0),
+        ];
+        assert_eq!(
+            select_best_interface(&state, &scores).unwrap().interface,
+            "wan_a"
+        );
+    }
+
+    #[test]
+    fn test_stable_interface_preferred_with_stability_weight() {
+        let mut config = test_config();
+        config.global.stability_weight = 1.0;
+        let state = AppState::new(config);
+
+        // 两个接口平均分都是 70，wan_a 波动大、wan_b 稳定
+        for (a, b) in [(90.0, 70.0), (50.0, 70.0), (90.0, 70.0)] {
+            let mut scores = vec![score("wan_a", a), score("wan_b", b)];
+            apply_stability_weighting(&state, &mut scores);
+        }
+
+        let mut scores = vec![score("wan_a", 70.0), score("wan_b", 70.0)];
+        sort_scores(&mut scores);
+        apply_stability_weighting(&state, &mut scores);
+        assert_eq!(scores[0].interface, "wan_b");
+        assert_eq!(scores[0].score, 70.0);
+        assert!(scores[1].score < 70.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_probes_standby_on_schedule() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.keepalive_interval = 30;
+        config.interfaces[0].keepalive = true;
+        config.interfaces[1].keepalive = true;
+        let mut state = AppState::new(config);
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        *state.tester.get_mut().unwrap() =
+            Arc::new(NetworkTester::new(5, 4).with_runner(runner.clone()));
+        set_current(&state, "wan_a").await;
+
+        let loop_task = tokio::spawn(run_keepalive_loop(Arc::new(state)));
+        // 第 0、30、60 秒各发送一次
+        sleep(Duration::from_secs(61)).await;
+        loop_task.abort();
+
+        // 当前活动接口不需要保活
+        assert_eq!(runner.count("ping -I wan_b -c 1"), 3);
+        assert_eq!(runner.count("ping -I wan_a"), 0);
+        assert!(runner.calls().iter().all(|c| c.ends_with(" 8.8.8.8")));
+    }
+
+    #[tokio::test]
+    async fn test_check_every_reuses_last_result() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.targets[1].check_every = 3;
+        let mut state = AppState::new(config);
+        let runner = Arc::new(MockRunner::new(|_| {
+            output(
+                0,
+                "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                 rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+            )
+        }));
+        *state.tester.get_mut().unwrap() =
+            Arc::new(NetworkTester::new(5, 4).with_runner(runner.clone()));
+        let config = state.config();
+        let interfaces: Vec<&NetworkInterface> = config.interfaces.iter().collect();
+
+        let mut probed = Vec::new();
+        let mut first_tested_at = None;
+        for iteration in 1..=7 {
+            let before = runner.count("ping -I wan_a -c 4");
+            let results = probe_interfaces(&state, &interfaces, iteration).await;
+            let slow: Vec<_> = results
+                .iter()
+                .filter(|r| r.target == "10.0.0.1/32")
+                .collect();
+            // 每轮都有两个接口的结果参与评分
+            assert_eq!(slow.len(), 2);
+
+            let pings = runner.count("ping -I wan_a -c 4") - before;
+            if pings == 2 {
+                probed.push(iteration);
+                first_tested_at = Some(slow[0].tested_at);
+            } else {
+                // 沿用上次探测的结果
+                assert_eq!(pings, 1);
+                assert_eq!(Some(slow[0].tested_at), first_tested_at);
+            }
+        }
+
+        assert_eq!(probed, vec![1, 4, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_functional_verify_failure_rolls_back() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.functional_verify = true;
+        let mut state = AppState::new(config);
+        // 路由配置验证通过（没有需要验证的静态路由），但经新路由 ping 不通
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("ip route get") {
+                output(0, "10.0.0.1 via 10.0.1.1 dev wan_b src 10.0.1.2\n")
+            } else if cmd.starts_with("ping") {
+                output(1, "")
+            } else {
+                output(0, "")
+            }
+        }));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(runner.clone()),
+        ));
+        set_current(&state, "wan_a").await;
+
+        let wan_b = state.config().interfaces[1].clone();
+        let mut manager = state.manager.write().await;
+        manager
+            .switch_to_interface(&wan_b, false, None)
+            .await
+            .unwrap();
+        let verification =
+            verify_after_switch(&state, &mut manager, &wan_b, Some("wan_a"), &[], &[]).await;
+
+        assert_eq!(verification, Verification::RolledBack);
+        assert_eq!(manager.current_interface(), Some("wan_a"));
+        // 探测的是关键目标
+        assert_eq!(runner.count("ip route get 10.0.0.1"), 1);
+        assert_eq!(runner.count("ping -c 3"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pre_switch_command_vetoes_switch() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.pre_switch_command = Some("backup-running && exit 1".to_string());
+        let mut state = AppState::new(config);
+        let manager_runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(manager_runner.clone()),
+        ));
+        let gate_runner = Arc::new(MockRunner::new(|_| output(1, "")));
+        state.runner = gate_runner.clone();
+        set_current(&state, "wan_a").await;
+
+        let wan_b = state.config().interfaces[1].clone();
+        assert!(execute_switch(&state, &wan_b, &[]).await.is_none());
+
+        // 检查命令得到切换信息，否决后不触碰路由
+        assert_eq!(
+            gate_runner.calls(),
+            vec![
+                "env ROUTES_MONITOR_EVENT=pre_switch ROUTES_MONITOR_FROM=wan_a \
+                 ROUTES_MONITOR_TO=wan_b sh -c backup-running && exit 1"
+            ]
+        );
+        assert!(manager_runner.calls().is_empty());
+        assert_eq!(
+            state.manager.read().await.current_interface(),
+            Some("wan_a")
+        );
+
+        // 命令通过时照常切换
+        state.runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        assert!(execute_switch(&state, &wan_b, &[]).await.is_some());
+        assert_eq!(
+            state.manager.read().await.current_interface(),
+            Some("wan_b")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_switch_command_failure_rolls_back() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.post_switch_command = Some("/usr/bin/app-health".to_string());
+        let mut state = AppState::new(config);
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(Arc::new(MockRunner::new(|_| output(0, "")))),
+        ));
+        let gate_runner = Arc::new(MockRunner::new(|_| output(2, "")));
+        state.runner = gate_runner.clone();
+        set_current(&state, "wan_a").await;
+
+        let wan_b = state.config().interfaces[1].clone();
+        assert!(execute_switch(&state, &wan_b, &[]).await.is_none());
+        assert_eq!(gate_runner.count("env ROUTES_MONITOR_EVENT=post_switch"), 1);
+        assert_eq!(
+            state.manager.read().await.current_interface(),
+            Some("wan_a")
+        );
+        // 回滚的接口按刚被切走处理
+        assert!(state.failed_over_at.read().await.contains_key("wan_b"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_verification_is_not_recorded_as_switch() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.post_switch_command = Some("/usr/bin/app-health".to_string());
+        let mut state = AppState::new(config);
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(Arc::new(MockRunner::new(|_| output(0, "")))),
+        ));
+        state.runner = Arc::new(MockRunner::new(|_| output(2, "")));
+        state
+            .failure_count
+            .write()
+            .await
+            .insert("wan_b".to_string(), 3);
+
+        // 启动后的首次切换没有可回滚的接口，验证失败即为 Failed
+        let wan_b = state.config().interfaces[1].clone();
+        assert!(execute_switch(&state, &wan_b, &[]).await.is_none());
+        assert!(state.last_switch_at.lock().unwrap().is_none());
+        assert!(state.last_switch_duration.read().await.is_none());
+        assert_eq!(state.failure_count.read().await.get("wan_b"), Some(&3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_duration_spans_switch_and_verify() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.manage_uci_routes = true;
+        config.global.functional_verify = true;
+        let mut state = AppState::new(config);
+        let runner = Arc::new(
+            MockRunner::new(|cmd| {
+                if cmd.starts_with("ip route") {
+                    output(0, "10.0.0.1 via 10.0.1.1 dev wan_b src 10.0.1.2\n")
+                } else {
+                    output(0, "")
+                }
+            })
+            .with_delay("/etc/init.d/network reload", Duration::from_secs(3))
+            .with_delay("ping", Duration::from_secs(2)),
+        );
+        state.manager = Arc::new(RwLock::new(OpenWrtManager::new().with_runner(runner)));
+        set_current(&state, "wan_a").await;
+
+        let wan_b = state.config().interfaces[1].clone();
+        let outcome = execute_switch(&state, &wan_b, &[]).await.unwrap();
+
+        // 重载 3 秒 + 等待路由生效 1 秒 + 功能验证 2 秒
+        assert_eq!(outcome.previous.as_deref(), Some("wan_a"));
+        assert_eq!(outcome.duration, Duration::from_secs(6));
+        assert_eq!(
+            *state.last_switch_duration.read().await,
+            Some(Duration::from_secs(6))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configs_run_independently() {
+        use crate::command::mock::{output, MockRunner};
+
+        // 两份配置各自的链路状况相反
+        let state_for = |name: &str, good: &'static str| {
+            let mut config = test_config();
+            config.global.failure_threshold = 1;
+            config.targets.truncate(1);
+            let mut state = AppState::new(config);
+            state.name = Some(name.to_string());
+            *state.tester.get_mut().unwrap() = Arc::new(NetworkTester::new(5, 4).with_runner(
+                Arc::new(MockRunner::new(move |cmd| {
+                    if cmd.contains(&format!("-I {} ", good)) {
+                        output(
+                            0,
+                            "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                             rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+                        )
+                    } else {
+                        output(1, "4 packets transmitted, 0 received, 100% packet loss\n")
+                    }
+                })),
+            ));
+            state.manager = Arc::new(RwLock::new(
+                OpenWrtManager::new().with_runner(Arc::new(MockRunner::new(|_| output(0, "")))),
+            ));
+            Arc::new(state)
+        };
+        let home = state_for("home", "wan_a");
+        let office = state_for("office", "wan_b");
+
+        let (home_summary, office_summary) = tokio::join!(
+            in_log_scope(&home, run_check_cycle(&home)),
+            in_log_scope(&office, run_check_cycle(&office)),
+        );
+        assert!(home_summary.unwrap().switched);
+        assert!(office_summary.unwrap().switched);
+
+        assert_eq!(home.manager.read().await.current_interface(), Some("wan_a"));
+        assert_eq!(
+            office.manager.read().await.current_interface(),
+            Some("wan_b")
+        );
+        assert_eq!(home.latest_scores.read().await[0].interface, "wan_a");
+        assert_eq!(office.latest_scores.read().await[0].interface, "wan_b");
+    }
+
+    #[test]
+    fn test_init_refuses_to_overwrite() {
+        let path =
+            std::env::temp_dir().join(format!("routes-monitor-init-{}.toml", std::process::id()));
+        std::fs::write(&path, "# 已有配置\n").unwrap();
+
+        let mut options = InitOptions {
+            path: Some(path.clone()),
+            force: false,
+        };
+        assert!(run_init(&options).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# 已有配置\n");
+
+        options.force = true;
+        run_init(&options).unwrap();
+        assert!(Config::from_file(&path).is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reload_config_applies_at_next_cycle() {
+        let path =
+            std::env::temp_dir().join(format!("routes-monitor-reload-{}.toml", std::process::id()));
+        let mut state = AppState::new(test_config());
+        state.config_path = Some(path.clone());
+
+        std::fs::write(
+            &path,
+            r#"
+            [[interfaces]]
+            name = "wan_c"
+            display_name = "C"
+            priority = 1
+            enabled = true
+
+            [[targets]]
+            address = "1.1.1.1"
+            description = "新目标"
+            weight = 1.0
+            "#,
+        )
+        .unwrap();
+        state.reload_config().unwrap();
+        // 新配置在下一轮检查开始时才替换
+        assert_eq!(state.config().interfaces.len(), 2);
+        state.apply_pending_config();
+        assert_eq!(state.config().interfaces[0].name, "wan_c");
+        assert_eq!(state.config().targets[0].address, "1.1.1.1");
+
+        // 校验失败时保留当前配置
+        std::fs::write(
+            &path,
+            "[[targets]]
+address = \"1.1.1.1\"\n",
+        )
+        .unwrap();
+        assert!(state.reload_config().is_err());
+        state.apply_pending_config();
+        assert_eq!(state.config().interfaces[0].name, "wan_c");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_dry_run_until_restart() {
+        use crate::command::mock::{output, MockRunner};
+
+        let path = std::env::temp_dir().join(format!(
+            "routes-monitor-reload-dry-run-{}.toml",
+            std::process::id()
+        ));
+        let mut config = test_config();
+        config.global.manage_uci_routes = true;
+        config.global.dry_run = true;
+        let mut state = AppState::new(config);
+        state.config_path = Some(path.clone());
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new()
+                .with_runner(runner.clone())
+                .with_dry_run(true),
+        ));
+        set_current(&state, "wan_a").await;
+        let before = runner.calls().len();
+
+        std::fs::write(
+            &path,
+            r#"
+            [global]
+            dry_run = false
+            manage_uci_routes = true
+
+            [[interfaces]]
+            name = "wan_a"
+            display_name = "A"
+            priority = 1
+            enabled = true
+
+            [[interfaces]]
+            name = "wan_b"
+            display_name = "B"
+            priority = 2
+            enabled = true
+
+            [[targets]]
+            address = "10.0.0.1/32"
+            description = "关键目标"
+            weight = 1.0
+            "#,
+        )
+        .unwrap();
+        state.reload_config().unwrap();
+        state.apply_pending_config();
+        std::fs::remove_file(&path).unwrap();
+
+        // 路由管理器按启动时的演练模式创建，重新加载不能关闭演练
+        assert!(state.config().global.dry_run);
+        manual_switch(&state, "wan_b").await.unwrap();
+        assert!(runner.calls()[before..]
+            .iter()
+            .all(|cmd| !cmd.starts_with("uci set") && !cmd.starts_with("uci commit")));
+        assert_eq!(
+            state.manager.read().await.current_interface(),
+            Some("wan_a")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_once_exit_status() {
+        use crate::command::mock::{output, MockRunner};
+
+        let state_for = |reachable: bool| {
+            let mut config = test_config();
+            config.targets.truncate(1);
+            let mut state = AppState::new(config);
+            *state.tester.get_mut().unwrap() = Arc::new(NetworkTester::new(5, 4).with_runner(
+                Arc::new(MockRunner::new(move |_| {
+                    if reachable {
+                        output(
+                            0,
+                            "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                             rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+                        )
+                    } else {
+                        output(1, "4 packets transmitted, 0 received, 100% packet loss\n")
+                    }
+                })),
+            ));
+            state.manager = Arc::new(RwLock::new(
+                OpenWrtManager::new().with_runner(Arc::new(MockRunner::new(|_| output(0, "")))),
+            ));
+            Arc::new(state)
+        };
+
+        // 一次检查即选定接口并切换，正常退出
+        let state = state_for(true);
+        assert!(run_once(std::slice::from_ref(&state)).await.is_ok());
+        assert!(state.manager.read().await.current_interface().is_some());
+        assert_eq!(state.check_count.load(Ordering::Relaxed), 1);
+
+        // 没有可达接口时返回错误（非零退出状态）
+        assert!(run_once(&[state_for(false)]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_check_is_read_only() {
+        use crate::command::mock::{output, MockRunner};
+
+        let state_for = |reachable: bool| {
+            let mut config = test_config();
+            config.global.manage_uci_routes = true;
+            config.targets.truncate(1);
+            let mut state = AppState::new(config);
+            *state.tester.get_mut().unwrap() = Arc::new(NetworkTester::new(5, 4).with_runner(
+                Arc::new(MockRunner::new(move |_| {
+                    if reachable {
+                        output(
+                            0,
+                            "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                             rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+                        )
+                    } else {
+                        output(1, "4 packets transmitted, 0 received, 100% packet loss\n")
+                    }
+                })),
+            ));
+            let manager_runner = Arc::new(MockRunner::new(|_| output(0, "")));
+            state.manager = Arc::new(RwLock::new(
+                OpenWrtManager::new().with_runner(manager_runner.clone()),
+            ));
+            (Arc::new(state), manager_runner)
+        };
+
+        // 只评分不切换：不执行任何路由命令，也不计入检查次数
+        let (state, manager_runner) = state_for(true);
+        assert!(run_check(std::slice::from_ref(&state)).await.is_ok());
+        assert!(manager_runner.calls().is_empty());
+        assert!(state.manager.read().await.current_interface().is_none());
+        assert_eq!(state.check_count.load(Ordering::Relaxed), 0);
+
+        // 没有可达接口时返回错误（非零退出状态）
+        let (state, manager_runner) = state_for(false);
+        assert!(run_check(&[state]).await.is_err());
+        assert!(manager_runner.calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drain_wait_releases_locks() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut state = AppState::new(test_config());
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new()
+                .with_runner(runner.clone())
+                .with_switch_style(config::SwitchStyle::Drain, Duration::from_secs(20)),
+        ));
+        set_current(&state, "wan_a").await;
+        let state = Arc::new(state);
+
+        let switch = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                let mut wan_b = state.config().interfaces[1].clone();
+                wan_b.gateway = Some("10.0.1.1".to_string());
+                execute_switch(&state, &wan_b, &[]).await.is_some()
+            }
+        });
+
+        // 排空等待期间 /status、控制套接字等仍可获取锁
+        sleep(Duration::from_secs(10)).await;
+        assert!(!switch.is_finished());
+        assert!(state.manager.try_read().is_ok());
+        assert!(state.switching.try_write().is_ok());
+        assert_eq!(runner.count("ip route del default dev wan_a"), 0);
+
+        assert!(switch.await.unwrap());
+        assert_eq!(runner.count("ip route del default dev wan_a"), 1);
+        assert_eq!(runner.count("ip route replace default via 10.0.1.1"), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_probing_waits_for_switch_to_settle() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.manage_uci_routes = true;
+        let mut state = AppState::new(config);
+        let manager_runner = Arc::new(
+            MockRunner::new(|_| output(0, ""))
+                .with_delay("/etc/init.d/network reload", Duration::from_secs(3)),
+        );
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(manager_runner),
+        ));
+        let tester_runner = Arc::new(MockRunner::new(|_| {
+            output(
+                0,
+                "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                 rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+            )
+        }));
+        *state.tester.get_mut().unwrap() =
+            Arc::new(NetworkTester::new(5, 4).with_runner(tester_runner.clone()));
+        let state = Arc::new(state);
+
+        let switch = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                let wan_b = state.config().interfaces[1].clone();
+                execute_switch(&state, &wan_b, &[]).await.is_some()
+            }
+        });
+        tokio::task::yield_now().await;
+
+        let probe = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                let config = state.config();
+                let interfaces: Vec<&NetworkInterface> = config.interfaces.iter().collect();
+                probe_interfaces(&state, &interfaces, 1).await.len()
+            }
+        });
+
+        // 重载（3 秒）和等待路由生效（1 秒）期间不开始探测
+        sleep(Duration::from_secs(2)).await;
+        assert!(!switch.is_finished());
+        assert_eq!(tester_runner.count("ping"), 0);
+
+        assert!(switch.await.unwrap());
+        assert!(probe.await.unwrap() > 0);
+        assert!(tester_runner.count("ping") > 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_verified_against_route_plan() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.manage_uci_routes = true;
+        let mut state = AppState::new(config);
+        // 切换到 wan_b 后，关键目标仍固定在唯一可达的 wan_a
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "uci show network" => output(
+                0,
+                "network.route_8_8_8_8=route\n\
+                 network.route_8_8_8_8.interface='wan_b'\n\
+                 network.route_8_8_8_8.target='8.8.8.8'\n\
+                 network.route_10_0_0_1_32=route\n\
+                 network.route_10_0_0_1_32.interface='wan_a'\n\
+                 network.route_10_0_0_1_32.target='10.0.0.1/32'\n",
+            ),
+            _ => output(0, ""),
+        }));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(runner.clone()),
+        ));
+        set_current(&state, "wan_a").await;
+
+        let results = vec![
+            result("wan_a", "8.8.8.8", true),
+            result("wan_b", "8.8.8.8", true),
+            result("wan_a", "10.0.0.1/32", true),
+            result("wan_b", "10.0.0.1/32", false),
+        ];
+        let wan_b = state.config().interfaces[1].clone();
+        let outcome = execute_switch(&state, &wan_b, &results).await;
+
+        assert!(outcome.is_some());
+        assert_eq!(
+            state.manager.read().await.current_interface(),
+            Some("wan_b")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_route_drift_detected_and_corrected() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.manage_uci_routes = true;
+        let mut state = AppState::new(config);
+        // 其他管理员把关键目标的路由改到了 wan_a
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "uci show network" => output(
+                0,
+                "network.route_10_0_0_1_32=route\n\
+                 network.route_10_0_0_1_32.interface='wan_a'\n\
+                 network.route_10_0_0_1_32.target='10.0.0.1/32'\n",
+            ),
+            _ if cmd.starts_with("ip route show") => {
+                output(0, "10.0.0.1 dev wan_b proto static scope link\n")
+            }
+            _ => output(0, ""),
+        }));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(runner.clone()),
+        ));
+
+        // 尚未设置过路由时不检查
+        assert!(correct_route_drift(&state).await.is_empty());
+        assert_eq!(runner.count("uci show network"), 0);
+
+        remember_routes(
+            &state,
+            &[RouteAssignment {
+                target: "10.0.0.1/32".to_string(),
+                interface: "wan_b".to_string(),
+            }],
+        );
+        let drift = correct_route_drift(&state).await;
+        assert_eq!(
+            drift,
+            vec![openwrt::RouteDrift {
+                target: "10.0.0.1/32".to_string(),
+                expected: "wan_b".to_string(),
+                actual: Some("wan_a".to_string()),
+            }]
+        );
+        assert_eq!(
+            runner.count("uci set network.route_10_0_0_1_32.interface=wan_b"),
+            1
+        );
+        assert_eq!(runner.count("uci commit network"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_switch_leaves_uci_untouched() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.manage_uci_routes = true;
+        config.global.dry_run = true;
+        let mut state = AppState::new(config);
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "uci show network" => output(
+                0,
+                "network.route_10_0_0_1_32=route\n\
+                 network.route_10_0_0_1_32.interface='wan_a'\n\
+                 network.route_10_0_0_1_32.target='10.0.0.1/32'\n",
+            ),
+            _ => output(0, ""),
+        }));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new()
+                .with_runner(runner.clone())
+                .with_dry_run(true),
+        ));
+        set_current(&state, "wan_a").await;
+        let before = runner.calls().len();
+
+        // 一条已有路由需要改写、一条需要新建，都只记录不执行
+        let wan_b = state.config().interfaces[1].clone();
+        let results = vec![
+            result("wan_b", "8.8.8.8", true),
+            result("wan_b", "10.0.0.1/32", true),
+        ];
+        log_dry_run_switch(&state, &wan_b, &results).await;
+
+        assert_eq!(runner.calls()[before..], ["uci show network"]);
+        assert_eq!(
+            state.manager.read().await.current_interface(),
+            Some("wan_a")
+        );
+
+        // 演练中的路由不作为外部修改的检测依据
+        remember_routes(&state, &build_route_plan(&state, "wan_b", &results));
+        assert!(correct_route_drift(&state).await.is_empty());
+    }
+
+    async fn set_current(state: &AppState, name: &str) {
+        let interface = state
+            .config()
+            .interfaces
+            .iter()
+            .find(|i| i.name == name)
+            .unwrap()
+            .clone();
+        state
+            .manager
+            .write()
+            .await
+            .switch_to_interface(&interface, false, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_switch_decision_rationale() {
+        let mut config = test_config();
+        config.global.failure_threshold = 2;
+        let state = AppState::new(config);
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+
+        // 没有活动接口时直接切换
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::NoActiveInterface);
+        assert!(decision.should_switch());
+
+        // 第一次非最佳：未达到阈值
+        set_current(&state, "wan_a").await;
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.current.as_deref(), Some("wan_a"));
+        assert_eq!(decision.current_score, Some(70.0));
+        assert_eq!(decision.best_score, 90.0);
+        assert_eq!(decision.margin, Some(20.0));
+        assert_eq!(decision.outcome, DecisionOutcome::Blocked);
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::ThresholdNotMet {
+                failures: 1,
+                threshold: 2
+            }]
+        );
+        assert!(!decision.should_switch());
+
+        // 第二次非最佳：达到阈值，切换
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.failures, 2);
+        assert_eq!(decision.outcome, DecisionOutcome::ThresholdReached);
+        assert!(decision.gates.is_empty());
+        assert!(decision.should_switch());
+
+        // 当前接口已是最佳
+        let decision = should_switch_interface(&state, &scores[1], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::AlreadyBest);
+        assert!(!decision.should_switch());
+    }
+
+    #[tokio::test]
+    async fn test_emergency_switch_when_current_hard_down() {
+        let mut config = test_config();
+        config.global.failure_threshold = 5;
+        config.global.failback_penalty_window = 600;
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        // wan_b 刚被切走，本应处于回切惩罚期
+        state
+            .failed_over_at
+            .write()
+            .await
+            .insert("wan_b".to_string(), Instant::now());
+
+        let mut down = score("wan_a", 0.0);
+        down.reachable_count = 0;
+        let scores = vec![score("wan_b", 60.0), down];
+        assert!(current_hard_down(Some("wan_a"), &scores));
+        assert!(!current_hard_down(Some("wan_b"), &scores));
+
+        // 第一次检查就切换，不等待失败阈值
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::Emergency);
+        assert!(decision.gates.is_empty());
+        assert!(decision.should_switch());
+
+        // 没有可用接口时不切换
+        let mut also_down = score("wan_b", 0.0);
+        also_down.reachable_count = 0;
+        let decision = should_switch_interface(&state, &also_down, &scores)
+            .await
+            .unwrap();
+        assert_ne!(decision.outcome, DecisionOutcome::Emergency);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_interface_holds_unless_unreachable() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.pinned_interface = Some("wan_a".to_string());
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        // 固定接口仍可达时不切换到评分更高的接口
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 40.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::Pinned {
+                interface: "wan_a".to_string()
+            }]
+        );
+        assert!(!decision.should_switch());
+
+        // 固定接口完全不可达时按评分正常切换
+        let mut down = score("wan_a", 0.0);
+        down.reachable_count = 0;
+        let scores = vec![score("wan_b", 90.0), down];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::Emergency);
+        assert!(decision.should_switch());
+
+        // 取消固定后恢复正常选择
+        pin_interface(&state, None).await.unwrap();
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 40.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+        assert!(pin_interface(&state, Some("wan_x")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_switch_decision_auto_switch_disabled() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.auto_switch = false;
+        let state = AppState::new(config);
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        set_current(&state, "wan_a").await;
+
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::ThresholdReached);
+        assert_eq!(decision.gates, vec![SwitchGate::AutoSwitchDisabled]);
+        assert!(!decision.should_switch());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_rate_cap_suppresses_and_releases() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.max_switches_per_hour = 2;
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        // 连续两次切换不受限制
+        for (from, to) in [("wan_a", "wan_b"), ("wan_b", "wan_a")] {
+            let scores = vec![score(to, 90.0), score(from, 70.0)];
+            let decision = should_switch_interface(&state, &scores[0], &scores)
+                .await
+                .unwrap();
+            assert!(decision.should_switch());
+            set_current(&state, to).await;
+            state.switch_rate.lock().unwrap().record(Instant::now());
+            tokio::time::advance(Duration::from_secs(60)).await;
+        }
+
+        // 第三次被阻止
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(!decision.should_switch());
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::RateLimited {
+                switches: 2,
+                limit: 2,
+                retry_after_secs: 3480,
+            }]
+        );
+
+        // 当前接口完全断开时的紧急切换不受限频限制
+        let mut down = score("wan_a", 0.0);
+        down.reachable_count = 0;
+        let scores = vec![score("wan_b", 90.0), down];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::Emergency);
+        assert!(decision.should_switch());
+
+        // 最早的切换移出一小时窗口后解除
+        tokio::time::advance(Duration::from_secs(3480)).await;
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_emergency_switch_bypasses_gates() {
+        let mut config = test_config();
+        config.global.failure_threshold = 5;
+        config.global.switch_cooldown = 300;
+        config.global.max_switches_per_hour = 1;
+        config.global.freeze_windows = vec!["* * * * * *".to_string()];
+        config.global.pinned_interface = Some("wan_c".to_string());
+        config.interfaces.push(config::NetworkInterface {
+            name: "wan_c".to_string(),
+            ..config.interfaces[1].clone()
+        });
+        let gated = |config: Config| async {
+            let state = AppState::new(config);
+            set_current(&state, "wan_a").await;
+            *state.last_switch_at.lock().unwrap() = Some(Instant::now());
+            state.switch_rate.lock().unwrap().record(Instant::now());
+            state
+        };
+
+        // 冻结窗口、固定的其他接口、冷却、限频和失败阈值同时生效，当前接口完全断开时仍立即切换
+        let state = gated(config.clone()).await;
+        let mut down = score("wan_a", 0.0);
+        down.reachable_count = 0;
+        let scores = vec![score("wan_b", 90.0), score("wan_c", 80.0), down];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::Emergency);
+        assert!(decision.gates.is_empty());
+        assert!(decision.should_switch());
+
+        // 关闭自动切换仍然阻止紧急切换
+        config.global.auto_switch = false;
+        let state = gated(config).await;
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::Emergency);
+        assert_eq!(decision.gates, vec![SwitchGate::AutoSwitchDisabled]);
+        assert!(!decision.should_switch());
+    }
+
+    #[tokio::test]
+    async fn test_per_interface_failure_threshold() {
+        let mut config = test_config();
+        // 光纤 wan_a 出问题立即切走，昂贵的 LTE wan_b 接管后不急于让出
+        config.interfaces[0].failure_threshold = Some(1);
+        config.interfaces[1].failure_threshold = Some(5);
+        config.interfaces.push(NetworkInterface {
+            name: "wan_c".to_string(),
+            failure_threshold: None,
+            ..config.interfaces[0].clone()
+        });
+        let state = AppState::new(config);
+
+        set_current(&state, "wan_a").await;
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.failure_threshold, 1);
+        assert!(decision.should_switch());
+
+        set_current(&state, "wan_b").await;
+        let scores = vec![score("wan_a", 90.0), score("wan_b", 70.0)];
+        for failures in 1..5 {
+            let decision = should_switch_interface(&state, &scores[0], &scores)
+                .await
+                .unwrap();
+            assert_eq!(decision.failure_threshold, 5);
+            assert_eq!(decision.failures, failures);
+            assert!(!decision.should_switch());
+        }
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+
+        // 未配置的接口使用全局阈值
+        set_current(&state, "wan_c").await;
+        let scores = vec![score("wan_a", 90.0), score("wan_c", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.failure_threshold, 3);
+        assert!(!decision.should_switch());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_cooldown_blocks_rapid_reswitch() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.switch_cooldown = 300;
+        let state = AppState::new(config);
+        set_current(&state, "wan_b").await;
+        *state.last_switch_at.lock().unwrap() = Some(Instant::now());
+
+        // 冷却期内不按评分切回
+        tokio::time::advance(Duration::from_secs(60)).await;
+        let scores = vec![score("wan_a", 90.0), score("wan_b", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::ThresholdReached);
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::Cooldown {
+                remaining_secs: 240
+            }]
+        );
+        assert!(!decision.should_switch());
+
+        // 当前接口完全断开时不受冷却限制
+        let mut down = score("wan_b", 0.0);
+        down.reachable_count = 0;
+        let down_scores = vec![score("wan_a", 90.0), down];
+        let decision = should_switch_interface(&state, &down_scores[0], &down_scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+
+        // 冷却结束后正常切换
+        tokio::time::advance(Duration::from_secs(240)).await;
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+    }
+
+    #[tokio::test]
+    async fn test_switch_margin_prevents_flapping() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.switch_margin = 5.0;
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        // 两个接口分数在门槛内交替领先，始终不切换
+        for (a, b) in [(80.0, 84.0), (83.0, 80.0), (80.0, 84.9)] {
+            let scores = vec![score("wan_a", a), score("wan_b", b)];
+            let best = scores
+                .iter()
+                .max_by(|x, y| x.score.total_cmp(&y.score))
+                .unwrap();
+            let decision = should_switch_interface(&state, best, &scores)
+                .await
+                .unwrap();
+            assert_eq!(decision.outcome, DecisionOutcome::AlreadyBest);
+            assert!(!decision.should_switch());
+        }
+        assert_eq!(state.failure_count.read().await.get("wan_a"), Some(&0));
+
+        // 领先超过门槛后按阈值切换
+        let scores = vec![score("wan_b", 86.0), score("wan_a", 80.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+    }
+
+    #[tokio::test]
+    async fn test_ineligible_incumbent_does_not_block_switch() {
+        // 当前接口可达比例不足，即使分数更高也应切换到可参与选择的接口
+        let mut current = score("wan_a", 90.0);
+        current.eligibility = Eligibility::Ineligible(IneligibleReason::LowReachability {
+            reachable: 1,
+            total: 4,
+        });
+        let scores = vec![current, score("wan_b", 70.0)];
+
+        for switch_margin in [0.0, 5.0] {
+            let mut config = test_config();
+            config.global.failure_threshold = 1;
+            config.global.switch_margin = switch_margin;
+            let state = AppState::new(config);
+            set_current(&state, "wan_a").await;
+
+            let decision = should_switch_interface(&state, &scores[1], &scores)
+                .await
+                .unwrap();
+            assert_eq!(decision.margin, Some(-20.0));
+            assert_eq!(decision.outcome, DecisionOutcome::ThresholdReached);
+            assert!(decision.should_switch());
+            assert_eq!(state.failure_count.read().await.get("wan_a"), Some(&1));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failback_margin_decays_after_failover() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.failback_margin = 40.0;
+        config.global.failback_margin_window = 3600;
+        let state = AppState::new(config);
+        set_current(&state, "wan_b").await;
+
+        // 刚从 wan_a 切走，领先 20 分不足以回切
+        state
+            .failed_over_at
+            .write()
+            .await
+            .insert("wan_a".to_string(), Instant::now());
+        let scores = vec![score("wan_a", 90.0), score("wan_b", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(!decision.should_switch());
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::FailbackMargin {
+                margin: 20.0,
+                required: 40.0,
+            }]
+        );
+
+        // 半小时后门槛衰减到 20 分
+        tokio::time::advance(Duration::from_secs(1800)).await;
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+
+        // 衰减窗口结束后很小的领先也可回切
+        tokio::time::advance(Duration::from_secs(1800)).await;
+        let scores = vec![score("wan_a", 71.0), score("wan_b", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+
+        // 其他接口不受回切门槛限制
+        let scores = vec![score("lte", 71.0), score("wan_b", 70.0)];
+        state
+            .failed_over_at
+            .write()
+            .await
+            .insert("wan_a".to_string(), Instant::now());
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+    }
+
+    #[tokio::test]
+    async fn test_balance_respects_pin_and_freeze_window() {
+        use crate::command::mock::{output, MockRunner};
+
+        let mut config = test_config();
+        config.global.mode = RoutingMode::Balance;
+        config.global.balance_threshold = 20.0;
+        for (interface, gateway) in config.interfaces.iter_mut().zip(["10.0.0.1", "10.0.1.1"]) {
+            interface.gateway = Some(gateway.to_string());
+        }
+        let mut state = AppState::new(config);
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(runner.clone()),
+        ));
+        let scores = vec![score("wan_a", 90.0), score("wan_b", 80.0)];
+
+        assert!(apply_balance(&state, &scores[0], &scores).await);
+        assert!(state.manager.read().await.ecmp_active());
+
+        // 冻结窗口内不改写多路径路由
+        *state.freeze_windows.write().unwrap() = vec![(
+            "* * * * * *".to_string(),
+            decision::parse_freeze_window("* * * * * *").unwrap(),
+        )];
+        let shifted = vec![score("wan_a", 90.0), score("wan_b", 72.0)];
+        assert!(apply_balance(&state, &shifted[0], &shifted).await);
+        state.freeze_windows.write().unwrap().clear();
+
+        // 固定接口后只经固定接口出口
+        *state.pinned_interface.lock().unwrap() = Some("wan_b".to_string());
+        assert!(!apply_balance(&state, &scores[0], &scores).await);
+        assert!(!state.manager.read().await.ecmp_active());
+
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "ip route replace default nexthop via 10.0.0.1 dev wan_a weight 9 \
+                 nexthop via 10.0.1.1 dev wan_b weight 8",
+                "ip route replace default via 10.0.1.1 dev wan_b",
+            ]
+        );
+    }
+
+    /// 按当前决策标记被阻止的最佳接口，返回其不可选原因
+    async fn blocked_reason(state: &AppState) -> Option<IneligibleReason> {
+        let mut scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        let decision = should_switch_interface(state, &scores[0], &scores)
+            .await
+            .unwrap();
+        decision.mark_blocked_best(&mut scores);
+        match &scores[0].eligibility {
+            Eligibility::Ineligible(reason) => Some(reason.clone()),
+            Eligibility::Eligible => None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ineligible_reason_cooldown() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.switch_cooldown = 300;
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+        *state.last_switch_at.lock().unwrap() = Some(Instant::now());
+        tokio::time::advance(Duration::from_secs(100)).await;
+
+        assert_eq!(
+            blocked_reason(&state).await,
+            Some(IneligibleReason::Cooldown {
+                remaining_secs: 200
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ineligible_reason_pinned() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.pinned_interface = Some("wan_a".to_string());
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        assert_eq!(
+            blocked_reason(&state).await,
+            Some(IneligibleReason::Pinned {
+                interface: "wan_a".to_string()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ineligible_reason_freeze_window() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.freeze_windows = vec!["* * * * * *".to_string()];
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        assert_eq!(
+            blocked_reason(&state).await,
+            Some(IneligibleReason::FreezeWindow {
+                window: "* * * * * *".to_string()
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ineligible_reason_rate_limited() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.max_switches_per_hour = 1;
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+        state.switch_rate.lock().unwrap().record(Instant::now());
+        tokio::time::advance(Duration::from_secs(600)).await;
+
+        assert_eq!(
+            blocked_reason(&state).await,
+            Some(IneligibleReason::RateLimited {
+                retry_after_secs: 3000
+            })
+        );
+
+        // 未被阻止时最佳接口保持可选
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+        assert_eq!(blocked_reason(&state).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_switch_suppressed_in_freeze_window() {
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.freeze_windows = vec!["* * * * * *".to_string()];
+        let state = AppState::new(config);
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        set_current(&state, "wan_a").await;
+
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(decision.outcome, DecisionOutcome::ThresholdReached);
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::FreezeWindow {
+                window: "* * * * * *".to_string()
+            }]
+        );
+        assert!(!decision.should_switch());
+
+        // 窗口不匹配时正常切换
+        let mut config = test_config();
+        config.global.failure_threshold = 1;
+        config.global.freeze_windows = vec!["* * * * * * 2099".to_string()];
+        let state = AppState::new(config);
+        set_current(&state, "wan_a").await;
+
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert!(decision.should_switch());
+    }
+
+    #[test]
+    fn test_new_interface_waits_for_probation() {
+        let mut config = test_config();
+        config.global.new_interface_probation = 2;
+        let state = AppState::new(config);
+
+        // 启动时只有 wan_a
+        let mut scores = vec![score("wan_a", 50.0)];
+        apply_probation(&state, &mut scores);
+        assert!(scores[0].is_eligible());
+
+        // wan_b 新出现且评分更高，观察期内不能被选中
+        for remaining in [2, 1] {
+            let mut scores = vec![score("wan_b", 90.0), score("wan_a", 50.0)];
+            apply_probation(&state, &mut scores);
+            assert_eq!(
+                scores[0].eligibility,
+                Eligibility::Ineligible(IneligibleReason::Probation { remaining })
+            );
+            let best = state.tester().get_best_interface(&scores).unwrap();
+            assert_eq!(best.interface, "wan_a");
+        }
+
+        let mut scores = vec![score("wan_b", 90.0), score("wan_a", 50.0)];
+        apply_probation(&state, &mut scores);
+        let best = state.tester().get_best_interface(&scores).unwrap();
+        assert_eq!(best.interface, "wan_b");
+    }
+
+    #[test]
+    fn test_disabled_interface_probed_as_standby() {
+        let mut config = test_config();
+        config.interfaces[1].enabled = false;
+        assert!(standby_interfaces(&config).is_empty());
+
+        config.global.probe_disabled_interfaces = true;
+        let state = AppState::new(config);
+        let standby: Vec<_> = standby_interfaces(&state.config())
+            .iter()
+            .map(|i| i.name.clone())
+            .collect();
+        assert_eq!(standby, vec!["wan_b"]);
+
+        // 备用接口评分更高也不会被选中
+        let mut slow = result("wan_a", "8.8.8.8", true);
+        slow.latency_ms = Some(80.0);
+        let standby_scores = mark_standby(
+            state
+                .tester()
+                .calculate_scores(&[result("wan_b", "8.8.8.8", true)]),
+        );
+        let mut scores = state.tester().calculate_scores(&[slow]);
+        scores.extend(standby_scores);
+        sort_scores(&mut scores);
+
+        assert_eq!(scores[0].interface, "wan_b");
+        assert_eq!(
+            scores[0].eligibility,
+            Eligibility::Ineligible(IneligibleReason::Standby)
+        );
+        assert!(scores[0].score > 0.0);
+        let best = state.tester().get_best_interface(&scores).unwrap();
+        assert_eq!(best.interface, "wan_a");
+
+        let only_standby = &scores[..1];
+        assert!(state.tester().get_best_interface(only_standby).is_none());
+    }
+
+    #[test]
+    fn test_universally_down_target_excluded() {
+        let mut config = test_config();
+        config.global.ignore_universally_down_targets = true;
+        let state = AppState::new(config);
+
+        let mut slow = result("wan_b", "8.8.8.8", true);
+        slow.latency_ms = Some(50.0);
+        let results = vec![
+            result("wan_a", "8.8.8.8", true),
+            slow,
+            result("wan_a", "10.0.0.1/32", false),
+            result("wan_b", "10.0.0.1/32", false),
+        ];
+        assert_eq!(
+            network::universally_unreachable_targets(&results),
+            vec!["10.0.0.1/32"]
+        );
+
+        let before = state.tester().calculate_scores(&results);
+        state.dead_targets.lock().unwrap().record(&results);
+        let filtered = exclude_universally_down_targets(&state, results);
+        assert!(filtered.iter().all(|r| r.target == "8.8.8.8"));
+
+        let after = state.tester().calculate_scores(&filtered);
+        let order = |scores: &[InterfaceScore]| {
+            scores
+                .iter()
+                .map(|s| s.interface.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&before), order(&after));
+        assert!(after[0].score > before[0].score);
+        assert_eq!(state.dead_targets.lock().unwrap().streak("10.0.0.1/32"), 1);
+    }
+
+    #[test]
+    fn test_confirmation_keeps_interface_usable() {
+        let state = AppState::new(test_config());
+        let results = vec![
+            result("wan_a", "8.8.8.8", false),
+            result("wan_a", "10.0.0.1/32", false),
+            result("wan_b", "8.8.8.8", false),
+            result("wan_b", "10.0.0.1/32", false),
+        ];
+        // wan_a 的确认目标可达，wan_b 的确认目标也失败
+        let confirmations = vec![
+            result("wan_a", "1.1.1.1", true),
+            result("wan_a", "9.9.9.9", false),
+            result("wan_b", "1.1.1.1", false),
+            result("wan_b", "9.9.9.9", false),
+        ];
+
+        assert_eq!(
+            network::unreachable_interfaces(&results),
+            vec!["wan_a", "wan_b"]
+        );
+
+        let merged = network::merge_confirmations(&results, &confirmations);
+        assert_eq!(network::unreachable_interfaces(&merged), vec!["wan_b"]);
+
+        let scores = state.tester().calculate_scores(&merged);
+        assert_eq!(scores[0].interface, "wan_a");
+        assert_eq!(scores[0].reachable_count, 1);
+        assert!(scores[0].score > 0.0);
+        assert_eq!(scores[1].score, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_uci_disabled_interface_excluded() {
+        use command::mock::{output, MockRunner};
+
+        let mut state = AppState::new(test_config());
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "uci -q get network.wan_b.disabled" => output(0, "1\n"),
+            _ => output(1, ""),
+        }));
+        state.manager = Arc::new(RwLock::new(
+            OpenWrtManager::new().with_runner(runner.clone()),
+        ));
+
+        let config = state.config();
+        let interfaces = exclude_uci_disabled(&state, config.sorted_interfaces()).await;
+        let names: Vec<_> = interfaces.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["wan_a"]);
+        assert_eq!(runner.count("uci -q get"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_panic_in_check_is_recovered() {
+        let result: Result<()> = catch_panic(async {
+            let mut scores = vec![score("wan_a", 50.0), score("wan_b", 60.0)];
+            sort_scores(&mut scores);
+            panic!("模拟检查中的 panic");
+        })
+        .await;
+        assert!(result.is_err());
+
+        // 任务 panic 后仍可继续执行下一轮
+        let result = catch_panic(async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_poisoned_state_locks_are_recovered() {
+        let state = Arc::new(AppState::new(test_config()));
+        *state.pinned_interface.lock().unwrap() = Some("wan_a".to_string());
+
+        // 持有锁时发生 panic 会使锁中毒
+        let holder = Arc::clone(&state);
+        let result = std::thread::spawn(move || {
+            let _config = holder.config.write().unwrap();
+            let _pinned = holder.pinned_interface.lock().unwrap();
+            let _history = holder.history.lock().unwrap();
+            panic!("模拟持锁时的 panic");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(state.config.is_poisoned());
+        assert!(state.pinned_interface.is_poisoned());
+
+        // 之后的读写仍可继续，不会连带 panic
+        assert!(state.config().global.auto_switch);
+        let scores = vec![score("wan_b", 90.0), score("wan_a", 70.0)];
+        let decision = should_switch_interface(&state, &scores[0], &scores)
+            .await
+            .unwrap();
+        assert_eq!(
+            decision.gates,
+            vec![SwitchGate::Pinned {
+                interface: "wan_a".to_string()
+            }]
+        );
+        pin_interface(&state, None).await.unwrap();
+        state.apply_pending_config();
+    }
+
+    #[test]
+    fn test_sort_scores_with_nan_is_deterministic() {
+        let mut first = vec![
+            score("wan_a", 50.0),
+            score("wan_b", f64::NAN),
+            score("wan_c", 80.0),
+        ];
+        let mut second = vec![
+            score("wan_c", 80.0),
+            score("wan_a", 50.0),
+            score("wan_b", f64::NAN),
+        ];
+        sort_scores(&mut first);
+        sort_scores(&mut second);
+
+        let names = |scores: &[InterfaceScore]| {
+            scores
+                .iter()
+                .map(|s| s.interface.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&first), names(&second));
+        assert_eq!(names(&first), ["wan_c", "wan_a", "wan_b"]);
+    }
 
     #[test]
     fn test_config_path_priority() {
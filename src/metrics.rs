@@ -0,0 +1,379 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! Prometheus 文本格式的指标输出
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::network::{Eligibility, InterfaceScore, TestResult};
+
+/// 转义标签值中的反斜杠、双引号和换行
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// 写入一个指标族的 HELP/TYPE 头
+fn header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+/// 渲染指标
+///
+/// `targets` 为最近一次各 (接口, 目标) 的探测结果，为 `None` 时不输出逐目标指标；
+/// `switch_duration` 为最近一次切换的耗时，尚未切换过时不输出
+pub fn render(
+    scores: &[InterfaceScore],
+    active: Option<&str>,
+    targets: Option<&[TestResult]>,
+    switch_duration: Option<Duration>,
+) -> String {
+    let mut out = String::new();
+
+    header(&mut out, "routes_monitor_interface_score", "接口综合评分");
+    for score in scores {
+        let _ = writeln!(
+            out,
+            "routes_monitor_interface_score{{interface=\"{}\"}} {}",
+            escape_label(&score.interface),
+            score.score
+        );
+    }
+
+    header(
+        &mut out,
+        "routes_monitor_interface_reachable_targets",
+        "接口可达的目标数量",
+    );
+    for score in scores {
+        let _ = writeln!(
+            out,
+            "routes_monitor_interface_reachable_targets{{interface=\"{}\"}} {}",
+            escape_label(&score.interface),
+            score.reachable_count
+        );
+    }
+
+    header(
+        &mut out,
+        "routes_monitor_interface_reachable_targets_by_family",
+        "接口按地址族统计的可达目标数量（不含域名目标）",
+    );
+    for score in scores {
+        for (family, count) in [("ipv4", score.reachable_v4), ("ipv6", score.reachable_v6)] {
+            let _ = writeln!(
+                out,
+                "routes_monitor_interface_reachable_targets_by_family{{interface=\"{}\",family=\"{}\"}} {}",
+                escape_label(&score.interface),
+                family,
+                count
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "routes_monitor_interface_latency_ms",
+        "接口平均延迟（毫秒）",
+    );
+    for score in scores.iter().filter(|s| s.avg_latency_ms.is_finite()) {
+        let _ = writeln!(
+            out,
+            "routes_monitor_interface_latency_ms{{interface=\"{}\"}} {}",
+            escape_label(&score.interface),
+            score.avg_latency_ms
+        );
+    }
+
+    header(
+        &mut out,
+        "routes_monitor_interface_packet_loss",
+        "接口平均丢包率（0-1）",
+    );
+    for score in scores {
+        let _ = writeln!(
+            out,
+            "routes_monitor_interface_packet_loss{{interface=\"{}\"}} {}",
+            escape_label(&score.interface),
+            score.avg_packet_loss
+        );
+    }
+
+    header(
+        &mut out,
+        "routes_monitor_interface_eligible",
+        "接口能否参与选择（1 为可以，reason 为不能参与的原因）",
+    );
+    for score in scores {
+        let reason = match &score.eligibility {
+            Eligibility::Eligible => "",
+            Eligibility::Ineligible(reason) => reason.key(),
+        };
+        let _ = writeln!(
+            out,
+            "routes_monitor_interface_eligible{{interface=\"{}\",reason=\"{}\"}} {}",
+            escape_label(&score.interface),
+            reason,
+            u8::from(score.is_eligible())
+        );
+    }
+
+    header(
+        &mut out,
+        "routes_monitor_active_interface",
+        "当前活动接口（1 为活动）",
+    );
+    for score in scores {
+        let _ = writeln!(
+            out,
+            "routes_monitor_active_interface{{interface=\"{}\"}} {}",
+            escape_label(&score.interface),
+            u8::from(active == Some(score.interface.as_str()))
+        );
+    }
+
+    if let Some(duration) = switch_duration {
+        header(
+            &mut out,
+            "routes_monitor_switch_duration_seconds",
+            "最近一次切换从决定切换到验证完成的耗时（秒）",
+        );
+        let _ = writeln!(
+            out,
+            "routes_monitor_switch_duration_seconds {}",
+            duration.as_secs_f64()
+        );
+    }
+
+    if let Some(results) = targets {
+        header(
+            &mut out,
+            "routes_monitor_target_reachable",
+            "目标经该接口是否可达（1 为可达）",
+        );
+        for result in results {
+            let _ = writeln!(
+                out,
+                "routes_monitor_target_reachable{{interface=\"{}\",target=\"{}\"}} {}",
+                escape_label(&result.interface),
+                escape_label(&result.target),
+                u8::from(result.reachable)
+            );
+        }
+
+        header(
+            &mut out,
+            "routes_monitor_target_latency_ms",
+            "目标经该接口的平均延迟（毫秒）",
+        );
+        for result in results {
+            if let Some(latency) = result.latency_ms {
+                let _ = writeln!(
+                    out,
+                    "routes_monitor_target_latency_ms{{interface=\"{}\",target=\"{}\"}} {}",
+                    escape_label(&result.interface),
+                    escape_label(&result.target),
+                    latency
+                );
+            }
+        }
+    }
+
+    out
+}
+
+/// 为每个样本添加 `config` 标签（同时运行多个配置时区分来源）
+pub fn with_config_label(text: &str, config: &str) -> String {
+    let label = format!("config=\"{}\"", escape_label(config));
+    let mut out = String::new();
+    for line in text.lines() {
+        if line.starts_with('#') {
+            out.push_str(line);
+        } else if let Some((name, rest)) = line.split_once('{') {
+            let _ = write!(out, "{}{{{},{}", name, label, rest);
+        } else if let Some((name, value)) = line.split_once(' ') {
+            let _ = write!(out, "{}{{{}}} {}", name, label, value);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// 合并多份指标文本：同名指标族的 HELP/TYPE 只保留一次，样本按指标族归并
+pub fn merge(texts: &[String]) -> String {
+    let mut families: Vec<(String, Vec<&str>, Vec<&str>)> = Vec::new();
+
+    for text in texts {
+        for line in text.lines() {
+            let family = match line
+                .strip_prefix("# HELP ")
+                .or(line.strip_prefix("# TYPE "))
+            {
+                Some(rest) => rest.split(' ').next().unwrap_or_default(),
+                None => line.split(['{', ' ']).next().unwrap_or_default(),
+            };
+            let index = match families.iter().position(|(name, _, _)| name == family) {
+                Some(index) => index,
+                None => {
+                    families.push((family.to_string(), Vec::new(), Vec::new()));
+                    families.len() - 1
+                }
+            };
+
+            let (_, headers, samples) = &mut families[index];
+            if line.starts_with('#') {
+                if !headers.contains(&line) {
+                    headers.push(line);
+                }
+            } else {
+                samples.push(line);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (_, headers, samples) in families {
+        for line in headers.into_iter().chain(samples) {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::IneligibleReason;
+
+    fn score(interface: &str, value: f64) -> InterfaceScore {
+        InterfaceScore {
+            interface: interface.to_string(),
+            reachable_count: 1,
+            reachable_v4: 1,
+            reachable_v6: 0,
+            avg_latency_ms: 12.5,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: 0.0,
+            score: value,
+            eligibility: Eligibility::Eligible,
+        }
+    }
+
+    fn result(interface: &str, target: &str, latency: Option<f64>) -> TestResult {
+        TestResult {
+            interface: interface.to_string(),
+            target: target.to_string(),
+            resolved_address: None,
+            reachable: latency.is_some(),
+            latency_ms: latency,
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(if latency.is_some() { 0.0 } else { 1.0 }),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_per_target_metrics() {
+        let scores = vec![score("wan_a", 80.0), score("wan_b", 60.0)];
+        let results = vec![
+            result("wan_a", "8.8.8.8", Some(10.0)),
+            result("wan_b", "8.8.8.8", None),
+        ];
+
+        let text = render(&scores, Some("wan_a"), Some(&results), None);
+        assert!(text.contains("routes_monitor_interface_score{interface=\"wan_a\"} 80"));
+        assert!(text.contains("routes_monitor_active_interface{interface=\"wan_a\"} 1"));
+        assert!(text.contains(
+            "routes_monitor_interface_reachable_targets_by_family{interface=\"wan_a\",family=\"ipv6\"} 0"
+        ));
+        assert!(text.contains("routes_monitor_active_interface{interface=\"wan_b\"} 0"));
+        assert!(
+            text.contains("routes_monitor_interface_eligible{interface=\"wan_a\",reason=\"\"} 1")
+        );
+        assert!(text
+            .contains("routes_monitor_target_reachable{interface=\"wan_a\",target=\"8.8.8.8\"} 1"));
+        assert!(text
+            .contains("routes_monitor_target_reachable{interface=\"wan_b\",target=\"8.8.8.8\"} 0"));
+        assert!(text.contains(
+            "routes_monitor_target_latency_ms{interface=\"wan_a\",target=\"8.8.8.8\"} 10"
+        ));
+        // 不可达的目标没有延迟数据
+        assert!(!text.contains("routes_monitor_target_latency_ms{interface=\"wan_b\""));
+
+        // 未启用时不输出逐目标指标
+        let text = render(&scores, Some("wan_a"), None, None);
+        assert!(text.contains("routes_monitor_interface_score"));
+        assert!(!text.contains("routes_monitor_target_"));
+        assert!(!text.contains("routes_monitor_switch_duration_seconds"));
+
+        let mut scores = scores;
+        scores[1].eligibility =
+            Eligibility::Ineligible(IneligibleReason::Probation { remaining: 2 });
+        let text = render(&scores, None, None, Some(Duration::from_millis(4500)));
+        assert!(text.contains(
+            "routes_monitor_interface_eligible{interface=\"wan_b\",reason=\"probation\"} 0"
+        ));
+        assert!(text.contains("routes_monitor_switch_duration_seconds 4.5"));
+    }
+
+    #[test]
+    fn test_merge_configs_with_label() {
+        let a = with_config_label(
+            &render(&[score("wan_a", 80.0)], Some("wan_a"), None, None),
+            "home",
+        );
+        let b = with_config_label(
+            &render(
+                &[score("wan_x", 70.0)],
+                None,
+                None,
+                Some(Duration::from_secs(2)),
+            ),
+            "office",
+        );
+        let text = merge(&[a, b]);
+
+        assert!(
+            text.contains("routes_monitor_interface_score{config=\"home\",interface=\"wan_a\"} 80")
+        );
+        assert!(text
+            .contains("routes_monitor_interface_score{config=\"office\",interface=\"wan_x\"} 70"));
+        assert!(text.contains("routes_monitor_switch_duration_seconds{config=\"office\"} 2"));
+        // 每个指标族只有一个头，样本紧随其后
+        assert_eq!(
+            text.matches("# TYPE routes_monitor_interface_score gauge")
+                .count(),
+            1
+        );
+        let lines: Vec<&str> = text.lines().collect();
+        let header = lines
+            .iter()
+            .position(|l| *l == "# TYPE routes_monitor_interface_score gauge")
+            .unwrap();
+        assert!(lines[header + 1].contains("home"));
+        assert!(lines[header + 2].contains("office"));
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -6,54 +6,447 @@
 use anyhow::{Context, Result};
 use futures::future::join_all;
 use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::{Duration, Instant};
-use tokio::process::Command;
-use tokio::time::timeout;
 
-use crate::config::{NetworkInterface, TargetIP};
+use crate::command::{CommandRunner, SystemRunner};
+use crate::config::{
+    AddressFamily, CheckWeights, LatencyMetric, NetworkInterface, OptimizeFor, PingTool, ProbeType,
+    Prober, ScoringWeights, SpeedDirection, SpeedScoring, TargetIP, TimeoutLoss,
+    VirtualAggregation, VirtualInterface, VlanAggregation, DEFAULT_PING_SIZE,
+};
+use crate::dns;
+use crate::icmp;
+use crate::openwrt::OpenWrtManager;
+use crate::probe::{self, Transport};
+use crate::report::percentile;
+
+/// 接口不存在时的 errno
+const ENODEV: i32 = 19;
+
+/// 上传速度测试发送的数据量（1 MiB）
+const UPLOAD_TEST_SIZE: usize = 1024 * 1024;
 
 /// 网络测试结果
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TestResult {
     /// 接口名称
     pub interface: String,
     /// 目标地址
-    #[allow(dead_code)]
     pub target: String,
+    /// 域名目标实际探测的地址
+    pub resolved_address: Option<String>,
     /// 是否可达
     pub reachable: bool,
     /// 延迟（毫秒）
     pub latency_ms: Option<f64>,
+    /// 抖动：往返时间的标准差（毫秒）
+    pub jitter_ms: Option<f64>,
+    /// 逐包往返时间（毫秒），用于计算延迟百分位
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rtt_samples: Vec<f64>,
     /// 丢包率（0.0-1.0）
     pub packet_loss: Option<f64>,
     /// 下载速度（KB/s）
     pub download_speed: Option<f64>,
+    /// 上传速度（KB/s），仅 `speed_direction = "upload"` 的目标
+    pub upload_speed: Option<f64>,
+    /// 不可达的原因
+    pub failure_reason: Option<FailureReason>,
+    /// 多种检查加权得到的健康度（0.0-1.0），未配置检查权重时为空
+    pub health: Option<f64>,
+    /// 探测报文使用的 DSCP 值
+    pub dscp: Option<u8>,
     /// 测试时间
-    #[allow(dead_code)]
     pub tested_at: chrono::DateTime<chrono::Local>,
 }
 
+/// 探测失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// 没有任何应答
+    Timeout,
+    /// 收到目标/网络/端口不可达
+    Unreachable,
+    /// 收到 TTL 超时
+    TtlExceeded,
+    /// 收到管理性禁止（被防火墙过滤）
+    Filtered,
+    /// 接口在系统中不存在（如 USB 调制解调器被拔出）
+    InterfaceMissing,
+    /// 接口链路已断开（operstate 为 down），未进行探测
+    LinkDown,
+    /// 域名解析失败（系统解析器或接口指定的 DNS 服务器）
+    ResolveFailed,
+    /// 有应答但丢包率达到可达阈值
+    PacketLoss,
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            FailureReason::Timeout => "超时",
+            FailureReason::Unreachable => "目标不可达",
+            FailureReason::TtlExceeded => "TTL 超时",
+            FailureReason::Filtered => "被过滤",
+            FailureReason::InterfaceMissing => "接口不存在",
+            FailureReason::LinkDown => "链路断开",
+            FailureReason::ResolveFailed => "域名解析失败",
+            FailureReason::PacketLoss => "丢包过多",
+        };
+        f.write_str(text)
+    }
+}
+
+/// 检查类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckType {
+    /// ICMP echo
+    Icmp,
+    /// 通过 test_url 下载
+    Http,
+}
+
+/// 按权重合并各检查结果，返回加权成功比例（0.0-1.0）
+/// 没有检查结果或权重总和为 0 时返回 `None`
+pub fn composite_health(checks: &[(CheckType, bool)], weights: &CheckWeights) -> Option<f64> {
+    let weight_of = |check: CheckType| match check {
+        CheckType::Icmp => weights.icmp,
+        CheckType::Http => weights.http,
+    };
+
+    let total: f64 = checks.iter().map(|(check, _)| weight_of(*check)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let passed: f64 = checks
+        .iter()
+        .filter(|(_, ok)| *ok)
+        .map(|(check, _)| weight_of(*check))
+        .sum();
+    Some(passed / total)
+}
+
+/// 单次 ping 探测的统计结果
+#[derive(Debug, Clone, PartialEq)]
+struct PingStats {
+    /// 是否可达
+    reachable: bool,
+    /// 平均延迟（毫秒）
+    latency_ms: Option<f64>,
+    /// 丢包率（0.0-1.0）
+    packet_loss: Option<f64>,
+    /// 抖动：往返时间的标准差（毫秒）
+    jitter_ms: Option<f64>,
+    /// 逐包往返时间（毫秒）
+    rtt_samples: Vec<f64>,
+    /// 不可达的原因
+    failure_reason: Option<FailureReason>,
+}
+
+impl PingStats {
+    /// 部分丢包（有应答但不完整）
+    fn lossy(&self) -> bool {
+        self.packet_loss
+            .is_some_and(|loss| loss > 0.0 && loss < 1.0)
+    }
+
+    /// 合并多次探测：丢包率与延迟取平均，任一次可达即可达
+    fn merge(runs: &[PingStats]) -> Self {
+        let mean = |values: Vec<f64>| {
+            (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+        };
+        let reachable = runs.iter().any(|r| r.reachable);
+        Self {
+            reachable,
+            latency_ms: mean(runs.iter().filter_map(|r| r.latency_ms).collect()),
+            packet_loss: mean(runs.iter().filter_map(|r| r.packet_loss).collect()),
+            jitter_ms: mean(runs.iter().filter_map(|r| r.jitter_ms).collect()),
+            rtt_samples: runs.iter().flat_map(|r| r.rtt_samples.clone()).collect(),
+            failure_reason: if reachable {
+                None
+            } else {
+                runs.last().and_then(|r| r.failure_reason)
+            },
+        }
+    }
+
+    /// 完全失败的探测
+    fn failed(reason: Option<FailureReason>) -> Self {
+        Self {
+            reachable: false,
+            latency_ms: None,
+            packet_loss: Some(1.0),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            failure_reason: reason,
+        }
+    }
+}
+
 /// 接口综合评分
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct InterfaceScore {
     /// 接口名称
     pub interface: String,
     /// 可达目标数量
     pub reachable_count: usize,
+    /// 其中可达的 IPv4 目标数量
+    pub reachable_v4: usize,
+    /// 其中可达的 IPv6 目标数量（域名目标不计入任一地址族）
+    pub reachable_v6: usize,
     /// 平均延迟
     pub avg_latency_ms: f64,
+    /// 延迟中位数（毫秒），没有逐包往返时间时为空
+    pub p50_latency_ms: Option<f64>,
+    /// 延迟的 95 百分位（毫秒），反映偶发的延迟尖峰
+    pub p95_latency_ms: Option<f64>,
+    /// 平均抖动（毫秒），没有足够的往返时间样本时为空
+    pub avg_jitter_ms: Option<f64>,
     /// 平均丢包率
     pub avg_packet_loss: f64,
     /// 平均速度
     pub avg_speed: f64,
     /// 综合评分（越高越好）
     pub score: f64,
+    /// 能否参与选择
+    pub eligibility: Eligibility,
+}
+
+impl InterfaceScore {
+    /// 是否可以参与选择
+    pub fn is_eligible(&self) -> bool {
+        self.eligibility == Eligibility::Eligible
+    }
+}
+
+/// 接口能否参与选择
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Eligibility {
+    #[default]
+    Eligible,
+    /// 照常探测评分，但不参与选择
+    Ineligible(IneligibleReason),
+}
+
+/// 接口不能参与选择的原因
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum IneligibleReason {
+    /// 备用接口（已禁用，仅探测展示）
+    Standby,
+    /// 接口在系统中不存在
+    Absent,
+    /// 新出现的接口处于观察期
+    Probation {
+        /// 还需观察的检查轮数
+        remaining: u32,
+    },
+    /// 可达目标的比例低于 `min_reachable_ratio`
+    LowReachability {
+        /// 可达的目标数
+        reachable: usize,
+        /// 探测的目标数
+        total: usize,
+    },
+    /// 距上次切换不足冷却时间
+    Cooldown {
+        /// 剩余冷却时间（秒）
+        remaining_secs: u64,
+    },
+    /// 已固定到其他接口
+    Pinned {
+        /// 固定的接口
+        interface: String,
+    },
+    /// 处于切换冻结窗口内
+    FreezeWindow {
+        /// 生效的冻结窗口
+        window: String,
+    },
+    /// 最近一小时的切换次数已达上限
+    RateLimited {
+        /// 距可以再次切换的时间（秒）
+        retry_after_secs: u64,
+    },
+}
+
+impl IneligibleReason {
+    /// 指标标签中使用的标识
+    pub fn key(&self) -> &'static str {
+        match self {
+            IneligibleReason::Standby => "standby",
+            IneligibleReason::Absent => "absent",
+            IneligibleReason::Probation { .. } => "probation",
+            IneligibleReason::LowReachability { .. } => "low_reachability",
+            IneligibleReason::Cooldown { .. } => "cooldown",
+            IneligibleReason::Pinned { .. } => "pinned",
+            IneligibleReason::FreezeWindow { .. } => "freeze_window",
+            IneligibleReason::RateLimited { .. } => "rate_limited",
+        }
+    }
+
+    /// 评分表中接口名后的简短标记
+    pub fn label(&self) -> &'static str {
+        match self {
+            IneligibleReason::Standby => "备用",
+            IneligibleReason::Absent => "不存在",
+            IneligibleReason::Probation { .. } => "观察期",
+            IneligibleReason::LowReachability { .. } => "可达不足",
+            IneligibleReason::Cooldown { .. } => "冷却中",
+            IneligibleReason::Pinned { .. } => "已固定",
+            IneligibleReason::FreezeWindow { .. } => "冻结",
+            IneligibleReason::RateLimited { .. } => "限频",
+        }
+    }
+}
+
+impl fmt::Display for IneligibleReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IneligibleReason::Standby => write!(f, "接口已禁用，仅作为备用探测"),
+            IneligibleReason::Absent => write!(f, "接口在系统中不存在"),
+            IneligibleReason::Probation { remaining } => {
+                write!(f, "新接口观察期，还需 {} 轮检查", remaining)
+            }
+            IneligibleReason::LowReachability { reachable, total } => {
+                write!(f, "仅可达 {}/{} 个目标", reachable, total)
+            }
+            IneligibleReason::Cooldown { remaining_secs } => {
+                write!(f, "切换冷却中，还需 {} 秒", remaining_secs)
+            }
+            IneligibleReason::Pinned { interface } => write!(f, "已固定到接口 {}", interface),
+            IneligibleReason::FreezeWindow { window } => {
+                write!(f, "处于冻结窗口 [{}] 内", window)
+            }
+            IneligibleReason::RateLimited { retry_after_secs } => {
+                write!(f, "切换次数已达上限，{} 秒后可再切换", retry_after_secs)
+            }
+        }
+    }
+}
+
+/// 每个 (接口, 目标) 最近一次的探测结果
+#[derive(Default)]
+pub struct ResultCache {
+    results: std::sync::RwLock<HashMap<(String, String), TestResult>>,
+}
+
+impl ResultCache {
+    /// 用本轮结果更新缓存
+    pub fn update(&self, results: &[TestResult]) {
+        let mut cache = self.results.write().unwrap_or_else(PoisonError::into_inner);
+        for result in results {
+            cache.insert(
+                (result.interface.clone(), result.target.clone()),
+                result.clone(),
+            );
+        }
+    }
+
+    /// 所有缓存的结果（按接口、目标排序）
+    pub fn snapshot(&self) -> Vec<TestResult> {
+        let mut results: Vec<TestResult> = self
+            .results
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect();
+        results.sort_by(|a, b| (&a.interface, &a.target).cmp(&(&b.interface, &b.target)));
+        results
+    }
+
+    /// 查询指定接口到指定目标的最近一次结果
+    pub fn last_result(&self, interface: &str, target: &str) -> Option<TestResult> {
+        self.results
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(interface.to_string(), target.to_string()))
+            .cloned()
+    }
+}
+
+/// 缓存的域名解析结果
+struct CachedAddresses {
+    /// 解析得到的地址
+    addresses: Vec<IpAddr>,
+    /// 解析时间
+    resolved_at: Instant,
 }
 
 /// 网络测试器
 pub struct NetworkTester {
     timeout_duration: Duration,
     concurrent_tests: usize,
+    /// 连通性探测方式
+    prober: Prober,
+    /// 原生探测不可用（已回退到 ping 命令）
+    native_unavailable: AtomicBool,
+    /// ping 程序
+    ping_tool: PingTool,
+    /// fping 无法执行（已回退到逐个目标的 ping 命令）
+    fping_unavailable: AtomicBool,
+    /// 速度评分方式
+    speed_scoring: SpeedScoring,
+    /// 评分权重与归一化基准
+    scoring: ScoringWeights,
+    /// ping 命令超时时的丢包计算方式
+    timeout_loss: TimeoutLoss,
+    /// 正式测量前先发送一次预热 ping
+    warmup_ping: bool,
+    /// 延迟评分使用的统计量
+    latency_metric: LatencyMetric,
+    /// 每次探测发送的 ping 报文数
+    ping_count: u32,
+    /// ping 负载大小（字节）
+    ping_size: u32,
+    /// TCP 探测时每次使用随机源端口
+    randomize_source_port: bool,
+    /// 外部命令执行器
+    runner: Arc<dyn CommandRunner>,
+    /// 域名目标的解析结果缓存
+    dns_cache: Mutex<HashMap<String, CachedAddresses>>,
+    /// 域名解析结果的缓存时间
+    dns_cache_ttl: Duration,
+    /// 接口 DNS 服务器的端口
+    resolver_port: u16,
+    /// 各接口的评分偏移
+    score_offsets: HashMap<String, f64>,
+    /// 按性价比选择时各接口评分的折算除数（`1 + cost_weight * cost`）
+    cost_divisors: HashMap<String, f64>,
+    /// 检查类型权重（配置后计算目标健康度）
+    check_weights: Option<CheckWeights>,
+    /// 丢包率低于该值才视为可达
+    reachability_loss_threshold: f64,
+    /// 可达目标比例低于该值的接口不参与选择
+    min_reachable_ratio: f64,
+    /// 出现部分丢包时立即补测的最多次数（0 表示不补测）
+    lossy_retries: u32,
+    /// 外部评分命令（经 `sh -c` 执行）
+    scorer_command: Option<String>,
+    /// 系统负载（1 分钟平均）超过该值时推迟速度测试
+    max_load: Option<f64>,
+    /// 读取系统负载
+    load_source: Arc<dyn Fn() -> Option<f64> + Send + Sync>,
+    /// 本轮因负载过高推迟速度测试
+    speed_tests_deferred: AtomicBool,
+    /// 带宽已饱和、本轮推迟速度测试的接口
+    saturated: std::sync::RwLock<HashSet<String>>,
+    /// 同时进行的速度测试数上限（所有接口共享）
+    speed_test_permits: tokio::sync::Semaphore,
+    /// 读取接口链路状态的 sysfs 目录
+    sysfs_net: PathBuf,
 }
 
 impl NetworkTester {
@@ -62,11 +455,221 @@ impl NetworkTester {
         Self {
             timeout_duration: Duration::from_secs(timeout_secs),
             concurrent_tests,
+            prober: Prober::default(),
+            native_unavailable: AtomicBool::new(false),
+            ping_tool: PingTool::default(),
+            fping_unavailable: AtomicBool::new(false),
+            speed_scoring: SpeedScoring::default(),
+            scoring: ScoringWeights::default(),
+            timeout_loss: TimeoutLoss::default(),
+            warmup_ping: false,
+            latency_metric: LatencyMetric::default(),
+            ping_count: 4,
+            ping_size: DEFAULT_PING_SIZE,
+            randomize_source_port: false,
+            runner: Arc::new(SystemRunner),
+            dns_cache: Mutex::new(HashMap::new()),
+            dns_cache_ttl: Duration::from_secs(300),
+            resolver_port: 53,
+            score_offsets: HashMap::new(),
+            cost_divisors: HashMap::new(),
+            check_weights: None,
+            reachability_loss_threshold: 1.0,
+            min_reachable_ratio: 0.0,
+            lossy_retries: 0,
+            scorer_command: None,
+            max_load: None,
+            load_source: Arc::new(system_load),
+            speed_tests_deferred: AtomicBool::new(false),
+            saturated: std::sync::RwLock::new(HashSet::new()),
+            speed_test_permits: tokio::sync::Semaphore::new(1),
+            sysfs_net: PathBuf::from("/sys/class/net"),
         }
     }
 
+    /// 使用指定的命令执行器
+    #[cfg(test)]
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// 向接口 DNS 服务器的指定端口查询
+    #[cfg(test)]
+    pub fn with_resolver_port(mut self, port: u16) -> Self {
+        self.resolver_port = port;
+        self
+    }
+
+    /// 设置评分权重与归一化基准
+    pub fn with_scoring(mut self, scoring: ScoringWeights) -> Self {
+        self.scoring = scoring.normalized();
+        self
+    }
+
+    /// 设置各接口的评分偏移（为 0 的接口可省略）
+    pub fn with_score_offsets(mut self, interfaces: &[NetworkInterface]) -> Self {
+        self.score_offsets = interfaces
+            .iter()
+            .filter(|i| i.score_offset != 0.0)
+            .map(|i| (i.name.clone(), i.score_offset))
+            .collect();
+        self
+    }
+
+    /// 设置优化目标，按性价比选择时评分按接口成本折算（成本为 0 的接口不受影响）
+    pub fn with_costs(
+        mut self,
+        optimize_for: OptimizeFor,
+        cost_weight: f64,
+        interfaces: &[NetworkInterface],
+    ) -> Self {
+        self.cost_divisors = match optimize_for {
+            OptimizeFor::Performance => HashMap::new(),
+            OptimizeFor::Value => interfaces
+                .iter()
+                .filter(|i| i.cost > 0.0 && cost_weight > 0.0)
+                .map(|i| (i.name.clone(), 1.0 + cost_weight * i.cost))
+                .collect(),
+        };
+        self
+    }
+
+    /// 设置检查类型权重，`None` 时目标只按 ping 判断可达
+    pub fn with_check_weights(mut self, check_weights: Option<CheckWeights>) -> Self {
+        self.check_weights = check_weights;
+        self
+    }
+
+    /// 设置可达的丢包率阈值，丢包率达到该值的目标视为不可达
+    pub fn with_reachability_loss_threshold(mut self, threshold: f64) -> Self {
+        self.reachability_loss_threshold = threshold;
+        self
+    }
+
+    /// 设置参与选择所需的最低可达目标比例
+    pub fn with_min_reachable_ratio(mut self, ratio: f64) -> Self {
+        self.min_reachable_ratio = ratio;
+        self
+    }
+
+    /// 设置部分丢包时的补测次数，补测结果与首次结果合并后再记录
+    pub fn with_lossy_retries(mut self, retries: u32) -> Self {
+        self.lossy_retries = retries;
+        self
+    }
+
+    /// 设置外部评分命令，配置后各接口的评分由该命令给出
+    pub fn with_scorer_command(mut self, scorer_command: Option<String>) -> Self {
+        self.scorer_command = scorer_command;
+        self
+    }
+
+    /// 设置推迟速度测试的系统负载上限
+    pub fn with_max_load(mut self, max_load: Option<f64>) -> Self {
+        self.max_load = max_load;
+        self
+    }
+
+    /// 设置同时进行的速度测试数上限，避免多个 curl 争抢带宽互相拉低测量结果
+    pub fn with_concurrent_speed_tests(mut self, limit: usize) -> Self {
+        self.speed_test_permits = tokio::sync::Semaphore::new(limit);
+        self
+    }
+
+    /// 使用指定的系统负载来源
+    #[cfg(test)]
+    pub fn with_load_source<F>(mut self, load_source: F) -> Self
+    where
+        F: Fn() -> Option<f64> + Send + Sync + 'static,
+    {
+        self.load_source = Arc::new(load_source);
+        self
+    }
+
+    /// 使用指定的 sysfs 网络设备目录读取链路状态
+    #[cfg(test)]
+    pub fn with_sysfs_net(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sysfs_net = path.into();
+        self
+    }
+
+    /// 设置是否在正式测量前发送预热 ping
+    pub fn with_warmup_ping(mut self, warmup_ping: bool) -> Self {
+        self.warmup_ping = warmup_ping;
+        self
+    }
+
+    /// 设置域名目标解析结果的缓存时间
+    pub fn with_dns_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.dns_cache_ttl = ttl;
+        self
+    }
+
+    /// 设置延迟评分使用平均值还是 95 百分位
+    pub fn with_latency_metric(mut self, latency_metric: LatencyMetric) -> Self {
+        self.latency_metric = latency_metric;
+        self
+    }
+
+    /// 设置每次探测的 ping 报文数和负载大小（目标可单独覆盖）
+    pub fn with_ping(mut self, count: u32, size: u32) -> Self {
+        self.ping_count = count.max(1);
+        self.ping_size = size;
+        self
+    }
+
+    /// 设置 TCP 探测时是否使用随机源端口
+    pub fn with_randomize_source_port(mut self, randomize_source_port: bool) -> Self {
+        self.randomize_source_port = randomize_source_port;
+        self
+    }
+
+    /// 设置连通性探测方式
+    pub fn with_prober(mut self, prober: Prober) -> Self {
+        self.prober = prober;
+        self
+    }
+
+    /// 设置 ping 程序
+    pub fn with_ping_tool(mut self, ping_tool: PingTool) -> Self {
+        self.ping_tool = ping_tool;
+        self
+    }
+
+    /// 设置速度评分方式
+    pub fn with_speed_scoring(mut self, speed_scoring: SpeedScoring) -> Self {
+        self.speed_scoring = speed_scoring;
+        self
+    }
+
+    /// 设置 ping 命令超时时的丢包计算方式
+    pub fn with_timeout_loss(mut self, timeout_loss: TimeoutLoss) -> Self {
+        self.timeout_loss = timeout_loss;
+        self
+    }
+
+    /// 设置带宽已饱和的接口，这些接口本轮推迟速度测试
+    pub fn set_saturated(&self, interfaces: HashSet<String>) {
+        *self
+            .saturated
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = interfaces;
+    }
+
     /// 测试单个接口到单个目标的连接
+    #[cfg(test)]
     pub async fn test_single(&self, interface: &NetworkInterface, target: &TargetIP) -> TestResult {
+        self.test_target(interface, target, None).await
+    }
+
+    /// 测试单个目标；`probed` 为 fping 已批量探测得到的结果，此时不再单独 ping
+    async fn test_target(
+        &self,
+        interface: &NetworkInterface,
+        target: &TargetIP,
+        probed: Option<PingStats>,
+    ) -> TestResult {
         debug!(
             "测试接口 {} 到目标 {} ({})",
             interface.name, target.address, target.description
@@ -75,50 +678,215 @@ impl NetworkTester {
         // 移除 CIDR 后缀（如 /32）以进行 ping 测试
         let ping_target = target.address.split('/').next().unwrap_or(&target.address);
 
-        // 进行 ping 测试（4次）并解析结果
-        let (reachable, latency_ms, packet_loss) = self
-            .ping_test_with_stats(&interface.name, ping_target, 4)
-            .await;
-
-        // 如果配置了测试 URL，进行速度测试
-        let download_speed = if reachable && target.test_url.is_some() {
-            self.speed_test(&interface.name, target.test_url.as_ref().unwrap())
+        // 域名目标先解析再探测，避免 ping 每轮重复解析并掩盖 DNS 故障：
+        // 配置了接口 DNS 服务器时经该服务器解析（解析失败同样说明该链路有问题），
+        // 否则使用系统解析器并按 dns_cache_ttl 缓存
+        let is_hostname = ping_target.parse::<IpAddr>().is_err();
+        let probe_address = match &interface.resolver {
+            _ if !is_hostname => Some(ping_target.to_string()),
+            Some(resolver) => self
+                .resolve(&interface.name, ping_target, resolver, interface.family)
                 .await
-                .ok()
-        } else {
-            None
+                .map(|ip| ip.to_string()),
+            None => self
+                .resolve_system(ping_target, interface.family)
+                .await
+                .map(|ip| ip.to_string()),
+        };
+
+        let count = target.ping_count.unwrap_or(self.ping_count).max(1);
+        let stats = match (&probe_address, target.probe, target.port) {
+            (Some(address), ProbeType::Tcp, Some(port)) => {
+                self.tcp_connect_test(&interface.name, address, port, count)
+                    .await
+            }
+            (Some(address), _, _) => {
+                let size = target.ping_size.unwrap_or(self.ping_size);
+                let stats = match probed {
+                    Some(stats) => stats,
+                    None => {
+                        // 预热 ping 唤醒 ARP/ND 与空闲的 PPPoE 链路，结果直接丢弃
+                        if self.warmup_ping {
+                            self.warmup(&interface.name, address).await;
+                        }
+
+                        // 进行 ping 测试并解析结果
+                        self.ping_test_with_stats(
+                            &interface.name,
+                            address,
+                            count,
+                            size,
+                            target.tos(),
+                        )
+                        .await
+                    }
+                };
+                self.retry_lossy(&interface.name, address, count, size, target.tos(), stats)
+                    .await
+            }
+            (None, _, _) => PingStats::failed(Some(FailureReason::ResolveFailed)),
+        };
+        let stats = self.apply_loss_threshold(stats);
+        let mut reachable = stats.reachable;
+        let mut failure_reason = stats.failure_reason;
+        let mut health = None;
+
+        // 如果配置了测试 URL，进行速度测试（系统繁忙时推迟）
+        // 配置了检查权重时 ping 失败也做 HTTP 检查，它本身就是一项健康度指标
+        let speed_test_due = target.test_url.is_some()
+            && !self.speed_tests_deferred.load(Ordering::Relaxed)
+            && !self
+                .saturated
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains(&interface.name);
+        let (download_speed, upload_speed) = match &target.test_url {
+            Some(test_url) if speed_test_due && (reachable || self.check_weights.is_some()) => {
+                match target.speed_direction {
+                    SpeedDirection::Download => {
+                        (self.speed_test(&interface.name, test_url).await.ok(), None)
+                    }
+                    SpeedDirection::Upload => (
+                        None,
+                        self.speed_test_upload(&interface.name, test_url).await.ok(),
+                    ),
+                }
+            }
+            _ => (None, None),
         };
 
+        if let Some(weights) = &self.check_weights {
+            let mut checks = vec![(CheckType::Icmp, stats.reachable)];
+            if speed_test_due {
+                checks.push((CheckType::Http, download_speed.or(upload_speed).is_some()));
+            }
+            health = composite_health(&checks, weights);
+
+            // 任一检查成功即视为可达，具体健康度由 health 体现
+            if health.is_some_and(|h| h > 0.0) {
+                reachable = true;
+                failure_reason = None;
+            }
+        }
+
         TestResult {
             interface: interface.name.clone(),
             target: target.address.clone(),
+            resolved_address: probe_address.filter(|_| is_hostname),
             reachable,
-            latency_ms,
-            packet_loss,
+            latency_ms: stats.latency_ms,
+            jitter_ms: stats.jitter_ms,
+            rtt_samples: stats.rtt_samples,
+            packet_loss: stats.packet_loss,
             download_speed,
+            upload_speed,
+            failure_reason,
+            health,
+            dscp: target.dscp,
             tested_at: chrono::Local::now(),
         }
     }
 
-    /// 测试单个接口到所有目标
+    /// 测试单个接口到所有目标（跳过 `skip` 中的目标地址）
     pub async fn test_interface(
         &self,
         interface: &NetworkInterface,
         targets: &[TargetIP],
+        skip: &HashSet<String>,
     ) -> Vec<TestResult> {
         info!(
             "开始测试接口: {} ({})",
             interface.name, interface.display_name
         );
 
+        let mut targets = interface.effective_targets(targets);
+        targets.retain(|t| !skip.contains(&t.address));
+
+        // 链路断开时每个目标都只会等到超时，直接记为不可达
+        if !self.interface_is_up(&interface.name).await {
+            info!("接口 {} 链路已断开，跳过本轮探测", interface.name);
+            return targets
+                .iter()
+                .map(|target| TestResult {
+                    interface: interface.name.clone(),
+                    target: target.address.clone(),
+                    resolved_address: None,
+                    reachable: false,
+                    latency_ms: None,
+                    jitter_ms: None,
+                    rtt_samples: Vec::new(),
+                    packet_loss: None,
+                    download_speed: None,
+                    upload_speed: None,
+                    failure_reason: Some(FailureReason::LinkDown),
+                    health: None,
+                    dscp: target.dscp,
+                    tested_at: chrono::Local::now(),
+                })
+                .collect();
+        }
+
+        let results = self.probe_targets(interface, &targets).await;
+        if interface.vlans.is_empty() {
+            return results;
+        }
+
+        // 逐个 VLAN 子接口探测相同目标，速度测试只在主接口上进行
+        let vlan_targets: Vec<TargetIP> = targets
+            .iter()
+            .map(|t| TargetIP {
+                test_url: None,
+                ..t.clone()
+            })
+            .collect();
+        let mut vlan_results = Vec::new();
+        for vlan in &interface.vlans {
+            let device = NetworkInterface {
+                name: vlan.device_name(),
+                vlans: Vec::new(),
+                ..interface.clone()
+            };
+            debug!("测试接口 {} 的 VLAN 子接口 {}", interface.name, device.name);
+            vlan_results.push(self.probe_targets(&device, &vlan_targets).await);
+        }
+
+        aggregate_vlan_results(results, &vlan_results, interface.vlan_aggregation)
+    }
+
+    /// 接口链路是否处于工作状态
+    ///
+    /// 读取 `/sys/class/net/<设备>/operstate`（PPPoE 接口先按物理接口名查找），
+    /// 只有明确为 down 时才视为断开；找不到设备或状态未知时交由探测判断
+    pub async fn interface_is_up(&self, interface: &str) -> bool {
+        let physical = OpenWrtManager::convert_to_physical_interface(interface);
+        for device in [physical.as_str(), interface] {
+            let path = self.sysfs_net.join(device).join("operstate");
+            if let Ok(state) = tokio::fs::read_to_string(&path).await {
+                return !matches!(state.trim(), "down" | "lowerlayerdown" | "notpresent");
+            }
+        }
+        true
+    }
+
+    /// 通过指定接口探测所有目标（分批并发）
+    async fn probe_targets(
+        &self,
+        interface: &NetworkInterface,
+        targets: &[TargetIP],
+    ) -> Vec<TestResult> {
         let mut results = Vec::new();
+        let mut probed = if self.ping_tool == PingTool::Fping && self.prober == Prober::Ping {
+            self.fping_targets(&interface.name, targets).await
+        } else {
+            HashMap::new()
+        };
 
         // 分批并发测试
         for chunk in targets.chunks(self.concurrent_tests) {
             let mut tasks = Vec::new();
 
             for target in chunk {
-                let task = self.test_single(interface, target);
+                let task = self.test_target(interface, target, probed.remove(&target.address));
                 tasks.push(task);
             }
 
@@ -130,22 +898,24 @@ impl NetworkTester {
         results
     }
 
-    /// 测试所有接口（并发测试所有接口）
+    /// 测试所有接口（并发测试所有接口，跳过 `skip` 中的目标地址）
     pub async fn test_all_interfaces(
         &self,
         interfaces: &[&NetworkInterface],
         targets: &[TargetIP],
+        skip: &HashSet<String>,
     ) -> Vec<TestResult> {
         info!(
             "开始并发测试所有接口，共 {} 个接口，{} 个目标",
             interfaces.len(),
             targets.len()
         );
+        self.update_speed_test_deferral();
 
         // 为每个接口创建测试任务
         let mut tasks = Vec::new();
         for interface in interfaces {
-            let task = self.test_interface(interface, targets);
+            let task = self.test_interface(interface, targets, skip);
             tasks.push(task);
         }
 
@@ -161,6 +931,33 @@ impl NetworkTester {
         all_results
     }
 
+    /// 按当前系统负载决定本轮是否推迟速度测试（连通性探测照常进行）
+    fn update_speed_test_deferral(&self) {
+        let deferred = match (self.max_load, (self.load_source)()) {
+            (Some(max_load), Some(load)) if load > max_load => {
+                info!(
+                    "系统负载 {:.2} 超过上限 {:.2}，本轮推迟速度测试",
+                    load, max_load
+                );
+                true
+            }
+            _ => false,
+        };
+        self.speed_tests_deferred.store(deferred, Ordering::Relaxed);
+    }
+
+    /// 测试所有接口到给定目标（不应用接口的目标覆盖，用于确认目标）
+    pub async fn test_exact_targets(
+        &self,
+        interfaces: &[&NetworkInterface],
+        targets: &[TargetIP],
+    ) -> Vec<TestResult> {
+        let tasks = interfaces
+            .iter()
+            .map(|interface| self.probe_targets(interface, targets));
+        join_all(tasks).await.into_iter().flatten().collect()
+    }
+
     /// 计算接口评分
     pub fn calculate_scores(&self, results: &[TestResult]) -> Vec<InterfaceScore> {
         // 按接口分组
@@ -170,7 +967,7 @@ impl NetworkTester {
         for result in results {
             interface_results
                 .entry(result.interface.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(result);
         }
 
@@ -181,24 +978,46 @@ impl NetworkTester {
             let reachable_results: Vec<_> = iface_results.iter().filter(|r| r.reachable).collect();
 
             let reachable_count = reachable_results.len();
+            let (reachable_v4, reachable_v6) =
+                reachable_results
+                    .iter()
+                    .fold((0, 0), |(v4, v6), r| match target_ip(&r.target) {
+                        Some(IpAddr::V4(_)) => (v4 + 1, v6),
+                        Some(IpAddr::V6(_)) => (v4, v6 + 1),
+                        None => (v4, v6),
+                    });
 
             if reachable_count == 0 {
                 // 完全不可达的接口得分为 0
+                let absent = iface_results
+                    .iter()
+                    .all(|r| r.failure_reason == Some(FailureReason::InterfaceMissing));
                 scores.push(InterfaceScore {
                     interface,
                     reachable_count: 0,
+                    reachable_v4: 0,
+                    reachable_v6: 0,
                     avg_latency_ms: f64::INFINITY,
+                    p50_latency_ms: None,
+                    p95_latency_ms: None,
+                    avg_jitter_ms: None,
                     avg_packet_loss: 1.0,
                     avg_speed: 0.0,
                     score: 0.0,
+                    eligibility: if absent {
+                        Eligibility::Ineligible(IneligibleReason::Absent)
+                    } else {
+                        Eligibility::Eligible
+                    },
                 });
                 continue;
             }
 
-            // 计算平均延迟
+            // 计算平均延迟（忽略 NaN/无穷大/负数等异常值，下同）
             let latencies: Vec<f64> = reachable_results
                 .iter()
                 .filter_map(|r| r.latency_ms)
+                .filter(|v| v.is_finite() && *v >= 0.0)
                 .collect();
 
             let avg_latency_ms = if !latencies.is_empty() {
@@ -207,10 +1026,31 @@ impl NetworkTester {
                 100.0 // 默认延迟
             };
 
+            // 汇总各目标的逐包往返时间计算延迟百分位
+            let mut samples: Vec<f64> = reachable_results
+                .iter()
+                .flat_map(|r| r.rtt_samples.iter().copied())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .collect();
+            samples.sort_by(f64::total_cmp);
+            let p50_latency_ms = percentile(&samples, 50.0);
+            let p95_latency_ms = percentile(&samples, 95.0);
+
+            // 计算平均抖动
+            let jitters: Vec<f64> = reachable_results
+                .iter()
+                .filter_map(|r| r.jitter_ms)
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .collect();
+            let avg_jitter_ms =
+                (!jitters.is_empty()).then(|| jitters.iter().sum::<f64>() / jitters.len() as f64);
+
             // 计算平均丢包率
             let packet_losses: Vec<f64> = reachable_results
                 .iter()
                 .filter_map(|r| r.packet_loss)
+                .filter(|v| v.is_finite())
+                .map(|v| v.clamp(0.0, 1.0))
                 .collect();
 
             let avg_packet_loss = if !packet_losses.is_empty() {
@@ -219,10 +1059,11 @@ impl NetworkTester {
                 0.0
             };
 
-            // 计算平均速度
+            // 计算平均速度（各目标按其测速方向取下载或上传速度）
             let speeds: Vec<f64> = reachable_results
                 .iter()
-                .filter_map(|r| r.download_speed)
+                .filter_map(|r| r.download_speed.or(r.upload_speed))
+                .filter(|v| v.is_finite() && *v >= 0.0)
                 .collect();
 
             let avg_speed = if !speeds.is_empty() {
@@ -231,13 +1072,26 @@ impl NetworkTester {
                 0.0
             };
 
-            // 综合评分计算
-            // 公式: score = (reachable_ratio * 30) + (latency_score * 25) + (packet_loss_score * 25) + (speed_score * 20)
-            let reachable_ratio = reachable_count as f64 / iface_results.len() as f64;
+            // 综合评分计算：各项指标归一化到 0-100 分后按 scoring 中的权重加权求和
+            // 配置了检查权重时按各目标的健康度累加，得到小数形式的可达比例
+            let reachable_ratio = iface_results
+                .iter()
+                .map(|r| match r.health {
+                    Some(health) if health.is_finite() => health.clamp(0.0, 1.0),
+                    _ if r.reachable => 1.0,
+                    _ => 0.0,
+                })
+                .sum::<f64>()
+                / iface_results.len() as f64;
 
-            // 延迟评分：延迟越低分数越高（使用倒数归一化）
-            let latency_score = if avg_latency_ms > 0.0 {
-                (1000.0 / avg_latency_ms).min(100.0)
+            // 延迟评分：延迟越低分数越高（相对延迟基准按倒数归一化）
+            // 按 p95 评分时没有逐包往返时间的接口退回平均延迟
+            let scored_latency_ms = match self.latency_metric {
+                LatencyMetric::Mean => avg_latency_ms,
+                LatencyMetric::P95 => p95_latency_ms.unwrap_or(avg_latency_ms),
+            };
+            let latency_score = if scored_latency_ms > 0.0 {
+                (self.scoring.latency_reference_ms * 100.0 / scored_latency_ms).min(100.0)
             } else {
                 100.0
             };
@@ -245,209 +1099,3261 @@ impl NetworkTester {
             // 丢包率评分：丢包率越低分数越高
             let packet_loss_score = (1.0 - avg_packet_loss) * 100.0;
 
-            // 速度评分：速度越高分数越高（以 1MB/s 为满分基准）
-            let speed_score = (avg_speed / 1024.0 * 100.0).min(100.0);
+            // 抖动评分：与延迟相同按抖动基准的倒数归一化，没有抖动数据时不扣分
+            let jitter_score = match avg_jitter_ms {
+                Some(jitter) if jitter > 0.0 => {
+                    (self.scoring.jitter_reference_ms * 100.0 / jitter).min(100.0)
+                }
+                _ => 100.0,
+            };
 
-            // 评分权重：优先速度(40%)、其次丢包率(20%)、最后延迟(10%)，基础可达性(30%)
-            let score = (reachable_ratio * 30.0)
-                + (speed_score * 0.40)
-                + (packet_loss_score * 0.20)
-                + (latency_score * 0.10);
+            // 速度部分在所有接口统计完成后再计入
+            let weights = &self.scoring;
+            let score = (reachable_ratio * 100.0 * weights.weight_reachable)
+                + (packet_loss_score * weights.weight_packet_loss)
+                + (latency_score * weights.weight_latency)
+                + (jitter_score * weights.weight_jitter);
+
+            // 只能到达少数目标的接口不论其他指标多好都不参与选择
+            let total = iface_results.len();
+            let eligibility = if (reachable_count as f64) < self.min_reachable_ratio * total as f64
+            {
+                info!(
+                    "接口 {} 仅可达 {}/{} 个目标，低于最低可达比例 {:.0}%，不参与选择",
+                    interface,
+                    reachable_count,
+                    total,
+                    self.min_reachable_ratio * 100.0
+                );
+                Eligibility::Ineligible(IneligibleReason::LowReachability {
+                    reachable: reachable_count,
+                    total,
+                })
+            } else {
+                Eligibility::Eligible
+            };
 
             scores.push(InterfaceScore {
                 interface,
                 reachable_count,
+                reachable_v4,
+                reachable_v6,
                 avg_latency_ms,
+                p50_latency_ms,
+                p95_latency_ms,
+                avg_jitter_ms,
                 avg_packet_loss,
                 avg_speed,
                 score,
+                eligibility,
             });
         }
 
+        // 速度评分：速度越高分数越高
+        let fastest = scores.iter().map(|s| s.avg_speed).fold(0.0, f64::max);
+        for score in &mut scores {
+            let speed_score = match self.speed_scoring {
+                // 达到速度基准得满分
+                SpeedScoring::Absolute => {
+                    (score.avg_speed / self.scoring.speed_reference_kb * 100.0).min(100.0)
+                }
+                // 本轮最快的接口得满分，其余按比例
+                SpeedScoring::Relative if fastest > 0.0 => score.avg_speed / fastest * 100.0,
+                SpeedScoring::Relative => 0.0,
+            };
+            score.score += speed_score * self.scoring.weight_speed;
+
+            // 人工评分偏移，完全不可达的接口保持 0 分
+            if score.reachable_count > 0 {
+                if let Some(offset) = self.score_offsets.get(&score.interface) {
+                    score.score += offset;
+                }
+                if let Some(divisor) = self.cost_divisors.get(&score.interface) {
+                    score.score /= divisor;
+                }
+            }
+        }
+
         // 按评分降序排序
-        scores.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        sort_scores(&mut scores);
 
         scores
     }
 
-    /// 使用 ping 测试连接性（简单版本，向后兼容）
-    #[allow(dead_code)]
-    async fn ping_test(&self, interface: &str, target: &str) -> bool {
-        let (reachable, _, _) = self.ping_test_with_stats(interface, target, 1).await;
-        reachable
-    }
-
-    /// 使用 ping 测试连接性并返回统计信息
-    /// 返回: (是否可达, 平均延迟ms, 丢包率0.0-1.0)
-    async fn ping_test_with_stats(
-        &self,
-        interface: &str,
-        target: &str,
-        count: u32,
-    ) -> (bool, Option<f64>, Option<f64>) {
-        // 在 OpenWrt 上使用 ping 命令测试连接
-        // -I 指定接口，-c 指定次数，-W 指定超时
-        let result = timeout(
-            self.timeout_duration * count,
-            Command::new("ping")
-                .arg("-I")
-                .arg(interface)
-                .arg("-c")
-                .arg(count.to_string())
-                .arg("-W")
-                .arg(format!("{}", self.timeout_duration.as_secs()))
-                .arg(target)
-                .output(),
-        )
-        .await;
-
-        match result {
-            Ok(Ok(output)) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-
-                // 解析 ping 输出
-                // 格式示例: "4 packets transmitted, 3 received, 25% packet loss, time 3005ms"
-                // "rtt min/avg/max/mdev = 10.123/15.456/20.789/3.210 ms"
-
-                let mut packet_loss = None;
-                let mut avg_latency = None;
-
-                for line in stdout.lines() {
-                    // 解析丢包率
-                    if line.contains("packet loss") {
-                        if let Some(loss_str) = line.split('%').next() {
-                            if let Some(num_str) = loss_str.split_whitespace().last() {
-                                if let Ok(loss) = num_str.parse::<f64>() {
-                                    packet_loss = Some(loss / 100.0);
-                                }
-                            }
-                        }
-                    }
-
-                    // 解析平均延迟
-                    if line.contains("rtt") || line.contains("round-trip") {
-                        // 格式: "rtt min/avg/max/mdev = 10.123/15.456/20.789/3.210 ms"
-                        if let Some(stats_part) = line.split('=').nth(1) {
-                            let parts: Vec<&str> = stats_part.trim().split('/').collect();
-                            if parts.len() >= 2 {
-                                if let Ok(avg) = parts[1].trim().parse::<f64>() {
-                                    avg_latency = Some(avg);
-                                }
-                            }
-                        }
-                    }
-                }
-
-                let reachable = output.status.success() && packet_loss.unwrap_or(1.0) < 1.0;
+    /// 计算各接口评分，配置了外部评分命令时用其结果替换内置评分
+    pub async fn score_interfaces(&self, results: &[TestResult]) -> Vec<InterfaceScore> {
+        let mut scores = self.calculate_scores(results);
+        let Some(command) = &self.scorer_command else {
+            return scores;
+        };
 
-                if reachable {
+        for score in &mut scores {
+            let iface_results: Vec<&TestResult> = results
+                .iter()
+                .filter(|r| r.interface == score.interface)
+                .collect();
+            match self.external_score(command, &iface_results).await {
+                Ok(value) => {
                     debug!(
-                        "Ping 成功: {} -> {} (延迟: {:.2}ms, 丢包: {:.1}%)",
-                        interface,
-                        target,
-                        avg_latency.unwrap_or(0.0),
-                        packet_loss.unwrap_or(0.0) * 100.0
+                        "外部评分: {} {:.2} -> {:.2}",
+                        score.interface, score.score, value
                     );
-                } else {
-                    debug!("Ping 失败: {} -> {}", interface, target);
+                    score.score = value;
                 }
-
-                (reachable, avg_latency, packet_loss)
-            }
-            Ok(Err(e)) => {
-                warn!("执行 ping 命令失败: {}", e);
-                (false, None, Some(1.0))
-            }
-            Err(_) => {
-                warn!("Ping 超时: {} -> {}", interface, target);
-                (false, None, Some(1.0))
+                Err(e) => warn!(
+                    "外部评分命令对接口 {} 失败，使用内置评分: {:#}",
+                    score.interface, e
+                ),
             }
         }
-    }
 
-    /// 速度测试
-    async fn speed_test(&self, interface: &str, test_url: &str) -> Result<f64> {
-        let _start = Instant::now();
+        sort_scores(&mut scores);
+        scores
+    }
 
-        // 使用 curl 通过指定接口下载测试文件
-        let result = timeout(
-            self.timeout_duration * 2, // 速度测试给更多时间
-            Command::new("curl")
-                .arg("--interface")
-                .arg(interface)
-                .arg("-s")
-                .arg("-o")
-                .arg("/dev/null")
-                .arg("-w")
-                .arg("%{speed_download}")
-                .arg(test_url)
-                .output(),
-        )
-        .await
-        .context("速度测试超时")?
-        .context("执行 curl 命令失败")?;
+    /// 将接口的测试结果以 JSON 写入外部评分命令的标准输入，读取标准输出中的评分
+    async fn external_score(&self, command: &str, results: &[&TestResult]) -> Result<f64> {
+        let input = serde_json::to_vec(results).context("序列化测试结果失败")?;
+        let output = self
+            .runner
+            .run_with_input("sh", &["-c", command], &input, self.timeout_duration)
+            .await
+            .context("执行评分命令失败")?;
 
-        if !result.status.success() {
-            anyhow::bail!("curl 命令执行失败");
+        if !output.status.success() {
+            anyhow::bail!(
+                "评分命令退出状态 {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
         }
 
-        let speed_bytes = String::from_utf8_lossy(&result.stdout)
-            .trim()
-            .parse::<f64>()
-            .context("解析速度数据失败")?;
-
-        // 转换为 KB/s
-        let speed_kb = speed_bytes / 1024.0;
-
-        debug!(
-            "速度测试完成: {} -> {} ({:.2} KB/s)",
-            interface, test_url, speed_kb
-        );
-
-        Ok(speed_kb)
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match stdout.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => anyhow::bail!("评分命令输出无效: {:?}", stdout.trim()),
+        }
     }
 
-    /// 获取最佳接口
-    pub fn get_best_interface<'a>(
+    /// 经接口向指定的 DNS 服务器解析域名，查询从绑定到该接口的套接字发出
+    ///
+    /// 单栈接口只查询对应地址族的记录，双栈接口先查 A 记录，没有时再查 AAAA
+    async fn resolve(
         &self,
-        scores: &'a [InterfaceScore],
-    ) -> Option<&'a InterfaceScore> {
-        scores.first()
-    }
+        interface: &str,
+        host: &str,
+        resolver: &str,
+        family: AddressFamily,
+    ) -> Option<IpAddr> {
+        let server = match resolver.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, self.resolver_port),
+            Err(_) => {
+                warn!("DNS 服务器地址无效: {}", resolver);
+                return None;
+            }
+        };
+        let socket = match dns::open_socket(interface, server) {
+            Ok(socket) => socket,
+            Err(e) => {
+                warn!("创建经 {} 的 DNS 查询套接字失败: {}", interface, e);
+                return None;
+            }
+        };
+
+        let record_types: &[u16] = match family {
+            AddressFamily::V4 => &[dns::TYPE_A],
+            AddressFamily::V6 => &[dns::TYPE_AAAA],
+            AddressFamily::Dual => &[dns::TYPE_A, dns::TYPE_AAAA],
+        };
+        for &record_type in record_types {
+            match dns::query(&socket, host, record_type, self.timeout_duration).await {
+                Ok(Some(ip)) => {
+                    debug!("通过 {} ({}) 解析 {} -> {}", resolver, interface, host, ip);
+                    return Some(ip);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    debug!(
+                        "通过 {} ({}) 解析 {} 失败: {}",
+                        resolver, interface, host, e
+                    );
+                    return None;
+                }
+            }
+        }
+
+        debug!("通过 {} ({}) 解析 {} 失败", resolver, interface, host);
+        None
+    }
+
+    /// 使用系统解析器解析域名，结果在 dns_cache_ttl 内复用
+    ///
+    /// 单栈接口只取对应地址族的地址，双栈接口优先 IPv4
+    async fn resolve_system(&self, host: &str, family: AddressFamily) -> Option<IpAddr> {
+        let cached = self
+            .dns_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(host)
+            .filter(|cached| cached.resolved_at.elapsed() < self.dns_cache_ttl)
+            .map(|cached| cached.addresses.clone());
+
+        let addresses = match cached {
+            Some(addresses) => addresses,
+            None => {
+                let lookup = tokio::net::lookup_host((host, 0));
+                let addresses: Vec<IpAddr> =
+                    match tokio::time::timeout(self.timeout_duration, lookup).await {
+                        Ok(Ok(addrs)) => addrs.map(|addr| addr.ip()).collect(),
+                        Ok(Err(e)) => {
+                            warn!("解析目标域名 {} 失败，本轮视为不可达: {}", host, e);
+                            return None;
+                        }
+                        Err(_) => {
+                            warn!("解析目标域名 {} 超时，本轮视为不可达", host);
+                            return None;
+                        }
+                    };
+                debug!("解析 {} -> {:?}", host, addresses);
+                self.dns_cache
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(
+                        host.to_string(),
+                        CachedAddresses {
+                            addresses: addresses.clone(),
+                            resolved_at: Instant::now(),
+                        },
+                    );
+                addresses
+            }
+        };
+
+        let address = match family {
+            AddressFamily::V4 => addresses.iter().find(|ip| ip.is_ipv4()),
+            AddressFamily::V6 => addresses.iter().find(|ip| ip.is_ipv6()),
+            AddressFamily::Dual => addresses
+                .iter()
+                .find(|ip| ip.is_ipv4())
+                .or_else(|| addresses.first()),
+        };
+        if address.is_none() {
+            warn!(
+                "目标域名 {} 没有可用的 {:?} 地址，本轮视为不可达",
+                host, family
+            );
+        }
+        address.copied()
+    }
+
+    /// 发送一次预热 ping，不计入任何统计
+    async fn warmup(&self, interface: &str, target: &str) {
+        // 预热失败不代表链路故障，正式测量照常进行
+        if self.ping_once(interface, target).await {
+            debug!("预热 ping 完成: {} -> {}", interface, target)
+        } else {
+            debug!("预热 ping 未收到应答: {} -> {}", interface, target)
+        }
+    }
+
+    /// 通过备用接口发送一次保活 ping，防止链路因空闲断开，不计入任何统计
+    pub async fn keepalive(&self, interface: &str, target: &str) -> bool {
+        let answered = self.ping_once(interface, target).await;
+        debug!(
+            "保活 ping: {} -> {} ({})",
+            interface,
+            target,
+            if answered { "有应答" } else { "无应答" }
+        );
+        answered
+    }
+
+    /// 发送单个 ping 报文，返回是否收到应答
+    async fn ping_once(&self, interface: &str, target: &str) -> bool {
+        let wait_arg = self.timeout_duration.as_secs().to_string();
+        let mut args = vec!["-I", interface, "-c", "1", "-W", &wait_arg];
+        args.extend(family_arg(target));
+        args.push(target);
+
+        matches!(
+            self.runner
+                .run_with_timeout("ping", &args, self.timeout_duration)
+                .await,
+            Ok(output) if output.status.is_some_and(|s| s.success())
+        )
+    }
+
+    /// 有应答但丢包率达到阈值时视为不可达，延迟与丢包率照常保留
+    /// 出现部分丢包时立即补测，合并各次结果以平滑瞬时的单包丢失
+    async fn retry_lossy(
+        &self,
+        interface: &str,
+        address: &str,
+        count: u32,
+        size: u32,
+        tos: Option<u8>,
+        first: PingStats,
+    ) -> PingStats {
+        // 完全不可达是真实故障，不补测
+        let mut runs = vec![first];
+        while runs.len() <= self.lossy_retries as usize && runs.last().is_some_and(PingStats::lossy)
+        {
+            debug!("接口 {} 到 {} 出现部分丢包，补测一次", interface, address);
+            runs.push(
+                self.ping_test_with_stats(interface, address, count, size, tos)
+                    .await,
+            );
+        }
+
+        if runs.len() == 1 {
+            return runs.remove(0);
+        }
+        PingStats::merge(&runs)
+    }
+
+    fn apply_loss_threshold(&self, mut stats: PingStats) -> PingStats {
+        if stats.reachable && stats.packet_loss.unwrap_or(0.0) >= self.reachability_loss_threshold {
+            stats.reachable = false;
+            stats.failure_reason = Some(FailureReason::PacketLoss);
+        }
+        stats
+    }
+
+    /// 使用 ping 测试连接性（简单版本，向后兼容）
+    #[allow(dead_code)]
+    async fn ping_test(&self, interface: &str, target: &str) -> bool {
+        self.ping_test_with_stats(interface, target, 1, DEFAULT_PING_SIZE, None)
+            .await
+            .reachable
+    }
+
+    /// 使用 ping 测试连接性并返回统计信息（`size` 为负载字节数，`tos` 为探测报文的 ToS 字节）
+    async fn ping_test_with_stats(
+        &self,
+        interface: &str,
+        target: &str,
+        count: u32,
+        size: u32,
+        tos: Option<u8>,
+    ) -> PingStats {
+        // 原生探测只支持 IP 字面量，域名仍交给 ping 命令解析
+        if self.prober != Prober::Ping && !self.native_unavailable.load(Ordering::Relaxed) {
+            if let Ok(addr) = target.parse::<IpAddr>() {
+                if let Some(stats) = self.native_ping(interface, addr, count, size, tos).await {
+                    return stats;
+                }
+            }
+        }
+
+        self.ping_command_with_stats(interface, target, count, size, tos)
+            .await
+    }
+
+    /// 用 ICMP 套接字探测 IP 目标
+    /// 返回: 探测结果；创建套接字时缺少权限或协议不受支持时返回 `None`，此后改用 ping 命令
+    async fn native_ping(
+        &self,
+        interface: &str,
+        addr: IpAddr,
+        count: u32,
+        size: u32,
+        tos: Option<u8>,
+    ) -> Option<PingStats> {
+        let raw = self.prober == Prober::Raw;
+        let socket = match icmp::open_socket(interface, addr, tos, raw) {
+            Ok(socket) => socket,
+            Err(e) if e.raw_os_error() == Some(ENODEV) => {
+                debug!("原生探测: 接口 {} 不存在", interface);
+                return Some(PingStats::failed(Some(FailureReason::InterfaceMissing)));
+            }
+            Err(e) if icmp::is_unsupported(&e) => {
+                if !self.native_unavailable.swap(true, Ordering::Relaxed) {
+                    warn!("无法使用原生 ICMP 探测，回退到 ping 命令: {}", e);
+                }
+                return None;
+            }
+            Err(e) => {
+                warn!("创建 ICMP 探测套接字失败: {} -> {}: {}", interface, addr, e);
+                return Some(PingStats::failed(None));
+            }
+        };
+
+        let samples = match icmp::ping(&socket, addr, count, size, self.timeout_duration, raw).await
+        {
+            Ok(samples) => samples,
+            Err(e) => {
+                debug!("原生探测: {} -> {} 失败: {}", interface, addr, e);
+                return Some(PingStats::failed(None));
+            }
+        };
+        let (reachable, latency_ms, packet_loss) = icmp::stats_from_samples(&samples);
+        let received: Vec<f64> = samples.iter().flatten().copied().collect();
+        debug!(
+            "原生探测: {} -> {} (延迟: {:?}ms, 丢包: {:?})",
+            interface, addr, latency_ms, packet_loss
+        );
+        // 原生套接字只接收 echo 应答，失败一律视为超时
+        Some(PingStats {
+            reachable,
+            latency_ms,
+            packet_loss,
+            jitter_ms: rtt_mdev(&received),
+            rtt_samples: received,
+            failure_reason: (!reachable).then_some(FailureReason::Timeout),
+        })
+    }
+
+    /// 用 fping 批量探测 IP 目标：按报文数、负载大小和 ToS 分组，每组调用一次
+    /// 返回: 目标地址 → 探测结果；TCP 目标、域名目标和 fping 无法执行时不在其中，仍逐个探测
+    async fn fping_targets(
+        &self,
+        interface: &str,
+        targets: &[TargetIP],
+    ) -> HashMap<String, PingStats> {
+        let mut probed = HashMap::new();
+        if self.fping_unavailable.load(Ordering::Relaxed) {
+            return probed;
+        }
+
+        // (报文数, 负载大小, ToS) → [(目标, 探测地址)]
+        type GroupKey = (u32, u32, Option<u8>);
+        let mut groups: BTreeMap<GroupKey, Vec<(&str, &str)>> = BTreeMap::new();
+        for target in targets {
+            let address = target.address.split('/').next().unwrap_or(&target.address);
+            if (target.probe == ProbeType::Tcp && target.port.is_some())
+                || address.parse::<IpAddr>().is_err()
+            {
+                continue;
+            }
+            let count = target.ping_count.unwrap_or(self.ping_count).max(1);
+            let size = target.ping_size.unwrap_or(self.ping_size);
+            groups
+                .entry((count, size, target.tos()))
+                .or_default()
+                .push((target.address.as_str(), address));
+        }
+
+        for ((count, size, tos), members) in groups {
+            let addresses: Vec<&str> = members.iter().map(|(_, address)| *address).collect();
+            if self.warmup_ping {
+                self.fping(interface, &addresses, 1, size, tos).await;
+            }
+            let Some(mut stats) = self.fping(interface, &addresses, count, size, tos).await else {
+                break;
+            };
+            for (target, address) in members {
+                let result = stats
+                    .remove(address)
+                    .unwrap_or_else(|| PingStats::failed(Some(FailureReason::Timeout)));
+                probed.insert(target.to_string(), result);
+            }
+        }
+
+        probed
+    }
+
+    /// 执行一次 fping 并解析标准错误中的汇总行
+    /// 返回: 地址 → 探测结果；fping 无法执行时为 `None`
+    async fn fping(
+        &self,
+        interface: &str,
+        addresses: &[&str],
+        count: u32,
+        size: u32,
+        tos: Option<u8>,
+    ) -> Option<HashMap<String, PingStats>> {
+        // -q 只输出汇总，-t 为等待应答的超时（毫秒），-b 指定负载大小（默认值时省略），-O 指定 ToS
+        let count_arg = count.to_string();
+        let timeout_arg = self.timeout_duration.as_millis().to_string();
+        let size_arg = (size != DEFAULT_PING_SIZE).then(|| size.to_string());
+        let tos_arg = tos.map(|tos| tos.to_string());
+        let mut args = vec!["-I", interface, "-c", &count_arg, "-q", "-t", &timeout_arg];
+        if let Some(size) = &size_arg {
+            args.extend(["-b", size.as_str()]);
+        }
+        if let Some(tos) = &tos_arg {
+            args.extend(["-O", tos.as_str()]);
+        }
+        args.extend(addresses);
+
+        let output = match self
+            .runner
+            .run_with_timeout("fping", &args, self.timeout_duration * (count + 1))
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                if !self.fping_unavailable.swap(true, Ordering::Relaxed) {
+                    warn!("无法执行 fping，回退到逐个目标的 ping 命令: {}", e);
+                }
+                return None;
+            }
+        };
+
+        // 有目标不可达时 fping 以非零状态退出，结果仍以汇总行为准
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_missing_interface_error(&stderr) {
+            debug!("fping 失败: 接口 {} 不存在", interface);
+            return Some(
+                addresses
+                    .iter()
+                    .map(|address| {
+                        (
+                            address.to_string(),
+                            PingStats::failed(Some(FailureReason::InterfaceMissing)),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+
+        let stats = parse_fping_summary(&stderr);
+        debug!(
+            "fping: {} 个目标经接口 {} 探测，{} 个有汇总结果",
+            addresses.len(),
+            interface,
+            stats.len()
+        );
+        Some(stats)
+    }
+
+    /// 通过 TCP 连接测试连通性：经接口发起 `count` 次连接（与 ping 报文数相同），
+    /// 延迟取成功连接耗时的平均值，丢包率为连接失败的比例
+    async fn tcp_connect_test(
+        &self,
+        interface: &str,
+        addr: &str,
+        port: u16,
+        count: u32,
+    ) -> PingStats {
+        let target = match addr.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, port),
+            Err(_) => match tokio::net::lookup_host((addr, port))
+                .await
+                .ok()
+                .and_then(|mut a| a.next())
+            {
+                Some(target) => target,
+                None => {
+                    debug!("TCP 探测: 无法解析 {}", addr);
+                    return PingStats::failed(Some(FailureReason::ResolveFailed));
+                }
+            },
+        };
+
+        let mut latencies = Vec::new();
+        let mut failure_reason = None;
+        for _ in 0..count {
+            let socket = match probe::probe_socket(
+                Some(interface),
+                target,
+                Transport::Tcp,
+                self.randomize_source_port,
+            ) {
+                Ok(socket) => socket,
+                Err(e) if e.raw_os_error() == Some(ENODEV) => {
+                    debug!("TCP 探测: 接口 {} 不存在", interface);
+                    return PingStats::failed(Some(FailureReason::InterfaceMissing));
+                }
+                Err(e) => {
+                    warn!("创建 TCP 探测套接字失败: {}", e);
+                    return PingStats::failed(None);
+                }
+            };
+
+            let timeout = self.timeout_duration;
+            let started = std::time::Instant::now();
+            let connected = tokio::task::spawn_blocking(move || {
+                socket.connect_timeout(&target.into(), timeout)
+            })
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+
+            match connected {
+                Ok(()) => latencies.push(started.elapsed().as_secs_f64() * 1000.0),
+                Err(e) => {
+                    debug!("TCP 探测: {} -> {} 连接失败: {}", interface, target, e);
+                    failure_reason = Some(if e.kind() == std::io::ErrorKind::TimedOut {
+                        FailureReason::Timeout
+                    } else {
+                        FailureReason::Unreachable
+                    });
+                }
+            }
+        }
+
+        let reachable = !latencies.is_empty();
+        let packet_loss = (count as usize - latencies.len()) as f64 / count as f64;
+        let latency_ms = reachable.then(|| latencies.iter().sum::<f64>() / latencies.len() as f64);
+        debug!(
+            "TCP 探测: {} -> {} (延迟: {:?}ms, 丢包: {:.1}%)",
+            interface,
+            target,
+            latency_ms,
+            packet_loss * 100.0
+        );
+
+        PingStats {
+            reachable,
+            latency_ms,
+            packet_loss: Some(packet_loss),
+            jitter_ms: rtt_mdev(&latencies),
+            rtt_samples: latencies,
+            failure_reason: if reachable { None } else { failure_reason },
+        }
+    }
+
+    /// 调用 ping 命令测试连接性并解析统计信息
+    async fn ping_command_with_stats(
+        &self,
+        interface: &str,
+        target: &str,
+        count: u32,
+        size: u32,
+        tos: Option<u8>,
+    ) -> PingStats {
+        // 在 OpenWrt 上使用 ping 命令测试连接
+        // -I 指定接口，-c 指定次数，-W 指定超时，-s 指定负载大小（默认值时省略），
+        // -Q 指定 ToS，IPv6 目标加 -6
+        let count_arg = count.to_string();
+        let wait_arg = self.timeout_duration.as_secs().to_string();
+        let size_arg = (size != DEFAULT_PING_SIZE).then(|| size.to_string());
+        let tos_arg = tos.map(|tos| tos.to_string());
+        let mut args = vec!["-I", interface, "-c", &count_arg, "-W", &wait_arg];
+        if let Some(size) = &size_arg {
+            args.extend(["-s", size.as_str()]);
+        }
+        if let Some(tos) = &tos_arg {
+            args.extend(["-Q", tos.as_str()]);
+        }
+        args.extend(family_arg(target));
+        args.push(target);
+
+        let output = match self
+            .runner
+            .run_with_timeout("ping", &args, self.timeout_duration * count)
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("执行 ping 命令失败: {}", e);
+                return PingStats::failed(None);
+            }
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let completed = output.status.is_some();
+
+        if is_missing_interface_error(&String::from_utf8_lossy(&output.stderr)) {
+            debug!("Ping 失败: 接口 {} 不存在", interface);
+            return PingStats::failed(Some(FailureReason::InterfaceMissing));
+        }
+
+        let (reachable, latency_ms, packet_loss) = if completed {
+            let success = output.status.is_some_and(|status| status.success());
+            let (packet_loss, avg_latency) = parse_ping_summary(&stdout);
+            (
+                success && packet_loss.unwrap_or(1.0) < 1.0,
+                avg_latency,
+                packet_loss,
+            )
+        } else {
+            // 外层超时：根据已输出的应答行估算
+            match self.timeout_loss {
+                TimeoutLoss::Partial => {
+                    let (packet_loss, avg_latency) = parse_ping_replies(&stdout, count);
+                    warn!(
+                        "Ping 超时: {} -> {}，按已收到的部分结果计算 (丢包: {:.1}%)",
+                        interface,
+                        target,
+                        packet_loss * 100.0
+                    );
+                    (avg_latency.is_some(), avg_latency, Some(packet_loss))
+                }
+                TimeoutLoss::Total => {
+                    warn!("Ping 超时: {} -> {}", interface, target);
+                    (false, None, Some(1.0))
+                }
+            }
+        };
+
+        let jitter_ms = parse_ping_jitter(&stdout);
+        let rtt_samples = parse_reply_times(&stdout);
+        if reachable {
+            debug!(
+                "Ping 成功: {} -> {} (延迟: {:.2}ms, 抖动: {:.2}ms, 丢包: {:.1}%)",
+                interface,
+                target,
+                latency_ms.unwrap_or(0.0),
+                jitter_ms.unwrap_or(0.0),
+                packet_loss.unwrap_or(0.0) * 100.0
+            );
+            return PingStats {
+                reachable,
+                latency_ms,
+                packet_loss,
+                jitter_ms,
+                rtt_samples,
+                failure_reason: None,
+            };
+        }
+
+        // 收到 ICMP 差错报文说明报文到达了网络，只是被路由或过滤拦下
+        let reason = parse_failure_reason(&stdout).unwrap_or(FailureReason::Timeout);
+        debug!("Ping 失败: {} -> {} ({})", interface, target, reason);
+
+        PingStats {
+            reachable,
+            latency_ms,
+            packet_loss,
+            jitter_ms,
+            rtt_samples,
+            failure_reason: Some(reason),
+        }
+    }
+
+    /// 速度测试
+    async fn speed_test(&self, interface: &str, test_url: &str) -> Result<f64> {
+        // 等待其他接口的速度测试结束（ping 探测不受限制）
+        let _permit = self.speed_test_permits.acquire().await?;
+
+        // 使用 curl 通过指定接口下载测试文件
+        let args = [
+            "--interface",
+            interface,
+            "-s",
+            "-o",
+            "/dev/null",
+            "-w",
+            "%{speed_download}",
+            test_url,
+        ];
+        let result = self
+            .runner
+            .run_with_timeout("curl", &args, self.timeout_duration * 2) // 速度测试给更多时间
+            .await
+            .context("执行 curl 命令失败")?;
+
+        match result.status {
+            None => anyhow::bail!("速度测试超时"),
+            Some(status) if !status.success() => anyhow::bail!("curl 命令执行失败"),
+            Some(_) => {}
+        }
+
+        let speed_kb = parse_curl_speed(&result.stdout)?;
+
+        debug!(
+            "速度测试完成: {} -> {} ({:.2} KB/s)",
+            interface, test_url, speed_kb
+        );
+
+        Ok(speed_kb)
+    }
+
+    /// 上传速度测试：经标准输入向 `upload_url` 上传固定大小的数据
+    async fn speed_test_upload(&self, interface: &str, upload_url: &str) -> Result<f64> {
+        let _permit = self.speed_test_permits.acquire().await?;
+
+        let args = [
+            "--interface",
+            interface,
+            "-s",
+            "-o",
+            "/dev/null",
+            "-T",
+            "-",
+            "-w",
+            "%{speed_upload}",
+            upload_url,
+        ];
+        let payload = vec![0u8; UPLOAD_TEST_SIZE];
+        let output = self
+            .runner
+            .run_with_input("curl", &args, &payload, self.timeout_duration * 2)
+            .await
+            .context("执行 curl 上传命令失败")?;
+
+        if !output.status.success() {
+            anyhow::bail!("curl 上传命令执行失败");
+        }
+
+        let speed_kb = parse_curl_speed(&output.stdout)?;
+
+        debug!(
+            "上传速度测试完成: {} -> {} ({:.2} KB/s)",
+            interface, upload_url, speed_kb
+        );
+
+        Ok(speed_kb)
+    }
+
+    /// 获取能够到达指定目标的接口列表
+    pub fn reachable_interfaces<'a>(
+        &self,
+        results: &'a [TestResult],
+        target: &str,
+    ) -> Vec<&'a str> {
+        let mut interfaces: Vec<&str> = results
+            .iter()
+            .filter(|r| r.reachable && r.target == target)
+            .map(|r| r.interface.as_str())
+            .collect();
+        interfaces.sort_unstable();
+        interfaces.dedup();
+        interfaces
+    }
+
+    /// 获取最佳接口（跳过备用、不存在和观察期中的接口）
+    pub fn get_best_interface<'a>(
+        &self,
+        scores: &'a [InterfaceScore],
+    ) -> Option<&'a InterfaceScore> {
+        scores.iter().find(|s| s.is_eligible())
+    }
+}
+
+/// 解析 curl `-w` 输出的速度（字节/秒），转换为 KB/s
+fn parse_curl_speed(stdout: &[u8]) -> Result<f64> {
+    let speed_bytes = String::from_utf8_lossy(stdout)
+        .trim()
+        .parse::<f64>()
+        .context("解析速度数据失败")?;
+    Ok(speed_bytes / 1024.0)
+}
+
+/// 目标地址（可带 CIDR 后缀，如 `/128`）对应的 IP，域名返回 `None`
+fn target_ip(target: &str) -> Option<IpAddr> {
+    target.split('/').next()?.parse().ok()
+}
+
+/// ping 命令的地址族参数：IPv6 目标需要显式加 `-6`，部分 ping 实现不会按地址自动切换
+fn family_arg(target: &str) -> Option<&'static str> {
+    target_ip(target)
+        .is_some_and(|ip| ip.is_ipv6())
+        .then_some("-6")
+}
+
+/// 读取系统 1 分钟平均负载
+fn system_load() -> Option<f64> {
+    std::fs::read_to_string("/proc/loadavg")
+        .ok()
+        .and_then(|content| parse_loadavg(&content))
+}
+
+/// 解析 /proc/loadavg 的第一列（1 分钟平均负载）
+fn parse_loadavg(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+/// 所有目标均不可达的接口
+pub fn unreachable_interfaces(results: &[TestResult]) -> Vec<String> {
+    let mut interfaces: Vec<String> = Vec::new();
+    for result in results {
+        if !interfaces.contains(&result.interface) {
+            interfaces.push(result.interface.clone());
+        }
+    }
+
+    interfaces.retain(|iface| {
+        results
+            .iter()
+            .filter(|r| &r.interface == iface)
+            .all(|r| !r.reachable)
+    });
+    interfaces
+}
+
+/// 在所有接口上都不可达的目标（至少需要两个接口的结果才能判断）
+pub fn universally_unreachable_targets(results: &[TestResult]) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for result in results {
+        if !targets.contains(&result.target) {
+            targets.push(result.target.clone());
+        }
+    }
+
+    targets.retain(|target| {
+        let target_results: Vec<_> = results.iter().filter(|r| &r.target == target).collect();
+        let mut interfaces: Vec<&str> = target_results
+            .iter()
+            .map(|r| r.interface.as_str())
+            .collect();
+        interfaces.sort_unstable();
+        interfaces.dedup();
+        interfaces.len() > 1 && target_results.iter().all(|r| !r.reachable)
+    });
+    targets
+}
+
+/// 目标在第 `iteration` 轮检查（从 1 开始）是否需要探测
+///
+/// `check_every = N` 的目标在第 1、1+N、1+2N… 轮探测
+pub fn due_this_cycle(target: &TargetIP, iteration: u64) -> bool {
+    let every = u64::from(target.check_every.max(1));
+    iteration.saturating_sub(1).is_multiple_of(every)
+}
+
+/// 持续失效目标的熔断器
+///
+/// 目标连续 `threshold` 轮在所有接口上都不可达后，只每隔 `reprobe_every` 轮探测一次，
+/// 一旦在任一接口上可达即恢复每轮探测
+#[derive(Debug, Default)]
+pub struct DeadTargetBreaker {
+    threshold: u32,
+    reprobe_every: u32,
+    /// 各目标连续在所有接口上不可达的轮数
+    streak: HashMap<String, u32>,
+    /// 熔断后已跳过的轮数
+    skipped: HashMap<String, u32>,
+}
+
+impl DeadTargetBreaker {
+    /// `threshold` 为 0 时不熔断
+    pub fn new(threshold: u32, reprobe_every: u32) -> Self {
+        Self {
+            threshold,
+            reprobe_every: reprobe_every.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// 目标连续在所有接口上不可达的轮数
+    pub fn streak(&self, target: &str) -> u32 {
+        self.streak.get(target).copied().unwrap_or_default()
+    }
+
+    fn tripped(&self, target: &str) -> bool {
+        self.threshold > 0
+            && self
+                .streak
+                .get(target)
+                .is_some_and(|n| *n >= self.threshold)
+    }
+
+    /// 本轮需要跳过的目标，调用一次即推进一轮
+    pub fn skipped_targets<'a>(
+        &mut self,
+        targets: impl IntoIterator<Item = &'a str>,
+    ) -> HashSet<String> {
+        let mut skip = HashSet::new();
+        for target in targets {
+            if !self.tripped(target) {
+                continue;
+            }
+            let count = self.skipped.entry(target.to_string()).or_default();
+            *count += 1;
+            if *count >= self.reprobe_every {
+                // 定期重新探测，确认目标是否恢复
+                *count = 0;
+            } else {
+                skip.insert(target.to_string());
+            }
+        }
+        skip
+    }
+
+    /// 根据本轮的探测结果更新计数
+    pub fn record(&mut self, results: &[TestResult]) {
+        let down = universally_unreachable_targets(results);
+        let mut probed: Vec<&str> = results.iter().map(|r| r.target.as_str()).collect();
+        probed.sort_unstable();
+        probed.dedup();
+
+        for target in probed {
+            if down.iter().any(|d| d == target) {
+                let count = self.streak.entry(target.to_string()).or_default();
+                *count += 1;
+                if self.threshold > 0 && *count == self.threshold {
+                    warn!(
+                        "目标 {} 已连续 {} 轮在所有接口上不可达，降低探测频率（每 {} 轮一次），建议从配置中移除",
+                        target, count, self.reprobe_every
+                    );
+                }
+            } else if let Some(count) = self.streak.remove(target) {
+                self.skipped.remove(target);
+                if self.threshold > 0 && count >= self.threshold {
+                    info!("目标 {} 已恢复可达，恢复每轮探测", target);
+                }
+            }
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// 合并确认目标的探测结果
+///
+/// 确认目标有任一可达的接口视为链路正常（主目标失败归因于目标侧），
+/// 其评分改用确认目标的结果；确认目标也全部失败的接口保留原结果
+pub fn merge_confirmations(
+    results: &[TestResult],
+    confirmations: &[TestResult],
+) -> Vec<TestResult> {
+    let confirmed: Vec<&str> = confirmations
+        .iter()
+        .filter(|r| r.reachable)
+        .map(|r| r.interface.as_str())
+        .collect();
+
+    results
+        .iter()
+        .filter(|r| !confirmed.contains(&r.interface.as_str()))
+        .chain(
+            confirmations
+                .iter()
+                .filter(|r| confirmed.contains(&r.interface.as_str())),
+        )
+        .cloned()
+        .collect()
+}
+
+/// 解析 ping 的汇总行
+/// 返回: (丢包率0.0-1.0, 平均延迟ms)
+fn parse_ping_summary(stdout: &str) -> (Option<f64>, Option<f64>) {
+    // 格式示例: "4 packets transmitted, 3 received, 25% packet loss, time 3005ms"
+    // "rtt min/avg/max/mdev = 10.123/15.456/20.789/3.210 ms"
+    let mut packet_loss = None;
+    let mut avg_latency = None;
+
+    for line in stdout.lines() {
+        // 解析丢包率
+        if line.contains("packet loss") {
+            if let Some(loss_str) = line.split('%').next() {
+                if let Some(num_str) = loss_str.split_whitespace().last() {
+                    if let Ok(loss) = num_str.parse::<f64>() {
+                        packet_loss = Some(loss / 100.0);
+                    }
+                }
+            }
+        }
+
+        // 解析平均延迟
+        if line.contains("rtt") || line.contains("round-trip") {
+            if let Some(stats_part) = line.split('=').nth(1) {
+                let parts: Vec<&str> = stats_part.trim().split('/').collect();
+                if parts.len() >= 2 {
+                    if let Ok(avg) = parts[1].trim().parse::<f64>() {
+                        avg_latency = Some(avg);
+                    }
+                }
+            }
+        }
+    }
+
+    (packet_loss, avg_latency)
+}
+
+/// 每轮探测次数预算
+///
+/// 关键目标每轮都探测；其余目标按权重从高到低排列，从轮转游标处依次选取直到预算用完，
+/// 本轮未选中的目标在之后的轮次轮到
+#[derive(Debug, Default)]
+pub struct ProbeBudget {
+    /// 每轮最多探测次数（0 表示不限制）
+    max_probes: usize,
+    /// 下一轮从第几个非关键目标开始
+    cursor: usize,
+}
+
+impl ProbeBudget {
+    pub fn new(max_probes: usize) -> Self {
+        Self {
+            max_probes,
+            cursor: 0,
+        }
+    }
+
+    /// 返回本轮超出预算而跳过的目标
+    ///
+    /// `targets` 为本轮待探测的目标及各自的探测次数（探测该目标的接口数）
+    pub fn over_budget(&mut self, targets: &[(&TargetIP, usize)]) -> HashSet<String> {
+        if self.max_probes == 0 {
+            return HashSet::new();
+        }
+
+        let mut used: usize = targets
+            .iter()
+            .filter(|(t, _)| t.critical)
+            .map(|(_, cost)| cost)
+            .sum();
+        let mut optional: Vec<&(&TargetIP, usize)> =
+            targets.iter().filter(|(t, _)| !t.critical).collect();
+        if optional.is_empty() {
+            return HashSet::new();
+        }
+        optional.sort_by(|a, b| b.0.weight.total_cmp(&a.0.weight));
+
+        let start = self.cursor % optional.len();
+        let mut taken = 0;
+        let mut skipped = HashSet::new();
+        for i in 0..optional.len() {
+            let (target, cost) = optional[(start + i) % optional.len()];
+            if skipped.is_empty() && used + cost <= self.max_probes {
+                used += cost;
+                taken += 1;
+            } else {
+                skipped.insert(target.address.clone());
+            }
+        }
+
+        self.cursor = start + taken;
+        skipped
+    }
+}
+
+/// 将主接口与各 VLAN 子接口到同一目标的结果合并为主接口的结果
+///
+/// `vlan_results` 中每一项为一个 VLAN 对全部目标的结果，目标顺序与 `results` 一致
+pub fn aggregate_vlan_results(
+    results: Vec<TestResult>,
+    vlan_results: &[Vec<TestResult>],
+    aggregation: VlanAggregation,
+) -> Vec<TestResult> {
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut result)| {
+            let members: Vec<&TestResult> = vlan_results.iter().filter_map(|r| r.get(i)).collect();
+            let total = members.len() + 1;
+            let reachable_count =
+                members.iter().filter(|r| r.reachable).count() + result.reachable as usize;
+            let latencies: Vec<f64> = std::iter::once(result.latency_ms)
+                .chain(members.iter().map(|r| r.latency_ms))
+                .flatten()
+                .collect();
+            let losses = std::iter::once(result.packet_loss.unwrap_or(1.0))
+                .chain(members.iter().map(|r| r.packet_loss.unwrap_or(1.0)));
+
+            match aggregation {
+                VlanAggregation::Worst => {
+                    result.reachable = reachable_count == total;
+                    result.latency_ms = latencies.into_iter().reduce(f64::max);
+                    result.packet_loss = losses.reduce(f64::max);
+                }
+                VlanAggregation::Average => {
+                    result.reachable = reachable_count > 0;
+                    result.latency_ms = (!latencies.is_empty())
+                        .then(|| latencies.iter().sum::<f64>() / latencies.len() as f64);
+                    result.packet_loss = Some(losses.sum::<f64>() / total as f64);
+                    let ratio = reachable_count as f64 / total as f64;
+                    result.health = Some(result.health.unwrap_or(1.0).min(1.0) * ratio);
+                }
+            }
+
+            if result.reachable {
+                result.failure_reason = None;
+            } else if result.failure_reason.is_none() {
+                result.failure_reason = members.iter().find_map(|r| r.failure_reason);
+            }
+            result
+        })
+        .collect()
+}
+
+/// 判断 ping/curl 的错误输出是否表示接口不存在
+fn is_missing_interface_error(stderr: &str) -> bool {
+    // 格式示例: "ping: SO_BINDTODEVICE: No such device"
+    // "ping: unknown iface wwan0"
+    // "curl: (45) bind failed with errno 19: No such device"
+    let stderr = stderr.to_ascii_lowercase();
+    stderr.contains("no such device")
+        || stderr.contains("unknown iface")
+        || stderr.contains("unknown interface")
+        || stderr.contains("errno 19")
+}
+
+/// 解析 `fping -q -c` 的汇总行
+///
+/// 格式示例: "8.8.8.8 : xmt/rcv/%loss = 4/4/0%, min/avg/max = 9.12/10.3/11.5"
+/// 全部丢失时没有延迟部分: "10.0.0.1 : xmt/rcv/%loss = 4/0/100%"
+fn parse_fping_summary(stderr: &str) -> HashMap<String, PingStats> {
+    let mut stats = HashMap::new();
+    for line in stderr.lines() {
+        let Some((address, summary)) = line.split_once(" : ") else {
+            continue;
+        };
+        let Some(counts) = summary
+            .strip_prefix("xmt/rcv/%loss = ")
+            .and_then(|rest| rest.split(',').next())
+        else {
+            continue;
+        };
+        let mut counts = counts.split('/');
+        let (Some(Ok(sent)), Some(Ok(received))) = (
+            counts.next().map(|n| n.trim().parse::<u32>()),
+            counts.next().map(|n| n.trim().parse::<u32>()),
+        ) else {
+            continue;
+        };
+        if sent == 0 {
+            continue;
+        }
+
+        let latency_ms = summary
+            .split_once("min/avg/max = ")
+            .and_then(|(_, rtt)| rtt.split('/').nth(1))
+            .and_then(|avg| avg.trim().parse::<f64>().ok());
+        let reachable = received > 0;
+        stats.insert(
+            address.trim().to_string(),
+            PingStats {
+                reachable,
+                latency_ms,
+                packet_loss: Some(1.0 - received.min(sent) as f64 / sent as f64),
+                jitter_ms: None,
+                rtt_samples: Vec::new(),
+                failure_reason: (!reachable).then_some(FailureReason::Timeout),
+            },
+        );
+    }
+    stats
+}
+
+/// 从 ping 输出中识别 ICMP 差错报文
+fn parse_failure_reason(stdout: &str) -> Option<FailureReason> {
+    // 格式示例: "From 10.0.0.1 icmp_seq=1 Destination Host Unreachable"
+    // "From 10.0.0.1 icmp_seq=1 Time to live exceeded"
+    // "From 10.0.0.1 icmp_seq=1 Packet filtered"
+    // "From fe80::1%eth0 icmp_seq=1 Destination unreachable: Administratively prohibited"
+    stdout.lines().find_map(|line| {
+        let line = line.to_ascii_lowercase();
+        if line.contains("filtered") || line.contains("prohibited") {
+            Some(FailureReason::Filtered)
+        } else if line.contains("time to live exceeded")
+            || line.contains("ttl exceeded")
+            || line.contains("time exceeded")
+        {
+            Some(FailureReason::TtlExceeded)
+        } else if line.contains("unreachable") {
+            Some(FailureReason::Unreachable)
+        } else {
+            None
+        }
+    })
+}
+
+/// 根据逐包应答行估算结果（用于没有汇总行的部分输出）
+/// 返回: (丢包率0.0-1.0, 平均延迟ms)
+fn parse_ping_replies(stdout: &str, count: u32) -> (f64, Option<f64>) {
+    let latencies = parse_reply_times(stdout);
+
+    let expected = count.max(latencies.len() as u32).max(1) as f64;
+    let packet_loss = 1.0 - latencies.len() as f64 / expected;
+    let avg_latency = if latencies.is_empty() {
+        None
+    } else {
+        Some(latencies.iter().sum::<f64>() / latencies.len() as f64)
+    };
+
+    (packet_loss, avg_latency)
+}
+
+/// 解析 ping 输出中的抖动（毫秒）
+///
+/// 优先取 `rtt min/avg/max/mdev` 中的 mdev；busybox 的汇总行没有 mdev 时，按各应答行的
+/// `time=` 计算标准差
+fn parse_ping_jitter(stdout: &str) -> Option<f64> {
+    // 格式示例: "rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms"
+    let mdev = stdout
+        .lines()
+        .filter(|line| line.contains("mdev"))
+        .filter_map(|line| line.split('=').nth(1))
+        .filter_map(|stats| stats.trim().split('/').nth(3))
+        .filter_map(|value| value.split_whitespace().next()?.parse::<f64>().ok())
+        .next();
+    if mdev.is_some() {
+        return mdev;
+    }
+
+    rtt_mdev(&parse_reply_times(stdout))
+}
+
+/// 解析各应答行的往返时间（毫秒）
+fn parse_reply_times(stdout: &str) -> Vec<f64> {
+    // 格式示例: "64 bytes from 8.8.8.8: icmp_seq=1 ttl=117 time=10.5 ms"
+    stdout
+        .lines()
+        .filter(|line| line.contains("bytes from"))
+        .filter_map(|line| line.split("time=").nth(1))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .filter_map(|value| value.parse::<f64>().ok())
+        .collect()
+}
+
+/// 往返时间的标准差（与 ping 的 mdev 算法相同），少于两个样本时无法衡量抖动
+fn rtt_mdev(latencies: &[f64]) -> Option<f64> {
+    if latencies.len() < 2 {
+        return None;
+    }
+    let n = latencies.len() as f64;
+    let mean = latencies.iter().sum::<f64>() / n;
+    let variance = latencies.iter().map(|v| v * v).sum::<f64>() / n - mean * mean;
+    Some(variance.max(0.0).sqrt())
+}
+
+/// 按评分降序排序，NaN/无穷大等异常评分排在最后
+pub fn sort_scores(scores: &mut [InterfaceScore]) {
+    scores.sort_by(|a, b| match (a.score.is_finite(), b.score.is_finite()) {
+        (true, true) => b.score.total_cmp(&a.score),
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        (false, false) => a.interface.cmp(&b.interface),
+    });
+}
+
+/// 将虚拟接口的成员评分合并为虚拟接口的评分，其余接口保持不变
+///
+/// 只合并可参与选择的成员；没有可用成员的虚拟接口不出现在结果中
+pub fn virtual_interface_scores(
+    virtual_interfaces: &[VirtualInterface],
+    scores: &[InterfaceScore],
+) -> Vec<InterfaceScore> {
+    let is_member = |name: &str| {
+        virtual_interfaces
+            .iter()
+            .any(|v| v.members.iter().any(|m| m == name))
+    };
+    let mut units: Vec<InterfaceScore> = scores
+        .iter()
+        .filter(|s| !is_member(&s.interface))
+        .cloned()
+        .collect();
+
+    for virtual_interface in virtual_interfaces {
+        let members: Vec<&InterfaceScore> = scores
+            .iter()
+            .filter(|s| virtual_interface.members.contains(&s.interface))
+            .filter(|s| s.is_eligible())
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+
+        let unit = match virtual_interface.aggregation {
+            VirtualAggregation::Best => {
+                let mut ranked: Vec<InterfaceScore> =
+                    members.iter().map(|s| (*s).clone()).collect();
+                sort_scores(&mut ranked);
+                ranked.swap_remove(0)
+            }
+            VirtualAggregation::Average => {
+                let n = members.len() as f64;
+                let latencies: Vec<f64> = members
+                    .iter()
+                    .map(|s| s.avg_latency_ms)
+                    .filter(|v| v.is_finite())
+                    .collect();
+                let jitters: Vec<f64> = members.iter().filter_map(|s| s.avg_jitter_ms).collect();
+                let mean = |values: Vec<f64>| {
+                    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+                };
+                InterfaceScore {
+                    interface: String::new(),
+                    reachable_count: members.iter().map(|s| s.reachable_count).sum(),
+                    reachable_v4: members.iter().map(|s| s.reachable_v4).sum(),
+                    reachable_v6: members.iter().map(|s| s.reachable_v6).sum(),
+                    avg_latency_ms: if latencies.is_empty() {
+                        f64::INFINITY
+                    } else {
+                        latencies.iter().sum::<f64>() / latencies.len() as f64
+                    },
+                    p50_latency_ms: mean(members.iter().filter_map(|s| s.p50_latency_ms).collect()),
+                    p95_latency_ms: mean(members.iter().filter_map(|s| s.p95_latency_ms).collect()),
+                    avg_jitter_ms: mean(jitters),
+                    avg_packet_loss: members.iter().map(|s| s.avg_packet_loss).sum::<f64>() / n,
+                    avg_speed: members.iter().map(|s| s.avg_speed).sum(),
+                    score: members.iter().map(|s| s.score).sum::<f64>() / n,
+                    eligibility: Eligibility::Eligible,
+                }
+            }
+        };
+        units.push(InterfaceScore {
+            interface: virtual_interface.name.clone(),
+            ..unit
+        });
+    }
+
+    sort_scores(&mut units);
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AddressFamily;
+
+    #[test]
+    fn test_score_calculation() {
+        let results = vec![
+            TestResult {
+                interface: "eth0".to_string(),
+                target: "8.8.8.8".to_string(),
+                resolved_address: None,
+                reachable: true,
+                latency_ms: Some(10.0),
+                jitter_ms: None,
+                rtt_samples: Vec::new(),
+                packet_loss: Some(0.0),
+                download_speed: Some(1024.0),
+                upload_speed: None,
+                failure_reason: None,
+                health: None,
+                dscp: None,
+                tested_at: chrono::Local::now(),
+            },
+            TestResult {
+                interface: "eth0".to_string(),
+                target: "1.1.1.1".to_string(),
+                resolved_address: None,
+                reachable: true,
+                latency_ms: Some(15.0),
+                jitter_ms: None,
+                rtt_samples: Vec::new(),
+                packet_loss: Some(0.0),
+                download_speed: Some(2048.0),
+                upload_speed: None,
+                failure_reason: None,
+                health: None,
+                dscp: None,
+                tested_at: chrono::Local::now(),
+            },
+        ];
+
+        let tester = NetworkTester::new(5, 4);
+        let scores = tester.calculate_scores(&results);
+
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].interface, "eth0");
+        assert_eq!(scores[0].reachable_count, 2);
+    }
+
+    #[test]
+    fn test_result_cache_last_result() {
+        let result = |target: &str, latency: f64| TestResult {
+            interface: "eth0".to_string(),
+            target: target.to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+
+        let cache = ResultCache::default();
+        cache.update(&[result("8.8.8.8", 10.0), result("1.1.1.1", 20.0)]);
+        cache.update(&[result("8.8.8.8", 30.0)]);
+
+        let latest = cache.last_result("eth0", "8.8.8.8").unwrap();
+        assert_eq!(latest.latency_ms, Some(30.0));
+        assert_eq!(
+            cache.last_result("eth0", "1.1.1.1").unwrap().latency_ms,
+            Some(20.0)
+        );
+        assert!(cache.last_result("eth1", "8.8.8.8").is_none());
+        assert!(cache.last_result("eth0", "9.9.9.9").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_native_probe_failure_keeps_native_prober() {
+        use crate::command::mock::{output, MockRunner};
+
+        let target = IpAddr::from([127, 0, 0, 1]);
+        // 选择当前环境能创建套接字的探测方式，都不能创建时无法验证
+        let Some(prober) = [Prober::Native, Prober::Raw]
+            .into_iter()
+            .find(|prober| icmp::open_socket("lo", target, None, *prober == Prober::Raw).is_ok())
+        else {
+            return;
+        };
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        let tester = NetworkTester::new(1, 1)
+            .with_runner(runner.clone())
+            .with_prober(prober);
+
+        // 超过 IP 报文上限的负载在发送时出错，只算一次失败的探测
+        let stats = tester
+            .ping_test_with_stats("lo", "127.0.0.1", 1, 70000, None)
+            .await;
+        assert!(!stats.reachable);
+        assert!(!tester.native_unavailable.load(Ordering::Relaxed));
+        assert!(runner.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_single_stack_interface_ignores_other_family() {
+        use crate::command::mock::{output, MockRunner};
+
+        let runner = Arc::new(MockRunner::new(|_| {
+            output(
+                0,
+                "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                 rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+            )
+        }));
+        let interface = NetworkInterface {
+            name: "wan_v4".to_string(),
+            display_name: "wan_v4".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::V4,
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = |address: &str| TargetIP {
+            address: address.to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+        let targets = vec![target("8.8.8.8"), target("2001:4860:4860::8888")];
+
+        let tester = NetworkTester::new(5, 4).with_runner(runner.clone());
+        let results = tester
+            .test_interface(&interface, &targets, &HashSet::new())
+            .await;
+
+        // 只探测 IPv4 目标，不会因 IPv6 目标必然失败而被扣分
+        assert_eq!(runner.calls(), vec!["ping -I wan_v4 -c 4 -W 5 8.8.8.8"]);
+        assert_eq!(results.len(), 1);
+        let dual = NetworkInterface {
+            family: AddressFamily::Dual,
+            ..interface.clone()
+        };
+        let scores = tester.calculate_scores(&results);
+        assert_eq!(scores[0].reachable_count, 1);
+        assert_eq!(dual.effective_targets(&targets).len(), 2);
+        assert_eq!(
+            interface.effective_targets(&[target("example.com")]).len(),
+            1
+        );
+
+        let v6 = NetworkInterface {
+            family: AddressFamily::V6,
+            ..interface
+        };
+        let v6_targets = v6.effective_targets(&targets);
+        assert_eq!(v6_targets.len(), 1);
+        assert_eq!(v6_targets[0].address, "2001:4860:4860::8888");
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_targets_use_ping6_and_count_per_family() {
+        use crate::command::mock::{error_output, output, MockRunner};
+
+        // IPv4 链路中断，IPv6 正常
+        let runner = Arc::new(MockRunner::new(|command| {
+            if command.contains(" -6 ") {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                     rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+                )
+            } else {
+                error_output(1, "")
+            }
+        }));
+        let interface = NetworkInterface {
+            name: "wan".to_string(),
+            display_name: "wan".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::Dual,
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = |address: &str| TargetIP {
+            address: address.to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+        let targets = vec![
+            target("8.8.8.8"),
+            target("2001:4860:4860::8888/128"),
+            target("2606:4700:4700::1111"),
+        ];
+
+        let tester = NetworkTester::new(5, 4).with_runner(runner.clone());
+        let results = tester
+            .test_interface(&interface, &targets, &HashSet::new())
+            .await;
+
+        let mut calls = runner.calls();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                "ping -I wan -c 4 -W 5 -6 2001:4860:4860::8888",
+                "ping -I wan -c 4 -W 5 -6 2606:4700:4700::1111",
+                "ping -I wan -c 4 -W 5 8.8.8.8",
+            ]
+        );
+
+        let scores = tester.calculate_scores(&results);
+        assert_eq!(scores[0].reachable_count, 2);
+        assert_eq!(scores[0].reachable_v4, 0);
+        assert_eq!(scores[0].reachable_v6, 2);
+    }
+
+    #[tokio::test]
+    async fn test_speed_tests_deferred_under_load() {
+        use crate::command::mock::{output, MockRunner};
+        use std::sync::atomic::AtomicU64;
+
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("curl") {
+                output(0, "1048576")
+            } else {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                     rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+                )
+            }
+        }));
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            display_name: "eth0".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let targets = vec![TargetIP {
+            address: "8.8.8.8".to_string(),
+            description: "test".to_string(),
+            test_url: Some("http://example.com/test.bin".to_string()),
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        }];
+
+        // 模拟的 1 分钟负载（以百分之一为单位保存）
+        let load = Arc::new(AtomicU64::new(350));
+        let source = load.clone();
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_max_load(Some(2.0))
+            .with_load_source(move || Some(source.load(Ordering::Relaxed) as f64 / 100.0));
+
+        // 负载超过上限：照常 ping，推迟速度测试
+        let results = tester
+            .test_all_interfaces(&[&interface], &targets, &HashSet::new())
+            .await;
+        assert!(results[0].reachable);
+        assert_eq!(results[0].download_speed, None);
+        assert_eq!(runner.count("ping"), 1);
+        assert_eq!(runner.count("curl"), 0);
+
+        // 负载回落后恢复速度测试
+        load.store(50, Ordering::Relaxed);
+        let results = tester
+            .test_all_interfaces(&[&interface], &targets, &HashSet::new())
+            .await;
+        assert_eq!(results[0].download_speed, Some(1024.0));
+        assert_eq!(runner.count("curl --interface eth0"), 1);
+
+        assert_eq!(parse_loadavg("0.42 0.35 0.30 1/123 4567\n"), Some(0.42));
+        assert_eq!(parse_loadavg(""), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_speed_tests_limited_across_interfaces() {
+        use crate::command::mock::{output, MockRunner};
+
+        let runner = Arc::new(
+            MockRunner::new(|cmd| {
+                if cmd.starts_with("curl") {
+                    output(0, "1048576")
+                } else {
+                    output(
+                        0,
+                        "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                         rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+                    )
+                }
+            })
+            .with_delay("curl", Duration::from_secs(10)),
+        );
+        let interfaces: Vec<NetworkInterface> = ["wan_a", "wan_b", "wan_c"]
+            .iter()
+            .map(|name| {
+                toml::from_str(&format!(
+                    "name = \"{name}\"\ndisplay_name = \"{name}\"\npriority = 1\nenabled = true"
+                ))
+                .unwrap()
+            })
+            .collect();
+        let interfaces: Vec<&NetworkInterface> = interfaces.iter().collect();
+        let targets: Vec<TargetIP> = vec![toml::from_str(
+            "address = \"8.8.8.8\"\ndescription = \"test\"\nweight = 1.0\n\
+             test_url = \"http://example.com/test.bin\"",
+        )
+        .unwrap()];
+
+        // 默认同一时间只有一个速度测试
+        let tester = NetworkTester::new(30, 4).with_runner(runner.clone());
+        let start = tokio::time::Instant::now();
+        let results = tester
+            .test_all_interfaces(&interfaces, &targets, &HashSet::new())
+            .await;
+        assert!(results.iter().all(|r| r.download_speed == Some(1024.0)));
+        assert_eq!(start.elapsed(), Duration::from_secs(30));
+
+        let tester = NetworkTester::new(30, 4)
+            .with_runner(runner.clone())
+            .with_concurrent_speed_tests(3);
+        let start = tokio::time::Instant::now();
+        tester
+            .test_all_interfaces(&interfaces, &targets, &HashSet::new())
+            .await;
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_link_down_interface_skipped() {
+        use crate::command::mock::{output, MockRunner};
+
+        let sysfs =
+            std::env::temp_dir().join(format!("routes-monitor-sysfs-{}", std::process::id()));
+        for (device, state) in [("wan_a", "down\n"), ("wan_b", "unknown\n")] {
+            std::fs::create_dir_all(sysfs.join(device)).unwrap();
+            std::fs::write(sysfs.join(device).join("operstate"), state).unwrap();
+        }
+
+        let runner = Arc::new(MockRunner::new(|_| {
+            output(
+                0,
+                "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                 rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+            )
+        }));
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_sysfs_net(&sysfs);
+
+        assert!(!tester.interface_is_up("wan_a").await);
+        // PPPoE 接口按物理接口名读取；ppp 设备的状态为 unknown，视为工作中
+        assert!(tester.interface_is_up("pppoe-wan_b").await);
+        // 找不到设备时交由探测判断
+        assert!(tester.interface_is_up("wan_c").await);
+
+        let interfaces: Vec<NetworkInterface> = ["wan_a", "pppoe-wan_b"]
+            .iter()
+            .map(|name| {
+                toml::from_str(&format!(
+                    "name = \"{name}\"\ndisplay_name = \"{name}\"\npriority = 1\nenabled = true"
+                ))
+                .unwrap()
+            })
+            .collect();
+        let interfaces: Vec<&NetworkInterface> = interfaces.iter().collect();
+        let targets: Vec<TargetIP> = vec![
+            toml::from_str("address = \"8.8.8.8\"\ndescription = \"a\"\nweight = 1.0").unwrap(),
+            toml::from_str("address = \"1.1.1.1\"\ndescription = \"b\"\nweight = 1.0").unwrap(),
+        ];
+
+        let results = tester
+            .test_all_interfaces(&interfaces, &targets, &HashSet::new())
+            .await;
+        let down: Vec<_> = results.iter().filter(|r| r.interface == "wan_a").collect();
+        assert_eq!(down.len(), 2);
+        assert!(down
+            .iter()
+            .all(|r| !r.reachable && r.failure_reason == Some(FailureReason::LinkDown)));
+        assert_eq!(runner.count("ping -I wan_a"), 0);
+        assert_eq!(runner.count("ping -I pppoe-wan_b"), 2);
+
+        std::fs::remove_dir_all(&sysfs).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fping_batches_targets_per_interface() {
+        use crate::command::mock::{error_output, output, MockRunner};
+
+        let summary = parse_fping_summary(
+            "8.8.8.8 : xmt/rcv/%loss = 4/3/25%, min/avg/max = 9.12/10.5/11.5\n\
+             10.0.0.1 : xmt/rcv/%loss = 4/0/100%\n\
+             ICMP Host Unreachable from 10.0.0.254 for ICMP Echo sent to 10.0.0.1\n",
+        );
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary["8.8.8.8"].latency_ms, Some(10.5));
+        assert_eq!(summary["8.8.8.8"].packet_loss, Some(0.25));
+        assert!(!summary["10.0.0.1"].reachable);
+        assert_eq!(summary["10.0.0.1"].packet_loss, Some(1.0));
+
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("fping") {
+                // 有目标不可达时以 1 退出，汇总在标准错误中
+                error_output(
+                    1,
+                    "8.8.8.8 : xmt/rcv/%loss = 4/4/0%, min/avg/max = 9.0/10.0/11.0\n\
+                     1.1.1.1 : xmt/rcv/%loss = 2/2/0%, min/avg/max = 19.0/20.0/21.0\n\
+                     10.0.0.1 : xmt/rcv/%loss = 4/0/100%\n",
+                )
+            } else {
+                output(1, "")
+            }
+        }));
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_ping_tool(PingTool::Fping);
+        let interface: NetworkInterface =
+            toml::from_str("name = \"wan_a\"\ndisplay_name = \"A\"\npriority = 1\nenabled = true")
+                .unwrap();
+        let targets: Vec<TargetIP> = vec![
+            toml::from_str("address = \"8.8.8.8\"\ndescription = \"a\"\nweight = 1.0").unwrap(),
+            toml::from_str("address = \"10.0.0.1/32\"\ndescription = \"b\"\nweight = 1.0").unwrap(),
+            toml::from_str(
+                "address = \"1.1.1.1\"\ndescription = \"c\"\nweight = 1.0\nping_count = 2",
+            )
+            .unwrap(),
+        ];
+
+        let results = tester
+            .test_interface(&interface, &targets, &HashSet::new())
+            .await;
+
+        // 报文数相同的目标合并为一次调用，不再逐个调用 ping
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "fping -I wan_a -c 2 -q -t 5000 1.1.1.1",
+                "fping -I wan_a -c 4 -q -t 5000 8.8.8.8 10.0.0.1",
+            ]
+        );
+        let addresses: Vec<&str> = results.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(addresses, vec!["8.8.8.8", "10.0.0.1/32", "1.1.1.1"]);
+        assert!(results[0].reachable);
+        assert_eq!(results[0].latency_ms, Some(10.0));
+        assert!(!results[1].reachable);
+        assert_eq!(results[1].failure_reason, Some(FailureReason::Timeout));
+        assert_eq!(results[2].latency_ms, Some(20.0));
+
+        // fping 无法执行时回退到逐个目标的 ping，之后不再尝试 fping
+        let runner = Arc::new(
+            MockRunner::new(|_| {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                     rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+                )
+            })
+            .with_failure("fping"),
+        );
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_ping_tool(PingTool::Fping);
+        for _ in 0..2 {
+            let results = tester
+                .test_interface(&interface, &targets, &HashSet::new())
+                .await;
+            assert!(results.iter().all(|r| r.reachable));
+        }
+        assert_eq!(runner.count("fping"), 1);
+        assert_eq!(runner.count("ping -I wan_a"), 6);
+    }
+
+    #[tokio::test]
+    async fn test_upload_speed_direction() {
+        use crate::command::mock::{output, MockRunner};
+
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("curl") {
+                output(0, "524288")
+            } else {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                     rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+                )
+            }
+        }));
+        let interface: NetworkInterface =
+            toml::from_str("name = \"wan\"\ndisplay_name = \"wan\"\npriority = 1\nenabled = true")
+                .unwrap();
+        let targets: Vec<TargetIP> = vec![toml::from_str(
+            "address = \"8.8.8.8\"\ndescription = \"test\"\nweight = 1.0\n\
+             test_url = \"http://example.com/upload\"\nspeed_direction = \"upload\"",
+        )
+        .unwrap()];
+
+        let tester = NetworkTester::new(5, 4).with_runner(runner.clone());
+        let results = tester
+            .test_all_interfaces(&[&interface], &targets, &HashSet::new())
+            .await;
+        assert_eq!(results[0].download_speed, None);
+        assert_eq!(results[0].upload_speed, Some(512.0));
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            "curl --interface wan -s -o /dev/null -T - -w %{speed_upload} http://example.com/upload"
+        );
+        assert_eq!(runner.inputs()[0].len(), UPLOAD_TEST_SIZE);
+
+        // 上传速度按速度基准计入评分
+        let scores = tester.calculate_scores(&results);
+        assert_eq!(scores[0].avg_speed, 512.0);
+    }
 
     #[test]
-    fn test_score_calculation() {
+    fn test_min_reachable_ratio_disqualifies_interface() {
+        let result = |interface: &str, target: &str, reachable: bool, latency: f64| TestResult {
+            interface: interface.to_string(),
+            target: target.to_string(),
+            resolved_address: None,
+            reachable,
+            latency_ms: reachable.then_some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(if reachable { 0.0 } else { 1.0 }),
+            download_speed: reachable.then_some(8192.0),
+            upload_speed: None,
+            failure_reason: (!reachable).then_some(FailureReason::Timeout),
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        let targets = ["8.8.8.8", "1.1.1.1", "9.9.9.9", "208.67.222.222"];
+        let mut results = Vec::new();
+        for (i, target) in targets.iter().enumerate() {
+            // wan_a 只能到达一个目标，但延迟和速度都更好
+            results.push(result("wan_a", target, i == 0, 1.0));
+            results.push(result("wan_b", target, true, 80.0));
+        }
+
+        let tester = NetworkTester::new(5, 4).with_min_reachable_ratio(0.5);
+        let scores = tester.calculate_scores(&results);
+        let wan_a = scores.iter().find(|s| s.interface == "wan_a").unwrap();
+        assert_eq!(
+            wan_a.eligibility,
+            Eligibility::Ineligible(IneligibleReason::LowReachability {
+                reachable: 1,
+                total: 4
+            })
+        );
+        assert_eq!(
+            tester.get_best_interface(&scores).unwrap().interface,
+            "wan_b"
+        );
+
+        // 达到比例即可参与选择
+        let tester = NetworkTester::new(5, 4).with_min_reachable_ratio(0.25);
+        let scores = tester.calculate_scores(&results);
+        assert!(scores.iter().all(|s| s.is_eligible()));
+    }
+
+    #[tokio::test]
+    async fn test_external_scorer_replaces_builtin_score() {
+        use crate::command::mock::{output, MockRunner};
+
+        let result = |interface: &str, latency: f64| TestResult {
+            interface: interface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        let results = vec![result("wan_a", 10.0), result("wan_b", 50.0)];
+
+        let runner = Arc::new(MockRunner::new(|_| output(0, "87.5\n")));
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_scorer_command(Some("/usr/bin/my-scorer".to_string()));
+        let scores = tester.score_interfaces(&results).await;
+
+        assert!(scores.iter().all(|s| s.score == 87.5));
+        assert_eq!(runner.count("sh -c /usr/bin/my-scorer"), 2);
+        // 每个接口只收到自己的测试结果
+        let inputs = runner.inputs();
+        let wan_a = inputs.iter().find(|i| i.contains("wan_a")).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(wan_a).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 1);
+        assert_eq!(parsed[0]["latency_ms"], 10.0);
+        assert!(!wan_a.contains("wan_b"));
+
+        // 输出无效时回退到内置评分
+        let runner = Arc::new(MockRunner::new(|_| output(0, "not a number")));
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner)
+            .with_scorer_command(Some("/usr/bin/my-scorer".to_string()));
+        let builtin = tester.calculate_scores(&results);
+        let scores = tester.score_interfaces(&results).await;
+        assert_eq!(
+            scores.iter().map(|s| s.score).collect::<Vec<_>>(),
+            builtin.iter().map(|s| s.score).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reachability_loss_threshold() {
+        use crate::command::mock::{output, MockRunner};
+
+        // 目标地址的最后一段即丢包百分比
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd.rsplit('.').next() {
+            Some("50") => output(
+                0,
+                "4 packets transmitted, 2 received, 50% packet loss, time 3005ms\n\
+                 rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+            ),
+            Some("75") => output(
+                0,
+                "4 packets transmitted, 1 received, 75% packet loss, time 3005ms\n\
+                 rtt min/avg/max/mdev = 10.000/10.000/10.000/0.000 ms\n",
+            ),
+            _ => output(
+                1,
+                "4 packets transmitted, 0 received, 100% packet loss, time 3005ms\n",
+            ),
+        }));
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            display_name: "eth0".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = |address: &str| TargetIP {
+            address: address.to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+
+        for (threshold, expected) in [
+            (1.0, [true, true, false]),
+            (0.75, [true, false, false]),
+            (0.5, [false, false, false]),
+        ] {
+            let tester = NetworkTester::new(5, 4)
+                .with_runner(runner.clone())
+                .with_reachability_loss_threshold(threshold);
+            for (address, reachable) in ["10.0.0.50", "10.0.0.75", "10.0.0.100"]
+                .into_iter()
+                .zip(expected)
+            {
+                let result = tester.test_single(&interface, &target(address)).await;
+                assert_eq!(result.reachable, reachable, "{} @ {}", address, threshold);
+                if !reachable && result.latency_ms.is_some() {
+                    // 有应答但丢包过多：保留统计并给出原因
+                    assert_eq!(result.failure_reason, Some(FailureReason::PacketLoss));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lossy_probe_retried_and_merged() {
+        use crate::command::mock::{output, MockRunner};
+        use std::sync::atomic::AtomicUsize;
+
+        // 10.0.0.1 首次丢 1 个包，补测正常；10.0.0.2 完全不通
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let runner = Arc::new(MockRunner::new(move |cmd| {
+            if cmd.ends_with("10.0.0.2") {
+                return output(
+                    1,
+                    "4 packets transmitted, 0 received, 100% packet loss, time 3005ms\n",
+                );
+            }
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                output(
+                    0,
+                    "4 packets transmitted, 3 received, 25% packet loss, time 3005ms\n\
+                     rtt min/avg/max/mdev = 11.000/12.000/13.000/0.500 ms\n",
+                )
+            } else {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                     rtt min/avg/max/mdev = 7.000/8.000/9.000/0.500 ms\n",
+                )
+            }
+        }));
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            display_name: "eth0".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = |address: &str| TargetIP {
+            address: address.to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_lossy_retries(2);
+
+        let result = tester.test_single(&interface, &target("10.0.0.1")).await;
+        assert!(result.reachable);
+        assert_eq!(result.packet_loss, Some(0.125));
+        assert_eq!(result.latency_ms, Some(10.0));
+        // 补测无丢包后不再继续
+        let pings = |address: &str| {
+            runner
+                .calls()
+                .iter()
+                .filter(|c| c.starts_with("ping") && c.ends_with(address))
+                .count()
+        };
+        assert_eq!(pings("10.0.0.1"), 2);
+
+        // 100% 丢包不补测
+        let result = tester.test_single(&interface, &target("10.0.0.2")).await;
+        assert!(!result.reachable);
+        assert_eq!(pings("10.0.0.2"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connect_probe() {
+        let listener = crate::test_server::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(crate::test_server::serve(listener));
+
+        let interface = NetworkInterface {
+            name: "lo".to_string(),
+            display_name: "lo".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = |port: u16| TargetIP {
+            address: "127.0.0.1".to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Tcp,
+            port: Some(port),
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+        let tester = NetworkTester::new(2, 1).with_ping(3, DEFAULT_PING_SIZE);
+
+        // 经接口连接测试服务，不调用 ping；连接次数与 ping 报文数相同
+        let result = tester.test_single(&interface, &target(port)).await;
+        assert!(result.reachable);
+        assert_eq!(result.packet_loss, Some(0.0));
+        assert!(result.latency_ms.is_some());
+        assert_eq!(result.rtt_samples.len(), 3);
+
+        // 目标的 ping_count 优先于全局值
+        let single = TargetIP {
+            ping_count: Some(1),
+            ..target(port)
+        };
+        let result = tester.test_single(&interface, &single).await;
+        assert_eq!(result.rtt_samples.len(), 1);
+
+        // 未监听的端口连接被拒绝，视为不可达
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let result = tester.test_single(&interface, &target(closed)).await;
+        assert!(!result.reachable);
+        assert_eq!(result.packet_loss, Some(1.0));
+        assert_eq!(result.failure_reason, Some(FailureReason::Unreachable));
+    }
+
+    #[tokio::test]
+    async fn test_warmup_ping_not_aggregated() {
+        use crate::command::mock::{output, MockRunner};
+
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.contains("-c 1 ") {
+                // 预热包唤醒链路，延迟很高
+                output(
+                    0,
+                    "64 bytes from 8.8.8.8: icmp_seq=1 ttl=117 time=900.0 ms\n\
+                     1 packets transmitted, 1 received, 0% packet loss, time 0ms\n\
+                     rtt min/avg/max/mdev = 900.0/900.0/900.0/0.000 ms\n",
+                )
+            } else {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3005ms\n\
+                     rtt min/avg/max/mdev = 9.000/10.000/11.000/0.500 ms\n",
+                )
+            }
+        }));
+        let interface = NetworkInterface {
+            name: "eth0".to_string(),
+            display_name: "eth0".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = TargetIP {
+            address: "8.8.8.8/32".to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_warmup_ping(true);
+        let result = tester.test_single(&interface, &target).await;
+
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "ping -I eth0 -c 1 -W 5 8.8.8.8",
+                "ping -I eth0 -c 4 -W 5 8.8.8.8",
+            ]
+        );
+        assert!(result.reachable);
+        assert_eq!(result.latency_ms, Some(10.0));
+        assert_eq!(result.packet_loss, Some(0.0));
+
+        // 未启用时不发送预热包
+        let tester = NetworkTester::new(5, 4).with_runner(runner.clone());
+        tester.test_single(&interface, &target).await;
+        assert_eq!(runner.count("ping -I eth0 -c 1"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_interface_detected() {
+        use crate::command::mock::{error_output, MockRunner};
+
+        let runner = Arc::new(MockRunner::new(|_| {
+            error_output(2, "ping: SO_BINDTODEVICE: No such device\n")
+        }));
+        let tester = NetworkTester::new(5, 4).with_runner(runner);
+        let interface = NetworkInterface {
+            name: "wwan0".to_string(),
+            display_name: "LTE".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let target = TargetIP {
+            address: "8.8.8.8".to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+
+        let result = tester.test_single(&interface, &target).await;
+        assert!(!result.reachable);
+        assert_eq!(result.failure_reason, Some(FailureReason::InterfaceMissing));
+
+        // 不存在的接口单独标记，且不会被选中
+        let mut other = result.clone();
+        other.interface = "wan".to_string();
+        other.failure_reason = Some(FailureReason::Timeout);
+        let scores = tester.calculate_scores(&[result, other]);
+        let wwan = scores.iter().find(|s| s.interface == "wwan0").unwrap();
+        assert_eq!(
+            wwan.eligibility,
+            Eligibility::Ineligible(IneligibleReason::Absent)
+        );
+        assert!(scores
+            .iter()
+            .find(|s| s.interface == "wan")
+            .unwrap()
+            .is_eligible());
+        assert_eq!(tester.get_best_interface(&scores).unwrap().interface, "wan");
+
+        assert!(is_missing_interface_error("ping: unknown iface wwan0"));
+        assert!(is_missing_interface_error(
+            "curl: (45) bind failed with errno 19: No such device"
+        ));
+        assert!(!is_missing_interface_error(
+            "ping: sendmsg: Network is unreachable"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_interface_resolver_used_for_hostnames() {
+        use crate::command::mock::{output, MockRunner};
+
+        let runner = Arc::new(MockRunner::new(|_| {
+            output(
+                0,
+                "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                 rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+            )
+        }));
+        // 本地 DNS 服务器，记录查询来自哪个地址
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let responder = std::thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (len, peer) = server.recv_from(&mut buffer).unwrap();
+            let reply = dns::response(&buffer[..len], dns::TYPE_A, &[93, 184, 216, 34]);
+            server.send_to(&reply, peer).unwrap();
+        });
+        let tester = NetworkTester::new(5, 4)
+            .with_runner(runner.clone())
+            .with_resolver_port(port);
+        // 查询从绑定到被探测接口的套接字发出
+        let mut interface = NetworkInterface {
+            name: "lo".to_string(),
+            display_name: "WAN".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: Some("127.0.0.1".to_string()),
+            vlans: Vec::new(),
+            vlan_aggregation: VlanAggregation::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let mut target = TargetIP {
+            address: "www.example.com".to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+
+        let result = tester.test_single(&interface, &target).await;
+        responder.join().unwrap();
+        assert!(result.reachable);
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].starts_with("ping -I lo") && calls[0].ends_with(" 93.184.216.34"));
+        assert_eq!(result.resolved_address.as_deref(), Some("93.184.216.34"));
+
+        // IP 目标不做解析，未配置 DNS 服务器的接口改用系统解析器
+        target.address = "8.8.8.8".to_string();
+        let result = tester.test_single(&interface, &target).await;
+        assert_eq!(result.resolved_address, None);
+        interface.name = "wan".to_string();
+        interface.resolver = None;
+        target.address = "localhost".to_string();
+        let result = tester.test_single(&interface, &target).await;
+        assert_eq!(result.resolved_address.as_deref(), Some("127.0.0.1"));
+        assert!(runner.calls().last().unwrap().ends_with(" 127.0.0.1"));
+
+        // 系统解析结果在缓存有效期内复用；无法解析的域名视为不可达
+        assert!(tester.dns_cache.lock().unwrap().contains_key("localhost"));
+        target.address = "nonexistent.invalid".to_string();
+        let result = tester.test_single(&interface, &target).await;
+        assert!(!result.reachable);
+        assert_eq!(result.failure_reason, Some(FailureReason::ResolveFailed));
+
+        // 带 DSCP 标记的目标以对应的 ToS 字节探测，并记录在结果上
+        target.address = "8.8.8.8".to_string();
+        target.dscp = Some(46);
+        let result = tester.test_single(&interface, &target).await;
+        assert_eq!(result.dscp, Some(46));
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            "ping -I wan -c 4 -W 5 -Q 184 8.8.8.8"
+        );
+
+        // 目标覆盖的报文数与负载大小作为 -c 与 -s 参数
+        target.dscp = None;
+        target.ping_count = Some(2);
+        target.ping_size = Some(1400);
+        tester.test_single(&interface, &target).await;
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            "ping -I wan -c 2 -W 5 -s 1400 8.8.8.8"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_vlan_probing_and_aggregation() {
+        use crate::command::mock::{output, MockRunner};
+        use crate::config::VlanProbe;
+
+        // eth0.200（IPTV VLAN）不通，其余正常
+        let runner = Arc::new(MockRunner::new(|line| {
+            if line.starts_with("ping -I eth0.200") {
+                output(
+                    1,
+                    "4 packets transmitted, 0 received, 100% packet loss, time 3004ms\n",
+                )
+            } else {
+                output(
+                    0,
+                    "4 packets transmitted, 4 received, 0% packet loss, time 3004ms\n\
+                     rtt min/avg/max/mdev = 9.0/10.0/11.0/0.5 ms\n",
+                )
+            }
+        }));
+        let tester = NetworkTester::new(5, 4).with_runner(runner.clone());
+        let mut interface = NetworkInterface {
+            name: "wan".to_string(),
+            display_name: "WAN".to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: None,
+            score_offset: 0.0,
+            resolver: None,
+            vlans: [100, 200]
+                .iter()
+                .map(|id| VlanProbe {
+                    id: *id,
+                    device: "eth0".to_string(),
+                })
+                .collect(),
+            vlan_aggregation: VlanAggregation::Worst,
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        };
+        let targets = [TargetIP {
+            address: "8.8.8.8".to_string(),
+            description: "test".to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical: false,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        }];
+
+        let results = tester
+            .test_interface(&interface, &targets, &HashSet::new())
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].interface, "wan");
+        assert!(!results[0].reachable);
+        assert_eq!(results[0].packet_loss, Some(1.0));
+        for device in ["wan", "eth0.100", "eth0.200"] {
+            assert_eq!(runner.count(&format!("ping -I {} ", device)), 1);
+        }
+
+        interface.vlan_aggregation = VlanAggregation::Average;
+        let results = tester
+            .test_interface(&interface, &targets, &HashSet::new())
+            .await;
+        assert!(results[0].reachable);
+        assert_eq!(results[0].latency_ms, Some(10.0));
+        assert!((results[0].packet_loss.unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        assert!((results[0].health.unwrap() - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_probe_budget_prioritizes_critical_targets() {
+        let target = |address: &str, critical: bool| TargetIP {
+            address: address.to_string(),
+            description: address.to_string(),
+            test_url: None,
+            weight: 1.0,
+            critical,
+            check_every: 1,
+            dscp: None,
+            probe: ProbeType::Icmp,
+            port: None,
+            ping_count: None,
+            ping_size: None,
+            speed_direction: SpeedDirection::default(),
+        };
+        let targets = [
+            target("10.0.0.1", true),
+            target("8.8.8.8", false),
+            target("1.1.1.1", false),
+            target("9.9.9.9", false),
+        ];
+        // 每个目标经 2 个接口探测，预算 6 次：关键目标之外只能再探测 2 个目标
+        let candidates: Vec<(&TargetIP, usize)> = targets.iter().map(|t| (t, 2)).collect();
+        let mut budget = ProbeBudget::new(6);
+
+        let skipped = |set: HashSet<String>| {
+            let mut list: Vec<String> = set.into_iter().collect();
+            list.sort();
+            list
+        };
+        assert_eq!(skipped(budget.over_budget(&candidates)), vec!["9.9.9.9"]);
+        assert_eq!(skipped(budget.over_budget(&candidates)), vec!["1.1.1.1"]);
+        assert_eq!(skipped(budget.over_budget(&candidates)), vec!["8.8.8.8"]);
+        assert_eq!(skipped(budget.over_budget(&candidates)), vec!["9.9.9.9"]);
+
+        // 预算不足时关键目标仍然探测
+        let mut budget = ProbeBudget::new(1);
+        assert_eq!(
+            skipped(budget.over_budget(&candidates)),
+            vec!["1.1.1.1", "8.8.8.8", "9.9.9.9"]
+        );
+
+        // 未设置预算时不跳过
+        assert!(ProbeBudget::new(0).over_budget(&candidates).is_empty());
+    }
+
+    #[test]
+    fn test_dead_target_probed_at_reduced_cadence() {
+        let mut breaker = DeadTargetBreaker::new(3, 4);
+        let targets = ["8.8.8.8", "192.0.2.1"];
+        let round = |target: &str, reachable: bool| -> Vec<TestResult> {
+            ["wan_a", "wan_b"]
+                .iter()
+                .map(|iface| TestResult {
+                    interface: iface.to_string(),
+                    target: target.to_string(),
+                    resolved_address: None,
+                    reachable,
+                    latency_ms: None,
+                    jitter_ms: None,
+                    rtt_samples: Vec::new(),
+                    packet_loss: None,
+                    download_speed: None,
+                    upload_speed: None,
+                    failure_reason: None,
+                    health: None,
+                    dscp: None,
+                    tested_at: chrono::Local::now(),
+                })
+                .collect()
+        };
+
+        // 192.0.2.1 一直不可达，记录每轮是否被探测
+        let mut probed_rounds = Vec::new();
+        for cycle in 1..=12 {
+            let skip = breaker.skipped_targets(targets);
+            assert!(!skip.contains("8.8.8.8"));
+            let mut results = round("8.8.8.8", true);
+            if !skip.contains("192.0.2.1") {
+                probed_rounds.push(cycle);
+                results.extend(round("192.0.2.1", false));
+            }
+            breaker.record(&results);
+        }
+        // 前 3 轮正常探测，熔断后每 4 轮探测一次
+        assert_eq!(probed_rounds, vec![1, 2, 3, 7, 11]);
+
+        // 重新探测时恢复可达，之后每轮探测
+        while breaker.skipped_targets(targets).contains("192.0.2.1") {}
+        breaker.record(&round("192.0.2.1", true));
+        assert!(breaker.skipped_targets(targets).is_empty());
+
+        // threshold 为 0 时不熔断
+        let mut breaker = DeadTargetBreaker::new(0, 4);
+        for _ in 0..10 {
+            assert!(breaker.skipped_targets(targets).is_empty());
+            breaker.record(&round("192.0.2.1", false));
+        }
+    }
+
+    #[test]
+    fn test_virtual_interface_aggregation() {
+        let score = |interface: &str, value: f64, speed: f64| InterfaceScore {
+            interface: interface.to_string(),
+            reachable_count: 2,
+            reachable_v4: 2,
+            reachable_v6: 0,
+            avg_latency_ms: value / 2.0,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: speed,
+            score: value,
+            eligibility: Eligibility::Eligible,
+        };
+        let mut fiber = VirtualInterface {
+            name: "fiber".to_string(),
+            members: vec!["wan_a".to_string(), "wan_b".to_string()],
+            aggregation: VirtualAggregation::Best,
+        };
+        let mut scores = vec![
+            score("wan_a", 60.0, 100.0),
+            score("wan_b", 80.0, 300.0),
+            score("lte", 70.0, 50.0),
+        ];
+
+        // 取最佳成员：80 > 70
+        let units = virtual_interface_scores(std::slice::from_ref(&fiber), &scores);
+        let names: Vec<&str> = units.iter().map(|s| s.interface.as_str()).collect();
+        assert_eq!(names, vec!["fiber", "lte"]);
+        assert_eq!(units[0].score, 80.0);
+        assert_eq!(units[0].avg_speed, 300.0);
+
+        // 取平均：70 不高于 LTE，速度按成员之和
+        fiber.aggregation = VirtualAggregation::Average;
+        let units = virtual_interface_scores(std::slice::from_ref(&fiber), &scores);
+        let unit = units.iter().find(|s| s.interface == "fiber").unwrap();
+        assert_eq!(unit.score, 70.0);
+        assert_eq!(unit.avg_speed, 400.0);
+        assert_eq!(unit.reachable_count, 4);
+        assert_eq!(unit.avg_latency_ms, 35.0);
+
+        // 不可选择的成员不参与合并
+        scores[1].eligibility =
+            Eligibility::Ineligible(IneligibleReason::Probation { remaining: 1 });
+        let units = virtual_interface_scores(std::slice::from_ref(&fiber), &scores);
+        let unit = units.iter().find(|s| s.interface == "fiber").unwrap();
+        assert_eq!(unit.score, 60.0);
+    }
+
+    #[test]
+    fn test_composite_health() {
+        // HTTP 成功的权重是 ICMP 的 3 倍
+        let weights = CheckWeights {
+            icmp: 1.0,
+            http: 3.0,
+        };
+
+        let ping_only = [(CheckType::Icmp, true), (CheckType::Http, false)];
+        let http_only = [(CheckType::Icmp, false), (CheckType::Http, true)];
+        assert_eq!(composite_health(&ping_only, &weights), Some(0.25));
+        assert_eq!(composite_health(&http_only, &weights), Some(0.75));
+        assert_eq!(
+            composite_health(&[(CheckType::Icmp, true)], &weights),
+            Some(1.0)
+        );
+        assert_eq!(composite_health(&[], &weights), None);
+
+        // 部分健康的目标按比例计入可达比例
+        let tester = NetworkTester::new(5, 4);
+        let make = |interface: &str, health: f64| TestResult {
+            interface: interface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(20.0),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: Some(health),
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        let scores = tester.calculate_scores(&[make("flaky", 0.25), make("healthy", 1.0)]);
+        let flaky = scores.iter().find(|s| s.interface == "flaky").unwrap();
+        let healthy = scores.iter().find(|s| s.interface == "healthy").unwrap();
+        assert!((healthy.score - flaky.score - 30.0 * 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_partial_ping_output_parsing() {
+        // ping 输出两条应答后被外层超时中断，没有汇总行
+        let stdout = "PING 8.8.8.8 (8.8.8.8) from 192.168.1.2 eth0: 56(84) bytes of data.\n\
+                      64 bytes from 8.8.8.8: icmp_seq=1 ttl=117 time=10.0 ms\n\
+                      64 bytes from 8.8.8.8: icmp_seq=2 ttl=117 time=30.0 ms\n";
+
+        assert_eq!(parse_ping_summary(stdout), (None, None));
+        assert_eq!(parse_ping_replies(stdout, 4), (0.5, Some(20.0)));
+        assert_eq!(parse_ping_replies("", 4), (1.0, None));
+    }
+
+    #[test]
+    fn test_parse_failure_reason() {
+        let cases = [
+            (
+                "From 192.168.1.1 icmp_seq=1 Destination Host Unreachable",
+                FailureReason::Unreachable,
+            ),
+            (
+                "From 192.168.1.1 icmp_seq=1 Destination Net Unreachable",
+                FailureReason::Unreachable,
+            ),
+            (
+                "From 192.168.1.1 icmp_seq=1 Destination Port Unreachable",
+                FailureReason::Unreachable,
+            ),
+            (
+                "From 10.0.0.1 icmp_seq=1 Time to live exceeded",
+                FailureReason::TtlExceeded,
+            ),
+            (
+                "From 2001:db8::1 icmp_seq=1 Time exceeded: Hop limit",
+                FailureReason::TtlExceeded,
+            ),
+            (
+                "From 10.0.0.1 icmp_seq=1 Packet filtered",
+                FailureReason::Filtered,
+            ),
+            (
+                "From 10.0.0.1 icmp_seq=1 Destination Host Prohibited",
+                FailureReason::Filtered,
+            ),
+            (
+                "From 2001:db8::1 icmp_seq=1 Destination unreachable: Administratively prohibited",
+                FailureReason::Filtered,
+            ),
+        ];
+
+        for (line, expected) in cases {
+            let stdout = format!(
+                "PING 8.8.8.8 (8.8.8.8): 56 data bytes\n{}\n\n\
+                 --- 8.8.8.8 ping statistics ---\n\
+                 1 packets transmitted, 0 packets received, +1 errors, 100% packet loss\n",
+                line
+            );
+            assert_eq!(parse_failure_reason(&stdout), Some(expected), "{}", line);
+        }
+
+        // 没有差错报文时无法判断（由调用方视为超时）
+        let silent = "PING 8.8.8.8 (8.8.8.8): 56 data bytes\n\n\
+                      --- 8.8.8.8 ping statistics ---\n\
+                      4 packets transmitted, 0 packets received, 100% packet loss\n";
+        assert_eq!(parse_failure_reason(silent), None);
+    }
+
+    #[test]
+    fn test_parse_ping_summary() {
+        let stdout = "4 packets transmitted, 3 received, 25% packet loss, time 3005ms\n\
+                      rtt min/avg/max/mdev = 10.123/15.456/20.789/3.210 ms\n";
+        assert_eq!(parse_ping_summary(stdout), (Some(0.25), Some(15.456)));
+    }
+
+    #[test]
+    fn test_parse_ping_jitter() {
+        let stdout = "4 packets transmitted, 3 received, 25% packet loss, time 3005ms\n\
+                      rtt min/avg/max/mdev = 10.123/15.456/20.789/3.210 ms\n";
+        assert_eq!(parse_ping_jitter(stdout), Some(3.21));
+
+        // busybox 的汇总行没有 mdev，按应答行计算
+        let stdout = "64 bytes from 8.8.8.8: seq=0 ttl=117 time=10.000 ms\n\
+                      64 bytes from 8.8.8.8: seq=1 ttl=117 time=14.000 ms\n\
+                      round-trip min/avg/max = 10.000/12.000/14.000 ms\n";
+        assert_eq!(parse_ping_jitter(stdout), Some(2.0));
+
+        // 单个应答无法衡量抖动
+        assert_eq!(
+            parse_ping_jitter("64 bytes from 8.8.8.8: seq=0 ttl=117 time=10.000 ms\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_jitter_weight_prefers_stable_link() {
+        let result = |iface: &str, jitter: f64| TestResult {
+            interface: iface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(20.0),
+            jitter_ms: Some(jitter),
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        // 延迟相同，wan_b 抖动大
+        let results = vec![result("wan_a", 1.0), result("wan_b", 8.0)];
+
+        // 默认抖动权重为 0，两者评分相同
+        let scores = NetworkTester::new(5, 4).calculate_scores(&results);
+        assert_eq!(scores[0].score, scores[1].score);
+        let wan_b = scores.iter().find(|s| s.interface == "wan_b").unwrap();
+        assert_eq!(wan_b.avg_jitter_ms, Some(8.0));
+
+        let scoring = ScoringWeights {
+            weight_jitter: 0.2,
+            ..ScoringWeights::default()
+        };
+        let mut scores = NetworkTester::new(5, 4)
+            .with_scoring(scoring)
+            .calculate_scores(&results);
+        sort_scores(&mut scores);
+        assert_eq!(scores[0].interface, "wan_a");
+        // 抖动 8ms 为基准 2ms 的 4 倍，抖动项得 25 分
+        let gap = (100.0 - 25.0) * 0.2 / 1.2;
+        assert!((scores[0].score - scores[1].score - gap).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_p95_latency_metric_penalizes_spikes() {
+        let result = |iface: &str, samples: Vec<f64>| TestResult {
+            interface: iface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(samples.iter().sum::<f64>() / samples.len() as f64),
+            jitter_ms: None,
+            rtt_samples: samples,
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        // 平均延迟相同，wan_b 偶有 2 秒的尖峰
+        let mut spiky = vec![10.0; 19];
+        spiky.push(1910.0);
+        let results = vec![result("wan_a", vec![105.0; 20]), result("wan_b", spiky)];
+
+        let scores = NetworkTester::new(5, 4).calculate_scores(&results);
+        assert_eq!(scores[0].score, scores[1].score);
+        let wan_b = scores.iter().find(|s| s.interface == "wan_b").unwrap();
+        assert_eq!(wan_b.p50_latency_ms, Some(10.0));
+        assert_eq!(wan_b.p95_latency_ms, Some(10.0));
+
+        // 第 20 个样本才是尖峰，p95 取第 19 个；再多一个尖峰即落入 p95
+        let mut spiky = vec![10.0; 18];
+        spiky.extend([1000.0, 1000.0]);
+        let results = vec![result("wan_a", vec![105.0; 20]), result("wan_b", spiky)];
+        let scores = NetworkTester::new(5, 4)
+            .with_latency_metric(LatencyMetric::P95)
+            .calculate_scores(&results);
+        assert_eq!(scores[0].interface, "wan_a");
+        assert_eq!(scores[1].p95_latency_ms, Some(1000.0));
+    }
+
+    #[test]
+    fn test_non_finite_metrics_are_ignored() {
+        let result = |target: &str, latency: f64, loss: f64, speed: f64| TestResult {
+            interface: "eth0".to_string(),
+            target: target.to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(loss),
+            download_speed: Some(speed),
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
         let results = vec![
-            TestResult {
-                interface: "eth0".to_string(),
+            result("8.8.8.8", 10.0, 0.0, 512.0),
+            result("1.1.1.1", f64::NAN, f64::INFINITY, f64::NAN),
+        ];
+
+        let scores = NetworkTester::new(5, 4).calculate_scores(&results);
+        assert_eq!(scores[0].avg_latency_ms, 10.0);
+        assert_eq!(scores[0].avg_packet_loss, 0.0);
+        assert_eq!(scores[0].avg_speed, 512.0);
+        assert!(scores[0].score.is_finite());
+    }
+
+    #[test]
+    fn test_sort_scores_puts_non_finite_last() {
+        let score = |interface: &str, score: f64| InterfaceScore {
+            interface: interface.to_string(),
+            reachable_count: 1,
+            reachable_v4: 1,
+            reachable_v6: 0,
+            avg_latency_ms: 10.0,
+            p50_latency_ms: None,
+            p95_latency_ms: None,
+            avg_jitter_ms: None,
+            avg_packet_loss: 0.0,
+            avg_speed: 0.0,
+            score,
+            eligibility: Eligibility::Eligible,
+        };
+        let mut scores = vec![
+            score("wan_nan", f64::NAN),
+            score("wan_a", 40.0),
+            score("wan_inf", f64::INFINITY),
+            score("wan_b", 70.0),
+        ];
+        sort_scores(&mut scores);
+
+        let order: Vec<_> = scores.iter().map(|s| s.interface.as_str()).collect();
+        assert_eq!(order, vec!["wan_b", "wan_a", "wan_inf", "wan_nan"]);
+    }
+
+    #[test]
+    fn test_score_offset_shifts_ranking() {
+        let result = |iface: &str, latency: f64| TestResult {
+            interface: iface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        let results = vec![result("wan_a", 20.0), result("wan_b", 40.0)];
+
+        let base = NetworkTester::new(5, 4).calculate_scores(&results);
+        assert_eq!(base[0].interface, "wan_a");
+
+        let interfaces: Vec<NetworkInterface> = [("wan_a", 0.0), ("wan_b", 5.0)]
+            .iter()
+            .map(|(name, offset)| NetworkInterface {
+                name: name.to_string(),
+                display_name: name.to_string(),
+                priority: 1,
+                enabled: true,
+                table_id: None,
+                gateway: None,
+                score_offset: *offset,
+                resolver: None,
+                vlans: Vec::new(),
+                vlan_aggregation: VlanAggregation::default(),
+                keepalive: false,
+                targets: None,
+                extra_targets: Vec::new(),
+                alternate_gateways: Vec::new(),
+                family: AddressFamily::default(),
+                bandwidth_mbps: None,
+                cost: 0.0,
+                failure_threshold: None,
+            })
+            .collect();
+        let shifted = NetworkTester::new(5, 4)
+            .with_score_offsets(&interfaces)
+            .calculate_scores(&results);
+
+        let score_of = |scores: &[InterfaceScore], name: &str| {
+            scores.iter().find(|s| s.interface == name).unwrap().score
+        };
+        assert_eq!(score_of(&shifted, "wan_a"), score_of(&base, "wan_a"));
+        assert!((score_of(&shifted, "wan_b") - score_of(&base, "wan_b") - 5.0).abs() < 1e-9);
+        assert_eq!(shifted[0].interface, "wan_b");
+    }
+
+    #[test]
+    fn test_value_optimization_prefers_cheaper_link() {
+        let result = |iface: &str, latency: f64| TestResult {
+            interface: iface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: None,
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        // 光纤免费但略慢，LTE 略快但按流量计费
+        let results = vec![result("wan_fiber", 25.0), result("wan_lte", 15.0)];
+        let interfaces: Vec<NetworkInterface> = [("wan_fiber", 0.0), ("wan_lte", 2.0)]
+            .iter()
+            .map(|(name, cost)| NetworkInterface {
+                name: name.to_string(),
+                display_name: name.to_string(),
+                priority: 1,
+                enabled: true,
+                table_id: None,
+                gateway: None,
+                score_offset: 0.0,
+                resolver: None,
+                vlans: Vec::new(),
+                vlan_aggregation: VlanAggregation::default(),
+                keepalive: false,
+                targets: None,
+                extra_targets: Vec::new(),
+                alternate_gateways: Vec::new(),
+                family: AddressFamily::default(),
+                bandwidth_mbps: None,
+                cost: *cost,
+                failure_threshold: None,
+            })
+            .collect();
+
+        let performance = NetworkTester::new(5, 4)
+            .with_costs(OptimizeFor::Performance, 1.0, &interfaces)
+            .calculate_scores(&results);
+        assert_eq!(performance[0].interface, "wan_lte");
+
+        let value = NetworkTester::new(5, 4)
+            .with_costs(OptimizeFor::Value, 0.5, &interfaces)
+            .calculate_scores(&results);
+        assert_eq!(value[0].interface, "wan_fiber");
+        // 免费链路评分不变，LTE 评分除以 1 + 0.5 * 2
+        let score_of = |scores: &[InterfaceScore], name: &str| {
+            scores.iter().find(|s| s.interface == name).unwrap().score
+        };
+        assert_eq!(
+            score_of(&value, "wan_fiber"),
+            score_of(&performance, "wan_fiber")
+        );
+        assert!(
+            (score_of(&value, "wan_lte") * 2.0 - score_of(&performance, "wan_lte")).abs() < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_scoring_weights_rebalance_ranking() {
+        let result = |iface: &str, latency: f64, speed: f64| TestResult {
+            interface: iface.to_string(),
+            target: "8.8.8.8".to_string(),
+            resolved_address: None,
+            reachable: true,
+            latency_ms: Some(latency),
+            jitter_ms: None,
+            rtt_samples: Vec::new(),
+            packet_loss: Some(0.0),
+            download_speed: Some(speed),
+            upload_speed: None,
+            failure_reason: None,
+            health: None,
+            dscp: None,
+            tested_at: chrono::Local::now(),
+        };
+        // 光纤延迟高但速度快，LTE 延迟低但速度慢
+        let results = vec![
+            result("wan_fiber", 40.0, 1024.0),
+            result("wan_lte", 10.0, 256.0),
+        ];
+
+        // 默认权重与原有公式一致：30 + 20 + 100/4 * 0.1 + 100 * 0.4
+        let default = NetworkTester::new(5, 4).calculate_scores(&results);
+        assert_eq!(default[0].interface, "wan_fiber");
+        assert!((default[0].score - 92.5).abs() < 1e-9);
+
+        let latency_first = ScoringWeights {
+            weight_latency: 0.6,
+            weight_speed: 0.1,
+            latency_reference_ms: 20.0,
+            ..ScoringWeights::default()
+        };
+        let scores = NetworkTester::new(5, 4)
+            .with_scoring(latency_first)
+            .calculate_scores(&results);
+        assert_eq!(scores[0].interface, "wan_lte");
+        // 低于延迟基准得满分，权重总和 1.2 折算为比例：(30 + 20 + 100 * 0.6 + 25 * 0.1) / 1.2
+        assert!((scores[0].score - 93.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_relative_speed_scoring() {
+        // 三条都远低于 1MB/s 的慢速链路，其余指标相同
+        let results: Vec<TestResult> = [("wan_a", 20.0), ("wan_b", 40.0), ("wan_c", 80.0)]
+            .iter()
+            .map(|(iface, speed)| TestResult {
+                interface: iface.to_string(),
                 target: "8.8.8.8".to_string(),
+                resolved_address: None,
                 reachable: true,
-                latency_ms: Some(10.0),
-                packet_loss: Some(0.0),
-                download_speed: Some(1024.0),
-                tested_at: chrono::Local::now(),
-            },
-            TestResult {
-                interface: "eth0".to_string(),
-                target: "1.1.1.1".to_string(),
-                reachable: true,
-                latency_ms: Some(15.0),
+                latency_ms: Some(20.0),
+                jitter_ms: None,
+                rtt_samples: Vec::new(),
                 packet_loss: Some(0.0),
-                download_speed: Some(2048.0),
+                download_speed: Some(*speed),
+                upload_speed: None,
+                failure_reason: None,
+                health: None,
+                dscp: None,
                 tested_at: chrono::Local::now(),
-            },
-        ];
+            })
+            .collect();
 
-        let tester = NetworkTester::new(5, 4);
-        let scores = tester.calculate_scores(&results);
+        let absolute = NetworkTester::new(5, 4).calculate_scores(&results);
+        let spread = absolute[0].score - absolute[2].score;
+        assert!(spread < 3.0, "绝对评分下速度差异被压平: {}", spread);
 
-        assert_eq!(scores.len(), 1);
-        assert_eq!(scores[0].interface, "eth0");
-        assert_eq!(scores[0].reachable_count, 2);
+        let relative = NetworkTester::new(5, 4)
+            .with_speed_scoring(SpeedScoring::Relative)
+            .calculate_scores(&results);
+        let order: Vec<_> = relative.iter().map(|s| s.interface.as_str()).collect();
+        assert_eq!(order, vec!["wan_c", "wan_b", "wan_a"]);
+        // 最快接口得满速度分，其余按比例
+        assert!((relative[0].score - relative[1].score - 20.0).abs() < 1e-9);
+        assert!((relative[1].score - relative[2].score - 10.0).abs() < 1e-9);
     }
 }
@@ -0,0 +1,173 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 切换通知
+//!
+//! 切换成功并通过验证后向 webhook POST 切换详情。通知在后台任务中发送，
+//! 失败只记录警告，不影响也不阻塞监控循环
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::config::NotifyConfig;
+use crate::decision::SwitchDecision;
+
+/// 一次接口切换的通知内容
+#[derive(Debug, Clone, Serialize)]
+pub struct SwitchNotification {
+    /// 原接口
+    pub from: Option<String>,
+    /// 新接口
+    pub to: String,
+    /// 新接口评分
+    pub score: f64,
+    /// 原接口评分
+    pub previous_score: Option<f64>,
+    /// 切换原因（决策结果）
+    pub reason: String,
+    /// 切换完成时间
+    pub timestamp: DateTime<Local>,
+}
+
+impl SwitchNotification {
+    /// 根据切换决策生成通知
+    pub fn from_decision(decision: &SwitchDecision, from: Option<String>) -> Self {
+        let reason = serde_json::to_value(decision.outcome)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        Self {
+            from,
+            to: decision.best.clone(),
+            score: decision.best_score,
+            previous_score: decision.current_score,
+            reason,
+            timestamp: Local::now(),
+        }
+    }
+
+    /// 生成请求体：有模板时替换占位符（按 JSON 字符串转义），否则为 JSON 格式的通知内容
+    pub fn render(&self, template: Option<&str>) -> String {
+        let Some(template) = template else {
+            return serde_json::to_string(self).unwrap_or_default();
+        };
+
+        let escape = |value: &str| {
+            let quoted = serde_json::to_string(value).unwrap_or_default();
+            quoted[1..quoted.len() - 1].to_string()
+        };
+        let score = |score: Option<f64>| score.map(|s| format!("{:.2}", s)).unwrap_or_default();
+        template
+            .replace("{from}", &escape(self.from.as_deref().unwrap_or("")))
+            .replace("{to}", &escape(&self.to))
+            .replace("{score}", &score(Some(self.score)))
+            .replace("{previous_score}", &score(self.previous_score))
+            .replace("{reason}", &escape(&self.reason))
+            .replace("{timestamp}", &self.timestamp.to_rfc3339())
+    }
+}
+
+/// 发送通知，超过配置的超时时间视为失败
+pub async fn send(config: &NotifyConfig, notification: &SwitchNotification) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout))
+        .build()
+        .context("创建 HTTP 客户端失败")?;
+
+    let response = client
+        .post(&config.webhook_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(notification.render(config.template.as_deref()))
+        .send()
+        .await
+        .context("请求 webhook 失败")?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("webhook 返回 {}", status);
+    }
+    Ok(())
+}
+
+/// 在后台任务中发送通知
+pub fn spawn(config: NotifyConfig, notification: SwitchNotification) {
+    tokio::spawn(async move {
+        match send(&config, &notification).await {
+            Ok(()) => info!("已发送切换通知: {}", notification.to),
+            Err(e) => warn!("发送切换通知失败: {:#}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::Router;
+    use tokio::sync::mpsc;
+
+    fn notification() -> SwitchNotification {
+        SwitchNotification {
+            from: Some("wan_a".to_string()),
+            to: "wan_b".to_string(),
+            score: 82.5,
+            previous_score: Some(40.4),
+            reason: "threshold_reached".to_string(),
+            timestamp: Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let body = notification().render(Some(
+            r#"{"text": "{from} ({previous_score}) -> {to} ({score}): {reason}"}"#,
+        ));
+        assert_eq!(
+            body,
+            r#"{"text": "wan_a (40.40) -> wan_b (82.50): threshold_reached"}"#
+        );
+
+        // 占位符的值按 JSON 字符串转义
+        let mut quoted = notification();
+        quoted.to = "wan \"b\"".to_string();
+        assert_eq!(quoted.render(Some("{to}")), r#"wan \"b\""#);
+
+        let json: serde_json::Value = serde_json::from_str(&notification().render(None)).unwrap();
+        assert_eq!(json["from"], "wan_a");
+        assert_eq!(json["previous_score"], 40.4);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_webhook() {
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |body: String| async move {
+                sender.send(body).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut config = NotifyConfig {
+            webhook_url: format!("http://{}/hook", addr),
+            template: None,
+            timeout: 2,
+        };
+        send(&config, &notification()).await.unwrap();
+        let body: serde_json::Value =
+            serde_json::from_str(&received.recv().await.unwrap()).unwrap();
+        assert_eq!(body["to"], "wan_b");
+        assert_eq!(body["reason"], "threshold_reached");
+
+        // 非 2xx 响应视为失败
+        config.webhook_url = format!("http://{}/missing", addr);
+        assert!(send(&config, &notification()).await.is_err());
+    }
+}
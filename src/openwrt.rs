@@ -5,32 +5,190 @@
 
 use anyhow::{Context, Result};
 use log::{debug, info, warn};
-use tokio::process::Command;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io;
+use std::ops::RangeInclusive;
+use std::process::Output;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::command::{CommandRunner, SystemRunner};
+use crate::config::{Backend, NetworkInterface, SwitchStyle};
+
+/// 网关解析失败时的最大尝试次数
+const GATEWAY_RESOLVE_ATTEMPTS: u32 = 3;
+/// 网关解析重试间隔
+const GATEWAY_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// 排空切换期间新默认路由使用的 metric（高于原默认路由，不抢占现有流量）
+const DRAIN_METRIC: &str = "100";
+
+/// 单个目标的静态路由分配
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteAssignment {
+    /// 目标地址
+    pub target: String,
+    /// 路由出口（逻辑接口名）
+    pub interface: String,
+}
+
+/// 排空切换中等待完成的后半段
+#[derive(Debug, Clone)]
+struct PendingDrain {
+    /// 旧接口
+    previous: String,
+    /// 新接口
+    interface: String,
+    /// 新接口的网关
+    gateway: String,
+}
+
+/// 被外部修改的托管路由
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteDrift {
+    /// 目标地址
+    pub target: String,
+    /// 本程序设置的出口（物理接口名）
+    pub expected: String,
+    /// UCI 中的实际出口（路由被删除时为空）
+    pub actual: Option<String>,
+}
+
+/// 启动时保存的路由状态，退出时据此恢复托管路由的原始出口
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteSnapshot {
+    /// 内核路由表（`ip route show` 的输出）
+    pub kernel_routes: String,
+    /// UCI 中本程序命名的 `route_*` 配置段: (配置段, 目标, 出口)
+    pub sections: Vec<(String, String, String)>,
+}
 
-use crate::config::NetworkInterface;
+/// 缓存的网关解析结果
+struct CachedGateway {
+    /// 网关地址
+    gateway: String,
+    /// 解析时接口的地址（地址变化时缓存失效）
+    address: Option<String>,
+    /// 解析时间
+    resolved_at: Instant,
+}
 
 /// OpenWrt 路由管理器
 pub struct OpenWrtManager {
     /// 当前活动接口
     current_interface: Option<String>,
+    /// 上次切换前的活动接口，切换验证失败时回滚到该接口
+    previous_interface: Option<String>,
+    /// 上次切换前各 `route_*` 配置段的出口: (配置段, 目标, 出口)
+    previous_routes: Vec<(String, String, String)>,
+    /// 上次排空切换前的默认路由（`ip route show default` 的各行），回滚时恢复
+    previous_default_routes: Vec<String>,
+    /// 已添加备用默认路由、等待排空后完成的切换
+    pending_drain: Option<PendingDrain>,
     /// 路由规则标记（用于识别本程序创建的规则）
     #[allow(dead_code)]
     rule_marker: String,
+    /// 外部命令执行器
+    runner: Arc<dyn CommandRunner>,
+    /// 接口网关缓存
+    gateway_cache: Mutex<HashMap<String, CachedGateway>>,
+    /// 网关缓存有效期
+    gateway_cache_ttl: Duration,
+    /// 切换方式
+    switch_style: SwitchStyle,
+    /// 排空切换时等待现有连接结束的时间
+    drain_time: Duration,
+    /// 重载后静态路由仍未生效时是否允许重启网络服务
+    allow_network_restart: bool,
+    /// 演练模式：只记录将要执行的 UCI 修改
+    dry_run: bool,
+    /// 读写 UCI 路由和重载网络的方式
+    backend: Backend,
+    /// 本程序使用的策略路由规则优先级范围
+    rule_priorities: RangeInclusive<u32>,
+    /// 当前生效的多路径默认路由: (接口, 权重)，未启用时为空
+    ecmp_routes: Vec<(String, u32)>,
 }
 
+/// 提交 UCI 更改后等待静态路由生效的最长时间
+const ROUTE_SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 等待静态路由生效时的轮询间隔
+const ROUTE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 impl OpenWrtManager {
     /// 创建新的 OpenWrt 管理器
     pub fn new() -> Self {
         Self {
             current_interface: None,
+            previous_interface: None,
+            previous_routes: Vec::new(),
+            previous_default_routes: Vec::new(),
+            pending_drain: None,
             rule_marker: "routes-monitor".to_string(),
+            runner: Arc::new(SystemRunner),
+            gateway_cache: Mutex::new(HashMap::new()),
+            gateway_cache_ttl: Duration::from_secs(30),
+            switch_style: SwitchStyle::default(),
+            drain_time: Duration::from_secs(30),
+            allow_network_restart: false,
+            dry_run: false,
+            backend: Backend::default(),
+            rule_priorities: 100..=999,
+            ecmp_routes: Vec::new(),
         }
     }
 
+    /// 使用指定的命令执行器
+    #[cfg(test)]
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// 设置网关缓存有效期
+    pub fn with_gateway_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.gateway_cache_ttl = ttl;
+        self
+    }
+
+    /// 设置切换方式及排空时间
+    pub fn with_switch_style(mut self, style: SwitchStyle, drain_time: Duration) -> Self {
+        self.switch_style = style;
+        self.drain_time = drain_time;
+        self
+    }
+
+    /// 设置重载无效时是否允许重启网络服务
+    pub fn with_network_restart(mut self, allow: bool) -> Self {
+        self.allow_network_restart = allow;
+        self
+    }
+
+    /// 设置读写 UCI 路由和重载网络的方式
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// 设置本程序使用的策略路由规则优先级：从 `base` 开始共 `range` 个
+    pub fn with_rule_priorities(mut self, base: u32, range: u32) -> Self {
+        self.rule_priorities = base..=base + range.saturating_sub(1);
+        self
+    }
+
+    /// 设置演练模式
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
     /// 将逻辑接口名转换为物理接口名
     /// pppoe-wan_cm -> wan_cm
     /// pppoe-wan_ct1 -> wan_ct1
-    fn convert_to_physical_interface(logical_name: &str) -> String {
+    /// eth0.100 -> eth0.100（VLAN 设备名保持不变）
+    pub fn convert_to_physical_interface(logical_name: &str) -> String {
         logical_name.trim_start_matches("pppoe-").to_string()
     }
 
@@ -47,13 +205,16 @@ impl OpenWrtManager {
         &mut self,
         interface: &NetworkInterface,
         manage_uci_routes: bool,
-        static_route_targets: Option<&[String]>,
+        static_route_targets: Option<&[RouteAssignment]>,
     ) -> Result<()> {
         info!(
             "开始切换到接口: {} ({})",
             interface.name, interface.display_name
         );
 
+        // 上一次排空切换尚未完成时立即完成，不遗留备用默认路由
+        self.complete_drain().await?;
+
         // 如果已经是当前接口，则跳过
         if let Some(current) = &self.current_interface {
             if current == &interface.name {
@@ -62,11 +223,30 @@ impl OpenWrtManager {
             }
         }
 
+        self.previous_interface = self.current_interface.clone();
+        self.previous_routes.clear();
+        self.previous_default_routes.clear();
+
         // 使用 UCI 配置管理静态路由（持久化到 /etc/config/network）
         // 只修改 UCI 配置，让 OpenWrt 自己处理路由
         if manage_uci_routes {
-            if let Some(targets) = static_route_targets {
-                self.manage_static_routes(targets, &interface.name).await?;
+            if let Some(assignments) = static_route_targets {
+                // 记录改写前的出口，验证失败时据此回滚
+                self.previous_routes = self
+                    .get_uci_static_routes()
+                    .await?
+                    .into_iter()
+                    .filter(|(section, _, _)| section.starts_with("route_"))
+                    .collect();
+                self.manage_static_routes(assignments).await?;
+            }
+        }
+
+        if self.switch_style == SwitchStyle::Drain {
+            if let Some(previous) = self.current_interface.clone() {
+                // 记录切换前的默认路由，验证失败时据此回滚
+                self.previous_default_routes = self.default_routes().await?;
+                self.start_drain(&previous, interface).await?;
             }
         }
 
@@ -77,14 +257,292 @@ impl OpenWrtManager {
         Ok(())
     }
 
+    /// 逐步迁移默认路由，让旧接口上的现有连接有时间结束
+    ///
+    /// 1. 以较高 metric 添加新接口的默认路由
+    /// 2. 等待 drain_time（由调用方在释放锁后等待，见 [`Self::pending_drain_time`]）
+    /// 3. 删除旧接口的默认路由
+    /// 4. 将新接口的默认路由提升为主路由
+    ///
+    /// 这里只执行第 1 步，第 3、4 步由 [`Self::complete_drain`] 完成
+    async fn start_drain(&mut self, previous: &str, interface: &NetworkInterface) -> Result<()> {
+        let gateway = match &interface.gateway {
+            Some(gw) => gw.clone(),
+            None => self.get_interface_gateway(&interface.name).await?,
+        };
+        let new_route = [
+            "default",
+            "via",
+            gateway.as_str(),
+            "dev",
+            interface.name.as_str(),
+        ];
+
+        info!(
+            "排空切换 [1/4]: 添加 {} 的备用默认路由 (via {}, metric {})",
+            interface.name, gateway, DRAIN_METRIC
+        );
+        self.run_route_command("add", &new_route, Some(DRAIN_METRIC))
+            .await?;
+
+        info!(
+            "排空切换 [2/4]: 等待 {} 秒让 {} 上的现有连接结束",
+            self.drain_time.as_secs(),
+            previous
+        );
+        self.pending_drain = Some(PendingDrain {
+            previous: previous.to_string(),
+            interface: interface.name.clone(),
+            gateway,
+        });
+        Ok(())
+    }
+
+    /// 排空切换已添加备用默认路由、尚未完成时需要等待的时间
+    pub fn pending_drain_time(&self) -> Option<Duration> {
+        self.pending_drain.as_ref().map(|_| self.drain_time)
+    }
+
+    /// 完成排空切换：删除旧接口的默认路由，并将新接口的默认路由提升为主路由
+    /// 没有进行中的排空切换时不做任何操作
+    pub async fn complete_drain(&mut self) -> Result<()> {
+        let Some(drain) = self.pending_drain.take() else {
+            return Ok(());
+        };
+        let new_route = [
+            "default",
+            "via",
+            drain.gateway.as_str(),
+            "dev",
+            drain.interface.as_str(),
+        ];
+
+        info!("排空切换 [3/4]: 删除 {} 的默认路由", drain.previous);
+        if let Err(e) = self
+            .run_route_command("del", &["default", "dev", &drain.previous], None)
+            .await
+        {
+            warn!("删除旧默认路由失败: {}", e);
+        }
+
+        info!(
+            "排空切换 [4/4]: 将 {} 的默认路由设为主路由",
+            drain.interface
+        );
+        self.run_route_command("replace", &new_route, None).await?;
+        if let Err(e) = self
+            .run_route_command("del", &new_route, Some(DRAIN_METRIC))
+            .await
+        {
+            warn!("删除备用默认路由失败: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// 当前的默认路由（`ip route show default` 的各行）
+    async fn default_routes(&self) -> Result<Vec<String>> {
+        let output = self
+            .runner
+            .run("ip", &["route", "show", "default"])
+            .await
+            .context("获取默认路由失败")?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// 撤销排空切换对默认路由的修改：删除新接口的默认路由，恢复切换前的默认路由
+    async fn restore_default_routes(&mut self, interface: &str) -> Result<()> {
+        if let Some(drain) = self.pending_drain.take() {
+            let backup = ["default", "via", &drain.gateway, "dev", &drain.interface];
+            if let Err(e) = self
+                .run_route_command("del", &backup, Some(DRAIN_METRIC))
+                .await
+            {
+                warn!("删除备用默认路由失败: {}", e);
+            }
+        }
+        let _ = self
+            .run_route_command("del", &["default", "dev", interface], None)
+            .await;
+
+        for route in std::mem::take(&mut self.previous_default_routes) {
+            // linkdown/dead 是内核报告的状态，不是可设置的路由属性
+            let route: Vec<&str> = route
+                .split_whitespace()
+                .filter(|token| !matches!(*token, "linkdown" | "dead"))
+                .collect();
+            self.run_route_command("replace", &route, None).await?;
+        }
+        info!("已恢复切换前的默认路由");
+        Ok(())
+    }
+
+    /// 是否已在多个接口间设置多路径默认路由
+    pub fn ecmp_active(&self) -> bool {
+        !self.ecmp_routes.is_empty()
+    }
+
+    /// 按评分计算多路径路由权重：每 10 分为 1 份，取值 1-10
+    ///
+    /// 粗粒度的权重避免评分的细微波动导致路由频繁重写
+    pub fn ecmp_weight(score: f64) -> u32 {
+        (score / 10.0).ceil().clamp(1.0, 10.0) as u32
+    }
+
+    /// 在多个接口间设置多路径（ECMP）默认路由，权重与评分成正比
+    ///
+    /// 只有一个接口时恢复为普通的单出口默认路由；
+    /// 与当前生效的接口和权重相同时不重复执行
+    pub async fn setup_ecmp_routes(&mut self, members: &[(&NetworkInterface, f64)]) -> Result<()> {
+        if members.is_empty() {
+            anyhow::bail!("没有可用于多路径路由的接口");
+        }
+
+        let plan: Vec<(String, u32)> = members
+            .iter()
+            .map(|(interface, score)| (interface.name.clone(), Self::ecmp_weight(*score)))
+            .collect();
+        if plan == self.ecmp_routes {
+            debug!("多路径默认路由未变化，跳过设置");
+            return Ok(());
+        }
+
+        let mut gateways = Vec::with_capacity(members.len());
+        for (interface, _) in members {
+            let gateway = match &interface.gateway {
+                Some(gw) => gw.clone(),
+                None => self.get_interface_gateway(&interface.name).await?,
+            };
+            gateways.push(gateway);
+        }
+
+        let mut route = vec!["default".to_string()];
+        if let [gateway] = gateways.as_slice() {
+            route.extend([
+                "via".to_string(),
+                gateway.clone(),
+                "dev".to_string(),
+                plan[0].0.clone(),
+            ]);
+        } else {
+            for (gateway, (name, weight)) in gateways.iter().zip(&plan) {
+                route.extend([
+                    "nexthop".to_string(),
+                    "via".to_string(),
+                    gateway.clone(),
+                    "dev".to_string(),
+                    name.clone(),
+                    "weight".to_string(),
+                    weight.to_string(),
+                ]);
+            }
+        }
+        let route: Vec<&str> = route.iter().map(String::as_str).collect();
+
+        let summary = plan
+            .iter()
+            .map(|(name, weight)| format!("{}×{}", name, weight))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.dry_run {
+            info!(
+                "[演练] 将设置默认路由: ip route replace {}",
+                route.join(" ")
+            );
+        } else {
+            self.run_route_command("replace", &route, None).await?;
+            if plan.len() > 1 {
+                info!("已设置多路径默认路由: {}", summary);
+            } else {
+                info!("已恢复单出口默认路由: {}", plan[0].0);
+            }
+        }
+
+        self.ecmp_routes = if plan.len() > 1 { plan } else { Vec::new() };
+        Ok(())
+    }
+
+    /// 执行 `ip route <action> ...`，可选追加 metric
+    async fn run_route_command(
+        &self,
+        action: &str,
+        route: &[&str],
+        metric: Option<&str>,
+    ) -> Result<()> {
+        let mut args = vec!["route", action];
+        args.extend_from_slice(route);
+        if let Some(metric) = metric {
+            args.extend_from_slice(&["metric", metric]);
+        }
+
+        let output = self
+            .runner
+            .run("ip", &args)
+            .await
+            .with_context(|| format!("执行 ip route {} 命令失败", action))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("ip {} 失败: {}", args.join(" "), stderr.trim());
+        }
+
+        Ok(())
+    }
+
+    /// 按接口的 `table_id` 建立策略路由：先清除本程序优先级范围内的旧规则，
+    /// 再为每个配置了路由表的接口添加规则和表内默认路由
+    /// 返回: 建立了策略路由的接口数量（没有接口配置 `table_id` 时不做任何修改）
+    pub async fn setup_policy_routes(&self, interfaces: &[NetworkInterface]) -> Result<usize> {
+        let routed: Vec<_> = interfaces
+            .iter()
+            .filter(|i| i.enabled && i.table_id.is_some())
+            .collect();
+        if routed.is_empty() {
+            return Ok(0);
+        }
+
+        if self.dry_run {
+            for interface in &routed {
+                info!(
+                    "[演练] 将建立策略路由: oif {} -> table {}（优先级 {}）",
+                    interface.name,
+                    interface.table_id.unwrap_or_default(),
+                    self.rule_priorities.start()
+                );
+            }
+            return Ok(routed.len());
+        }
+
+        self.clear_policy_rules().await?;
+        for interface in &routed {
+            self.setup_policy_routing(interface).await?;
+        }
+        self.flush_route_cache().await?;
+
+        Ok(routed.len())
+    }
+
     /// 获取当前所有策略路由规则
     async fn get_current_rules(&self) -> Result<Vec<String>> {
-        let output = Command::new("ip")
-            .args(&["rule", "show"])
-            .output()
+        let output = self
+            .runner
+            .run("ip", &["rule", "show"])
             .await
             .context("获取路由规则失败")?;
 
+        if !output.status.success() {
+            anyhow::bail!(
+                "获取路由规则失败: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
         let rules = String::from_utf8_lossy(&output.stdout)
             .lines()
             .map(|s| s.to_string())
@@ -93,44 +551,32 @@ impl OpenWrtManager {
         Ok(rules)
     }
 
-    /// 清除旧的路由规则
-    /// 策略：
-    /// 1. 清除默认路由（会被新接口的默认路由替代）
-    /// 2. 清除所有优先级在 100-999 范围内的规则（本程序使用的范围）
-    /// 3. 清除指定路由表中的所有路由
-    async fn clear_old_routes(&self) -> Result<()> {
-        info!("清除旧的路由规则...");
-
-        // 1. 删除默认路由
-        // 注意：可能返回错误（如果没有默认路由），我们忽略错误
-        let _ = Command::new("ip")
-            .args(&["route", "del", "default"])
-            .output()
-            .await;
-
-        debug!("默认路由已清除");
+    /// 清除本程序创建的策略路由规则
+    /// 只删除优先级在本程序使用范围内的规则（默认 100-999），不影响 mwan3 等其他工具的规则
+    pub async fn clear_policy_rules(&self) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        info!("清除旧的策略路由规则...");
 
-        // 2. 获取现有规则，只删除我们创建的（优先级 100-999）
+        // 获取现有规则，只删除我们创建的（优先级在配置的范围内）
         if let Ok(rules) = self.get_current_rules().await {
             for rule in rules {
-                // 检查是否是我们的优先级范围
                 if let Some(priority) = self.extract_priority(&rule) {
-                    if priority >= 100 && priority <= 999 {
-                        debug!("删除路由规则: {}", rule);
-                        let _ = Command::new("ip")
-                            .args(&["rule", "del", "priority", &priority.to_string()])
-                            .output()
-                            .await;
-                    }
+                    debug!("删除路由规则: {}", rule);
+                    let _ = self
+                        .runner
+                        .run("ip", &["rule", "del", "priority", &priority.to_string()])
+                        .await;
                 }
             }
         } else {
             // 如果无法获取规则列表，退回到批量删除
             warn!("无法获取规则列表，使用批量删除模式");
-            for priority in 100..=999 {
-                let _ = Command::new("ip")
-                    .args(&["rule", "del", "priority", &priority.to_string()])
-                    .output()
+            for priority in self.rule_priorities.clone() {
+                let _ = self
+                    .runner
+                    .run("ip", &["rule", "del", "priority", &priority.to_string()])
                     .await;
             }
         }
@@ -140,10 +586,11 @@ impl OpenWrtManager {
         Ok(())
     }
 
-    /// 从规则字符串中提取优先级
+    /// 从规则字符串中提取优先级，不在本程序使用范围内的规则返回 `None`
     /// 例如: "100: from all lookup 100" -> Some(100)
     fn extract_priority(&self, rule: &str) -> Option<u32> {
-        rule.split(':').next()?.trim().parse().ok()
+        let priority = rule.split(':').next()?.trim().parse().ok()?;
+        self.rule_priorities.contains(&priority).then_some(priority)
     }
 
     /// 设置策略路由
@@ -153,18 +600,21 @@ impl OpenWrtManager {
         // 如果配置了路由表 ID，设置策略路由
         if let Some(table_id) = interface.table_id {
             // 添加路由规则：从指定接口出去的流量使用指定路由表
-            let output = Command::new("ip")
-                .args(&[
-                    "rule",
-                    "add",
-                    "oif",
-                    &interface.name,
-                    "table",
-                    &table_id.to_string(),
-                    "priority",
-                    "100",
-                ])
-                .output()
+            let output = self
+                .runner
+                .run(
+                    "ip",
+                    &[
+                        "rule",
+                        "add",
+                        "oif",
+                        &interface.name,
+                        "table",
+                        &table_id.to_string(),
+                        "priority",
+                        &self.rule_priorities.start().to_string(),
+                    ],
+                )
                 .await
                 .context("执行 ip rule add 命令失败")?;
 
@@ -178,21 +628,32 @@ impl OpenWrtManager {
 
             debug!("策略路由规则已添加: table {}", table_id);
 
-            // 在指定路由表中添加默认路由
-            if let Some(gateway) = &interface.gateway {
-                let output = Command::new("ip")
-                    .args(&[
-                        "route",
-                        "add",
-                        "default",
-                        "via",
-                        gateway,
-                        "dev",
-                        &interface.name,
-                        "table",
-                        &table_id.to_string(),
-                    ])
-                    .output()
+            // 在指定路由表中添加默认路由（未配置网关时自动获取）
+            let gateway = match &interface.gateway {
+                Some(gateway) => Some(gateway.clone()),
+                None => self
+                    .get_interface_gateway(&interface.name)
+                    .await
+                    .inspect_err(|e| warn!("{:#}，路由表 {} 不添加默认路由", e, table_id))
+                    .ok(),
+            };
+            if let Some(gateway) = &gateway {
+                let output = self
+                    .runner
+                    .run(
+                        "ip",
+                        &[
+                            "route",
+                            "add",
+                            "default",
+                            "via",
+                            gateway,
+                            "dev",
+                            &interface.name,
+                            "table",
+                            &table_id.to_string(),
+                        ],
+                    )
                     .await
                     .context("执行 ip route add 命令失败")?;
 
@@ -212,6 +673,7 @@ impl OpenWrtManager {
     }
 
     /// 设置默认网关
+    #[allow(dead_code)]
     async fn set_default_gateway(&self, interface: &NetworkInterface) -> Result<()> {
         info!("设置默认网关: {}", interface.name);
 
@@ -224,17 +686,20 @@ impl OpenWrtManager {
         };
 
         // 添加默认路由
-        let output = Command::new("ip")
-            .args(&[
-                "route",
-                "add",
-                "default",
-                "via",
-                &gateway,
-                "dev",
-                &interface.name,
-            ])
-            .output()
+        let output = self
+            .runner
+            .run(
+                "ip",
+                &[
+                    "route",
+                    "add",
+                    "default",
+                    "via",
+                    &gateway,
+                    "dev",
+                    &interface.name,
+                ],
+            )
             .await
             .context("执行 ip route add default 命令失败")?;
 
@@ -249,11 +714,115 @@ impl OpenWrtManager {
     }
 
     /// 获取接口的网关地址
+    /// 结果按接口缓存，有效期内直接复用；解析失败时短暂重试
     async fn get_interface_gateway(&self, interface: &str) -> Result<String> {
+        if let Some(cached) = self
+            .gateway_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(interface)
+        {
+            if cached.resolved_at.elapsed() < self.gateway_cache_ttl {
+                debug!("使用缓存的网关: {} -> {}", interface, cached.gateway);
+                return Ok(cached.gateway.clone());
+            }
+        }
+
+        let mut attempt = 1;
+        let gateway = loop {
+            match self.resolve_interface_gateway(interface).await {
+                Ok(gateway) => break gateway,
+                Err(e) if attempt < GATEWAY_RESOLVE_ATTEMPTS => {
+                    debug!(
+                        "解析接口 {} 网关失败（第 {} 次），稍后重试: {}",
+                        interface, attempt, e
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(GATEWAY_RETRY_DELAY).await;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let address = self.interface_address(interface).await;
+        self.gateway_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                interface.to_string(),
+                CachedGateway {
+                    gateway: gateway.clone(),
+                    address,
+                    resolved_at: Instant::now(),
+                },
+            );
+
+        Ok(gateway)
+    }
+
+    /// 检查缓存网关对应接口的地址，地址变化的接口使缓存失效
+    pub async fn refresh_gateway_cache(&self) {
+        let cached: Vec<(String, Option<String>)> = self
+            .gateway_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(iface, entry)| (iface.clone(), entry.address.clone()))
+            .collect();
+
+        for (interface, address) in cached {
+            let current = self.interface_address(&interface).await;
+            if current != address {
+                info!(
+                    "接口 {} 地址已变化 ({:?} -> {:?})，清除网关缓存",
+                    interface, address, current
+                );
+                self.gateway_cache
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&interface);
+            }
+        }
+    }
+
+    /// 获取接口当前的地址
+    async fn interface_address(&self, interface: &str) -> Option<String> {
+        let output = self
+            .runner
+            .run("ip", &["-o", "addr", "show", "dev", interface])
+            .await
+            .ok()?;
+
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .skip_while(|word| *word != "inet" && *word != "inet6")
+            .nth(1)
+            .map(|addr| addr.to_string())
+    }
+
+    /// 接口是否在 UCI 中被禁用（`network.<iface>.disabled = 1`）
+    pub async fn is_uci_disabled(&self, interface: &str) -> bool {
+        let output = self
+            .runner
+            .run(
+                "uci",
+                &["-q", "get", &format!("network.{}.disabled", interface)],
+            )
+            .await;
+
+        // 未设置该选项时 uci 返回非零，视为启用
+        match output {
+            Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout).trim() == "1",
+            _ => false,
+        }
+    }
+
+    /// 解析接口的网关地址（不使用缓存）
+    async fn resolve_interface_gateway(&self, interface: &str) -> Result<String> {
         // 使用 UCI 命令获取接口配置（OpenWrt 特有）
-        let output = Command::new("uci")
-            .args(&["get", &format!("network.{}.gateway", interface)])
-            .output()
+        let output = self
+            .runner
+            .run("uci", &["get", &format!("network.{}.gateway", interface)])
             .await;
 
         match output {
@@ -267,9 +836,9 @@ impl OpenWrtManager {
         }
 
         // 如果 UCI 失败，尝试从路由表获取
-        let output = Command::new("ip")
-            .args(&["route", "show", "dev", interface])
-            .output()
+        let output = self
+            .runner
+            .run("ip", &["route", "show", "dev", interface])
             .await
             .context("获取接口路由失败")?;
 
@@ -292,9 +861,9 @@ impl OpenWrtManager {
     async fn flush_route_cache(&self) -> Result<()> {
         debug!("刷新路由缓存...");
 
-        let output = Command::new("ip")
-            .args(&["route", "flush", "cache"])
-            .output()
+        let output = self
+            .runner
+            .run("ip", &["route", "flush", "cache"])
             .await
             .context("执行 ip route flush cache 命令失败")?;
 
@@ -308,60 +877,148 @@ impl OpenWrtManager {
     }
 
     /// 验证接口切换是否成功
-    /// 检查被监控的 UCI 静态路由是否已正确配置到目标接口
-    pub async fn verify_switch(&self, interface: &NetworkInterface) -> Result<bool> {
+    /// 按本次切换应用的路由分配逐条检查 UCI 静态路由：关键目标或地址族不兼容的目标
+    /// 可能被分配到其他接口，只要与分配一致即视为正确；UCI 中尚不存在的路由不在此验证
+    pub async fn verify_switch(
+        &self,
+        interface: &NetworkInterface,
+        plan: &[RouteAssignment],
+    ) -> Result<bool> {
         info!("验证接口切换: {}", interface.name);
 
-        let physical_interface = Self::convert_to_physical_interface(&interface.name);
-
-        // 检查 UCI 静态路由是否已配置到目标接口
-        let routes = self.get_uci_static_routes().await?;
-
-        if routes.is_empty() {
+        if plan.is_empty() {
             info!("没有 UCI 静态路由需要验证");
             return Ok(true);
         }
 
-        // 只验证由本程序管理的路由（route_ 前缀的命名路由）
-        let managed_routes: Vec<_> = routes
-            .iter()
-            .filter(|(section, _, _)| section.starts_with("route_"))
+        let drift: Vec<_> = self
+            .route_drift(plan)
+            .await?
+            .into_iter()
+            .filter(|route| route.actual.is_some())
             .collect();
-
-        if managed_routes.is_empty() {
-            info!("没有本程序管理的路由需要验证");
+        if drift.is_empty() {
+            info!(
+                "接口切换验证成功: {} ({} 条路由与分配一致)",
+                interface.name,
+                plan.len()
+            );
             return Ok(true);
         }
 
-        let all_correct = managed_routes
-            .iter()
-            .all(|(_, _, iface)| iface == &physical_interface);
+        warn!("接口切换验证失败: {} 条路由与分配不一致", drift.len());
+        for route in &drift {
+            warn!(
+                "  路由 {} 应指向 {}，实际为 {}",
+                route.target,
+                route.expected,
+                route.actual.as_deref().unwrap_or_default()
+            );
+        }
+
+        Ok(false)
+    }
 
-        if all_correct {
+    /// 功能验证：不绑定接口、按路由表探测目标，确认流量确实经由新接口转发
+    pub async fn verify_traffic(
+        &self,
+        interface: &NetworkInterface,
+        target: &str,
+        wait: Duration,
+    ) -> Result<bool> {
+        info!("功能验证: 经路由表探测 {} ({})", target, interface.name);
+
+        // 域名目标无法查询路由，只做连通性探测
+        if target.parse::<std::net::IpAddr>().is_ok() {
+            let output = self
+                .runner
+                .run("ip", &["route", "get", target])
+                .await
+                .context("查询路由失败")?;
+            let route = String::from_utf8_lossy(&output.stdout);
+            let dev = route
+                .split_whitespace()
+                .skip_while(|word| *word != "dev")
+                .nth(1);
+            let physical = Self::convert_to_physical_interface(&interface.name);
+
+            if dev != Some(interface.name.as_str()) && dev != Some(physical.as_str()) {
+                warn!(
+                    "功能验证失败: 到 {} 的路由经由 {}，而不是 {}",
+                    target,
+                    dev.unwrap_or("(无)"),
+                    interface.name
+                );
+                return Ok(false);
+            }
+        }
+
+        let wait_arg = wait.as_secs().max(1).to_string();
+        let output = self
+            .runner
+            .run("ping", &["-c", "3", "-W", &wait_arg, target])
+            .await
+            .context("执行 ping 失败")?;
+
+        if !output.status.success() {
+            warn!("功能验证失败: 经 {} 无法到达 {}", interface.name, target);
+            return Ok(false);
+        }
+
+        info!("功能验证通过: 经 {} 可以到达 {}", interface.name, target);
+        Ok(true)
+    }
+
+    /// 经指定下一跳探测目标，用于检查同一链路上的备用网关
+    ///
+    /// 临时添加经该网关到目标的主机路由，探测后无论成败都会删除
+    /// 返回: 是否可达；演练模式只记录将要添加的路由，不探测，返回 `None`
+    pub async fn probe_via_next_hop(
+        &self,
+        interface: &str,
+        gateway: &str,
+        target: std::net::IpAddr,
+        wait: Duration,
+    ) -> Result<Option<bool>> {
+        let host = match target {
+            std::net::IpAddr::V4(_) => format!("{}/32", target),
+            std::net::IpAddr::V6(_) => format!("{}/128", target),
+        };
+        let route = [host.as_str(), "via", gateway, "dev", interface];
+        if self.dry_run {
             info!(
-                "接口切换验证成功: {} (物理接口: {})",
-                interface.name, physical_interface
+                "[演练] 将临时添加路由探测备用网关: ip route add {}",
+                route.join(" ")
             );
-        } else {
-            warn!("接口切换验证失败: 部分路由未指向 {}", physical_interface);
-            for (section, target, iface) in &managed_routes {
-                if *iface != physical_interface {
-                    warn!("  路由 {} ({}) 仍指向 {}", section, target, iface);
-                }
-            }
+            return Ok(None);
+        }
+        self.run_route_command("add", &route, None).await?;
+
+        let wait_arg = wait.as_secs().max(1).to_string();
+        let target_arg = target.to_string();
+        let probe = self
+            .runner
+            .run(
+                "ping",
+                &["-c", "3", "-W", &wait_arg, "-I", interface, &target_arg],
+            )
+            .await;
+
+        if let Err(e) = self.run_route_command("del", &route, None).await {
+            warn!("删除临时路由 {} via {} 失败: {}", host, gateway, e);
         }
 
-        Ok(all_correct)
+        let output = probe.context("执行 ping 失败")?;
+        Ok(Some(output.status.success()))
     }
 
     /// 备份当前路由配置
-    #[allow(dead_code)]
     pub async fn backup_routes(&self) -> Result<String> {
         info!("备份当前路由配置...");
 
-        let output = Command::new("ip")
-            .args(&["route", "show"])
-            .output()
+        let output = self
+            .runner
+            .run("ip", &["route", "show"])
             .await
             .context("获取路由表失败")?;
 
@@ -372,22 +1029,97 @@ impl OpenWrtManager {
         Ok(routes)
     }
 
+    /// 保存内核路由表和各 `route_*` 配置段当前的出口
+    pub async fn snapshot_routes(&self) -> Result<RouteSnapshot> {
+        let kernel_routes = self.backup_routes().await?;
+        let sections: Vec<_> = self
+            .get_uci_static_routes()
+            .await?
+            .into_iter()
+            .filter(|(section, _, _)| section.starts_with("route_"))
+            .collect();
+        info!("已保存 {} 条静态路由的原始出口", sections.len());
+
+        Ok(RouteSnapshot {
+            kernel_routes,
+            sections,
+        })
+    }
+
+    /// 把快照中出口已被修改的配置段改回原始出口并提交
+    /// 返回: 恢复的路由数量
+    pub async fn restore_routes(&self, snapshot: &RouteSnapshot) -> Result<usize> {
+        self.restore_sections(&snapshot.sections).await
+    }
+
+    /// 撤销上一次切换：恢复排空切换改动的默认路由，把 `route_*` 配置段改回切换前的出口并提交，
+    /// 活动接口恢复为切换前的接口
+    /// 返回: 恢复的路由数量
+    pub async fn rollback_switch(&mut self) -> Result<usize> {
+        if !self.previous_default_routes.is_empty() {
+            let interface = self.current_interface.clone().unwrap_or_default();
+            self.restore_default_routes(&interface).await?;
+        }
+        let restored = self.restore_sections(&self.previous_routes).await?;
+        self.previous_routes.clear();
+        self.current_interface = self.previous_interface.take();
+        Ok(restored)
+    }
+
+    /// 把出口已被修改的配置段改回给定的出口并提交
+    async fn restore_sections(&self, sections: &[(String, String, String)]) -> Result<usize> {
+        let current = self.get_uci_static_routes().await?;
+        let mut restored = Vec::new();
+
+        for (section, target, interface) in sections {
+            let unchanged = current
+                .iter()
+                .any(|(s, _, i)| s == section && i == interface);
+            if unchanged {
+                continue;
+            }
+
+            let output = self
+                .set_route_interface(section, interface)
+                .await
+                .context("恢复 UCI 路由失败")?;
+            if !output.status.success() {
+                warn!(
+                    "恢复路由 {} 失败: {}",
+                    target,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                continue;
+            }
+
+            info!("恢复路由: {} -> {}", target, interface);
+            restored.push(RouteAssignment {
+                target: target.clone(),
+                interface: interface.clone(),
+            });
+        }
+
+        if !restored.is_empty() {
+            self.commit_uci_changes(&restored).await?;
+        }
+
+        Ok(restored.len())
+    }
+
     /// 使用 ip route 命令管理静态路由（不持久化）
     /// 用于动态切换监控目标IP的路由，不修改UCI配置
+    #[allow(dead_code)]
     async fn manage_ip_static_routes(&self, targets: &[String], interface: &str) -> Result<()> {
         info!("更新静态IP路由到接口: {}", interface);
 
         for target in targets {
             // 删除旧路由（如果存在）
-            let _ = Command::new("ip")
-                .args(&["route", "del", target])
-                .output()
-                .await;
+            let _ = self.runner.run("ip", &["route", "del", target]).await;
 
             // 添加新路由
-            let output = Command::new("ip")
-                .args(&["route", "add", target, "dev", interface])
-                .output()
+            let output = self
+                .runner
+                .run("ip", &["route", "add", target, "dev", interface])
                 .await
                 .context(format!("添加路由 {} 失败", target))?;
 
@@ -410,19 +1142,22 @@ impl OpenWrtManager {
         info!("持久化接口配置: {}", interface.name);
 
         // 设置网络接口优先级
-        let _ = Command::new("uci")
-            .args(&[
-                "set",
-                &format!("network.{}.metric", interface.name),
-                &interface.priority.to_string(),
-            ])
-            .output()
+        let _ = self
+            .runner
+            .run(
+                "uci",
+                &[
+                    "set",
+                    &format!("network.{}.metric", interface.name),
+                    &interface.priority.to_string(),
+                ],
+            )
             .await;
 
         // 提交配置
-        let output = Command::new("uci")
-            .args(&["commit", "network"])
-            .output()
+        let output = self
+            .runner
+            .run("uci", &["commit", "network"])
             .await
             .context("提交 UCI 配置失败")?;
 
@@ -436,13 +1171,12 @@ impl OpenWrtManager {
     }
 
     /// 重启网络服务（慎用）
-    #[allow(dead_code)]
     pub async fn restart_network(&self) -> Result<()> {
         warn!("重启网络服务...");
 
-        let output = Command::new("/etc/init.d/network")
-            .arg("restart")
-            .output()
+        let output = self
+            .runner
+            .run("/etc/init.d/network", &["restart"])
             .await
             .context("重启网络服务失败")?;
 
@@ -458,6 +1192,7 @@ impl OpenWrtManager {
     /// 更新 UCI 配置中的静态路由接口
     /// 策略：替换接口而非删除配置，保留目标 IP
     /// 如果路由不存在，则创建新的路由
+    #[allow(dead_code)]
     async fn update_uci_routes(
         &self,
         old_interface: Option<&str>,
@@ -491,13 +1226,9 @@ impl OpenWrtManager {
                     section, target, interface, new_interface
                 );
 
-                // 使用 uci set 命令替换接口
-                let output = Command::new("uci")
-                    .args(&[
-                        "set",
-                        &format!("network.{}.interface={}", section, new_interface),
-                    ])
-                    .output()
+                // 替换路由配置段的接口
+                let output = self
+                    .set_route_interface(&section, new_interface)
                     .await
                     .context("执行 uci set 命令失败")?;
 
@@ -512,7 +1243,7 @@ impl OpenWrtManager {
         }
 
         // 3. 提交并应用更改
-        self.commit_uci_changes().await?;
+        self.commit_uci_changes(&[]).await?;
 
         info!("UCI 静态路由更新完成");
         Ok(())
@@ -521,9 +1252,13 @@ impl OpenWrtManager {
     /// 获取 UCI 配置中的所有静态路由
     /// 返回: Vec<(section_name, target, interface)>
     async fn get_uci_static_routes(&self) -> Result<Vec<(String, String, String)>> {
-        let output = Command::new("uci")
-            .args(&["show", "network"])
-            .output()
+        if self.backend == Backend::Ubus {
+            return self.get_ubus_static_routes().await;
+        }
+
+        let output = self
+            .runner
+            .run("uci", &["show", "network"])
             .await
             .context("执行 uci show 命令失败")?;
 
@@ -580,6 +1315,80 @@ impl OpenWrtManager {
         Ok(routes)
     }
 
+    /// 通过 ubus 读取所有静态路由
+    /// 返回: Vec<(section_name, target, interface)>
+    async fn get_ubus_static_routes(&self) -> Result<Vec<(String, String, String)>> {
+        let output = self
+            .ubus_call("uci", "get", json!({"config": "network", "type": "route"}))
+            .await
+            .context("执行 ubus call uci get 失败")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "通过 ubus 获取 UCI 配置失败: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let routes = Self::parse_ubus_routes(&output.stdout)?;
+        debug!("找到 {} 条 UCI 静态路由", routes.len());
+        Ok(routes)
+    }
+
+    /// 解析 `ubus call uci get` 返回的路由配置段（匿名配置段为 cfgXXXXXX 形式的内部名称）
+    fn parse_ubus_routes(stdout: &[u8]) -> Result<Vec<(String, String, String)>> {
+        let reply: serde_json::Value =
+            serde_json::from_slice(stdout).context("解析 ubus 返回的 UCI 配置失败")?;
+
+        let mut routes: Vec<_> = reply["values"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(section, values)| {
+                let target = values["target"].as_str()?;
+                let interface = values["interface"].as_str()?;
+                Some((section.clone(), target.to_string(), interface.to_string()))
+            })
+            .collect();
+        routes.sort();
+        Ok(routes)
+    }
+
+    /// 调用 ubus 方法，参数为 JSON
+    async fn ubus_call(
+        &self,
+        object: &str,
+        method: &str,
+        args: serde_json::Value,
+    ) -> io::Result<Output> {
+        let args = args.to_string();
+        self.runner
+            .run("ubus", &["call", object, method, &args])
+            .await
+    }
+
+    /// 设置路由配置段的出口（尚未提交）
+    async fn set_route_interface(&self, section: &str, interface: &str) -> io::Result<Output> {
+        match self.backend {
+            Backend::Shell => {
+                let cmd = format!("network.{}.interface={}", section, interface);
+                self.runner.run("uci", &["set", &cmd]).await
+            }
+            Backend::Ubus => {
+                self.ubus_call(
+                    "uci",
+                    "set",
+                    json!({
+                        "config": "network",
+                        "section": section,
+                        "values": {"interface": interface},
+                    }),
+                )
+                .await
+            }
+        }
+    }
+
     /// 从 UCI 配置行中提取值
     /// 例如: "network.route_wan.interface='wan_cm'" -> Some("wan_cm")
     fn extract_uci_value(line: &str) -> Option<String> {
@@ -591,15 +1400,21 @@ impl OpenWrtManager {
     }
 
     /// 提交 UCI 更改并使网络配置生效
-    async fn commit_uci_changes(&self) -> Result<()> {
+    ///
+    /// 部分 OpenWrt 版本上 reload 不会真正应用路由变更，若 `expected` 中的路由
+    /// 在等待时间内仍未生效且允许重启，则改用 restart
+    async fn commit_uci_changes(&self, expected: &[RouteAssignment]) -> Result<()> {
         info!("提交 UCI 配置更改并使网络生效...");
 
         // 1. 提交 network 配置
-        let output = Command::new("uci")
-            .args(&["commit", "network"])
-            .output()
-            .await
-            .context("提交 UCI 配置失败")?;
+        let output = match self.backend {
+            Backend::Shell => self.runner.run("uci", &["commit", "network"]).await,
+            Backend::Ubus => {
+                self.ubus_call("uci", "commit", json!({"config": "network"}))
+                    .await
+            }
+        }
+        .context("提交 UCI 配置失败")?;
 
         if !output.status.success() {
             anyhow::bail!(
@@ -610,11 +1425,11 @@ impl OpenWrtManager {
         debug!("UCI 配置已提交");
 
         // 2. 重新加载网络配置（使用 reload 而不是 restart，避免中断连接）
-        let output = Command::new("/etc/init.d/network")
-            .arg("reload")
-            .output()
-            .await
-            .context("重载网络配置失败")?;
+        let output = match self.backend {
+            Backend::Shell => self.runner.run("/etc/init.d/network", &["reload"]).await,
+            Backend::Ubus => self.ubus_call("network", "reload", json!({})).await,
+        }
+        .context("重载网络配置失败")?;
 
         if !output.status.success() {
             warn!(
@@ -625,29 +1440,122 @@ impl OpenWrtManager {
             info!("网络配置已重载，静态路由已生效");
         }
 
-        // 3. 等待网络配置应用（给系统一些时间）
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        // 3. 等待网络配置应用
+        if self.wait_for_routes(expected).await {
+            return Ok(());
+        }
+
+        if !self.allow_network_restart {
+            warn!(
+                "网络重载后 {} 秒内静态路由仍未生效，可启用 allow_network_restart 以重启网络服务",
+                ROUTE_SETTLE_TIMEOUT.as_secs()
+            );
+            return Ok(());
+        }
+
+        warn!(
+            "网络重载后 {} 秒内静态路由仍未生效，升级为重启网络服务",
+            ROUTE_SETTLE_TIMEOUT.as_secs()
+        );
+        self.restart_network().await?;
+
+        if !self.wait_for_routes(expected).await {
+            warn!("重启网络服务后静态路由仍未生效，请手动检查");
+        }
 
         Ok(())
     }
 
-    /// 为指定目标 IP 列表创建或更新 UCI 静态路由
+    /// 轮询内核路由表，等待所有路由指向预期接口
+    /// 返回: 是否在超时前全部生效
+    async fn wait_for_routes(&self, expected: &[RouteAssignment]) -> bool {
+        let deadline = Instant::now() + ROUTE_SETTLE_TIMEOUT;
+
+        loop {
+            tokio::time::sleep(ROUTE_POLL_INTERVAL).await;
+
+            let mut pending = Vec::new();
+            for assignment in expected {
+                if !self.route_applied(assignment).await {
+                    pending.push(assignment.target.as_str());
+                }
+            }
+
+            if pending.is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                debug!("仍未生效的静态路由: {}", pending.join(", "));
+                return false;
+            }
+        }
+    }
+
+    /// 内核中到目标的路由是否已经过指定接口
+    async fn route_applied(&self, assignment: &RouteAssignment) -> bool {
+        let Ok(output) = self
+            .runner
+            .run("ip", &["route", "show", &assignment.target])
+            .await
+        else {
+            return false;
+        };
+
+        let routes = String::from_utf8_lossy(&output.stdout);
+        let physical = Self::convert_to_physical_interface(&assignment.interface);
+        routes.lines().any(|line| {
+            let dev = line
+                .split_whitespace()
+                .skip_while(|word| *word != "dev")
+                .nth(1);
+            dev.is_some_and(|dev| {
+                dev == assignment.interface
+                    || dev == physical
+                    || Self::convert_to_physical_interface(dev) == physical
+            })
+        })
+    }
+
+    /// 比较 UCI 中的静态路由与本程序上次设置的路由分配，找出被外部修改或删除的路由
+    pub async fn route_drift(&self, intended: &[RouteAssignment]) -> Result<Vec<RouteDrift>> {
+        let routes = self.get_uci_static_routes().await?;
+
+        let drift = intended
+            .iter()
+            .filter_map(|assignment| {
+                let expected = Self::convert_to_physical_interface(&assignment.interface);
+                let target_base = assignment.target.trim_end_matches("/32");
+                let actual = routes
+                    .iter()
+                    .find(|(_, t, _)| t.trim_end_matches("/32") == target_base)
+                    .map(|(_, _, interface)| interface.clone());
+                (actual.as_deref() != Some(expected.as_str())).then(|| RouteDrift {
+                    target: assignment.target.clone(),
+                    expected,
+                    actual,
+                })
+            })
+            .collect();
+
+        Ok(drift)
+    }
+
+    /// 按每个目标的路由分配创建或更新 UCI 静态路由
     /// 用于管理配置文件中指定的目标 IP
-    /// 只更新被监控的目标，使用物理接口名
-    pub async fn manage_static_routes(&self, targets: &[String], interface: &str) -> Result<()> {
-        // 转换为物理接口名
-        let physical_interface = Self::convert_to_physical_interface(interface);
-        info!(
-            "管理 {} 个目标 IP 的静态路由，逻辑接口: {} -> 物理接口: {}",
-            targets.len(),
-            interface,
-            physical_interface
-        );
+    /// 每个目标可以指向不同的接口，使用物理接口名
+    /// 没有任何变更时不会提交和重载网络；演练模式下只记录将要执行的修改
+    pub async fn manage_static_routes(&self, assignments: &[RouteAssignment]) -> Result<()> {
+        info!("管理 {} 个目标 IP 的静态路由", assignments.len());
 
         // 获取现有路由
         let existing_routes = self.get_uci_static_routes().await?;
+        let mut changed = false;
+
+        for assignment in assignments {
+            let target = &assignment.target;
+            // 转换为物理接口名
+            let physical_interface = Self::convert_to_physical_interface(&assignment.interface);
 
-        for target in targets {
             // 查找是否已存在该目标的路由（支持带/32或不带/32）
             let target_base = target.trim_end_matches("/32");
             let existing_route = existing_routes.iter().find(|(_, t, _)| {
@@ -657,15 +1565,19 @@ impl OpenWrtManager {
 
             if let Some((section, _, old_interface)) = existing_route {
                 // 路由已存在，检查是否需要更新接口
-                if old_interface != &physical_interface {
+                if old_interface != &physical_interface && self.dry_run {
+                    info!(
+                        "[演练] 将改写路由配置段 {}: {} ({} -> {})",
+                        section, target, old_interface, physical_interface
+                    );
+                    changed = true;
+                } else if old_interface != &physical_interface {
                     info!(
                         "更新被监控路由: {} ({} -> {})",
                         target, old_interface, physical_interface
                     );
-                    let cmd = format!("network.{}.interface={}", section, physical_interface);
-                    let output = Command::new("uci")
-                        .args(&["set", &cmd])
-                        .output()
+                    let output = self
+                        .set_route_interface(section, &physical_interface)
                         .await
                         .context("更新 UCI 路由失败")?;
 
@@ -675,6 +1587,7 @@ impl OpenWrtManager {
                             String::from_utf8_lossy(&output.stderr)
                         );
                     }
+                    changed = true;
                 } else {
                     debug!(
                         "被监控路由 {} 接口已正确设置为 {}",
@@ -685,11 +1598,18 @@ impl OpenWrtManager {
                 // 路由不存在，创建新路由
                 info!("创建新静态路由: {} via {}", target, physical_interface);
                 self.create_uci_route(target, &physical_interface).await?;
+                changed = true;
             }
         }
 
         // 提交更改
-        self.commit_uci_changes().await?;
+        if changed && self.dry_run {
+            info!("[演练] 跳过提交 UCI 配置和重载网络");
+        } else if changed {
+            self.commit_uci_changes(assignments).await?;
+        } else {
+            debug!("静态路由无变化，跳过提交");
+        }
 
         Ok(())
     }
@@ -697,13 +1617,44 @@ impl OpenWrtManager {
     /// 创建新的 UCI 静态路由
     async fn create_uci_route(&self, target: &str, interface: &str) -> Result<()> {
         // 生成路由名称（使用 IP 作为标识）
-        let route_name = format!(
-            "route_{}",
-            target.replace('/', "_").replace('.', "_").replace(':', "_")
-        );
+        let route_name = format!("route_{}", target.replace(['/', '.', ':'], "_"));
+
+        if self.dry_run {
+            info!(
+                "[演练] 将创建路由配置段 {}: {} -> {}",
+                route_name, target, interface
+            );
+            return Ok(());
+        }
 
         debug!("创建 UCI 路由: {} -> {}", route_name, target);
 
+        // ubus 一次调用即可创建带选项的命名配置段
+        if self.backend == Backend::Ubus {
+            let output = self
+                .ubus_call(
+                    "uci",
+                    "add",
+                    json!({
+                        "config": "network",
+                        "type": "route",
+                        "name": route_name,
+                        "values": {"interface": interface, "target": target},
+                    }),
+                )
+                .await
+                .context("执行 ubus call uci add 失败")?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "通过 ubus 创建路由 {} 失败: {}",
+                    route_name,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            info!("静态路由 {} 创建成功", route_name);
+            return Ok(());
+        }
+
         // 创建路由配置段
         let commands = vec![
             format!("network.{}=route", route_name),
@@ -712,9 +1663,9 @@ impl OpenWrtManager {
         ];
 
         for cmd in commands {
-            let output = Command::new("uci")
-                .args(&["set", &cmd])
-                .output()
+            let output = self
+                .runner
+                .run("uci", &["set", &cmd])
                 .await
                 .context("执行 uci set 命令失败")?;
 
@@ -741,10 +1692,658 @@ impl Default for OpenWrtManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::AddressFamily;
+
+    use crate::command::mock::{output, MockRunner};
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn test_openwrt_manager_creation() {
         let manager = OpenWrtManager::new();
         assert!(manager.current_interface().is_none());
     }
+
+    fn interface(name: &str, gateway: Option<&str>) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            priority: 1,
+            enabled: true,
+            table_id: None,
+            gateway: gateway.map(str::to_string),
+            score_offset: 0.0,
+            resolver: None,
+            vlans: Vec::new(),
+            vlan_aggregation: Default::default(),
+            keepalive: false,
+            targets: None,
+            extra_targets: Vec::new(),
+            alternate_gateways: Vec::new(),
+            family: AddressFamily::default(),
+            bandwidth_mbps: None,
+            cost: 0.0,
+            failure_threshold: None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshot_and_restore_routes() {
+        // 启动时路由指向 wan_a，运行期间被切换到 wan_b
+        let switched = Arc::new(AtomicUsize::new(0));
+        let runner = Arc::new(MockRunner::new({
+            let switched = switched.clone();
+            move |cmd| match cmd {
+                "uci show network" => {
+                    let interface = if switched.load(Ordering::SeqCst) > 0 {
+                        "wan_b"
+                    } else {
+                        "wan_a"
+                    };
+                    output(
+                        0,
+                        &format!(
+                            "network.wan_a=interface\n\
+                             network.route_10_0_0_1_32=route\n\
+                             network.route_10_0_0_1_32.interface='{}'\n\
+                             network.route_10_0_0_1_32.target='10.0.0.1/32'\n\
+                             network.@route[0]=route\n\
+                             network.@route[0].interface='lan'\n\
+                             network.@route[0].target='192.168.2.0/24'\n",
+                            interface
+                        ),
+                    )
+                }
+                "ip route show" => output(0, "default via 192.168.1.1 dev wan_a\n"),
+                _ if cmd.starts_with("ip route show") => {
+                    output(0, "10.0.0.1 dev wan_a proto static scope link\n")
+                }
+                _ => output(0, ""),
+            }
+        }));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+
+        // 只保存本程序命名的 route_* 配置段
+        let snapshot = manager.snapshot_routes().await.unwrap();
+        assert_eq!(
+            snapshot.kernel_routes,
+            "default via 192.168.1.1 dev wan_a\n"
+        );
+        assert_eq!(
+            snapshot.sections,
+            vec![(
+                "route_10_0_0_1_32".to_string(),
+                "10.0.0.1/32".to_string(),
+                "wan_a".to_string()
+            )]
+        );
+
+        // 出口未变时不提交
+        assert_eq!(manager.restore_routes(&snapshot).await.unwrap(), 0);
+        assert_eq!(runner.count("uci commit network"), 0);
+
+        switched.store(1, Ordering::SeqCst);
+        assert_eq!(manager.restore_routes(&snapshot).await.unwrap(), 1);
+        assert_eq!(
+            runner.count("uci set network.route_10_0_0_1_32.interface=wan_a"),
+            1
+        );
+        assert_eq!(runner.count("uci commit network"), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rollback_switch_restores_previous_routes() {
+        // 记录 UCI 中路由的当前出口，uci set 时随之改变
+        let exit = Arc::new(std::sync::Mutex::new("wan_a".to_string()));
+        let runner = Arc::new(MockRunner::new({
+            let exit = exit.clone();
+            move |cmd| {
+                if let Some(interface) =
+                    cmd.strip_prefix("uci set network.route_10_0_0_1_32.interface=")
+                {
+                    *exit.lock().unwrap() = interface.to_string();
+                    return output(0, "");
+                }
+                match cmd {
+                    "uci show network" => output(
+                        0,
+                        &format!(
+                            "network.route_10_0_0_1_32=route\n\
+                             network.route_10_0_0_1_32.interface='{}'\n\
+                             network.route_10_0_0_1_32.target='10.0.0.1/32'\n",
+                            exit.lock().unwrap()
+                        ),
+                    ),
+                    _ => output(0, ""),
+                }
+            }
+        }));
+        let mut manager = OpenWrtManager::new().with_runner(runner.clone());
+        manager
+            .switch_to_interface(&interface("wan_a", None), false, None)
+            .await
+            .unwrap();
+
+        let plan = [RouteAssignment {
+            target: "10.0.0.1/32".to_string(),
+            interface: "wan_b".to_string(),
+        }];
+        manager
+            .switch_to_interface(&interface("wan_b", None), true, Some(&plan))
+            .await
+            .unwrap();
+        assert_eq!(manager.current_interface(), Some("wan_b"));
+        assert_eq!(*exit.lock().unwrap(), "wan_b");
+
+        assert_eq!(manager.rollback_switch().await.unwrap(), 1);
+        assert_eq!(manager.current_interface(), Some("wan_a"));
+        assert_eq!(*exit.lock().unwrap(), "wan_a");
+        assert_eq!(runner.count("uci commit network"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_clear_policy_rules_respects_priority_range() {
+        let rules = "0:\tfrom all lookup local\n\
+                     100:\tfrom all lookup 100\n\
+                     2000:\tfrom all fwmark 0x100/0x3f00 lookup 1\n\
+                     3005:\tfrom all oif wan_a lookup 200\n\
+                     32766:\tfrom all lookup main\n";
+        let deleted = |manager: OpenWrtManager, runner: Arc<MockRunner>| async move {
+            manager.clear_policy_rules().await.unwrap();
+            runner
+                .calls()
+                .into_iter()
+                .filter(|c| c.starts_with("ip rule del"))
+                .collect::<Vec<_>>()
+        };
+
+        // 默认范围 100-999
+        let runner = Arc::new(MockRunner::new(move |_| output(0, rules)));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+        assert_eq!(
+            deleted(manager, runner).await,
+            vec!["ip rule del priority 100"]
+        );
+
+        // 自定义范围 3000-3009，不触碰 mwan3 在 2000 的规则
+        let runner = Arc::new(MockRunner::new(move |_| output(0, rules)));
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_rule_priorities(3000, 10);
+        assert_eq!(
+            deleted(manager, runner).await,
+            vec!["ip rule del priority 3005"]
+        );
+
+        // 无法获取规则列表时只批量删除配置范围内的优先级
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "ip rule show" => output(1, ""),
+            _ => output(0, ""),
+        }));
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_rule_priorities(3000, 10);
+        let deleted = deleted(manager, runner).await;
+        assert_eq!(deleted.len(), 10);
+        assert_eq!(deleted[0], "ip rule del priority 3000");
+        assert_eq!(deleted[9], "ip rule del priority 3009");
+    }
+
+    #[tokio::test]
+    async fn test_verify_switch_follows_route_plan() {
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "uci show network" => output(
+                0,
+                "network.route_8_8_8_8=route\n\
+                 network.route_8_8_8_8.interface='wan_b'\n\
+                 network.route_8_8_8_8.target='8.8.8.8'\n\
+                 network.route_10_0_0_1_32=route\n\
+                 network.route_10_0_0_1_32.interface='wan_a'\n\
+                 network.route_10_0_0_1_32.target='10.0.0.1/32'\n",
+            ),
+            _ => output(0, ""),
+        }));
+        let manager = OpenWrtManager::new().with_runner(runner);
+        let wan_b = interface("wan_b", None);
+        let assign = |target: &str, interface: &str| RouteAssignment {
+            target: target.to_string(),
+            interface: interface.to_string(),
+        };
+
+        // 关键目标按分配固定在 wan_a，不算验证失败
+        let plan = [assign("8.8.8.8", "wan_b"), assign("10.0.0.1/32", "wan_a")];
+        assert!(manager.verify_switch(&wan_b, &plan).await.unwrap());
+
+        let plan = [assign("8.8.8.8", "wan_b"), assign("10.0.0.1/32", "wan_b")];
+        assert!(!manager.verify_switch(&wan_b, &plan).await.unwrap());
+
+        // 没有托管路由时无需验证
+        assert!(manager.verify_switch(&wan_b, &[]).await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_setup_policy_routes() {
+        let rules = "0:\tfrom all lookup local\n\
+                     2000:\tfrom all fwmark 0x100/0x3f00 lookup 1\n\
+                     3001:\tfrom all oif wan_old lookup 150\n";
+        let runner = Arc::new(MockRunner::new(move |cmd| match cmd {
+            "ip rule show" => output(0, rules),
+            "uci get network.wan_b.gateway" => output(0, "192.168.2.1\n"),
+            _ => output(0, ""),
+        }));
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_rule_priorities(3000, 10);
+        let mut interfaces = vec![
+            interface("wan_a", Some("192.168.1.1")),
+            interface("wan_b", None),
+            interface("wan_c", Some("192.168.3.1")),
+        ];
+        interfaces[0].table_id = Some(100);
+        interfaces[1].table_id = Some(101);
+
+        assert_eq!(manager.setup_policy_routes(&interfaces).await.unwrap(), 2);
+        let changes: Vec<_> = runner
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("ip rule ") || c.starts_with("ip route add"))
+            .filter(|c| c != "ip rule show")
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                // 只清除范围内的旧规则，不触碰 mwan3 在 2000 的规则
+                "ip rule del priority 3001",
+                "ip rule add oif wan_a table 100 priority 3000",
+                "ip route add default via 192.168.1.1 dev wan_a table 100",
+                // 未配置网关时自动获取
+                "ip rule add oif wan_b table 101 priority 3000",
+                "ip route add default via 192.168.2.1 dev wan_b table 101",
+            ]
+        );
+        assert_eq!(runner.count("ip route flush cache"), 1);
+
+        // 没有接口配置 table_id 时不做任何修改
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+        let plain = [interface("wan_a", Some("192.168.1.1"))];
+        assert_eq!(manager.setup_policy_routes(&plain).await.unwrap(), 0);
+        assert!(runner.calls().is_empty());
+
+        // 演练模式只记录
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_dry_run(true);
+        assert_eq!(manager.setup_policy_routes(&interfaces).await.unwrap(), 2);
+        manager.clear_policy_rules().await.unwrap();
+        assert!(runner.calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ubus_backend_manages_routes() {
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("ubus call uci get") {
+                output(
+                    0,
+                    r#"{"values": {
+                        "cfg0a1b2c": {".anonymous": true, ".type": "route",
+                                      "interface": "wan_a", "target": "10.0.0.1/32"},
+                        "lan_extra": {".anonymous": false, ".type": "route", "interface": "lan"}
+                    }}"#,
+                )
+            } else if cmd.starts_with("ip route show") {
+                output(0, "10.0.0.0/24 dev wan_b proto static scope link\n")
+            } else {
+                output(0, "")
+            }
+        }));
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_backend(Backend::Ubus);
+
+        let assignments = [
+            RouteAssignment {
+                target: "10.0.0.1/32".to_string(),
+                interface: "pppoe-wan_b".to_string(),
+            },
+            RouteAssignment {
+                target: "10.0.0.2/32".to_string(),
+                interface: "wan_b".to_string(),
+            },
+        ];
+        manager.manage_static_routes(&assignments).await.unwrap();
+
+        let calls: Vec<String> = runner
+            .calls()
+            .into_iter()
+            .filter(|c| !c.starts_with("ip route show"))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                r#"ubus call uci get {"config":"network","type":"route"}"#,
+                r#"ubus call uci set {"config":"network","section":"cfg0a1b2c","values":{"interface":"wan_b"}}"#,
+                r#"ubus call uci add {"config":"network","name":"route_10_0_0_2_32","type":"route","values":{"interface":"wan_b","target":"10.0.0.2/32"}}"#,
+                r#"ubus call uci commit {"config":"network"}"#,
+                "ubus call network reload {}",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drain_switch_phases() {
+        let runner = Arc::new(MockRunner::new(|cmd| match cmd {
+            "ip route show default" => output(0, "default via 10.0.0.1 dev wan_a proto static\n"),
+            _ => output(0, ""),
+        }));
+        let mut manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_switch_style(SwitchStyle::Drain, Duration::from_secs(20));
+
+        // 首次设置接口时没有需要排空的旧接口
+        manager
+            .switch_to_interface(&interface("wan_a", None), false, None)
+            .await
+            .unwrap();
+        assert!(runner.calls().is_empty());
+        assert_eq!(manager.pending_drain_time(), None);
+
+        // 切换本身不等待，排空时间由调用方在释放锁后等待
+        let start = Instant::now();
+        manager
+            .switch_to_interface(&interface("wan_b", Some("10.0.1.1")), false, None)
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(manager.current_interface(), Some("wan_b"));
+        assert_eq!(manager.pending_drain_time(), Some(Duration::from_secs(20)));
+
+        manager.complete_drain().await.unwrap();
+        assert_eq!(manager.pending_drain_time(), None);
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "ip route show default",
+                "ip route add default via 10.0.1.1 dev wan_b metric 100",
+                "ip route del default dev wan_a",
+                "ip route replace default via 10.0.1.1 dev wan_b",
+                "ip route del default via 10.0.1.1 dev wan_b metric 100",
+            ]
+        );
+
+        // 回滚时恢复切换前的默认路由
+        let before = runner.calls().len();
+        manager.rollback_switch().await.unwrap();
+        assert_eq!(manager.current_interface(), Some("wan_a"));
+        assert_eq!(
+            runner.calls()[before..before + 2],
+            [
+                "ip route del default dev wan_b",
+                "ip route replace default via 10.0.0.1 dev wan_a proto static",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_setup_ecmp_routes_weights_by_score() {
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        let mut manager = OpenWrtManager::new().with_runner(runner.clone());
+        let wan_a = interface("wan_a", Some("10.0.0.1"));
+        let wan_b = interface("wan_b", Some("10.0.1.1"));
+
+        manager
+            .setup_ecmp_routes(&[(&wan_a, 88.0), (&wan_b, 79.5)])
+            .await
+            .unwrap();
+        assert!(manager.ecmp_active());
+
+        // 权重不变时不重复设置
+        manager
+            .setup_ecmp_routes(&[(&wan_a, 86.0), (&wan_b, 71.0)])
+            .await
+            .unwrap();
+
+        // 只剩一个接口时恢复单出口默认路由
+        manager.setup_ecmp_routes(&[(&wan_a, 90.0)]).await.unwrap();
+        assert!(!manager.ecmp_active());
+
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "ip route replace default nexthop via 10.0.0.1 dev wan_a weight 9 \
+                 nexthop via 10.0.1.1 dev wan_b weight 8",
+                "ip route replace default via 10.0.0.1 dev wan_a",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_traffic_checks_route_and_reachability() {
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("ip route get") {
+                output(
+                    0,
+                    "8.8.8.8 via 10.0.1.1 dev wan_b src 10.0.1.2 uid 0\n    cache\n",
+                )
+            } else {
+                output(0, "")
+            }
+        }));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+        let wait = Duration::from_secs(2);
+
+        assert!(manager
+            .verify_traffic(&interface("wan_b", None), "8.8.8.8", wait)
+            .await
+            .unwrap());
+        assert_eq!(
+            runner.calls(),
+            vec!["ip route get 8.8.8.8", "ping -c 3 -W 2 8.8.8.8"]
+        );
+
+        // 路由仍指向其他接口时不做探测
+        assert!(!manager
+            .verify_traffic(&interface("wan_a", None), "8.8.8.8", wait)
+            .await
+            .unwrap());
+        assert_eq!(runner.count("ping"), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_network_restart_when_reload_ineffective() {
+        let restarted = Arc::new(AtomicUsize::new(0));
+        let counter = restarted.clone();
+        let runner = Arc::new(MockRunner::new(move |cmd| {
+            if cmd == "/etc/init.d/network restart" {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            if cmd.starts_with("ip route show") {
+                // reload 不生效，restart 后路由才指向新接口
+                let dev = if counter.load(Ordering::SeqCst) > 0 {
+                    "wan_b"
+                } else {
+                    "wan_a"
+                };
+                return output(
+                    0,
+                    &format!("45.0.0.1 dev {} proto static scope link\n", dev),
+                );
+            }
+            output(0, "")
+        }));
+        let assignments = vec![RouteAssignment {
+            target: "45.0.0.1/32".to_string(),
+            interface: "wan_b".to_string(),
+        }];
+
+        // 未允许重启时只重载
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+        manager.manage_static_routes(&assignments).await.unwrap();
+        assert_eq!(runner.count("/etc/init.d/network reload"), 1);
+        assert_eq!(restarted.load(Ordering::SeqCst), 0);
+
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_network_restart(true);
+        let start = Instant::now();
+        manager.manage_static_routes(&assignments).await.unwrap();
+        assert_eq!(runner.count("/etc/init.d/network reload"), 2);
+        assert_eq!(restarted.load(Ordering::SeqCst), 1);
+        assert!(start.elapsed() >= ROUTE_SETTLE_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_probe_via_next_hop_cleans_up_route() {
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("ping") {
+                output(1, "")
+            } else {
+                output(0, "")
+            }
+        }));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+        let target = "8.8.8.8".parse().unwrap();
+        let wait = Duration::from_secs(2);
+
+        let reachable = manager
+            .probe_via_next_hop("wan", "10.0.0.254", target, wait)
+            .await
+            .unwrap();
+        assert_eq!(reachable, Some(false));
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "ip route add 8.8.8.8/32 via 10.0.0.254 dev wan",
+                "ping -c 3 -W 2 -I wan 8.8.8.8",
+                "ip route del 8.8.8.8/32 via 10.0.0.254 dev wan",
+            ]
+        );
+
+        // ping 无法执行时同样删除临时路由
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")).with_failure("ping"));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+        assert!(manager
+            .probe_via_next_hop("wan", "10.0.0.254", target, wait)
+            .await
+            .is_err());
+        assert_eq!(runner.count("ip route del 8.8.8.8/32"), 1);
+
+        // 演练模式不添加临时路由，也不探测
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_dry_run(true);
+        let reachable = manager
+            .probe_via_next_hop("wan", "10.0.0.254", target, wait)
+            .await
+            .unwrap();
+        assert_eq!(reachable, None);
+        assert!(runner.calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drain_switch_aborts_when_new_route_fails() {
+        let runner = Arc::new(MockRunner::new(|cmd| {
+            if cmd.starts_with("ip route add") {
+                output(2, "")
+            } else {
+                output(0, "")
+            }
+        }));
+        let mut manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_switch_style(SwitchStyle::Drain, Duration::from_secs(20));
+
+        manager
+            .switch_to_interface(&interface("wan_a", None), false, None)
+            .await
+            .unwrap();
+        let result = manager
+            .switch_to_interface(&interface("wan_b", Some("10.0.1.1")), false, None)
+            .await;
+
+        // 新路由添加失败时不删除旧路由，仍停留在原接口
+        assert!(result.is_err());
+        assert_eq!(manager.current_interface(), Some("wan_a"));
+        assert_eq!(runner.count("ip route del"), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gateway_cache_ttl_and_retry() {
+        let uci_calls = Arc::new(AtomicUsize::new(0));
+        let counter = uci_calls.clone();
+        let runner = Arc::new(MockRunner::new(move |cmd| {
+            if cmd.starts_with("uci get network.wan.gateway") {
+                // 第一次解析模拟瞬时失败
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return output(1, "");
+                }
+                return output(0, "10.0.0.1\n");
+            }
+            if cmd.starts_with("ip -o addr show dev wan") {
+                return output(
+                    0,
+                    "2: wan    inet 10.0.0.2/24 brd 10.0.0.255 scope global wan\n",
+                );
+            }
+            output(1, "")
+        }));
+
+        let manager = OpenWrtManager::new()
+            .with_runner(runner.clone())
+            .with_gateway_cache_ttl(Duration::from_secs(30));
+
+        // 首次解析失败后重试成功
+        assert_eq!(
+            manager.get_interface_gateway("wan").await.unwrap(),
+            "10.0.0.1"
+        );
+        assert_eq!(runner.count("uci get"), 2);
+
+        // 有效期内复用缓存
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(
+            manager.get_interface_gateway("wan").await.unwrap(),
+            "10.0.0.1"
+        );
+        assert_eq!(runner.count("uci get"), 2);
+
+        // 过期后重新解析
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(
+            manager.get_interface_gateway("wan").await.unwrap(),
+            "10.0.0.1"
+        );
+        assert_eq!(runner.count("uci get"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gateway_cache_invalidated_on_address_change() {
+        let address = Arc::new(Mutex::new("10.0.0.2/24"));
+        let current = address.clone();
+        let runner = Arc::new(MockRunner::new(move |cmd| {
+            if cmd.starts_with("uci get") {
+                return output(0, "10.0.0.1\n");
+            }
+            if cmd.starts_with("ip -o addr show") {
+                let line = format!(
+                    "2: wan    inet {} scope global wan\n",
+                    current.lock().unwrap()
+                );
+                return output(0, &line);
+            }
+            output(1, "")
+        }));
+        let manager = OpenWrtManager::new().with_runner(runner.clone());
+
+        manager.get_interface_gateway("wan").await.unwrap();
+        // UCI 中有网关时不需要回退到路由表解析
+        assert!(runner.calls().iter().all(|c| !c.starts_with("ip route")));
+        manager.refresh_gateway_cache().await;
+        assert_eq!(manager.gateway_cache.lock().unwrap().len(), 1);
+
+        *address.lock().unwrap() = "100.64.0.9/32";
+        manager.refresh_gateway_cache().await;
+        assert!(manager.gateway_cache.lock().unwrap().is_empty());
+    }
 }
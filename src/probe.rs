@@ -0,0 +1,105 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! TCP/UDP 探测套接字
+//!
+//! 运营商常按源端口对流量做哈希分流，固定源端口的探测会始终落在同一条内部路径上。
+//! 启用源端口随机化后每次探测显式绑定一个随机的临时端口，以采样不同的路径
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+
+/// 随机源端口的取值范围（IANA 动态端口）
+const EPHEMERAL_PORTS: RangeInclusive<u16> = 49152..=65535;
+/// 随机端口被占用时的重试次数
+const BIND_ATTEMPTS: usize = 8;
+
+/// 探测协议
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+/// 创建用于探测 `target` 的套接字
+///
+/// 指定 `interface` 时绑定到该接口；`randomize_source_port` 为真时绑定随机源端口，
+/// 否则由内核在连接时分配
+pub fn probe_socket(
+    interface: Option<&str>,
+    target: SocketAddr,
+    transport: Transport,
+    randomize_source_port: bool,
+) -> io::Result<Socket> {
+    let domain = Domain::for_address(target);
+    let socket = match transport {
+        Transport::Tcp => Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?,
+        Transport::Udp => Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?,
+    };
+
+    if let Some(interface) = interface {
+        socket.bind_device(Some(interface.as_bytes()))?;
+    }
+
+    if randomize_source_port {
+        bind_random_port(&socket, target.ip())?;
+    }
+
+    Ok(socket)
+}
+
+/// 绑定到随机的临时端口，端口被占用时换一个重试
+fn bind_random_port(socket: &Socket, target: IpAddr) -> io::Result<()> {
+    let unspecified = match target {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let mut last_error = None;
+    for _ in 0..BIND_ATTEMPTS {
+        let port = fastrand::u16(EPHEMERAL_PORTS);
+        match socket.bind(&SockAddr::from(SocketAddr::new(unspecified, port))) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrInUse)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn local_port(socket: &Socket) -> u16 {
+        socket.local_addr().unwrap().as_socket().unwrap().port()
+    }
+
+    #[test]
+    fn test_randomized_source_ports() {
+        let target: SocketAddr = "127.0.0.1:53".parse().unwrap();
+
+        let ports: HashSet<u16> = (0..8)
+            .map(|_| {
+                let socket = probe_socket(None, target, Transport::Udp, true).unwrap();
+                let port = local_port(&socket);
+                assert!(EPHEMERAL_PORTS.contains(&port));
+                port
+            })
+            .collect();
+        assert!(ports.len() > 1, "连续探测应使用不同的源端口");
+
+        let socket = probe_socket(None, target, Transport::Tcp, true).unwrap();
+        assert!(EPHEMERAL_PORTS.contains(&local_port(&socket)));
+
+        // 未启用时不显式绑定
+        let socket = probe_socket(None, target, Transport::Tcp, false).unwrap();
+        assert_eq!(local_port(&socket), 0);
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::results_log::LogRecord;
+
+/// 报告命令选项
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReportOptions {
+    /// 时间窗口（为空表示全部记录）
+    pub since: Option<Duration>,
+    /// 只统计指定接口
+    pub interface: Option<String>,
+    /// 以 JSON 输出
+    pub json: bool,
+    /// 结果日志路径（为空时使用配置文件中的 results_log）
+    pub log_path: Option<PathBuf>,
+}
+
+/// 单个接口的统计摘要
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceSummary {
+    /// 接口名称
+    pub interface: String,
+    /// 检查次数
+    pub checks: usize,
+    /// 在线率（至少一个目标可达的检查占比，百分比）
+    pub uptime_percent: f64,
+    /// 平均延迟
+    pub avg_latency_ms: Option<f64>,
+    /// P95 延迟
+    pub p95_latency_ms: Option<f64>,
+    /// 作为活动接口的时长（秒）
+    pub active_seconds: f64,
+    /// 切换到该接口的次数
+    pub switches_to: usize,
+}
+
+/// 时间窗口内的汇总报告
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// 统计起点
+    pub since: Option<DateTime<Local>>,
+    /// 切换总次数
+    pub switches: usize,
+    /// 各接口摘要
+    pub interfaces: Vec<InterfaceSummary>,
+}
+
+/// 解析时间窗口，例如 "90s"、"30m"、"24h"、"7d"
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("无效的时间窗口: {}", value))?;
+
+    let seconds = match unit {
+        "" | "s" => number,
+        "m" => number * 60,
+        "h" => number * 3600,
+        "d" => number * 86400,
+        _ => anyhow::bail!("无效的时间单位: {} (支持 s/m/h/d)", unit),
+    };
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// 按最近排名法计算百分位（`sorted` 须已升序排列）
+pub fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// 汇总结果日志
+pub fn summarize(
+    records: &[LogRecord],
+    since: Option<DateTime<Local>>,
+    interface: Option<&str>,
+) -> Report {
+    let in_window = |ts: &DateTime<Local>| since.is_none_or(|s| *ts >= s);
+
+    let mut checks: BTreeMap<String, (usize, usize, Vec<f64>)> = BTreeMap::new();
+    let mut active_at: BTreeMap<DateTime<Local>, Option<String>> = BTreeMap::new();
+    let mut switches_to: HashMap<String, usize> = HashMap::new();
+    let mut switches = 0;
+
+    for record in records.iter().filter(|r| in_window(&r.ts())) {
+        match record {
+            LogRecord::Check {
+                ts,
+                interface,
+                reachable_count,
+                avg_latency_ms,
+                active,
+                ..
+            } => {
+                active_at.insert(*ts, active.clone());
+
+                let entry = checks.entry(interface.clone()).or_default();
+                entry.0 += 1;
+                if *reachable_count > 0 {
+                    entry.1 += 1;
+                }
+                if let Some(latency) = avg_latency_ms {
+                    entry.2.push(*latency);
+                }
+            }
+            LogRecord::Switch { to, .. } => {
+                switches += 1;
+                *switches_to.entry(to.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    // 相邻两次检查之间的时长计入前一次检查时的活动接口
+    let mut active_seconds: HashMap<String, f64> = HashMap::new();
+    let timeline: Vec<_> = active_at.iter().collect();
+    for pair in timeline.windows(2) {
+        let (start, active) = pair[0];
+        let (end, _) = pair[1];
+        if let Some(active) = active {
+            let elapsed = (*end - *start).num_milliseconds() as f64 / 1000.0;
+            *active_seconds.entry(active.clone()).or_default() += elapsed;
+        }
+    }
+
+    let interfaces = checks
+        .into_iter()
+        .filter(|(name, _)| interface.is_none_or(|i| i == name))
+        .map(|(name, (total, up, mut latencies))| {
+            latencies.sort_by(f64::total_cmp);
+            let avg_latency_ms = if latencies.is_empty() {
+                None
+            } else {
+                Some(latencies.iter().sum::<f64>() / latencies.len() as f64)
+            };
+
+            InterfaceSummary {
+                checks: total,
+                uptime_percent: up as f64 / total as f64 * 100.0,
+                avg_latency_ms,
+                p95_latency_ms: percentile(&latencies, 95.0),
+                active_seconds: active_seconds.get(&name).copied().unwrap_or(0.0),
+                switches_to: switches_to.get(&name).copied().unwrap_or(0),
+                interface: name,
+            }
+        })
+        .collect();
+
+    Report {
+        since,
+        switches,
+        interfaces,
+    }
+}
+
+/// 渲染为文本表格
+pub fn render_table(report: &Report) -> String {
+    let format_ms = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.2}", v));
+
+    let mut out = String::new();
+    match report.since {
+        Some(since) => out.push_str(&format!(
+            "统计起点: {}\n",
+            since.format("%Y-%m-%d %H:%M:%S")
+        )),
+        None => out.push_str("统计范围: 全部记录\n"),
+    }
+    out.push_str(&format!("切换次数: {}\n\n", report.switches));
+    out.push_str(&format!(
+        "{:<15} {:<8} {:<10} {:<12} {:<12} {:<12} {:<8}\n",
+        "接口", "检查", "在线率(%)", "平均延迟", "P95延迟", "活动时长(h)", "切入"
+    ));
+    out.push_str(&format!("{}\n", "-".repeat(85)));
+
+    for summary in &report.interfaces {
+        out.push_str(&format!(
+            "{:<15} {:<8} {:<10.1} {:<12} {:<12} {:<12.2} {:<8}\n",
+            summary.interface,
+            summary.checks,
+            summary.uptime_percent,
+            format_ms(summary.avg_latency_ms),
+            format_ms(summary.p95_latency_ms),
+            summary.active_seconds / 3600.0,
+            summary.switches_to
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results_log;
+
+    const SYNTHETIC_LOG: &str = r#"
+{"type":"check","ts":"2026-01-01T00:00:00+00:00","interface":"wan_a","reachable_count":2,"target_count":2,"avg_latency_ms":10.0,"avg_packet_loss":0.0,"score":80.0,"active":"wan_a"}
+{"type":"check","ts":"2026-01-01T00:00:00+00:00","interface":"wan_b","reachable_count":2,"target_count":2,"avg_latency_ms":40.0,"avg_packet_loss":0.0,"score":60.0,"active":"wan_a"}
+{"type":"check","ts":"2026-01-01T00:01:00+00:00","interface":"wan_a","reachable_count":0,"target_count":2,"avg_latency_ms":null,"avg_packet_loss":1.0,"score":0.0,"active":"wan_b"}
+{"type":"check","ts":"2026-01-01T00:01:00+00:00","interface":"wan_b","reachable_count":2,"target_count":2,"avg_latency_ms":50.0,"avg_packet_loss":0.0,"score":58.0,"active":"wan_b"}
+{"type":"switch","ts":"2026-01-01T00:01:00+00:00","from":"wan_a","to":"wan_b"}
+not a json line
+{"type":"check","ts":"2026-01-01T00:03:00+00:00","interface":"wan_a","reachable_count":2,"target_count":2,"avg_latency_ms":20.0,"avg_packet_loss":0.0,"score":75.0,"active":"wan_b"}
+{"type":"check","ts":"2026-01-01T00:03:00+00:00","interface":"wan_b","reachable_count":1,"target_count":2,"avg_latency_ms":100.0,"avg_packet_loss":0.5,"score":40.0,"active":"wan_b"}
+"#;
+
+    #[test]
+    fn test_summarize_synthetic_log() {
+        let records = results_log::parse(SYNTHETIC_LOG);
+        assert_eq!(records.len(), 7);
+
+        let report = summarize(&records, None, None);
+        assert_eq!(report.switches, 1);
+        assert_eq!(report.interfaces.len(), 2);
+
+        let a = &report.interfaces[0];
+        assert_eq!(a.interface, "wan_a");
+        assert_eq!(a.checks, 3);
+        assert!((a.uptime_percent - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(a.avg_latency_ms, Some(15.0));
+        assert_eq!(a.p95_latency_ms, Some(20.0));
+        assert_eq!(a.active_seconds, 60.0);
+        assert_eq!(a.switches_to, 0);
+
+        let b = &report.interfaces[1];
+        assert_eq!(b.uptime_percent, 100.0);
+        assert_eq!(b.avg_latency_ms, Some(190.0 / 3.0));
+        assert_eq!(b.p95_latency_ms, Some(100.0));
+        assert_eq!(b.active_seconds, 120.0);
+        assert_eq!(b.switches_to, 1);
+    }
+
+    #[test]
+    fn test_summarize_window_and_interface_filter() {
+        let records = results_log::parse(SYNTHETIC_LOG);
+        let since = DateTime::parse_from_rfc3339("2026-01-01T00:01:00+00:00")
+            .unwrap()
+            .with_timezone(&Local);
+
+        let report = summarize(&records, Some(since), Some("wan_b"));
+        assert_eq!(report.switches, 1);
+        assert_eq!(report.interfaces.len(), 1);
+        assert_eq!(report.interfaces[0].checks, 2);
+        assert_eq!(report.interfaces[0].avg_latency_ms, Some(75.0));
+        assert_eq!(report.interfaces[0].active_seconds, 120.0);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+}
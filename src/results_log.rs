@@ -0,0 +1,105 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// 结果日志记录（JSONL，每行一条）
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogRecord {
+    /// 单个接口的一次检查结果
+    Check {
+        /// 检查时间
+        ts: DateTime<Local>,
+        /// 接口名称
+        interface: String,
+        /// 可达目标数量
+        reachable_count: usize,
+        /// 测试目标数量
+        target_count: usize,
+        /// 平均延迟（不可达时为空）
+        avg_latency_ms: Option<f64>,
+        /// 平均丢包率
+        avg_packet_loss: f64,
+        /// 综合评分
+        score: f64,
+        /// 本次检查结束时的活动接口
+        active: Option<String>,
+    },
+    /// 接口切换事件
+    Switch {
+        /// 切换时间
+        ts: DateTime<Local>,
+        /// 原接口
+        from: Option<String>,
+        /// 新接口
+        to: String,
+        /// 从决定切换到验证完成的耗时（秒）
+        #[serde(default)]
+        duration_secs: Option<f64>,
+    },
+}
+
+impl LogRecord {
+    /// 记录时间
+    pub fn ts(&self) -> DateTime<Local> {
+        match self {
+            LogRecord::Check { ts, .. } | LogRecord::Switch { ts, .. } => *ts,
+        }
+    }
+}
+
+/// 追加记录到结果日志
+pub fn append<P: AsRef<Path>>(path: P, records: &[LogRecord]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("无法打开结果日志: {:?}", path.as_ref()))?;
+
+    let mut buffer = String::new();
+    for record in records {
+        buffer.push_str(&serde_json::to_string(record)?);
+        buffer.push('\n');
+    }
+
+    file.write_all(buffer.as_bytes())
+        .with_context(|| format!("写入结果日志失败: {:?}", path.as_ref()))?;
+
+    Ok(())
+}
+
+/// 读取结果日志
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<LogRecord>> {
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("无法读取结果日志: {:?}", path.as_ref()))?;
+
+    Ok(parse(&content))
+}
+
+/// 解析 JSONL 内容，跳过无法解析的行
+pub fn parse(content: &str) -> Vec<LogRecord> {
+    let mut records = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("跳过结果日志第 {} 行: {}", index + 1, e),
+        }
+    }
+
+    records
+}
@@ -0,0 +1,177 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 系统日志（OpenWrt logd）
+//!
+//! 启用后将 warn/error 级别的日志和切换事件通过 `logger` 命令写入系统日志，
+//! 可以用 `logread -e routes-monitor` 查看
+
+use log::{Level, Log, Metadata, Record};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::command::CommandRunner;
+use crate::json_log;
+
+/// 写入系统日志时使用的标签
+pub const TAG: &str = "routes-monitor";
+
+tokio::task_local! {
+    /// 当前任务所属的配置名称（同时运行多个配置时作为日志前缀）
+    pub static LOG_SCOPE: String;
+}
+
+/// 系统日志优先级
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// 切换等重要事件
+    Notice,
+    Warning,
+    Error,
+}
+
+impl Priority {
+    fn as_str(self) -> &'static str {
+        match self {
+            Priority::Notice => "daemon.notice",
+            Priority::Warning => "daemon.warn",
+            Priority::Error => "daemon.err",
+        }
+    }
+}
+
+/// 待写入的日志条目
+type Entry = (Priority, String);
+
+static SENDER: OnceLock<UnboundedSender<Entry>> = OnceLock::new();
+
+/// 启用系统日志，此后的 warn/error 日志与事件会转发给 `logger`
+pub fn enable(runner: Arc<dyn CommandRunner>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    if SENDER.set(tx).is_ok() {
+        tokio::spawn(forward(rx, runner));
+    }
+}
+
+/// 记录一条事件（未启用系统日志时忽略）
+pub fn event(message: &str) {
+    if let Some(tx) = SENDER.get() {
+        let _ = tx.send((Priority::Notice, message.to_string()));
+    }
+}
+
+/// 将日志条目逐条写入系统日志
+async fn forward(mut rx: UnboundedReceiver<Entry>, runner: Arc<dyn CommandRunner>) {
+    while let Some((priority, message)) = rx.recv().await {
+        let args = ["-t", TAG, "-p", priority.as_str(), message.as_str()];
+        // 失败时只记录 debug 日志，避免 warn 日志再次被转发形成循环
+        match runner.run("logger", &args).await {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => log::debug!("logger 退出状态: {}", output.status),
+            Err(e) => log::debug!("执行 logger 失败: {}", e),
+        }
+    }
+}
+
+/// 包装 env_logger，把 warn/error 级别的日志同时转发到系统日志
+pub struct MirrorLogger {
+    inner: env_logger::Logger,
+    /// 启用 JSON 日志格式后使用的输出
+    json: Option<env_logger::Logger>,
+}
+
+impl MirrorLogger {
+    pub fn new(inner: env_logger::Logger) -> Self {
+        Self { inner, json: None }
+    }
+
+    /// 设置 JSON 格式的输出，`json_log::enable` 后改用它输出日志
+    pub fn with_json(mut self, json: env_logger::Logger) -> Self {
+        self.json = Some(json);
+        self
+    }
+
+    /// 安装为全局日志实现
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        let max_level = self.inner.filter();
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl MirrorLogger {
+    /// 交给 env_logger 输出，warn/error 同时转发到系统日志
+    fn forward(&self, record: &Record) {
+        match &self.json {
+            Some(json) if json_log::enabled() => json.log(record),
+            _ => self.inner.log(record),
+        }
+
+        let priority = match record.level() {
+            Level::Error => Priority::Error,
+            Level::Warn => Priority::Warning,
+            _ => return,
+        };
+        if let Some(tx) = SENDER.get() {
+            if self.inner.enabled(record.metadata()) {
+                let _ = tx.send((priority, record.args().to_string()));
+            }
+        }
+    }
+}
+
+impl Log for MirrorLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        match LOG_SCOPE.try_with(Clone::clone) {
+            Ok(scope) => self.forward(
+                &Record::builder()
+                    .args(format_args!("[{}] {}", scope, record.args()))
+                    .metadata(record.metadata().clone())
+                    .key_values(record.key_values())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            Err(_) => self.forward(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::mock::{output, MockRunner};
+
+    #[tokio::test]
+    async fn test_events_forwarded_to_logger() {
+        let runner = Arc::new(MockRunner::new(|_| output(0, "")));
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tx.send((Priority::Notice, "已从 wan_a 切换到 wan_b".to_string()))
+            .unwrap();
+        tx.send((Priority::Error, "接口切换失败".to_string()))
+            .unwrap();
+        drop(tx);
+        forward(rx, runner.clone()).await;
+
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "logger -t routes-monitor -p daemon.notice 已从 wan_a 切换到 wan_b",
+                "logger -t routes-monitor -p daemon.err 接口切换失败",
+            ]
+        );
+    }
+}
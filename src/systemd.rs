@@ -0,0 +1,177 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! systemd 服务通知
+//!
+//! 仅在 `NOTIFY_SOCKET` 存在（作为 `Type=notify` 服务运行）时生效：首轮检查结束后（无论结果如何）
+//! 发送 `READY=1`，每轮更新 `STATUS=`；服务配置了 `WatchdogSec` 时在检查结束后和等待下一轮期间
+//! 发送 `WATCHDOG=1`，检查卡住时 systemd 会重启服务。`WatchdogSec` 应大于单轮检查的最长耗时
+
+use log::debug;
+use sd_notify::NotifyState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::time::{sleep, Duration, Instant};
+
+/// systemd 通知
+pub struct Notifier {
+    /// 是否由 systemd 以 notify 方式启动
+    enabled: bool,
+    /// 看门狗喂狗间隔（`WatchdogSec` 的一半）
+    watchdog: Option<Duration>,
+    /// 已发送 READY=1
+    ready: AtomicBool,
+}
+
+impl Notifier {
+    pub fn new(enabled: bool, watchdog: Option<Duration>) -> Self {
+        Self {
+            enabled,
+            watchdog,
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// 根据 systemd 设置的环境变量创建
+    pub fn from_env() -> Self {
+        let enabled = std::env::var_os("NOTIFY_SOCKET").is_some();
+        let watchdog = watchdog_interval(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        )
+        .filter(|_| enabled);
+        Self::new(enabled, watchdog)
+    }
+
+    /// 一轮检查结束：更新状态并喂狗，首轮检查结束时通知服务已就绪
+    ///
+    /// 没有可用接口或检查出错也发送 `READY=1`，否则服务会一直停在启动中直至超时；
+    /// 具体结果体现在 `STATUS=` 中
+    pub fn check_completed(&self, status: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut states = vec![NotifyState::Status(status)];
+        if !self.ready.swap(true, Ordering::Relaxed) {
+            states.push(NotifyState::Ready);
+        }
+        if self.watchdog.is_some() {
+            states.push(NotifyState::Watchdog);
+        }
+        self.send(&states);
+    }
+
+    /// 服务开始停止
+    pub fn stopping(&self) {
+        if self.enabled {
+            self.send(&[NotifyState::Stopping]);
+        }
+    }
+
+    /// 等待下一轮检查，期间按看门狗间隔持续喂狗
+    pub async fn sleep(&self, duration: Duration) {
+        let Some(interval) = self.watchdog.filter(|_| self.enabled) else {
+            sleep(duration).await;
+            return;
+        };
+
+        let deadline = Instant::now() + duration;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            if remaining.is_zero() {
+                break;
+            }
+            sleep(remaining.min(interval)).await;
+            self.send(&[NotifyState::Watchdog]);
+        }
+    }
+
+    fn send(&self, states: &[NotifyState]) {
+        if let Err(e) = sd_notify::notify(false, states) {
+            debug!("发送 systemd 通知失败: {}", e);
+        }
+    }
+}
+
+/// 由 `WATCHDOG_USEC`/`WATCHDOG_PID` 计算喂狗间隔
+///
+/// 未设置、无法解析或为 0 时不启用看门狗；设置了 `WATCHDOG_PID` 时仅对该进程生效。
+/// 间隔取超时的一半，留出余量避免临界时刻超时
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    let usec = usec?.trim().parse::<u64>().ok().filter(|&usec| usec > 0)?;
+    if let Some(pid) = pid {
+        if pid.trim().parse::<u32>().ok() != Some(own_pid) {
+            return None;
+        }
+    }
+    Some(Duration::from_micros(usec) / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    fn received(socket: &UnixDatagram) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while let Ok(n) = socket.recv(&mut buffer) {
+            messages.push(String::from_utf8_lossy(&buffer[..n]).into_owned());
+        }
+        messages
+    }
+
+    #[test]
+    fn test_watchdog_interval() {
+        // WatchdogSec=30 时每 15 秒喂狗
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval(Some("1500"), Some("42"), 42),
+            Some(Duration::from_micros(750))
+        );
+        // 看门狗属于其他进程
+        assert_eq!(watchdog_interval(Some("30000000"), Some("7"), 42), None);
+        // 未设置、为 0 或无法解析
+        assert_eq!(watchdog_interval(None, None, 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(Some("30s"), None, 42), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ready_and_watchdog_notifications() {
+        let path =
+            std::env::temp_dir().join(format!("routes-monitor-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", &path);
+
+        let notifier = Notifier::new(true, Some(Duration::from_secs(5)));
+
+        // 首轮检查结束即发送 READY，即使没有可用接口
+        notifier.check_completed("没有可用接口");
+        assert_eq!(
+            received(&socket),
+            vec!["STATUS=没有可用接口\nREADY=1\nWATCHDOG=1\n"]
+        );
+
+        // 之后只更新状态并喂狗，不再重复 READY
+        notifier.check_completed("活动接口 wan_a");
+        assert_eq!(
+            received(&socket),
+            vec!["STATUS=活动接口 wan_a\nWATCHDOG=1\n"]
+        );
+
+        // 等待下一轮的 12 秒内每 5 秒喂狗一次，结束时再喂一次
+        notifier.sleep(Duration::from_secs(12)).await;
+        assert_eq!(received(&socket), vec!["WATCHDOG=1\n"; 3]);
+
+        std::env::remove_var("NOTIFY_SOCKET");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 内置 TCP 回显服务
+//!
+//! 用于本地自测：作为 TCP 探测目标确认接口绑定和连接探测是否正常，
+//! 接受连接后原样回显收到的数据，随进程退出。仅供测试和诊断，不用于生产环境
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 在指定地址上监听
+pub async fn bind(listen: &str) -> Result<TcpListener> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("无法监听测试服务地址: {}", listen))?;
+    info!("TCP 回显测试服务已启动: {}", listener.local_addr()?);
+    Ok(listener)
+}
+
+/// 持续接受连接，每个连接在独立任务中回显
+pub async fn serve(listener: TcpListener) -> Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await.context("接受测试连接失败")?;
+        debug!("测试服务收到连接: {}", peer);
+        tokio::spawn(async move {
+            if let Err(e) = echo(stream).await {
+                debug!("测试连接 {} 异常结束: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// 原样回显数据直到对端关闭连接
+async fn echo(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        stream.write_all(&buffer[..n]).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::{probe_socket, Transport};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_tcp_probe_against_test_server() {
+        let listener = bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        // 与 TCP 探测相同的方式建立连接并测量连接耗时
+        let socket = probe_socket(None, addr, Transport::Tcp, true).unwrap();
+        let started = Instant::now();
+        let stream = tokio::task::spawn_blocking(move || {
+            socket.connect_timeout(&addr.into(), Duration::from_secs(2))?;
+            socket.set_nonblocking(true)?;
+            Ok::<_, std::io::Error>(std::net::TcpStream::from(socket))
+        })
+        .await
+        .unwrap()
+        .expect("测试服务应可达");
+        let latency = started.elapsed();
+        assert!(latency < Duration::from_secs(2));
+
+        let mut stream = TcpStream::from_std(stream).unwrap();
+        stream.write_all(b"routes-monitor").await.unwrap();
+        let mut reply = [0u8; 14];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"routes-monitor");
+
+        // 服务未监听的端口不可达
+        drop(stream);
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let socket = probe_socket(None, closed, Transport::Tcp, false).unwrap();
+        assert!(socket
+            .connect_timeout(&closed.into(), Duration::from_secs(2))
+            .is_err());
+    }
+}
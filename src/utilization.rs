@@ -0,0 +1,119 @@
+// Copyright (c) 2026 Hikaru (i@rua.moe)
+// All rights reserved.
+// This software is licensed under CC BY-NC 4.0
+// Attribution required, Commercial use prohibited
+
+//! 接口带宽利用率
+//!
+//! 间隔一段时间读取两次 `/sys/class/net/<设备>/statistics/` 中的收发字节计数，
+//! 估算接口上其他流量占用的带宽，避免把已饱和链路的低速度和高延迟误判为链路质量差
+
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// 两次读取计数器的间隔
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 某一时刻的收发字节计数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CounterSnapshot {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// 两次快照之间的平均流量
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Utilization {
+    /// 下行速率（Mbps）
+    pub rx_mbps: f64,
+    /// 上行速率（Mbps）
+    pub tx_mbps: f64,
+    /// 相对链路带宽的利用率（0.0-1.0，取上下行中较高者），未配置带宽时为空
+    pub ratio: Option<f64>,
+}
+
+impl Utilization {
+    /// 根据间隔 `elapsed` 的两次快照计算流量，`capacity_mbps` 为链路带宽
+    /// 计数器回绕或接口重建（计数变小）时返回 `None`
+    pub fn between(
+        before: CounterSnapshot,
+        after: CounterSnapshot,
+        elapsed: Duration,
+        capacity_mbps: Option<f64>,
+    ) -> Option<Self> {
+        if elapsed.is_zero() {
+            return None;
+        }
+
+        let rate = |before: u64, after: u64| {
+            let bytes = after.checked_sub(before)?;
+            Some(bytes as f64 * 8.0 / elapsed.as_secs_f64() / 1_000_000.0)
+        };
+        let rx_mbps = rate(before.rx_bytes, after.rx_bytes)?;
+        let tx_mbps = rate(before.tx_bytes, after.tx_bytes)?;
+        let ratio = capacity_mbps
+            .filter(|c| *c > 0.0)
+            .map(|c| (rx_mbps.max(tx_mbps) / c).min(1.0));
+
+        Some(Self {
+            rx_mbps,
+            tx_mbps,
+            ratio,
+        })
+    }
+}
+
+/// 读取设备当前的收发字节计数
+pub fn read_counters(device: &str) -> io::Result<CounterSnapshot> {
+    let read = |name: &str| -> io::Result<u64> {
+        let path = format!("/sys/class/net/{}/statistics/{}", device, name);
+        fs::read_to_string(path)?
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+
+    Ok(CounterSnapshot {
+        rx_bytes: read("rx_bytes")?,
+        tx_bytes: read("tx_bytes")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utilization_from_counter_snapshots() {
+        let before = CounterSnapshot {
+            rx_bytes: 1_000_000,
+            tx_bytes: 500_000,
+        };
+        // 2 秒内下行 20 MB、上行 2.5 MB
+        let after = CounterSnapshot {
+            rx_bytes: 21_000_000,
+            tx_bytes: 3_000_000,
+        };
+
+        let usage =
+            Utilization::between(before, after, Duration::from_secs(2), Some(100.0)).unwrap();
+        assert_eq!(usage.rx_mbps, 80.0);
+        assert_eq!(usage.tx_mbps, 10.0);
+        assert_eq!(usage.ratio, Some(0.8));
+
+        // 未配置带宽时只报告速率
+        let usage = Utilization::between(before, after, Duration::from_secs(2), None).unwrap();
+        assert_eq!(usage.ratio, None);
+
+        // 计数器变小（接口重建）或间隔为 0 时无法计算
+        assert_eq!(
+            Utilization::between(after, before, Duration::from_secs(2), None),
+            None
+        );
+        assert_eq!(
+            Utilization::between(before, after, Duration::ZERO, None),
+            None
+        );
+    }
+}